@@ -1,6 +1,12 @@
 use std::env;
 
 fn main() {
+    // Host builds, e.g. `cargo test --lib`, have no chip and only contain the
+    // hardware independent code.
+    if env::var("TARGET") == env::var("HOST") {
+        return;
+    }
+
     // Array of all available chip features
     let chip_features = [
         "esp32",
@@ -1,5 +1,8 @@
 #![no_std]
-#![feature(type_alias_impl_trait)]
+#![cfg_attr(feature = "esp-hal", feature(type_alias_impl_trait))]
+// Host builds leave out the drivers, some of their constants and helpers
+// are only used by them.
+#![cfg_attr(not(feature = "esp-hal"), allow(dead_code))]
 
 //! # esp-ward
 //!
@@ -74,6 +77,9 @@
 //! ### `init_spi_default` and `init_spi_custom`
 //! Initializes the SPI peripheral with either default or custom configurations.
 //!
+//! ### `init_uart_default` and `init_uart_custom`
+//! Initializes the UART peripheral with either default or custom configurations.
+//!
 //! ### `init_wifi`
 //! Initializes Wi-Fi connection in async or non-async way - depending on your
 //! project
@@ -91,6 +97,7 @@
 //! See LICENSE-APACHE and LICENSE-MIT for details.
 
 // Import the necessary modules from `esp-hal`
+#[cfg(feature = "esp-hal")]
 pub use esp_hal::{
     clock::Clocks,
     gpio::{InputPin, OutputPin, Pins, IO},
@@ -106,6 +113,7 @@ pub use esp_hal::{
 };
 #[cfg(feature = "wifi")]
 pub mod connectivity;
+#[cfg(feature = "esp-hal")]
 pub mod display;
 pub mod peripherals;
 
@@ -283,6 +291,104 @@ macro_rules! init_spi_custom {
     };
 }
 
+// The default UART pins differ between chips, `init_uart_default` picks
+// them with `get_uart_pins`.
+#[cfg(feature = "esp32")]
+#[macro_export]
+macro_rules! get_uart_pins {
+    ($pins:ident) => {
+        ($pins.gpio17, $pins.gpio16)
+    };
+}
+
+#[cfg(any(feature = "esp32s2", feature = "esp32s3"))]
+#[macro_export]
+macro_rules! get_uart_pins {
+    ($pins:ident) => {
+        ($pins.gpio17, $pins.gpio18)
+    };
+}
+
+// GPIO8 and GPIO9 are strapping pins, GPIO20 and GPIO21 carry the console
+// and GPIO4 and GPIO5 are used by `init_spi_default`.
+#[cfg(any(feature = "esp32c2", feature = "esp32c3"))]
+#[macro_export]
+macro_rules! get_uart_pins {
+    ($pins:ident) => {
+        ($pins.gpio10, $pins.gpio3)
+    };
+}
+
+#[cfg(any(feature = "esp32c6", feature = "esp32h2"))]
+#[macro_export]
+macro_rules! get_uart_pins {
+    ($pins:ident) => {
+        ($pins.gpio10, $pins.gpio11)
+    };
+}
+
+/// Initializes the default UART configuration for the chip: the UART1
+/// peripheral at 9600 baud, 8 data bits, no parity and 1 stop bit, which is
+/// what most serial sensors use.
+///
+/// | Chip                | TX     | RX     |
+/// |---------------------|--------|--------|
+/// | ESP32               | GPIO17 | GPIO16 |
+/// | ESP32-S2, ESP32-S3  | GPIO17 | GPIO18 |
+/// | ESP32-C2, ESP32-C3  | GPIO10 | GPIO3  |
+/// | ESP32-C6, ESP32-H2  | GPIO10 | GPIO11 |
+///
+/// The TX pin of the chip goes to the RX pin of the sensor and vice versa.
+///
+/// # Examples
+/// ```no_run
+/// let peripherals = esp_ward::take_periph!();
+/// let system = esp_ward::take_system!(peripherals);
+/// let (clocks, pins, delay) = esp_ward::init_chip!(peripherals, system);
+/// let mut uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+/// ```
+#[macro_export]
+macro_rules! init_uart_default {
+    ($peripherals:ident, $pins:ident, $clocks:ident) => {{
+        let (tx, rx) = $crate::get_uart_pins!($pins);
+        $crate::init_uart_custom!($peripherals, $clocks, tx, rx, 9600)
+    }};
+}
+
+/// Initializes a custom UART configuration, allowing for arbitrary TX and RX
+/// pins and baud rate, with 8 data bits, no parity and 1 stop bit.
+///
+/// # Arguments
+/// * `$peripherals`: The peripherals instance taken from the board.
+/// * `$clocks`: The system clocks initialized beforehand.
+/// * `$tx_pin`: The pin to use for TX.
+/// * `$rx_pin`: The pin to use for RX.
+/// * `$baudrate`: The baud rate, e.g. `9600`.
+///
+/// # Examples
+/// ```no_run
+/// let peripherals = esp_ward::take_periph!();
+/// let system = esp_ward::take_system!(peripherals);
+/// let (clocks, pins, delay) = esp_ward::init_chip!(peripherals, system);
+/// let mut uart =
+///     esp_ward::init_uart_custom!(peripherals, clocks, pins.gpio4, pins.gpio5, 115200);
+/// ```
+#[macro_export]
+macro_rules! init_uart_custom {
+    ($peripherals:ident, $clocks:ident, $tx_pin:expr, $rx_pin:expr, $baudrate:expr) => {
+        esp_hal::uart::Uart::new_with_config(
+            $peripherals.UART1,
+            esp_hal::uart::config::Config::default().baudrate($baudrate),
+            Some(esp_hal::uart::TxRxPins::new_tx_rx(
+                $tx_pin.into_push_pull_output(),
+                $rx_pin.into_floating_input(),
+            )),
+            &$clocks,
+            None,
+        )
+    };
+}
+
 /// Pauses the execution for a specified number of milliseconds using a delay
 /// provider.
 ///
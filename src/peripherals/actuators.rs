@@ -0,0 +1,690 @@
+//! # Actuators Module
+//!
+//! This module provides digital output devices - relays, LEDs and generic
+//! switches - driven by any `OutputPin`. Outputs can be active-low, blink in
+//! patterns, be switched on for a timed pulse and respect a minimum on/off time
+//! to protect compressors and pumps from short cycling. An `Interlock` group
+//! keeps at most one of its members on.
+//!
+//! Timing is driven by timestamps you pass in, so `Switch::update` should be
+//! called periodically (e.g. every main loop iteration) for patterns, pulses
+//! and deferred switching to progress. The logic itself lives in
+//! `OutputController`, which does not touch any hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     actuators::{Led, Relay, SwitchConfig, HEARTBEAT},
+//!     button::ActiveLevel,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let mut led = Led::create_on_pins(pins.gpio8.into_push_pull_output());
+//! // Compressor relay module switching on a low level
+//! let mut compressor = Relay::create_on_pins_with_config(
+//!     pins.gpio4.into_push_pull_output(),
+//!     SwitchConfig {
+//!         active_level: ActiveLevel::Low,
+//!         min_on_ms: 60_000,
+//!         min_off_ms: 180_000,
+//!     },
+//! );
+//!
+//! let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//! led.blink(HEARTBEAT, None, now);
+//! compressor.turn_on(now);
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     led.update(now);
+//!     compressor.update(now);
+//! }
+//! ```
+
+use embedded_hal::digital::v2::OutputPin;
+
+use super::{button::ActiveLevel, PeripheralError, Writable};
+
+/// Slow blinking, 1 s on and 1 s off.
+pub const BLINK_SLOW: Pattern = Pattern::Blink {
+    on_ms: 1000,
+    off_ms: 1000,
+};
+
+/// Fast blinking, 100 ms on and 100 ms off.
+pub const BLINK_FAST: Pattern = Pattern::Blink {
+    on_ms: 100,
+    off_ms: 100,
+};
+
+/// Two short flashes followed by a pause.
+pub const HEARTBEAT: Pattern = Pattern::Sequence(&[100, 100, 100, 700]);
+
+/// "SOS" in Morse code.
+pub const SOS: Pattern = Pattern::Sequence(&[
+    150, 150, 150, 150, 150, 450, 450, 150, 450, 150, 450, 450, 150, 150, 150, 150, 150, 1500,
+]);
+
+/// A blink pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Regular blinking.
+    Blink { on_ms: u32, off_ms: u32 },
+    /// Durations in milliseconds alternating between on and off, starting
+    /// with on. A sequence with an odd number of durations is played twice
+    /// per cycle, the second time starting with off, so that the phases keep
+    /// alternating.
+    Sequence(&'static [u32]),
+}
+
+impl Pattern {
+    /// Returns the amount of steps in one cycle of the pattern.
+    pub fn len(&self) -> usize {
+        match self {
+            Pattern::Blink { .. } => 2,
+            Pattern::Sequence(steps) if steps.len() % 2 == 1 => steps.len() * 2,
+            Pattern::Sequence(steps) => steps.len(),
+        }
+    }
+
+    /// Returns `true` if the pattern has no steps.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the output state and the duration in milliseconds of a step.
+    /// An empty sequence is always off, with a zero duration.
+    pub fn step(&self, index: usize) -> (bool, u32) {
+        let duration = match self {
+            Pattern::Blink { on_ms, off_ms } => {
+                if index % 2 == 0 {
+                    *on_ms
+                } else {
+                    *off_ms
+                }
+            }
+            Pattern::Sequence([]) => return (false, 0),
+            Pattern::Sequence(steps) => steps[index % steps.len()],
+        };
+        (index % 2 == 0, duration)
+    }
+}
+
+/// Configuration of an output.
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchConfig {
+    /// Level of the pin at which the output is on.
+    pub active_level: ActiveLevel,
+    /// Minimum time in milliseconds the output stays on before it can be
+    /// switched off.
+    pub min_on_ms: u32,
+    /// Minimum time in milliseconds the output stays off before it can be
+    /// switched on again.
+    pub min_off_ms: u32,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        SwitchConfig {
+            active_level: ActiveLevel::High,
+            min_on_ms: 0,
+            min_off_ms: 0,
+        }
+    }
+}
+
+/// What the output is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Steady(bool),
+    Pulse {
+        until_ms: u64,
+    },
+    Pattern {
+        pattern: Pattern,
+        step: usize,
+        step_ends_ms: u64,
+        cycles_left: Option<u32>,
+    },
+}
+
+/// Hardware independent output state machine handling patterns, pulses and
+/// the minimum on/off times.
+pub struct OutputController {
+    config: SwitchConfig,
+    mode: Mode,
+    /// Current state of the output.
+    on: bool,
+    /// Time of the last change of the output.
+    changed_ms: Option<u64>,
+}
+
+impl OutputController {
+    /// Creates a new controller, the output starts off.
+    pub const fn new(config: SwitchConfig) -> Self {
+        OutputController {
+            config,
+            mode: Mode::Steady(false),
+            on: false,
+            changed_ms: None,
+        }
+    }
+
+    /// Returns the configuration of the controller.
+    pub fn config(&self) -> &SwitchConfig {
+        &self.config
+    }
+
+    /// Returns `true` if the output is on.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Returns `true` while a pulse or a pattern is running.
+    pub fn is_busy(&self) -> bool {
+        !matches!(self.mode, Mode::Steady(_))
+    }
+
+    /// Returns the state the output should have once the minimum on/off time
+    /// allows it.
+    pub fn target(&self) -> bool {
+        match self.mode {
+            Mode::Steady(on) => on,
+            Mode::Pulse { .. } => true,
+            Mode::Pattern { pattern, step, .. } => pattern.step(step).0,
+        }
+    }
+
+    /// Requests a steady state, stopping any pulse or pattern.
+    ///
+    /// # Returns
+    /// The state of the output after the request.
+    pub fn set(&mut self, on: bool, now_ms: u64) -> bool {
+        self.mode = Mode::Steady(on);
+        self.update(now_ms)
+    }
+
+    /// Switches the output on for `duration_ms`, then off.
+    ///
+    /// # Returns
+    /// The state of the output after the request.
+    pub fn pulse(&mut self, duration_ms: u32, now_ms: u64) -> bool {
+        self.mode = Mode::Pulse {
+            until_ms: now_ms + duration_ms as u64,
+        };
+        self.update(now_ms)
+    }
+
+    /// Starts a blink pattern.
+    ///
+    /// # Arguments
+    /// * `pattern` - The pattern to blink.
+    /// * `cycles` - Amount of cycles after which the output is switched off,
+    ///   `None` blinks until another request.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// The state of the output after the request.
+    pub fn blink(&mut self, pattern: Pattern, cycles: Option<u32>, now_ms: u64) -> bool {
+        self.mode = if pattern.is_empty() || cycles == Some(0) {
+            Mode::Steady(false)
+        } else {
+            Mode::Pattern {
+                pattern,
+                step: 0,
+                step_ends_ms: now_ms + pattern.step(0).1 as u64,
+                cycles_left: cycles,
+            }
+        };
+        self.update(now_ms)
+    }
+
+    /// Advances pulses and patterns and applies the target state if the
+    /// minimum on/off time allows it.
+    ///
+    /// # Returns
+    /// The state the output should be set to.
+    pub fn update(&mut self, now_ms: u64) -> bool {
+        match &mut self.mode {
+            Mode::Steady(_) => {}
+            Mode::Pulse { until_ms } => {
+                if now_ms >= *until_ms {
+                    self.mode = Mode::Steady(false);
+                }
+            }
+            Mode::Pattern {
+                pattern,
+                step,
+                step_ends_ms,
+                cycles_left,
+            } => {
+                while now_ms >= *step_ends_ms {
+                    *step += 1;
+                    if *step == pattern.len() {
+                        *step = 0;
+                        if let Some(left) = cycles_left {
+                            *left -= 1;
+                            if *left == 0 {
+                                self.mode = Mode::Steady(false);
+                                break;
+                            }
+                        }
+                    }
+                    // Zero length steps would never let the loop end.
+                    *step_ends_ms += pattern.step(*step).1.max(1) as u64;
+                }
+            }
+        }
+
+        let target = self.target();
+        if target != self.on {
+            let min_ms = if self.on {
+                self.config.min_on_ms
+            } else {
+                self.config.min_off_ms
+            };
+            let allowed = match self.changed_ms {
+                Some(changed_ms) => now_ms.saturating_sub(changed_ms) >= min_ms as u64,
+                None => true,
+            };
+            if allowed {
+                self.on = target;
+                self.changed_ms = Some(now_ms);
+            }
+        }
+        self.on
+    }
+}
+
+/// Output device that can be switched on and off, used by `Interlock`.
+pub trait Actuator {
+    /// Requests a steady state.
+    fn set(&mut self, on: bool, now_ms: u64);
+    /// Advances timing and applies deferred changes.
+    fn update(&mut self, now_ms: u64);
+    /// Returns `true` if the output is on.
+    fn is_on(&self) -> bool;
+}
+
+/// A digital output: relay, LED or any other on/off device.
+pub struct Switch<PIN> {
+    /// The output pin driving the device.
+    pub pin: PIN,
+    controller: OutputController,
+}
+
+/// A relay. Usually configured with `SwitchConfig::min_on_ms` and
+/// `SwitchConfig::min_off_ms` when driving motors or compressors.
+pub type Relay<PIN> = Switch<PIN>;
+
+/// A LED, usually used with `Switch::blink`.
+pub type Led<PIN> = Switch<PIN>;
+
+impl<PIN: OutputPin<Error = core::convert::Infallible>> Switch<PIN> {
+    /// Creates a new active-high `Switch` without minimum on/off times. The
+    /// output is switched off.
+    ///
+    /// # Arguments
+    /// * `pin` - The output pin driving the device.
+    ///
+    /// # Returns
+    /// A new `Switch` instance.
+    pub fn create_on_pins(pin: PIN) -> Self {
+        Self::create_on_pins_with_config(pin, SwitchConfig::default())
+    }
+
+    /// Creates a new `Switch` with the given configuration. The output is
+    /// switched off.
+    ///
+    /// # Arguments
+    /// * `pin` - The output pin driving the device.
+    /// * `config` - Active level and minimum on/off times.
+    ///
+    /// # Returns
+    /// A new `Switch` instance.
+    pub fn create_on_pins_with_config(pin: PIN, config: SwitchConfig) -> Self {
+        let mut switch = Switch {
+            pin,
+            controller: OutputController::new(config),
+        };
+        switch.apply();
+        switch
+    }
+
+    /// Switches the output on, or as soon as the minimum off time allows it.
+    pub fn turn_on(&mut self, now_ms: u64) {
+        self.set(true, now_ms);
+    }
+
+    /// Switches the output off, or as soon as the minimum on time allows it.
+    pub fn turn_off(&mut self, now_ms: u64) {
+        self.set(false, now_ms);
+    }
+
+    /// Switches the output to the opposite of its target state.
+    pub fn toggle(&mut self, now_ms: u64) {
+        let on = !self.controller.target();
+        self.set(on, now_ms);
+    }
+
+    /// Requests a steady state, stopping any pulse or pattern.
+    ///
+    /// # Arguments
+    /// * `on` - The requested state.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn set(&mut self, on: bool, now_ms: u64) {
+        self.controller.set(on, now_ms);
+        self.apply();
+    }
+
+    /// Switches the output on for `duration_ms`, then off.
+    ///
+    /// # Arguments
+    /// * `duration_ms` - Length of the pulse in milliseconds.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn pulse(&mut self, duration_ms: u32, now_ms: u64) {
+        self.controller.pulse(duration_ms, now_ms);
+        self.apply();
+    }
+
+    /// Starts a blink pattern.
+    ///
+    /// # Arguments
+    /// * `pattern` - The pattern to blink, e.g. `BLINK_SLOW` or `HEARTBEAT`.
+    /// * `cycles` - Amount of cycles after which the output is switched off,
+    ///   `None` blinks until another request.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn blink(&mut self, pattern: Pattern, cycles: Option<u32>, now_ms: u64) {
+        self.controller.blink(pattern, cycles, now_ms);
+        self.apply();
+    }
+
+    /// Advances pulses and patterns and applies changes deferred by the
+    /// minimum on/off time. Should be called periodically.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn update(&mut self, now_ms: u64) {
+        self.controller.update(now_ms);
+        self.apply();
+    }
+
+    /// Returns `true` if the output is on.
+    pub fn is_on(&self) -> bool {
+        self.controller.is_on()
+    }
+
+    /// Returns `true` while a pulse or a pattern is running.
+    pub fn is_busy(&self) -> bool {
+        self.controller.is_busy()
+    }
+
+    /// Sets the pin level matching the state of the controller.
+    fn apply(&mut self) {
+        let high =
+            self.controller.is_on() == (self.controller.config().active_level == ActiveLevel::High);
+        if high {
+            self.pin.set_high().unwrap();
+        } else {
+            self.pin.set_low().unwrap();
+        }
+    }
+}
+
+impl<PIN: OutputPin<Error = core::convert::Infallible>> Actuator for Switch<PIN> {
+    fn set(&mut self, on: bool, now_ms: u64) {
+        Switch::set(self, on, now_ms);
+    }
+
+    fn update(&mut self, now_ms: u64) {
+        Switch::update(self, now_ms);
+    }
+
+    fn is_on(&self) -> bool {
+        Switch::is_on(self)
+    }
+}
+
+impl<PIN: OutputPin<Error = core::convert::Infallible>> Writable for Switch<PIN> {
+    /// The requested state and the current time in milliseconds.
+    type Input = (bool, u64);
+    /// Requests a steady state, stopping any pulse or pattern.
+    ///
+    /// # Returns
+    /// Always `Ok(())`, the change may be deferred by the minimum on/off time.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        let (on, now_ms) = data;
+        self.set(on, now_ms);
+        Ok(())
+    }
+}
+
+/// A group of actuators of which at most one is on, e.g. the direction relays
+/// of a motor. Switching is break-before-make: the selected member is only
+/// switched on once all other members are off, which may be delayed by their
+/// minimum on time.
+pub struct Interlock<'a, const N: usize> {
+    members: [&'a mut dyn Actuator; N],
+    selected: Option<usize>,
+}
+
+impl<'a, const N: usize> Interlock<'a, N> {
+    /// Creates a new group and switches all members off.
+    ///
+    /// # Arguments
+    /// * `members` - The actuators of the group.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// A new `Interlock` instance.
+    pub fn new(members: [&'a mut dyn Actuator; N], now_ms: u64) -> Self {
+        let mut interlock = Interlock {
+            members,
+            selected: None,
+        };
+        interlock.update(now_ms);
+        interlock
+    }
+
+    /// Selects the member to be switched on, `None` switches all off.
+    ///
+    /// # Arguments
+    /// * `index` - Index of the member in the group.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::WriteError)` if the index is out of range.
+    pub fn select(&mut self, index: Option<usize>, now_ms: u64) -> Result<(), PeripheralError> {
+        if matches!(index, Some(index) if index >= N) {
+            return Err(PeripheralError::WriteError);
+        }
+        self.selected = index;
+        self.update(now_ms);
+        Ok(())
+    }
+
+    /// Applies the selection and advances all members. Should be called
+    /// periodically.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn update(&mut self, now_ms: u64) {
+        for (index, member) in self.members.iter_mut().enumerate() {
+            if Some(index) != self.selected {
+                member.set(false, now_ms);
+            }
+        }
+        if let Some(selected) = self.selected {
+            let others_off = self
+                .members
+                .iter()
+                .enumerate()
+                .all(|(index, member)| index == selected || !member.is_on());
+            if others_off {
+                self.members[selected].set(true, now_ms);
+            } else {
+                self.members[selected].update(now_ms);
+            }
+        }
+    }
+
+    /// Returns the selected member.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Returns the member which is currently on.
+    pub fn active(&self) -> Option<usize> {
+        self.members.iter().position(|member| member.is_on())
+    }
+}
+
+impl<'a, const N: usize> Writable for Interlock<'a, N> {
+    /// The member to be switched on and the current time in milliseconds.
+    type Input = (Option<usize>, u64);
+    /// Selects the member to be switched on, `None` switches all off.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::WriteError)` if the index is out of range.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        let (index, now_ms) = data;
+        self.select(index, now_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROTECTED: SwitchConfig = SwitchConfig {
+        active_level: ActiveLevel::High,
+        min_on_ms: 1000,
+        min_off_ms: 3000,
+    };
+
+    /// Output pin remembering its level.
+    struct MockPin {
+        high: bool,
+    }
+
+    impl OutputPin for MockPin {
+        type Error = core::convert::Infallible;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.high = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.high = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn empty_sequence_is_off() {
+        let pattern = Pattern::Sequence(&[]);
+        assert!(pattern.is_empty());
+        assert_eq!(pattern.step(0), (false, 0));
+        assert_eq!(pattern.step(3), (false, 0));
+
+        let mut output = OutputController::new(SwitchConfig::default());
+        assert!(!output.blink(pattern, None, 0));
+        assert!(!output.is_busy());
+    }
+
+    #[test]
+    fn odd_sequence_alternates() {
+        let pattern = Pattern::Sequence(&[100, 200, 300]);
+        assert_eq!(pattern.len(), 6);
+        assert_eq!(pattern.step(2), (true, 300));
+        assert_eq!(pattern.step(3), (false, 100));
+        assert_eq!(pattern.step(4), (true, 200));
+    }
+
+    #[test]
+    fn respects_minimum_on_off_time() {
+        let mut output = OutputController::new(PROTECTED);
+        // Nothing to protect before the first change.
+        assert!(output.set(true, 0));
+        assert!(output.set(false, 500));
+        assert!(!output.target());
+        assert!(output.update(999));
+        assert!(!output.update(1000));
+
+        assert!(!output.set(true, 2000));
+        assert!(!output.update(3999));
+        assert!(output.update(4000));
+    }
+
+    #[test]
+    fn pulses() {
+        let mut output = OutputController::new(SwitchConfig::default());
+        assert!(output.pulse(200, 100));
+        assert!(output.is_busy());
+        assert!(output.update(299));
+        assert!(!output.update(300));
+        assert!(!output.is_busy());
+    }
+
+    #[test]
+    fn counts_pattern_cycles() {
+        let mut output = OutputController::new(SwitchConfig::default());
+        assert!(output.blink(BLINK_FAST, Some(2), 0));
+        assert!(!output.update(100));
+        assert!(output.update(200));
+        assert!(!output.update(300));
+        assert!(output.is_busy());
+        assert!(!output.update(400));
+        assert!(!output.is_busy());
+        assert!(!output.update(500));
+
+        // Steps missed between updates are caught up.
+        assert!(output.blink(HEARTBEAT, None, 1000));
+        assert!(output.update(2200));
+        assert!(!output.update(2300));
+
+        assert!(!output.blink(BLINK_SLOW, Some(0), 3000));
+        assert!(!output.is_busy());
+    }
+
+    #[test]
+    fn writes_active_low_switch() {
+        let mut switch = Switch::create_on_pins_with_config(
+            MockPin { high: false },
+            SwitchConfig {
+                active_level: ActiveLevel::Low,
+                ..SwitchConfig::default()
+            },
+        );
+        assert!(switch.pin.high);
+        switch.write((true, 0)).unwrap();
+        assert!(switch.is_on());
+        assert!(!switch.pin.high);
+    }
+
+    #[test]
+    fn interlock_breaks_before_make() {
+        let mut forward = Switch::create_on_pins_with_config(MockPin { high: false }, PROTECTED);
+        let mut reverse = Switch::create_on_pins_with_config(MockPin { high: false }, PROTECTED);
+        let mut interlock = Interlock::new([&mut forward, &mut reverse], 0);
+
+        interlock.write((Some(0), 0)).unwrap();
+        assert_eq!(interlock.active(), Some(0));
+        // The forward relay has to stay on for its minimum on time.
+        interlock.write((Some(1), 500)).unwrap();
+        assert_eq!(interlock.active(), Some(0));
+        // The reverse relay is switched on once the forward relay is off.
+        interlock.update(1000);
+        assert_eq!(interlock.active(), Some(1));
+        assert_eq!(interlock.selected(), Some(1));
+
+        assert!(interlock.write((Some(2), 2000)).is_err());
+        interlock.write((None, 2000)).unwrap();
+        assert_eq!(interlock.active(), None);
+    }
+}
@@ -0,0 +1,496 @@
+//! # Analog Sensor Module
+//!
+//! This module provides a generic wrapper over any ADC-capable pin, suitable
+//! for potentiometers, LDRs, thermistors, capacitive soil-moisture probes and
+//! other sensors producing a voltage.
+//!
+//! Every reading can be oversampled (averaged), converted to millivolts and
+//! then to a physical value with a conversion curve. On chips with ADC
+//! calibration data in eFuse (ESP32-C2, ESP32-C3, ESP32-C6 and ESP32-S3) the
+//! `create_analog_sensor!` macro enables the calibration, so readings are
+//! converted to millivolts by `esp-hal`. On other chips, the conversion is an
+//! approximation from the full-scale voltage of the attenuation.
+//!
+//! Conversion curves implement the `Conversion` trait. `Linear`,
+//! `Interpolated` (piecewise-linear table) and `NtcThermistor`
+//! (Steinhart-Hart) are provided, and any function or closure taking and
+//! returning an `f32` can be used as well.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::analog::adc::Attenuation;
+//! use esp_ward::peripherals::analog::{Interpolated, NtcThermistor};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! // Soil moisture in percent: 2800 mV in the air, 1200 mV in water
+//! let (mut soil, mut adc) = esp_ward::create_analog_sensor!(
+//!     peripherals,
+//!     pins.gpio2.into_analog(),
+//!     Attenuation::Attenuation11dB
+//! );
+//! let mut soil = soil.with_curve(Interpolated {
+//!     points: &[(1200.0, 100.0), (2800.0, 0.0)],
+//! });
+//! println!("Soil moisture: {}%", soil.read_value(&mut adc).unwrap());
+//! ```
+//!
+//! NTC thermistors implement `TemperatureSensor` with `NtcSensor`:
+//! ```no_run
+//! let mut thermistor = esp_ward::create_ntc_sensor!(
+//!     peripherals,
+//!     pins.gpio3.into_analog(),
+//!     NtcThermistor::from_beta(10_000.0, 25.0, 3950.0, 10_000.0, 3300.0)
+//! );
+//! println!("Temperature: {}", thermistor.get_temperature().unwrap());
+//! ```
+
+use embedded_hal::adc::{Channel, OneShot};
+use esp_hal::{analog::adc::Attenuation, delay::Delay};
+
+use super::{PeripheralError, TemperatureSensor, UnifiedData};
+
+/// Maximum raw value of the 12-bit ADC.
+pub const ADC_MAX: u16 = 4095;
+
+/// Kelvin value of 0 degrees Celsius.
+const ZERO_CELSIUS: f32 = 273.15;
+
+/// Approximate voltage in millivolts corresponding to `ADC_MAX` for the given
+/// attenuation. Only used when the pin is not calibrated.
+pub fn full_scale_millivolts(attenuation: Attenuation) -> f32 {
+    match attenuation {
+        Attenuation::Attenuation0dB => 950.0,
+        Attenuation::Attenuation2p5dB => 1250.0,
+        Attenuation::Attenuation6dB => 1750.0,
+        Attenuation::Attenuation11dB => 3100.0,
+    }
+}
+
+/// Converts a voltage in millivolts to a physical value.
+pub trait Conversion {
+    fn convert(&self, millivolts: f32) -> f32;
+}
+
+impl<F: Fn(f32) -> f32> Conversion for F {
+    fn convert(&self, millivolts: f32) -> f32 {
+        self(millivolts)
+    }
+}
+
+/// Linear conversion: `value = millivolts * scale + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Linear {
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl Default for Linear {
+    /// Identity conversion, values are in millivolts.
+    fn default() -> Self {
+        Linear {
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl Conversion for Linear {
+    fn convert(&self, millivolts: f32) -> f32 {
+        millivolts * self.scale + self.offset
+    }
+}
+
+/// Piecewise-linear conversion defined by `(millivolts, value)` points sorted
+/// by millivolts. Values outside of the table are clamped to its ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interpolated<'a> {
+    pub points: &'a [(f32, f32)],
+}
+
+impl Conversion for Interpolated<'_> {
+    fn convert(&self, millivolts: f32) -> f32 {
+        interpolate(self.points, millivolts)
+    }
+}
+
+/// Interpolates `x` in a table of `(x, y)` points sorted by `x`. Values outside
+/// of the table are clamped to its ends, an empty table gives `0.0`.
+pub fn interpolate(points: &[(f32, f32)], x: f32) -> f32 {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 0.0,
+    };
+    if x <= first.0 {
+        return first.1;
+    }
+    if x >= last.0 {
+        return last.1;
+    }
+    for window in points.windows(2) {
+        let (x0, y0) = window[0];
+        let (x1, y1) = window[1];
+        if x <= x1 {
+            if x1 == x0 {
+                return y1;
+            }
+            return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+        }
+    }
+    last.1
+}
+
+/// NTC thermistor in a voltage divider with a series resistor, converted to
+/// degrees Celsius with the Steinhart-Hart equation
+/// `1/T = a + b * ln(R) + c * ln(R)^3`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtcThermistor {
+    /// Steinhart-Hart coefficients.
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    /// Resistance of the series resistor in ohms.
+    pub series_resistance: f32,
+    /// Supply voltage of the divider in millivolts.
+    pub supply_millivolts: f32,
+    /// `true` if the thermistor is between the ADC pin and GND (series
+    /// resistor to the supply), `false` if it is between the supply and the
+    /// ADC pin.
+    pub thermistor_to_ground: bool,
+}
+
+impl NtcThermistor {
+    /// Creates the thermistor from the parameters commonly found in
+    /// datasheets, the thermistor is expected between the ADC pin and GND.
+    ///
+    /// # Arguments
+    /// * `nominal_resistance` - Resistance at `nominal_temperature` in ohms.
+    /// * `nominal_temperature` - Usually 25 degrees Celsius.
+    /// * `beta` - The B coefficient of the thermistor.
+    /// * `series_resistance` - Resistance of the series resistor in ohms.
+    /// * `supply_millivolts` - Supply voltage of the divider.
+    pub fn from_beta(
+        nominal_resistance: f32,
+        nominal_temperature: f32,
+        beta: f32,
+        series_resistance: f32,
+        supply_millivolts: f32,
+    ) -> Self {
+        let b = 1.0 / beta;
+        NtcThermistor {
+            a: 1.0 / (nominal_temperature + ZERO_CELSIUS) - b * libm::logf(nominal_resistance),
+            b,
+            c: 0.0,
+            series_resistance,
+            supply_millivolts,
+            thermistor_to_ground: true,
+        }
+    }
+
+    /// Computes the resistance of the thermistor from the divider voltage.
+    pub fn resistance(&self, millivolts: f32) -> f32 {
+        let millivolts = millivolts.clamp(1.0, self.supply_millivolts - 1.0);
+        if self.thermistor_to_ground {
+            self.series_resistance * millivolts / (self.supply_millivolts - millivolts)
+        } else {
+            self.series_resistance * (self.supply_millivolts - millivolts) / millivolts
+        }
+    }
+
+    /// Computes the temperature in degrees Celsius from the resistance of the
+    /// thermistor.
+    pub fn temperature(&self, resistance: f32) -> f32 {
+        let ln_r = libm::logf(resistance);
+        1.0 / (self.a + self.b * ln_r + self.c * ln_r * ln_r * ln_r) - ZERO_CELSIUS
+    }
+}
+
+impl Conversion for NtcThermistor {
+    fn convert(&self, millivolts: f32) -> f32 {
+        self.temperature(self.resistance(millivolts))
+    }
+}
+
+/// Configuration of an analog reading.
+#[derive(Clone, Copy)]
+pub struct AnalogConfig {
+    /// Attenuation the pin was enabled with.
+    pub attenuation: Attenuation,
+    /// `true` if the pin was enabled with a calibration scheme, so the ADC
+    /// reads millivolts directly.
+    pub calibrated: bool,
+    /// Amount of readings averaged into one value.
+    pub oversampling: u8,
+}
+
+/// A generic analog sensor on an ADC pin. The ADC driver is passed to every
+/// reading, so several sensors can share it.
+pub struct AnalogSensor<PIN, C = Linear> {
+    /// The enabled ADC pin.
+    pub pin: PIN,
+    /// Configuration of the reading.
+    pub config: AnalogConfig,
+    /// Conversion from millivolts to the physical value.
+    pub curve: C,
+}
+
+impl<PIN> AnalogSensor<PIN, Linear> {
+    /// Creates a new `AnalogSensor` on an already enabled ADC pin, reporting
+    /// values in millivolts.
+    ///
+    /// # Arguments
+    /// * `pin` - The enabled ADC pin.
+    /// * `config` - Configuration of the reading.
+    ///
+    /// # Returns
+    /// A new `AnalogSensor` instance.
+    pub fn create_on_adc_pin(pin: PIN, config: AnalogConfig) -> Self {
+        AnalogSensor {
+            pin,
+            config,
+            curve: Linear::default(),
+        }
+    }
+}
+
+impl<PIN, C: Conversion> AnalogSensor<PIN, C> {
+    /// Replaces the conversion curve of the sensor.
+    pub fn with_curve<N: Conversion>(self, curve: N) -> AnalogSensor<PIN, N> {
+        AnalogSensor {
+            pin: self.pin,
+            config: self.config,
+            curve,
+        }
+    }
+
+    /// Sets the amount of readings averaged into one value.
+    pub fn set_oversampling(&mut self, samples: u8) {
+        self.config.oversampling = samples;
+    }
+
+    /// Reads the ADC, averaged over `oversampling` readings.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the value from the analog pin.
+    ///
+    /// # Returns
+    /// The raw value (or millivolts, if the pin is calibrated), or
+    /// `Err(PeripheralError::ReadError)` if the ADC cannot be read.
+    pub fn read_raw<ADCI, A>(&mut self, adc: &mut A) -> Result<u16, PeripheralError>
+    where
+        PIN: Channel<ADCI>,
+        A: OneShot<ADCI, u16, PIN>,
+    {
+        let samples = self.config.oversampling.max(1) as u32;
+        let mut sum = 0u32;
+        for _ in 0..samples {
+            match nb::block!(adc.read(&mut self.pin)) {
+                Ok(value) => sum += value as u32,
+                Err(_) => return Err(PeripheralError::ReadError),
+            }
+        }
+        Ok((sum / samples) as u16)
+    }
+
+    /// Reads the voltage on the pin in millivolts.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the value from the analog pin.
+    pub fn read_millivolts<ADCI, A>(&mut self, adc: &mut A) -> Result<f32, PeripheralError>
+    where
+        PIN: Channel<ADCI>,
+        A: OneShot<ADCI, u16, PIN>,
+    {
+        let raw = self.read_raw(adc)? as f32;
+        if self.config.calibrated {
+            Ok(raw)
+        } else {
+            Ok(raw * full_scale_millivolts(self.config.attenuation) / ADC_MAX as f32)
+        }
+    }
+
+    /// Reads the voltage on the pin and converts it with the curve of the
+    /// sensor.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the value from the analog pin.
+    pub fn read_value<ADCI, A>(&mut self, adc: &mut A) -> Result<f32, PeripheralError>
+    where
+        PIN: Channel<ADCI>,
+        A: OneShot<ADCI, u16, PIN>,
+    {
+        let millivolts = self.read_millivolts(adc)?;
+        Ok(self.curve.convert(millivolts))
+    }
+}
+
+/// An NTC thermistor owning its ADC driver, so it can implement
+/// `TemperatureSensor`.
+pub struct NtcSensor<ADCI, A, PIN> {
+    /// The ADC driver.
+    pub adc: A,
+    /// The thermistor on the ADC pin.
+    pub inner: AnalogSensor<PIN, NtcThermistor>,
+    _adc_instance: core::marker::PhantomData<ADCI>,
+}
+
+impl<ADCI, A, PIN> NtcSensor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    /// Creates a new `NtcSensor`.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC driver.
+    /// * `sensor` - The analog sensor with the thermistor curve.
+    ///
+    /// # Returns
+    /// A new `NtcSensor` instance.
+    pub fn create_on_adc(adc: A, sensor: AnalogSensor<PIN, NtcThermistor>) -> Self {
+        NtcSensor {
+            adc,
+            inner: sensor,
+            _adc_instance: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<ADCI, A, PIN> TemperatureSensor for NtcSensor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    /// Reads the temperature of the thermistor.
+    ///
+    /// # Returns
+    /// Returns an `Ok(f32)` representing the temperature in degrees Celsius if
+    /// the read is successful, or `Err(PeripheralError::ReadError)` if the ADC
+    /// cannot be read.
+    fn get_temperature(&mut self) -> Result<f32, PeripheralError> {
+        self.inner.read_value(&mut self.adc)
+    }
+}
+
+impl<ADCI, A, PIN> UnifiedData for NtcSensor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    type Output = f32;
+    /// Reads the temperature of the thermistor.
+    ///
+    /// # Returns
+    /// Returns an `Ok(f32)` representing the temperature in degrees Celsius if
+    /// the read is successful, or `Err(PeripheralError::ReadError)` if the ADC
+    /// cannot be read.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_temperature()
+    }
+}
+
+/// Macro for creating an `AnalogSensor` on an ADC1 pin, together with the ADC
+/// driver. On chips supporting it, the pin is enabled with the eFuse
+/// calibration (`AdcCalCurve`).
+///
+/// See `create_joystick!` for why this is a macro.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$pin` - The GPIO pin in analog mode.
+/// * `$attenuation` - The `esp_hal::analog::adc::Attenuation` of the pin.
+///
+/// # Returns
+/// A tuple containing the `AnalogSensor` instance and the initialized ADC.
+#[cfg(any(
+    feature = "esp32c2",
+    feature = "esp32c3",
+    feature = "esp32c6",
+    feature = "esp32s3"
+))]
+#[macro_export]
+macro_rules! create_analog_sensor {
+    ($peripherals: expr, $pin: expr, $attenuation: expr) => {{
+        let mut adc1_config = esp_hal::analog::adc::AdcConfig::<esp_hal::peripherals::ADC1>::new();
+        let pin = adc1_config.enable_pin_with_cal::<_, esp_hal::analog::adc::AdcCalCurve<
+                esp_hal::peripherals::ADC1,
+            >>($pin, $attenuation);
+        let adc1 = esp_hal::analog::adc::ADC::<esp_hal::peripherals::ADC1>::new(
+            $peripherals.ADC1,
+            adc1_config,
+        );
+
+        (
+            esp_ward::peripherals::analog::AnalogSensor::create_on_adc_pin(
+                pin,
+                esp_ward::peripherals::analog::AnalogConfig {
+                    attenuation: $attenuation,
+                    calibrated: true,
+                    oversampling: 8,
+                },
+            ),
+            adc1,
+        )
+    }};
+}
+
+#[cfg(not(any(
+    feature = "esp32c2",
+    feature = "esp32c3",
+    feature = "esp32c6",
+    feature = "esp32s3"
+)))]
+#[macro_export]
+macro_rules! create_analog_sensor {
+    ($peripherals: expr, $pin: expr, $attenuation: expr) => {{
+        let mut adc1_config = esp_hal::analog::adc::AdcConfig::<esp_hal::peripherals::ADC1>::new();
+        let pin = adc1_config.enable_pin($pin, $attenuation);
+        let adc1 = esp_hal::analog::adc::ADC::<esp_hal::peripherals::ADC1>::new(
+            $peripherals.ADC1,
+            adc1_config,
+        );
+
+        (
+            esp_ward::peripherals::analog::AnalogSensor::create_on_adc_pin(
+                pin,
+                esp_ward::peripherals::analog::AnalogConfig {
+                    attenuation: $attenuation,
+                    calibrated: false,
+                    oversampling: 8,
+                },
+            ),
+            adc1,
+        )
+    }};
+}
+
+pub use create_analog_sensor;
+
+/// Macro for creating an `NtcSensor` on an ADC1 pin. The pin uses 11 dB
+/// attenuation and owns the ADC1 driver.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$pin` - The GPIO pin in analog mode.
+/// * `$thermistor` - The `NtcThermistor` parameters.
+///
+/// # Returns
+/// A new `NtcSensor` instance.
+#[macro_export]
+macro_rules! create_ntc_sensor {
+    ($peripherals: expr, $pin: expr, $thermistor: expr) => {{
+        let (sensor, adc1) = esp_ward::create_analog_sensor!(
+            $peripherals,
+            $pin,
+            esp_hal::analog::adc::Attenuation::Attenuation11dB
+        );
+        esp_ward::peripherals::analog::NtcSensor::create_on_adc(
+            adc1,
+            sensor.with_curve($thermistor),
+        )
+    }};
+}
+
+pub use create_ntc_sensor;
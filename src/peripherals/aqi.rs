@@ -0,0 +1,599 @@
+//! # Air Quality Index Module
+//!
+//! This module converts raw concentrations measured by the sensors into the
+//! indices and categories shown on displays and dashboards:
+//! - US EPA Air Quality Index (AQI, 0 to 500) from PM2.5 and PM10, using the
+//!   breakpoints of the 2024 revision,
+//! - European Common Air Quality Index (CAQI, hourly grid) from PM2.5 and PM10,
+//! - comfort bands for indoor CO2 and TVOC levels.
+//!
+//! Every category has a name and a colour (a `peripherals::Color`, so it can
+//! be shown on an addressable LED with `ws2812` directly). The `*_from`
+//! functions read the sensor and compute the index in one go.
+//!
+//! The indices are meant to be computed from averaged concentrations (24 hours
+//! for the US AQI of PM, 1 hour for the CAQI); fed with instantaneous readings
+//! they give a "NowCast"-like indication only.
+//!
+//! Apart from the `*_from` helpers, the functions are pure maths.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{aqi, pms5003::Pms5003, uart::ReportingMode};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//! let mut sensor = Pms5003::create_on_uart(uart, delay, ReportingMode::Passive).unwrap();
+//!
+//! let aqi = aqi::us_aqi_from(&mut sensor).unwrap();
+//! println!("AQI {} ({})", aqi.index, aqi.category.name());
+//! ```
+
+use super::{CO2Sensor, Color, ParticulateMatter, ParticulateSensor, PeripheralError, VOCSensor};
+
+/// Pollutants an index is computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pollutant {
+    Pm2_5,
+    Pm10,
+}
+
+/// A breakpoint of an index: concentrations from `low` to `high` map
+/// linearly to the indices from `index_low` to `index_high`.
+struct Breakpoint {
+    low: f32,
+    high: f32,
+    index_low: u16,
+    index_high: u16,
+}
+
+const fn bp(low: f32, high: f32, index_low: u16, index_high: u16) -> Breakpoint {
+    Breakpoint {
+        low,
+        high,
+        index_low,
+        index_high,
+    }
+}
+
+/// Maps a concentration to an index by linear interpolation within its
+/// breakpoint. Concentrations above the last breakpoint are extrapolated
+/// along it, up to `max`.
+fn interpolate_index(concentration: f32, table: &[Breakpoint], max: u16) -> u16 {
+    let concentration = concentration.max(0.0);
+    let breakpoint = table
+        .iter()
+        .find(|bp| concentration <= bp.high)
+        .unwrap_or(&table[table.len() - 1]);
+    let index = (breakpoint.index_high - breakpoint.index_low) as f32
+        / (breakpoint.high - breakpoint.low)
+        * (concentration - breakpoint.low)
+        + breakpoint.index_low as f32;
+    libm::roundf(index).clamp(0.0, max as f32) as u16
+}
+
+const US_PM2_5: [Breakpoint; 6] = [
+    bp(0.0, 9.0, 0, 50),
+    bp(9.1, 35.4, 51, 100),
+    bp(35.5, 55.4, 101, 150),
+    bp(55.5, 125.4, 151, 200),
+    bp(125.5, 225.4, 201, 300),
+    bp(225.5, 325.4, 301, 500),
+];
+
+const US_PM10: [Breakpoint; 6] = [
+    bp(0.0, 54.0, 0, 50),
+    bp(55.0, 154.0, 51, 100),
+    bp(155.0, 254.0, 101, 150),
+    bp(255.0, 354.0, 151, 200),
+    bp(355.0, 424.0, 201, 300),
+    bp(425.0, 604.0, 301, 500),
+];
+
+/// Category of the US EPA AQI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AqiCategory {
+    Good,
+    Moderate,
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl AqiCategory {
+    /// Returns the category of an AQI value.
+    pub fn from_index(index: u16) -> Self {
+        match index {
+            0..=50 => AqiCategory::Good,
+            51..=100 => AqiCategory::Moderate,
+            101..=150 => AqiCategory::UnhealthyForSensitiveGroups,
+            151..=200 => AqiCategory::Unhealthy,
+            201..=300 => AqiCategory::VeryUnhealthy,
+            _ => AqiCategory::Hazardous,
+        }
+    }
+
+    /// Returns the official name of the category.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AqiCategory::Good => "Good",
+            AqiCategory::Moderate => "Moderate",
+            AqiCategory::UnhealthyForSensitiveGroups => "Unhealthy for Sensitive Groups",
+            AqiCategory::Unhealthy => "Unhealthy",
+            AqiCategory::VeryUnhealthy => "Very Unhealthy",
+            AqiCategory::Hazardous => "Hazardous",
+        }
+    }
+
+    /// Returns the official colour of the category.
+    pub fn color(&self) -> Color {
+        match self {
+            AqiCategory::Good => Color::rgb(0x00, 0xE4, 0x00),
+            AqiCategory::Moderate => Color::rgb(0xFF, 0xFF, 0x00),
+            AqiCategory::UnhealthyForSensitiveGroups => Color::rgb(0xFF, 0x7E, 0x00),
+            AqiCategory::Unhealthy => Color::rgb(0xFF, 0x00, 0x00),
+            AqiCategory::VeryUnhealthy => Color::rgb(0x8F, 0x3F, 0x97),
+            AqiCategory::Hazardous => Color::rgb(0x7E, 0x00, 0x23),
+        }
+    }
+}
+
+/// A US EPA AQI value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsAqi {
+    /// The index, 0 to 500.
+    pub index: u16,
+    pub category: AqiCategory,
+    /// The pollutant with the highest sub-index, which determines the index.
+    pub dominant: Pollutant,
+}
+
+/// Computes the US AQI sub-index of PM2.5.
+///
+/// # Arguments
+/// * `concentration` - PM2.5 in µg/m³, truncated to 0.1 µg/m³ as required.
+pub fn us_aqi_pm2_5(concentration: f32) -> u16 {
+    let truncated = libm::truncf(concentration * 10.0) / 10.0;
+    interpolate_index(truncated, &US_PM2_5, 500)
+}
+
+/// Computes the US AQI sub-index of PM10.
+///
+/// # Arguments
+/// * `concentration` - PM10 in µg/m³, truncated to 1 µg/m³ as required.
+pub fn us_aqi_pm10(concentration: f32) -> u16 {
+    interpolate_index(libm::truncf(concentration), &US_PM10, 500)
+}
+
+/// Computes the US AQI from particulate matter concentrations, the highest
+/// of the PM2.5 and PM10 sub-indices.
+pub fn us_aqi(pm: &ParticulateMatter) -> UsAqi {
+    let pm2_5 = us_aqi_pm2_5(pm.pm2_5);
+    let pm10 = us_aqi_pm10(pm.pm10);
+    let (index, dominant) = if pm10 > pm2_5 {
+        (pm10, Pollutant::Pm10)
+    } else {
+        (pm2_5, Pollutant::Pm2_5)
+    };
+    UsAqi {
+        index,
+        category: AqiCategory::from_index(index),
+        dominant,
+    }
+}
+
+/// Measures particulate matter and computes the US AQI.
+pub fn us_aqi_from<S: ParticulateSensor>(sensor: &mut S) -> Result<UsAqi, PeripheralError> {
+    Ok(us_aqi(&sensor.get_particulate_matter()?))
+}
+
+const CAQI_PM2_5: [Breakpoint; 4] = [
+    bp(0.0, 15.0, 0, 25),
+    bp(15.0, 30.0, 25, 50),
+    bp(30.0, 55.0, 50, 75),
+    bp(55.0, 110.0, 75, 100),
+];
+
+const CAQI_PM10: [Breakpoint; 4] = [
+    bp(0.0, 25.0, 0, 25),
+    bp(25.0, 50.0, 25, 50),
+    bp(50.0, 90.0, 50, 75),
+    bp(90.0, 180.0, 75, 100),
+];
+
+/// Highest CAQI value reported, the grid ends at 100 ("above 100").
+const CAQI_MAX: u16 = 200;
+
+/// Level of the European CAQI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CaqiLevel {
+    VeryLow,
+    Low,
+    Medium,
+    High,
+    VeryHigh,
+}
+
+impl CaqiLevel {
+    /// Returns the level of a CAQI value.
+    pub fn from_index(index: u16) -> Self {
+        match index {
+            0..=24 => CaqiLevel::VeryLow,
+            25..=49 => CaqiLevel::Low,
+            50..=74 => CaqiLevel::Medium,
+            75..=100 => CaqiLevel::High,
+            _ => CaqiLevel::VeryHigh,
+        }
+    }
+
+    /// Returns the name of the level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaqiLevel::VeryLow => "Very low",
+            CaqiLevel::Low => "Low",
+            CaqiLevel::Medium => "Medium",
+            CaqiLevel::High => "High",
+            CaqiLevel::VeryHigh => "Very high",
+        }
+    }
+
+    /// Returns the colour of the level.
+    pub fn color(&self) -> Color {
+        match self {
+            CaqiLevel::VeryLow => Color::rgb(0x79, 0xBC, 0x6A),
+            CaqiLevel::Low => Color::rgb(0xBB, 0xCF, 0x4C),
+            CaqiLevel::Medium => Color::rgb(0xEE, 0xC2, 0x0B),
+            CaqiLevel::High => Color::rgb(0xF2, 0x93, 0x05),
+            CaqiLevel::VeryHigh => Color::rgb(0xE8, 0x41, 0x6F),
+        }
+    }
+}
+
+/// A European CAQI value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Caqi {
+    /// The index; above 100 means "very high".
+    pub index: u16,
+    pub level: CaqiLevel,
+    /// The pollutant with the highest sub-index, which determines the index.
+    pub dominant: Pollutant,
+}
+
+/// Computes the CAQI sub-index of PM2.5 (hourly average in µg/m³).
+pub fn caqi_pm2_5(concentration: f32) -> u16 {
+    interpolate_index(concentration, &CAQI_PM2_5, CAQI_MAX)
+}
+
+/// Computes the CAQI sub-index of PM10 (hourly average in µg/m³).
+pub fn caqi_pm10(concentration: f32) -> u16 {
+    interpolate_index(concentration, &CAQI_PM10, CAQI_MAX)
+}
+
+/// Computes the CAQI from particulate matter concentrations, the highest of
+/// the PM2.5 and PM10 sub-indices.
+pub fn caqi(pm: &ParticulateMatter) -> Caqi {
+    let pm2_5 = caqi_pm2_5(pm.pm2_5);
+    let pm10 = caqi_pm10(pm.pm10);
+    let (index, dominant) = if pm10 > pm2_5 {
+        (pm10, Pollutant::Pm10)
+    } else {
+        (pm2_5, Pollutant::Pm2_5)
+    };
+    Caqi {
+        index,
+        level: CaqiLevel::from_index(index),
+        dominant,
+    }
+}
+
+/// Measures particulate matter and computes the CAQI.
+pub fn caqi_from<S: ParticulateSensor>(sensor: &mut S) -> Result<Caqi, PeripheralError> {
+    Ok(caqi(&sensor.get_particulate_matter()?))
+}
+
+/// Comfort band of the indoor CO2 concentration, a measure of ventilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Co2Comfort {
+    /// Below 800 ppm, close to outdoor air.
+    Excellent,
+    /// 800 to 1000 ppm.
+    Good,
+    /// 1000 to 1400 ppm, ventilation is advised.
+    Fair,
+    /// 1400 to 2000 ppm, ventilation is needed.
+    Poor,
+    /// 2000 ppm and above, drowsiness and headaches are likely.
+    Bad,
+}
+
+impl Co2Comfort {
+    /// Returns the band of a CO2 concentration in ppm.
+    pub fn from_ppm(ppm: f32) -> Self {
+        if ppm < 800.0 {
+            Co2Comfort::Excellent
+        } else if ppm < 1000.0 {
+            Co2Comfort::Good
+        } else if ppm < 1400.0 {
+            Co2Comfort::Fair
+        } else if ppm < 2000.0 {
+            Co2Comfort::Poor
+        } else {
+            Co2Comfort::Bad
+        }
+    }
+
+    /// Returns the name of the band.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Co2Comfort::Excellent => "Excellent",
+            Co2Comfort::Good => "Good",
+            Co2Comfort::Fair => "Fair",
+            Co2Comfort::Poor => "Poor",
+            Co2Comfort::Bad => "Bad",
+        }
+    }
+
+    /// Returns the colour of the band.
+    pub fn color(&self) -> Color {
+        match self {
+            Co2Comfort::Excellent => Color::rgb(0x00, 0xE4, 0x00),
+            Co2Comfort::Good => Color::rgb(0x9C, 0xD8, 0x4E),
+            Co2Comfort::Fair => Color::rgb(0xFF, 0xFF, 0x00),
+            Co2Comfort::Poor => Color::rgb(0xFF, 0x7E, 0x00),
+            Co2Comfort::Bad => Color::rgb(0xFF, 0x00, 0x00),
+        }
+    }
+}
+
+/// Measures CO2 and returns its comfort band.
+pub fn co2_comfort_from<S: CO2Sensor>(sensor: &mut S) -> Result<Co2Comfort, PeripheralError> {
+    Ok(Co2Comfort::from_ppm(sensor.get_co2()?))
+}
+
+/// Indoor air quality level of the TVOC concentration, as recommended for
+/// metal-oxide sensors like SGP30.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TvocLevel {
+    /// Below 65 ppb.
+    Excellent,
+    /// 65 to 220 ppb.
+    Good,
+    /// 220 to 660 ppb.
+    Moderate,
+    /// 660 to 2200 ppb.
+    Poor,
+    /// 2200 ppb and above.
+    Unhealthy,
+}
+
+impl TvocLevel {
+    /// Returns the level of a TVOC concentration in ppb.
+    pub fn from_ppb(ppb: f32) -> Self {
+        if ppb < 65.0 {
+            TvocLevel::Excellent
+        } else if ppb < 220.0 {
+            TvocLevel::Good
+        } else if ppb < 660.0 {
+            TvocLevel::Moderate
+        } else if ppb < 2200.0 {
+            TvocLevel::Poor
+        } else {
+            TvocLevel::Unhealthy
+        }
+    }
+
+    /// Returns the name of the level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TvocLevel::Excellent => "Excellent",
+            TvocLevel::Good => "Good",
+            TvocLevel::Moderate => "Moderate",
+            TvocLevel::Poor => "Poor",
+            TvocLevel::Unhealthy => "Unhealthy",
+        }
+    }
+
+    /// Returns the colour of the level.
+    pub fn color(&self) -> Color {
+        match self {
+            TvocLevel::Excellent => Color::rgb(0x00, 0xE4, 0x00),
+            TvocLevel::Good => Color::rgb(0x9C, 0xD8, 0x4E),
+            TvocLevel::Moderate => Color::rgb(0xFF, 0xFF, 0x00),
+            TvocLevel::Poor => Color::rgb(0xFF, 0x7E, 0x00),
+            TvocLevel::Unhealthy => Color::rgb(0xFF, 0x00, 0x00),
+        }
+    }
+}
+
+/// Measures TVOC and returns its level.
+pub fn tvoc_level_from<S: VOCSensor>(sensor: &mut S) -> Result<TvocLevel, PeripheralError> {
+    Ok(TvocLevel::from_ppb(sensor.get_voc()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pm(pm2_5: f32, pm10: f32) -> ParticulateMatter {
+        ParticulateMatter {
+            pm1_0: None,
+            pm2_5,
+            pm10,
+        }
+    }
+
+    #[test]
+    fn us_pm2_5_breakpoints() {
+        for (concentration, index) in [
+            (0.0, 0),
+            (9.0, 50),
+            // Truncated to 9.0, between the breakpoints.
+            (9.09, 50),
+            (9.1, 51),
+            (12.0, 56),
+            (35.4, 100),
+            (35.49, 100),
+            (35.5, 101),
+            (55.4, 150),
+            (55.5, 151),
+            (125.4, 200),
+            (125.5, 201),
+            (225.4, 300),
+            (225.5, 301),
+            (325.4, 500),
+            (600.0, 500),
+            (-3.0, 0),
+        ] {
+            assert_eq!(us_aqi_pm2_5(concentration), index, "{concentration}");
+        }
+    }
+
+    #[test]
+    fn us_pm10_breakpoints() {
+        for (concentration, index) in [
+            (0.0, 0),
+            (54.0, 50),
+            // Truncated to whole µg/m³.
+            (54.9, 50),
+            (55.0, 51),
+            (154.9, 100),
+            (155.0, 101),
+            (254.0, 150),
+            (255.0, 151),
+            (354.0, 200),
+            (355.0, 201),
+            (424.0, 300),
+            (425.0, 301),
+            (604.0, 500),
+            (1000.0, 500),
+        ] {
+            assert_eq!(us_aqi_pm10(concentration), index, "{concentration}");
+        }
+    }
+
+    #[test]
+    fn us_aqi_dominant_pollutant() {
+        assert_eq!(
+            us_aqi(&pm(12.0, 200.0)),
+            UsAqi {
+                index: 123,
+                category: AqiCategory::UnhealthyForSensitiveGroups,
+                dominant: Pollutant::Pm10,
+            }
+        );
+        assert_eq!(
+            us_aqi(&pm(35.4, 20.0)),
+            UsAqi {
+                index: 100,
+                category: AqiCategory::Moderate,
+                dominant: Pollutant::Pm2_5,
+            }
+        );
+    }
+
+    #[test]
+    fn us_categories() {
+        for (index, category) in [
+            (0, AqiCategory::Good),
+            (50, AqiCategory::Good),
+            (51, AqiCategory::Moderate),
+            (100, AqiCategory::Moderate),
+            (101, AqiCategory::UnhealthyForSensitiveGroups),
+            (150, AqiCategory::UnhealthyForSensitiveGroups),
+            (151, AqiCategory::Unhealthy),
+            (200, AqiCategory::Unhealthy),
+            (201, AqiCategory::VeryUnhealthy),
+            (300, AqiCategory::VeryUnhealthy),
+            (301, AqiCategory::Hazardous),
+            (500, AqiCategory::Hazardous),
+        ] {
+            assert_eq!(AqiCategory::from_index(index), category, "{index}");
+        }
+    }
+
+    #[test]
+    fn caqi_grid() {
+        for (concentration, index) in [
+            (0.0, 0),
+            (15.0, 25),
+            (30.0, 50),
+            (55.0, 75),
+            (110.0, 100),
+            (220.0, 150),
+            (1000.0, 200),
+        ] {
+            assert_eq!(caqi_pm2_5(concentration), index, "{concentration}");
+        }
+        for (concentration, index) in [
+            (0.0, 0),
+            (25.0, 25),
+            (50.0, 50),
+            (90.0, 75),
+            (180.0, 100),
+            (1000.0, 200),
+        ] {
+            assert_eq!(caqi_pm10(concentration), index, "{concentration}");
+        }
+
+        for (index, level) in [
+            (0, CaqiLevel::VeryLow),
+            (24, CaqiLevel::VeryLow),
+            (25, CaqiLevel::Low),
+            (49, CaqiLevel::Low),
+            (50, CaqiLevel::Medium),
+            (74, CaqiLevel::Medium),
+            (75, CaqiLevel::High),
+            (100, CaqiLevel::High),
+            (101, CaqiLevel::VeryHigh),
+        ] {
+            assert_eq!(CaqiLevel::from_index(index), level, "{index}");
+        }
+
+        assert_eq!(
+            caqi(&pm(10.0, 60.0)),
+            Caqi {
+                index: 56,
+                level: CaqiLevel::Medium,
+                dominant: Pollutant::Pm10,
+            }
+        );
+    }
+
+    #[test]
+    fn co2_bands() {
+        for (ppm, band) in [
+            (400.0, Co2Comfort::Excellent),
+            (799.9, Co2Comfort::Excellent),
+            (800.0, Co2Comfort::Good),
+            (999.9, Co2Comfort::Good),
+            (1000.0, Co2Comfort::Fair),
+            (1399.9, Co2Comfort::Fair),
+            (1400.0, Co2Comfort::Poor),
+            (1999.9, Co2Comfort::Poor),
+            (2000.0, Co2Comfort::Bad),
+            (5000.0, Co2Comfort::Bad),
+        ] {
+            assert_eq!(Co2Comfort::from_ppm(ppm), band, "{ppm}");
+        }
+    }
+
+    #[test]
+    fn tvoc_bands() {
+        for (ppb, level) in [
+            (0.0, TvocLevel::Excellent),
+            (64.9, TvocLevel::Excellent),
+            (65.0, TvocLevel::Good),
+            (219.9, TvocLevel::Good),
+            (220.0, TvocLevel::Moderate),
+            (659.9, TvocLevel::Moderate),
+            (660.0, TvocLevel::Poor),
+            (2199.9, TvocLevel::Poor),
+            (2200.0, TvocLevel::Unhealthy),
+        ] {
+            assert_eq!(TvocLevel::from_ppb(ppb), level, "{ppb}");
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! # Battery Monitor Module
+//!
+//! This module provides battery voltage monitoring over an ADC pin connected
+//! to the battery through a resistor divider. The state of charge is estimated
+//! from a discharge curve of the battery chemistry (LiPo, LiFePO4 and alkaline
+//! presets are provided) and a low-battery warning with hysteresis can be
+//! raised.
+//!
+//! `Battery` holds the divider, the chemistry and the warning state and does
+//! not touch any hardware, so it can be used with millivolts read by any other
+//! means. `BatteryMonitor` owns the ADC driver and implements `BatterySensor`,
+//! so the battery can be published like any other sensor.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     battery::{BatteryEvent, Chemistry, Divider},
+//!     BatterySensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! // 1S LiPo through a 100k/100k divider
+//! let mut battery = esp_ward::create_battery_monitor!(
+//!     peripherals,
+//!     pins.gpio3.into_analog(),
+//!     Divider::new(100_000.0, 100_000.0),
+//!     Chemistry::LiPo
+//! );
+//!
+//! loop {
+//!     println!(
+//!         "Battery: {} V, {}%",
+//!         battery.get_battery_voltage().unwrap(),
+//!         battery.get_state_of_charge().unwrap()
+//!     );
+//!     if let Some(BatteryEvent::Low) = battery.update_alarm().unwrap() {
+//!         println!("Battery low!");
+//!     }
+//!     esp_ward::wait!(delay, 60_000);
+//! }
+//! ```
+
+use embedded_hal::adc::{Channel, OneShot};
+use esp_hal::delay::Delay;
+
+use super::{
+    analog::{interpolate, AnalogSensor},
+    BatterySensor,
+    PeripheralError,
+    UnifiedData,
+};
+
+/// Discharge curve of a LiPo/Li-ion cell: `(millivolts, percent)`.
+pub const LIPO_CURVE: [(f32, f32); 21] = [
+    (3270.0, 0.0),
+    (3610.0, 5.0),
+    (3690.0, 10.0),
+    (3710.0, 15.0),
+    (3730.0, 20.0),
+    (3750.0, 25.0),
+    (3770.0, 30.0),
+    (3790.0, 35.0),
+    (3800.0, 40.0),
+    (3820.0, 45.0),
+    (3840.0, 50.0),
+    (3850.0, 55.0),
+    (3870.0, 60.0),
+    (3910.0, 65.0),
+    (3950.0, 70.0),
+    (3980.0, 75.0),
+    (4020.0, 80.0),
+    (4080.0, 85.0),
+    (4110.0, 90.0),
+    (4150.0, 95.0),
+    (4200.0, 100.0),
+];
+
+/// Discharge curve of a LiFePO4 cell at rest: `(millivolts, percent)`.
+pub const LIFEPO4_CURVE: [(f32, f32); 11] = [
+    (2500.0, 0.0),
+    (3000.0, 9.0),
+    (3200.0, 17.0),
+    (3220.0, 20.0),
+    (3250.0, 30.0),
+    (3270.0, 40.0),
+    (3300.0, 70.0),
+    (3320.0, 90.0),
+    (3350.0, 99.0),
+    (3400.0, 100.0),
+    (3650.0, 100.0),
+];
+
+/// Discharge curve of an alkaline cell: `(millivolts, percent)`.
+pub const ALKALINE_CURVE: [(f32, f32); 7] = [
+    (1000.0, 0.0),
+    (1100.0, 10.0),
+    (1200.0, 20.0),
+    (1300.0, 40.0),
+    (1400.0, 60.0),
+    (1500.0, 80.0),
+    (1600.0, 100.0),
+];
+
+/// Chemistry of the battery, defining its discharge curve per cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chemistry {
+    LiPo,
+    LiFePO4,
+    Alkaline,
+    /// Custom discharge curve of a cell, `(millivolts, percent)` sorted by
+    /// millivolts.
+    Custom(&'static [(f32, f32)]),
+}
+
+impl Chemistry {
+    /// Returns the discharge curve of a single cell.
+    pub fn curve(&self) -> &'static [(f32, f32)] {
+        match self {
+            Chemistry::LiPo => &LIPO_CURVE,
+            Chemistry::LiFePO4 => &LIFEPO4_CURVE,
+            Chemistry::Alkaline => &ALKALINE_CURVE,
+            Chemistry::Custom(curve) => curve,
+        }
+    }
+}
+
+/// Resistor divider between the battery and the ADC pin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divider {
+    /// Resistor between the battery and the ADC pin in ohms.
+    pub top: f32,
+    /// Resistor between the ADC pin and GND in ohms.
+    pub bottom: f32,
+}
+
+impl Divider {
+    /// Creates a new divider from its resistors in ohms.
+    pub const fn new(top: f32, bottom: f32) -> Self {
+        Divider { top, bottom }
+    }
+
+    /// Battery connected directly to the ADC pin.
+    pub const fn none() -> Self {
+        Divider {
+            top: 0.0,
+            bottom: 1.0,
+        }
+    }
+
+    /// Ratio of the battery voltage to the voltage on the ADC pin.
+    pub fn ratio(&self) -> f32 {
+        (self.top + self.bottom) / self.bottom
+    }
+}
+
+/// Change of the low-battery warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryEvent {
+    /// State of charge dropped to the low threshold.
+    Low,
+    /// State of charge rose above the recovery threshold.
+    Recovered,
+}
+
+/// Battery parameters and low-battery warning state.
+pub struct Battery {
+    /// Divider between the battery and the ADC pin.
+    pub divider: Divider,
+    /// Chemistry of the cells.
+    pub chemistry: Chemistry,
+    /// Amount of cells in series.
+    pub cells: u8,
+    /// State of charge (percent) at which the battery becomes low.
+    pub low_threshold: f32,
+    /// State of charge (percent) at which a low battery is recovered, should
+    /// be above `low_threshold` to avoid toggling.
+    pub recover_threshold: f32,
+    low: bool,
+}
+
+impl Battery {
+    /// Creates a new single cell battery warning at 15% and recovering at
+    /// 20%.
+    pub const fn new(divider: Divider, chemistry: Chemistry) -> Self {
+        Battery {
+            divider,
+            chemistry,
+            cells: 1,
+            low_threshold: 15.0,
+            recover_threshold: 20.0,
+            low: false,
+        }
+    }
+
+    /// Converts the voltage on the ADC pin to the battery voltage.
+    pub fn battery_millivolts(&self, pin_millivolts: f32) -> f32 {
+        pin_millivolts * self.divider.ratio()
+    }
+
+    /// Estimates the state of charge in percent from the battery voltage.
+    pub fn state_of_charge(&self, battery_millivolts: f32) -> f32 {
+        let cell_millivolts = battery_millivolts / self.cells.max(1) as f32;
+        interpolate(self.chemistry.curve(), cell_millivolts)
+    }
+
+    /// Returns `true` if the battery is low.
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    /// Updates the low-battery warning with the current state of charge.
+    ///
+    /// # Returns
+    /// `Some(BatteryEvent)` when the warning changed, `None` otherwise.
+    pub fn update_alarm(&mut self, state_of_charge: f32) -> Option<BatteryEvent> {
+        if !self.low && state_of_charge <= self.low_threshold {
+            self.low = true;
+            Some(BatteryEvent::Low)
+        } else if self.low && state_of_charge >= self.recover_threshold {
+            self.low = false;
+            Some(BatteryEvent::Recovered)
+        } else {
+            None
+        }
+    }
+}
+
+/// A battery monitor owning its ADC driver.
+pub struct BatteryMonitor<ADCI, A, PIN> {
+    /// The ADC driver.
+    pub adc: A,
+    /// The ADC pin connected to the divider.
+    pub sensor: AnalogSensor<PIN>,
+    /// Battery parameters and low-battery warning state.
+    pub battery: Battery,
+    _adc_instance: core::marker::PhantomData<ADCI>,
+}
+
+impl<ADCI, A, PIN> BatteryMonitor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    /// Creates a new `BatteryMonitor`.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC driver.
+    /// * `sensor` - The analog sensor on the divider, reporting millivolts.
+    /// * `battery` - Battery parameters.
+    ///
+    /// # Returns
+    /// A new `BatteryMonitor` instance.
+    pub fn create_on_adc(adc: A, sensor: AnalogSensor<PIN>, battery: Battery) -> Self {
+        BatteryMonitor {
+            adc,
+            sensor,
+            battery,
+            _adc_instance: core::marker::PhantomData,
+        }
+    }
+
+    /// Reads the battery and updates the low-battery warning.
+    ///
+    /// # Returns
+    /// `Ok(Some(BatteryEvent))` when the warning changed, `Ok(None)` otherwise,
+    /// or `Err(PeripheralError::ReadError)` if the ADC cannot be read.
+    pub fn update_alarm(&mut self) -> Result<Option<BatteryEvent>, PeripheralError> {
+        let state_of_charge = self.get_state_of_charge()?;
+        Ok(self.battery.update_alarm(state_of_charge))
+    }
+
+    fn read_battery_millivolts(&mut self) -> Result<f32, PeripheralError> {
+        let pin_millivolts = self.sensor.read_millivolts(&mut self.adc)?;
+        Ok(self.battery.battery_millivolts(pin_millivolts))
+    }
+}
+
+impl<ADCI, A, PIN> BatterySensor for BatteryMonitor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    /// Reads the battery voltage.
+    ///
+    /// # Returns
+    /// Returns an `Ok(f32)` representing the voltage in volts if the read is
+    /// successful, or `Err(PeripheralError::ReadError)` if the ADC cannot be
+    /// read.
+    fn get_battery_voltage(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_battery_millivolts()? / 1000.0)
+    }
+
+    /// Estimates the state of charge of the battery.
+    ///
+    /// # Returns
+    /// Returns an `Ok(f32)` representing the state of charge in percent if the
+    /// read is successful, or `Err(PeripheralError::ReadError)` if the ADC
+    /// cannot be read.
+    fn get_state_of_charge(&mut self) -> Result<f32, PeripheralError> {
+        let millivolts = self.read_battery_millivolts()?;
+        Ok(self.battery.state_of_charge(millivolts))
+    }
+}
+
+impl<ADCI, A, PIN> UnifiedData for BatteryMonitor<ADCI, A, PIN>
+where
+    PIN: Channel<ADCI>,
+    A: OneShot<ADCI, u16, PIN>,
+{
+    type Output = (f32, f32);
+    /// Reads the battery voltage and state of charge.
+    ///
+    /// # Returns
+    /// Returns an `Ok((f32, f32))` representing the voltage in volts and the
+    /// state of charge in percent if the read is successful, or
+    /// `Err(PeripheralError::ReadError)` if the ADC cannot be read.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        let millivolts = self.read_battery_millivolts()?;
+        Ok((
+            millivolts / 1000.0,
+            self.battery.state_of_charge(millivolts),
+        ))
+    }
+}
+
+/// Macro for creating a `BatteryMonitor` on an ADC1 pin. The pin uses 11 dB
+/// attenuation and owns the ADC1 driver.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$pin` - The GPIO pin in analog mode.
+/// * `$divider` - The `Divider` between the battery and the pin.
+/// * `$chemistry` - The `Chemistry` of the battery.
+///
+/// # Returns
+/// A new `BatteryMonitor` instance.
+#[macro_export]
+macro_rules! create_battery_monitor {
+    ($peripherals: expr, $pin: expr, $divider: expr, $chemistry: expr) => {{
+        let (sensor, adc1) = esp_ward::create_analog_sensor!(
+            $peripherals,
+            $pin,
+            esp_hal::analog::adc::Attenuation::Attenuation11dB
+        );
+        esp_ward::peripherals::battery::BatteryMonitor::create_on_adc(
+            adc1,
+            sensor,
+            esp_ward::peripherals::battery::Battery::new($divider, $chemistry),
+        )
+    }};
+}
+
+pub use create_battery_monitor;
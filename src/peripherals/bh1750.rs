@@ -0,0 +1,318 @@
+//! # BH1750 Light Sensor Module
+//!
+//! This module provides a driver for the ROHM BH1750 (BH1750FVI) ambient light
+//! sensor over I2C. It measures from 1 to 65535 lux in its high resolution
+//! mode (1 lux), 0.5 lux in the high resolution mode 2 and 4 lux in the fast
+//! low resolution mode.
+//!
+//! The measurement time register (`measurement_time`, 31 to 254, 69 by
+//! default) scales the sensitivity: larger values measure darker scenes with
+//! a finer resolution but take longer, smaller values extend the range to
+//! about 100000 lux, e.g. behind a dark window.
+//!
+//! In `Bh1750Mode::OneShot` every reading starts a measurement, waits for it
+//! and powers the sensor down afterwards; in `Bh1750Mode::Continuous` the
+//! sensor measures all the time and readings return the latest result.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     bh1750::{Bh1750, Bh1750Config, Bh1750Mode, Resolution},
+//!     LumiSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let config = Bh1750Config {
+//!     resolution: Resolution::High2,
+//!     mode: Bh1750Mode::OneShot,
+//!     ..Default::default()
+//! };
+//! let mut sensor = Bh1750::create_on_i2c_with_config(i2c, delay, config).unwrap();
+//! loop {
+//!     println!("Light: {} lx", sensor.get_lux().unwrap());
+//!     delay.delay_ms(10_000u32);
+//! }
+//! ```
+
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Read, Write},
+};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{I2cPeriph, LumiSensor, PeripheralError, Shutdown, UnifiedData};
+
+/// I2C address of the BH1750 with the ADDR pin low.
+pub const DEFAULT_ADDRESS: u8 = 0x23;
+/// I2C address of the BH1750 with the ADDR pin high.
+pub const ALTERNATE_ADDRESS: u8 = 0x5C;
+
+/// Default value of the measurement time register.
+pub const DEFAULT_MEASUREMENT_TIME: u8 = 69;
+/// Smallest value of the measurement time register.
+pub const MIN_MEASUREMENT_TIME: u8 = 31;
+/// Largest value of the measurement time register.
+pub const MAX_MEASUREMENT_TIME: u8 = 254;
+
+const OP_POWER_DOWN: u8 = 0x00;
+const OP_POWER_ON: u8 = 0x01;
+const OP_RESET: u8 = 0x07;
+const OP_CONTINUOUS: u8 = 0x10;
+const OP_ONE_TIME: u8 = 0x20;
+const OP_MEASUREMENT_TIME_HIGH: u8 = 0x40;
+const OP_MEASUREMENT_TIME_LOW: u8 = 0x60;
+
+/// Resolution of the measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// 1 lux, up to 180 ms per measurement.
+    High,
+    /// 0.5 lux, up to 180 ms per measurement.
+    High2,
+    /// 4 lux, up to 24 ms per measurement.
+    Low,
+}
+
+impl Resolution {
+    /// Offset of the resolution in the measurement opcodes.
+    fn opcode(self) -> u8 {
+        match self {
+            Resolution::High => 0x00,
+            Resolution::High2 => 0x01,
+            Resolution::Low => 0x03,
+        }
+    }
+
+    /// Longest measurement time in milliseconds at the default measurement
+    /// time register.
+    fn max_time_ms(self) -> u32 {
+        match self {
+            Resolution::High | Resolution::High2 => 180,
+            Resolution::Low => 24,
+        }
+    }
+}
+
+/// When the sensor measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bh1750Mode {
+    /// Measures all the time.
+    Continuous,
+    /// Measures on every reading and powers down in between.
+    OneShot,
+}
+
+/// Configuration of a BH1750.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bh1750Config {
+    /// I2C address, `DEFAULT_ADDRESS` or `ALTERNATE_ADDRESS`.
+    pub address: u8,
+    pub resolution: Resolution,
+    pub mode: Bh1750Mode,
+    /// Measurement time register, 31 to 254.
+    pub measurement_time: u8,
+}
+
+impl Default for Bh1750Config {
+    fn default() -> Self {
+        Bh1750Config {
+            address: DEFAULT_ADDRESS,
+            resolution: Resolution::High,
+            mode: Bh1750Mode::Continuous,
+            measurement_time: DEFAULT_MEASUREMENT_TIME,
+        }
+    }
+}
+
+impl Bh1750Config {
+    /// Returns the longest time of a measurement in milliseconds.
+    pub fn measurement_time_ms(&self) -> u32 {
+        let max_ms = self.resolution.max_time_ms() * self.measurement_time as u32;
+        (max_ms + DEFAULT_MEASUREMENT_TIME as u32 - 1) / DEFAULT_MEASUREMENT_TIME as u32
+    }
+
+    /// Converts a raw measurement to lux.
+    pub fn lux(&self, raw: u16) -> f32 {
+        let lux = raw as f32 / 1.2 * DEFAULT_MEASUREMENT_TIME as f32 / self.measurement_time as f32;
+        match self.resolution {
+            Resolution::High2 => lux / 2.0,
+            _ => lux,
+        }
+    }
+}
+
+/// Represents a BH1750 ambient light sensor.
+pub struct Bh1750<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the sensor is connected to.
+    pub bus: BUS,
+    /// Delay provider for waiting on measurements.
+    pub delay: Delay,
+    config: Bh1750Config,
+}
+
+impl<BUS, E> Bh1750<BUS>
+where
+    BUS: Read<Error = E> + Write<Error = E>,
+{
+    /// Resets and configures the BH1750.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the sensor is connected to.
+    /// * `delay` - A delay provider for waiting on measurements.
+    /// * `config` - Address, resolution, mode and measurement time, which is
+    ///   clamped to 31 to 254.
+    ///
+    /// # Returns
+    /// A new `Bh1750` instance, or `PeripheralError::InitializationFailed` if
+    /// the sensor does not respond.
+    pub fn create_on_i2c_with_config(
+        bus: BUS,
+        delay: Delay,
+        mut config: Bh1750Config,
+    ) -> Result<Self, PeripheralError> {
+        config.measurement_time = config
+            .measurement_time
+            .clamp(MIN_MEASUREMENT_TIME, MAX_MEASUREMENT_TIME);
+        let mut sensor = Bh1750 { bus, delay, config };
+        sensor
+            .init()
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(sensor)
+    }
+
+    fn init(&mut self) -> Result<(), PeripheralError> {
+        self.send(OP_POWER_ON)?;
+        self.send(OP_RESET)?;
+        self.write_measurement_time(self.config.measurement_time)?;
+        self.start()
+    }
+
+    fn send(&mut self, opcode: u8) -> Result<(), PeripheralError> {
+        self.bus
+            .write(self.config.address, &[opcode])
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn write_measurement_time(&mut self, measurement_time: u8) -> Result<(), PeripheralError> {
+        self.send(OP_MEASUREMENT_TIME_HIGH | (measurement_time >> 5))?;
+        self.send(OP_MEASUREMENT_TIME_LOW | (measurement_time & 0x1F))
+    }
+
+    /// Starts continuous measurements and waits for the first one; does
+    /// nothing in one-shot mode.
+    fn start(&mut self) -> Result<(), PeripheralError> {
+        if self.config.mode == Bh1750Mode::Continuous {
+            self.send(OP_CONTINUOUS | self.config.resolution.opcode())?;
+            self.delay.delay_ms(self.config.measurement_time_ms());
+        }
+        Ok(())
+    }
+
+    /// Returns the current configuration.
+    pub fn config(&self) -> &Bh1750Config {
+        &self.config
+    }
+
+    /// Sets the resolution.
+    pub fn set_resolution(&mut self, resolution: Resolution) -> Result<(), PeripheralError> {
+        self.config.resolution = resolution;
+        self.start()
+    }
+
+    /// Sets when the sensor measures.
+    pub fn set_mode(&mut self, mode: Bh1750Mode) -> Result<(), PeripheralError> {
+        self.config.mode = mode;
+        match mode {
+            Bh1750Mode::Continuous => {
+                self.send(OP_POWER_ON)?;
+                self.start()
+            }
+            Bh1750Mode::OneShot => self.send(OP_POWER_DOWN),
+        }
+    }
+
+    /// Sets the measurement time register, which scales the sensitivity.
+    ///
+    /// # Arguments
+    /// * `measurement_time` - The register value, clamped to 31 to 254.
+    pub fn set_measurement_time(&mut self, measurement_time: u8) -> Result<(), PeripheralError> {
+        let measurement_time = measurement_time.clamp(MIN_MEASUREMENT_TIME, MAX_MEASUREMENT_TIME);
+        self.write_measurement_time(measurement_time)?;
+        self.config.measurement_time = measurement_time;
+        self.start()
+    }
+
+    /// Reads a raw measurement, starting it first in one-shot mode.
+    pub fn read_raw(&mut self) -> Result<u16, PeripheralError> {
+        if self.config.mode == Bh1750Mode::OneShot {
+            self.send(OP_ONE_TIME | self.config.resolution.opcode())?;
+            self.delay.delay_ms(self.config.measurement_time_ms());
+        }
+        let mut data = [0u8; 2];
+        self.bus
+            .read(self.config.address, &mut data)
+            .map_err(|_| PeripheralError::ReadError)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Powers the sensor down until the next one-shot measurement or
+    /// `set_mode`.
+    pub fn power_down(&mut self) -> Result<(), PeripheralError> {
+        self.send(OP_POWER_DOWN)
+    }
+}
+
+impl I2cPeriph for Bh1750 {
+    type Returnable = Self;
+
+    /// Creates a new `Bh1750` with the default configuration.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the sensor.
+    /// * `delay` - A delay provider for waiting on measurements.
+    ///
+    /// # Returns
+    /// A result containing the `Bh1750` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Bh1750::create_on_i2c_with_config(bus, delay, Bh1750Config::default())
+    }
+}
+
+impl<BUS, E> LumiSensor for Bh1750<BUS>
+where
+    BUS: Read<Error = E> + Write<Error = E>,
+{
+    fn get_lux(&mut self) -> Result<f32, PeripheralError> {
+        let raw = self.read_raw()?;
+        Ok(self.config.lux(raw))
+    }
+}
+
+impl<BUS, E> UnifiedData for Bh1750<BUS>
+where
+    BUS: Read<Error = E> + Write<Error = E>,
+{
+    type Output = f32;
+    /// Measures the ambient light in lux.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_lux()
+    }
+}
+
+impl<BUS, E> Shutdown for Bh1750<BUS>
+where
+    BUS: Read<Error = E> + Write<Error = E>,
+{
+    /// Powers the sensor down, see `Bh1750::power_down`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.power_down()
+    }
+}
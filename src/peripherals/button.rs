@@ -2,89 +2,356 @@
 //!
 //! This module provides a simple interface for button handling with debouncing
 //! algorythm. It can be used to detect press and release events for a button
-//! connected to an input pin.
+//! connected to an input pin, as well as more complex gestures: clicks,
+//! double-clicks, long presses and hold-repeat.
+//!
+//! There are two ways to work with a `Button`:
+//! - `Button::poll` - blocking debouncing (waits 30 ms inside the call),
+//!   reports only `Pressed`/`Released`.
+//! - `Button::update` - non-blocking, time-based debouncing driven by a
+//!   timestamp you pass in. Reports every `Event`, including gestures.
+//!
+//! The gesture recognition itself lives in `GestureDetector`, which does not
+//! touch any hardware and can be fed with synthetic timestamps.
+//!
+//! For interrupt-driven operation see `Button::listen` and
+//! `peripherals::gpio_events`.
+//!
 //! ## Example
-/// ```no_run
-/// use esp_hal::delay::Delay;
-/// use esp_ward::peripherals::button::{Button, Event};
-///
-/// // Suppose the button is connected to GPIO23
-/// let peripherals = take_periph!();
-/// let system = take_system!(peripherals);
-/// let (clocks, pins) = init_chip!(peripherals, system);
-/// let mut button = Button::new(pins.gpio23.into_pull_up_input());
-/// let mut delay = Delay::new(&clocks);
-///
-/// loop {
-///     // With `match`
-///     match button.poll(&mut delay) {
-///         Event::Pressed => println!("Button pressed!"),
-///         Event::Released => println!("Button released!"),
-///         Event::Nothing => (),
-///     }
-///
-///     // Or `if let...`
-///     if let crate::peripherals::button::Event::Pressed = self.select.poll(&mut delay) {
-///         // your callback if button was pressed
-///     } else {
-///         // your callback if not
-///     }
-/// }
-/// ```
+//! ```no_run
+//! use esp_hal::{delay::Delay, systimer::SystemTimer};
+//! use esp_ward::peripherals::button::{ActiveLevel, Button, ButtonConfig, Event};
+//!
+//! // Suppose the button is connected to GPIO23 and closes to GND
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//! let mut button = Button::create_on_pins_with_config(
+//!     pins.gpio23.into_pull_up_input(),
+//!     ButtonConfig {
+//!         active_level: ActiveLevel::Low,
+//!         repeat_interval_ms: Some(200),
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     match button.update(now) {
+//!         Event::Click => println!("Click!"),
+//!         Event::DoubleClick => println!("Double click!"),
+//!         Event::LongPress => println!("Long press!"),
+//!         Event::Repeat => println!("Still holding..."),
+//!         _ => (),
+//!     }
+//! }
+//! ```
+#[cfg(feature = "esp-hal")]
 use embedded_hal::blocking::delay::DelayMs;
+#[cfg(feature = "esp-hal")]
 use esp_hal::delay::Delay;
 
-use super::{PeripheralError, UnifiedData};
+#[cfg(feature = "esp-hal")]
+use super::{
+    gpio_events::{handle_pin_interrupt, EventQueue},
+    PeripheralError,
+    UnifiedData,
+};
 
 /// Represents possible events from a button press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
+    /// The button went to the pressed state.
     Pressed,
+    /// The button went to the released state.
     Released,
+    /// A single short press, reported once the double-click window expired.
+    Click,
+    /// Two short presses within the double-click window.
+    DoubleClick,
+    /// The button has been held for at least `ButtonConfig::long_press_ms`.
+    LongPress,
+    /// The button is still held after a long press. Reported every
+    /// `ButtonConfig::repeat_interval_ms`.
+    Repeat,
     Nothing,
 }
+
+/// Logic level of the input pin at which the button is considered pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveLevel {
+    /// Pressed when the pin reads low (button closes to GND, pull-up).
+    Low,
+    /// Pressed when the pin reads high (button closes to VCC, pull-down).
+    High,
+}
+
+/// Configuration of the button wiring and gesture timings.
+/// All timings are in milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct ButtonConfig {
+    /// Level of the pin at which the button is pressed.
+    pub active_level: ActiveLevel,
+    /// Time the pin level has to stay unchanged to be accepted.
+    pub debounce_ms: u32,
+    /// Maximum time between the release of the first click and the press of
+    /// the second one to be reported as `Event::DoubleClick`.
+    pub double_click_ms: u32,
+    /// Hold time after which `Event::LongPress` is reported.
+    pub long_press_ms: u32,
+    /// Period of `Event::Repeat` after a long press, `None` disables it.
+    pub repeat_interval_ms: Option<u32>,
+}
+
+impl Default for ButtonConfig {
+    /// Default configuration. `ActiveLevel::High` is kept as default to match
+    /// the behaviour of `Button::poll`.
+    fn default() -> Self {
+        ButtonConfig {
+            active_level: ActiveLevel::High,
+            debounce_ms: 30,
+            double_click_ms: 300,
+            long_press_ms: 800,
+            repeat_interval_ms: None,
+        }
+    }
+}
+
+/// Hardware independent state machine turning a stream of raw button levels
+/// into debounced `Event`s.
+pub struct GestureDetector {
+    config: ButtonConfig,
+    /// Whether the first sample has been taken.
+    initialized: bool,
+    /// Last raw (not debounced) state and the time it was first seen.
+    raw: bool,
+    raw_since: u64,
+    /// Debounced state.
+    stable: bool,
+    /// Time of the debounced press.
+    pressed_at: u64,
+    /// Whether `LongPress` was already reported for the current press.
+    long_fired: bool,
+    /// Time of the next `Repeat` event.
+    next_repeat: u64,
+    /// Amount of short presses waiting for the double-click decision.
+    clicks: u8,
+    /// Time of the last debounced short release.
+    released_at: u64,
+    /// Event to be reported on the next update.
+    pending: Option<Event>,
+}
+
+impl GestureDetector {
+    /// Creates a new detector with the given timings.
+    pub const fn new(config: ButtonConfig) -> Self {
+        GestureDetector {
+            config,
+            initialized: false,
+            raw: false,
+            raw_since: 0,
+            stable: false,
+            pressed_at: 0,
+            long_fired: false,
+            next_repeat: 0,
+            clicks: 0,
+            released_at: 0,
+            pending: None,
+        }
+    }
+
+    /// Returns the configuration used by the detector.
+    pub fn config(&self) -> &ButtonConfig {
+        &self.config
+    }
+
+    /// Returns the debounced state of the button.
+    pub fn is_pressed(&self) -> bool {
+        self.stable
+    }
+
+    /// Feeds the detector with the current state of the button.
+    ///
+    /// This method should be called repeatedly (e.g. every main loop
+    /// iteration), at least once per `debounce_ms`. When several events happen
+    /// at once (e.g. `Released` and `DoubleClick`), they are reported on
+    /// consecutive calls.
+    ///
+    /// # Arguments
+    /// * `pressed` - `true` if the button is pressed right now (raw level).
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// An `Event`, or `Event::Nothing` if nothing happened.
+    pub fn update(&mut self, pressed: bool, now_ms: u64) -> Event {
+        if let Some(event) = self.pending.take() {
+            return event;
+        }
+
+        // The first sample is taken as the initial state, so a button held
+        // during startup is not reported as pressed.
+        if !self.initialized {
+            self.initialized = true;
+            self.raw = pressed;
+            self.stable = pressed;
+            self.raw_since = now_ms;
+            self.pressed_at = now_ms;
+            self.long_fired = pressed;
+            return Event::Nothing;
+        }
+
+        if pressed != self.raw {
+            self.raw = pressed;
+            self.raw_since = now_ms;
+        }
+
+        if self.raw != self.stable
+            && now_ms.saturating_sub(self.raw_since) >= self.config.debounce_ms as u64
+        {
+            self.stable = self.raw;
+            return if self.stable {
+                self.on_press()
+            } else {
+                self.on_release()
+            };
+        }
+
+        if self.stable {
+            if !self.long_fired
+                && now_ms.saturating_sub(self.pressed_at) >= self.config.long_press_ms as u64
+            {
+                self.long_fired = true;
+                self.clicks = 0;
+                if let Some(interval) = self.config.repeat_interval_ms {
+                    self.next_repeat = now_ms + interval as u64;
+                }
+                return Event::LongPress;
+            }
+            if let Some(interval) = self.config.repeat_interval_ms {
+                if self.long_fired && now_ms >= self.next_repeat {
+                    self.next_repeat = now_ms + interval as u64;
+                    return Event::Repeat;
+                }
+            }
+        } else if self.clicks == 1 && self.double_click_expired(now_ms) {
+            self.clicks = 0;
+            return Event::Click;
+        }
+
+        Event::Nothing
+    }
+
+    /// Handles a debounced transition to the pressed state.
+    fn on_press(&mut self) -> Event {
+        self.pressed_at = self.raw_since;
+        self.long_fired = false;
+
+        // The first click was not reported yet, but the window is over
+        if self.clicks == 1 && self.double_click_expired(self.raw_since) {
+            self.clicks = 0;
+            self.pending = Some(Event::Pressed);
+            return Event::Click;
+        }
+        Event::Pressed
+    }
+
+    /// Handles a debounced transition to the released state.
+    fn on_release(&mut self) -> Event {
+        if self.long_fired {
+            return Event::Released;
+        }
+
+        self.clicks += 1;
+        if self.clicks >= 2 {
+            self.clicks = 0;
+            self.pending = Some(Event::DoubleClick);
+        } else {
+            self.released_at = self.raw_since;
+        }
+        Event::Released
+    }
+
+    fn double_click_expired(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.released_at) > self.config.double_click_ms as u64
+    }
+}
+
 /// A generic button that can report press and release events.
 pub struct Button<T> {
     /// The input pin connected to the button.
     button: T,
     /// Tracks the current debounced state of the button.
     pressed: bool,
+    /// Gesture recognition used by `Button::update`.
+    detector: GestureDetector,
+    /// State of the button in the last interrupt event.
+    reported_active: bool,
 }
 
-/// Creates a new `Button` instance associated with a specific input pin.
-///
-/// # Arguments
-/// * `button` - The input pin the button is connected to.
-///
-/// # Returns
-/// A new `Button` instance that can be used to detect button events.
 impl<T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>> Button<T> {
+    /// Creates a new `Button` instance associated with a specific input pin.
+    ///
+    /// The button uses `ButtonConfig::default()`, i.e. it is considered
+    /// pressed when the pin reads high.
+    ///
+    /// # Arguments
+    /// * `button` - The input pin the button is connected to.
+    ///
+    /// # Returns
+    /// A new `Button` instance that can be used to detect button events.
     pub fn create_on_pins(button: T) -> Self {
+        Self::create_on_pins_with_config(button, ButtonConfig::default())
+    }
+
+    /// Creates a new `Button` instance with custom wiring and gesture timings.
+    ///
+    /// # Arguments
+    /// * `button` - The input pin the button is connected to.
+    /// * `config` - Active level and timings of the button.
+    ///
+    /// # Returns
+    /// A new `Button` instance that can be used to detect button events.
+    pub fn create_on_pins_with_config(button: T, config: ButtonConfig) -> Self {
         Button {
             button,
             pressed: true,
+            detector: GestureDetector::new(config),
+            reported_active: false,
         }
     }
+
+    /// Returns `true` if the pin is at the active level right now, without
+    /// any debouncing.
+    pub fn is_active(&self) -> bool {
+        match self.detector.config().active_level {
+            ActiveLevel::Low => self.button.is_low().unwrap(),
+            ActiveLevel::High => self.button.is_high().unwrap(),
+        }
+    }
+
     /// Updates the internal state of the button by reading its current state.
     fn check(&mut self) {
-        self.pressed = !self.button.is_low().unwrap();
+        self.pressed = self.is_active();
     }
 
     /// Polls the button to determine its current state and debounce it.
     ///
     /// This method should be called repeatedly to ensure accurate event
-    /// detection.
+    /// detection. It blocks for 30 ms when a change is detected, use
+    /// `Button::update` for non-blocking operation and gestures.
     ///
     /// # Arguments
     /// * `delay` - A delay provider used for debouncing.
     ///
     /// # Returns
     /// An `Event` indicating the debounced state change of the button.
-    pub(crate) fn poll(&mut self, delay: &mut Delay) -> Event {
-        let pressed_now = !self.button.is_low().unwrap();
+    #[cfg(feature = "esp-hal")]
+    pub fn poll(&mut self, delay: &mut Delay) -> Event {
+        let pressed_now = self.is_active();
         if !self.pressed && pressed_now {
             delay.delay_ms(30 as u32);
             self.check();
-            if !self.button.is_low().unwrap() {
+            if self.is_active() {
                 Event::Pressed
             } else {
                 Event::Nothing
@@ -92,7 +359,7 @@ impl<T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>>
         } else if self.pressed && !pressed_now {
             delay.delay_ms(30 as u32);
             self.check();
-            if self.button.is_low().unwrap() {
+            if !self.is_active() {
                 Event::Released
             } else {
                 Event::Nothing
@@ -101,8 +368,78 @@ impl<T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>>
             Event::Nothing
         }
     }
+
+    /// Reads the button and advances the gesture recognition without
+    /// blocking.
+    ///
+    /// This method should be called repeatedly, see
+    /// `GestureDetector::update`.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// An `Event`, or `Event::Nothing` if nothing happened.
+    pub fn update(&mut self, now_ms: u64) -> Event {
+        let pressed = self.is_active();
+        self.detector.update(pressed, now_ms)
+    }
+
+    /// Returns the debounced state of the button as seen by `Button::update`.
+    pub fn is_pressed(&self) -> bool {
+        self.detector.is_pressed()
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<T> Button<T>
+where
+    T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible> + esp_hal::gpio::Pin,
+{
+    /// Enables the GPIO interrupt on both edges of the button pin, see
+    /// `peripherals::gpio_events`.
+    pub fn listen(&mut self) {
+        self.reported_active = self.is_active();
+        self.button.listen(esp_hal::gpio::Event::AnyEdge);
+    }
+
+    /// Disables the GPIO interrupt of the button pin.
+    pub fn unlisten(&mut self) {
+        self.button.unlisten();
+    }
+
+    /// Handles the GPIO interrupt for this button. Should be called from the
+    /// `GPIO` interrupt handler.
+    ///
+    /// No debouncing is done here, every edge produces an event. A press
+    /// shorter than the interrupt latency is reported as both edges.
+    ///
+    /// # Arguments
+    /// * `queue` - The queue the event is pushed to.
+    /// * `source` - Identifier of this button stored in the event.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the interrupt was caused by this button.
+    pub fn handle_interrupt<const N: usize>(
+        &mut self,
+        queue: &EventQueue<N>,
+        source: u8,
+        now_ms: u64,
+    ) -> bool {
+        let active = self.is_active();
+        handle_pin_interrupt(
+            &mut self.button,
+            active,
+            &mut self.reported_active,
+            queue,
+            source,
+            now_ms,
+        )
+    }
 }
 
+#[cfg(feature = "esp-hal")]
 impl<T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>> UnifiedData
     for Button<T>
 {
@@ -119,3 +456,163 @@ impl<T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_EVENTS: usize = 16;
+
+    /// Runs a detector every millisecond over a timeline of raw levels, each
+    /// entry holding from its time on, and records the events with the time
+    /// they were reported.
+    fn run(
+        config: ButtonConfig,
+        timeline: &[(u64, bool)],
+        until: u64,
+    ) -> ([(Event, u64); MAX_EVENTS], usize) {
+        let mut detector = GestureDetector::new(config);
+        let mut events = [(Event::Nothing, 0); MAX_EVENTS];
+        let mut count = 0;
+        for now_ms in 0..=until {
+            let pressed = timeline
+                .iter()
+                .rev()
+                .find(|(at, _)| *at <= now_ms)
+                .map_or(false, |(_, pressed)| *pressed);
+            let event = detector.update(pressed, now_ms);
+            if event != Event::Nothing {
+                events[count] = (event, now_ms);
+                count += 1;
+            }
+        }
+        (events, count)
+    }
+
+    fn kinds(events: &[(Event, u64)]) -> [Event; MAX_EVENTS] {
+        let mut kinds = [Event::Nothing; MAX_EVENTS];
+        for (kind, (event, _)) in kinds.iter_mut().zip(events) {
+            *kind = *event;
+        }
+        kinds
+    }
+
+    #[test]
+    fn click_is_reported_after_double_click_window() {
+        let config = ButtonConfig::default();
+        let (events, count) = run(config, &[(100, true), (200, false)], 1000);
+        assert_eq!(
+            events[..count],
+            [
+                (Event::Pressed, 130),
+                (Event::Released, 230),
+                (Event::Click, 501),
+            ]
+        );
+    }
+
+    #[test]
+    fn bounce_shorter_than_debounce_is_ignored() {
+        let config = ButtonConfig::default();
+        let timeline = [(100, true), (110, false), (115, true), (125, false)];
+        let (_, count) = run(config, &timeline, 1000);
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn bouncy_press_is_reported_once() {
+        let config = ButtonConfig::default();
+        let timeline = [
+            (100, true),
+            (105, false),
+            (108, true),
+            (300, false),
+            (302, true),
+            (306, false),
+        ];
+        let (events, count) = run(config, &timeline, 1000);
+        assert_eq!(
+            events[..count],
+            [
+                (Event::Pressed, 138),
+                (Event::Released, 336),
+                (Event::Click, 607),
+            ]
+        );
+    }
+
+    #[test]
+    fn double_click_replaces_click() {
+        let config = ButtonConfig::default();
+        let timeline = [(100, true), (200, false), (300, true), (400, false)];
+        let (events, count) = run(config, &timeline, 1500);
+        assert_eq!(
+            kinds(&events[..count])[..count],
+            [
+                Event::Pressed,
+                Event::Released,
+                Event::Pressed,
+                Event::Released,
+                Event::DoubleClick,
+            ]
+        );
+    }
+
+    #[test]
+    fn presses_further_apart_are_two_clicks() {
+        let config = ButtonConfig::default();
+        let timeline = [(100, true), (200, false), (600, true), (700, false)];
+        let (events, count) = run(config, &timeline, 1500);
+        assert_eq!(
+            kinds(&events[..count])[..count],
+            [
+                Event::Pressed,
+                Event::Released,
+                Event::Click,
+                Event::Pressed,
+                Event::Released,
+                Event::Click,
+            ]
+        );
+    }
+
+    #[test]
+    fn long_press_without_repeat() {
+        let config = ButtonConfig::default();
+        let (events, count) = run(config, &[(100, true), (2000, false)], 3000);
+        assert_eq!(
+            events[..count],
+            [
+                (Event::Pressed, 130),
+                (Event::LongPress, 900),
+                (Event::Released, 2030),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_follows_long_press() {
+        let config = ButtonConfig {
+            repeat_interval_ms: Some(200),
+            ..Default::default()
+        };
+        let (events, count) = run(config, &[(100, true), (1450, false)], 2000);
+        assert_eq!(
+            events[..count],
+            [
+                (Event::Pressed, 130),
+                (Event::LongPress, 900),
+                (Event::Repeat, 1100),
+                (Event::Repeat, 1300),
+                (Event::Released, 1480),
+            ]
+        );
+    }
+
+    #[test]
+    fn button_held_at_startup_is_not_pressed() {
+        let config = ButtonConfig::default();
+        let (events, count) = run(config, &[(0, true), (500, false)], 1500);
+        assert_eq!(events[..count], [(Event::Released, 530)]);
+    }
+}
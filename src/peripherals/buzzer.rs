@@ -0,0 +1,707 @@
+//! # Buzzer Module
+//!
+//! This module plays tones and melodies on a passive buzzer driven by PWM.
+//! Melodies are written in RTTTL (Ring Tone Text Transfer Language), e.g.
+//! `"Beep:d=8,o=6,b=180:c,p,c"`. The format has three sections separated by
+//! colons: the name, the defaults (`d` duration, `o` octave, `b` beats per
+//! minute) and the comma separated notes. A note is written as
+//! `[duration]note[#][.][octave][.]`, where `p` is a pause. As in the RTTTL
+//! specification, octaves range from 4 to 7.
+//!
+//! Melodies can be played in two ways:
+//! - `Buzzer::play_blocking` - plays the whole melody before returning.
+//! - `Buzzer::play` - non-blocking, the melody progresses with every call of
+//!   `Buzzer::update` driven by a timestamp you pass in.
+//!
+//! The parser and `MelodyPlayer` do not touch any hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     buzzer::{Buzzer, PwmTone, Rtttl},
+//!     pwm::Pwm,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let pwm = esp_ward::init_pwm_custom!(peripherals, clocks);
+//! // The buzzer changes the frequency of its timer, so it needs its own one
+//! let timer = pwm
+//!     .timer(0, 1000, esp_hal::ledc::timer::config::Duty::Duty10Bit)
+//!     .unwrap();
+//! let channel = pwm.channel(0, &timer, pins.gpio10).unwrap();
+//! let mut buzzer = Buzzer::create_on_output(PwmTone::create_on_channel(&pwm, &timer, channel));
+//!
+//! let alarm = Rtttl::parse("Alarm:d=8,o=6,b=200:c,e,g,p,c,e,g").unwrap();
+//! buzzer.play_blocking(&alarm, &mut delay).unwrap();
+//!
+//! let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//! buzzer.play(&alarm, now).unwrap();
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     buzzer.update(now).unwrap();
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::blocking::delay::DelayMs;
+#[cfg(feature = "esp-hal")]
+use esp_hal::{delay::Delay, gpio::OutputPin};
+
+#[cfg(feature = "esp-hal")]
+use super::pwm::{Pwm, PwmChannel, PwmOutput, PwmTimer};
+use super::{PeripheralError, Shutdown};
+
+/// Errors of the RTTTL parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtttlError {
+    /// The text does not have the three colon separated sections.
+    MissingSection,
+    /// A default value is unknown or out of range, e.g. an octave outside of 4
+    /// to 7.
+    InvalidDefault,
+    /// A note cannot be parsed, the value is the index of the note.
+    InvalidNote(usize),
+}
+
+/// A note of a melody.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// Frequency of the note in hertz, 0 for a pause.
+    pub frequency_hz: u32,
+    /// Duration of the note in milliseconds.
+    pub duration_ms: u32,
+}
+
+/// Returns the frequency of a note in hertz, rounded to a whole number.
+///
+/// # Arguments
+/// * `semitone` - Semitone within the octave, 0 for C up to 11 for B.
+/// * `octave` - Scientific pitch octave, A4 is 440 Hz.
+pub fn note_frequency(semitone: u8, octave: u8) -> u32 {
+    let midi = (octave as i32 + 1) * 12 + semitone as i32;
+    libm::roundf(440.0 * libm::powf(2.0, (midi - 69) as f32 / 12.0)) as u32
+}
+
+/// A parsed RTTTL melody, borrowing the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rtttl<'a> {
+    /// Name of the melody.
+    pub name: &'a str,
+    /// Default duration of a note, as a fraction of a whole note.
+    pub duration: u8,
+    /// Default octave of a note.
+    pub octave: u8,
+    /// Tempo in beats (quarter notes) per minute.
+    pub bpm: u16,
+    notes: &'a str,
+}
+
+impl<'a> Rtttl<'a> {
+    /// Parses a melody. All notes are validated, so iterating over `notes`
+    /// never fails afterwards.
+    ///
+    /// # Returns
+    /// The parsed melody, or an `RtttlError` describing the first error.
+    pub fn parse(text: &'a str) -> Result<Self, RtttlError> {
+        let mut sections = text.splitn(3, ':');
+        let name = sections.next().ok_or(RtttlError::MissingSection)?.trim();
+        let defaults = sections.next().ok_or(RtttlError::MissingSection)?;
+        let notes = sections.next().ok_or(RtttlError::MissingSection)?;
+
+        let mut rtttl = Rtttl {
+            name,
+            duration: 4,
+            octave: 6,
+            bpm: 63,
+            notes,
+        };
+        for default in defaults.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (key, value) = default.split_once('=').ok_or(RtttlError::InvalidDefault)?;
+            let value: u16 = value
+                .trim()
+                .parse()
+                .map_err(|_| RtttlError::InvalidDefault)?;
+            match key.trim() {
+                "d" if is_valid_duration(value) => rtttl.duration = value as u8,
+                "o" if (MIN_OCTAVE..=MAX_OCTAVE).contains(&value) => rtttl.octave = value as u8,
+                "b" if value > 0 => rtttl.bpm = value,
+                _ => return Err(RtttlError::InvalidDefault),
+            }
+        }
+
+        for note in rtttl.notes() {
+            note?;
+        }
+        Ok(rtttl)
+    }
+
+    /// Returns an iterator over the notes of the melody.
+    pub fn notes(&self) -> Notes<'a> {
+        Notes {
+            rtttl: *self,
+            remaining: self.notes,
+            index: 0,
+        }
+    }
+
+    /// Returns the total duration of the melody in milliseconds.
+    pub fn duration_ms(&self) -> u32 {
+        self.notes()
+            .filter_map(Result::ok)
+            .map(|note| note.duration_ms)
+            .sum()
+    }
+
+    /// Duration of a whole note in milliseconds.
+    fn whole_note_ms(&self) -> u32 {
+        60_000 * 4 / self.bpm as u32
+    }
+
+    fn parse_note(&self, text: &str, index: usize) -> Result<Note, RtttlError> {
+        let error = RtttlError::InvalidNote(index);
+        let bytes = text.as_bytes();
+        let mut pos = 0;
+
+        let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+        let duration = if digits > 0 {
+            let value: u16 = text[..digits].parse().map_err(|_| error)?;
+            if !is_valid_duration(value) {
+                return Err(error);
+            }
+            pos = digits;
+            value as u32
+        } else {
+            self.duration as u32
+        };
+
+        let semitone = match bytes.get(pos).map(u8::to_ascii_lowercase) {
+            Some(b'c') => Some(0),
+            Some(b'd') => Some(2),
+            Some(b'e') => Some(4),
+            Some(b'f') => Some(5),
+            Some(b'g') => Some(7),
+            Some(b'a') => Some(9),
+            Some(b'b') | Some(b'h') => Some(11),
+            Some(b'p') => None,
+            _ => return Err(error),
+        };
+        pos += 1;
+
+        let mut sharp = false;
+        if bytes.get(pos) == Some(&b'#') {
+            sharp = true;
+            pos += 1;
+        }
+        // The dot is placed either before or after the octave.
+        let mut dotted = false;
+        if bytes.get(pos) == Some(&b'.') {
+            dotted = true;
+            pos += 1;
+        }
+        let mut octave = self.octave;
+        if let Some(digit) = bytes.get(pos).filter(|b| b.is_ascii_digit()) {
+            octave = digit - b'0';
+            pos += 1;
+            if !(MIN_OCTAVE..=MAX_OCTAVE).contains(&(octave as u16)) {
+                return Err(error);
+            }
+        }
+        if !dotted && bytes.get(pos) == Some(&b'.') {
+            dotted = true;
+            pos += 1;
+        }
+        if pos != bytes.len() {
+            return Err(error);
+        }
+
+        let mut duration_ms = self.whole_note_ms() / duration;
+        if dotted {
+            duration_ms += duration_ms / 2;
+        }
+        let frequency_hz = match semitone {
+            // B# is the C of the next octave.
+            Some(semitone) if sharp && semitone == 11 => note_frequency(0, octave + 1),
+            Some(semitone) => note_frequency(semitone + sharp as u8, octave),
+            None => 0,
+        };
+        Ok(Note {
+            frequency_hz,
+            duration_ms,
+        })
+    }
+}
+
+/// Lowest octave allowed by RTTTL.
+const MIN_OCTAVE: u16 = 4;
+/// Highest octave allowed by RTTTL.
+const MAX_OCTAVE: u16 = 7;
+
+fn is_valid_duration(value: u16) -> bool {
+    matches!(value, 1 | 2 | 4 | 8 | 16 | 32 | 64)
+}
+
+/// Iterator over the notes of an RTTTL melody.
+#[derive(Debug, Clone)]
+pub struct Notes<'a> {
+    rtttl: Rtttl<'a>,
+    remaining: &'a str,
+    index: usize,
+}
+
+impl<'a> Iterator for Notes<'a> {
+    type Item = Result<Note, RtttlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.remaining.is_empty() {
+                return None;
+            }
+            let (note, rest) = self
+                .remaining
+                .split_once(',')
+                .unwrap_or((self.remaining, ""));
+            self.remaining = rest;
+            let note = note.trim();
+            // Skip empty entries, e.g. a trailing comma.
+            if note.is_empty() {
+                continue;
+            }
+            let index = self.index;
+            self.index += 1;
+            return Some(self.rtttl.parse_note(note, index));
+        }
+    }
+}
+
+/// Change of the buzzer output requested by `MelodyPlayer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneChange {
+    /// Start a tone with the frequency in hertz.
+    Tone(u32),
+    /// Stop the tone.
+    Silence,
+}
+
+/// Share of a note the tone is sounding, the rest is silent so repeated notes
+/// can be told apart.
+const ARTICULATION: u32 = 90;
+
+/// Hardware independent, time driven player of a melody.
+#[derive(Debug, Clone, Default)]
+pub struct MelodyPlayer<'a> {
+    notes: Option<Notes<'a>>,
+    note_ends_ms: u64,
+    tone_ends_ms: u64,
+    sounding: bool,
+}
+
+impl<'a> MelodyPlayer<'a> {
+    /// Creates a new idle player.
+    pub const fn new() -> Self {
+        MelodyPlayer {
+            notes: None,
+            note_ends_ms: 0,
+            tone_ends_ms: 0,
+            sounding: false,
+        }
+    }
+
+    /// Starts a melody, replacing the one playing. The first note starts with
+    /// the next `update`.
+    pub fn start(&mut self, melody: &Rtttl<'a>, now_ms: u64) {
+        self.notes = Some(melody.notes());
+        self.note_ends_ms = now_ms;
+        self.tone_ends_ms = now_ms;
+    }
+
+    /// Stops the melody.
+    ///
+    /// # Returns
+    /// `Some(ToneChange::Silence)` if a tone was sounding.
+    pub fn stop(&mut self) -> Option<ToneChange> {
+        self.notes = None;
+        core::mem::take(&mut self.sounding).then_some(ToneChange::Silence)
+    }
+
+    /// Returns `true` while a melody is playing.
+    pub fn is_playing(&self) -> bool {
+        self.notes.is_some()
+    }
+
+    /// Advances the melody. Should be called at least every few milliseconds
+    /// for an accurate rhythm.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// The change of the output, `None` if the output stays as it is.
+    pub fn update(&mut self, now_ms: u64) -> Option<ToneChange> {
+        let notes = self.notes.as_mut()?;
+        if now_ms < self.note_ends_ms {
+            if self.sounding && now_ms >= self.tone_ends_ms {
+                self.sounding = false;
+                return Some(ToneChange::Silence);
+            }
+            return None;
+        }
+
+        match notes.next() {
+            Some(Ok(note)) => {
+                self.note_ends_ms = now_ms + note.duration_ms as u64;
+                self.tone_ends_ms = now_ms + (note.duration_ms * ARTICULATION / 100) as u64;
+                if note.frequency_hz == 0 {
+                    core::mem::take(&mut self.sounding).then_some(ToneChange::Silence)
+                } else {
+                    self.sounding = true;
+                    Some(ToneChange::Tone(note.frequency_hz))
+                }
+            }
+            _ => self.stop().or(Some(ToneChange::Silence)),
+        }
+    }
+}
+
+/// An output able to play a square wave tone.
+pub trait ToneOutput {
+    /// Starts a tone with the given frequency in hertz.
+    fn play_tone(&mut self, frequency_hz: u32) -> Result<(), PeripheralError>;
+    /// Stops the tone.
+    fn stop_tone(&mut self) -> Result<(), PeripheralError>;
+}
+
+/// A tone output on an LEDC channel. The frequency of the timer is changed
+/// for every tone, so the timer must not drive any other channel.
+#[cfg(feature = "esp-hal")]
+pub struct PwmTone<'a, 'd, O: OutputPin> {
+    pwm: &'a Pwm<'d>,
+    timer: &'a PwmTimer<'d>,
+    /// The channel driving the buzzer.
+    pub channel: PwmChannel<'a, O>,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<'a, 'd, O: OutputPin> PwmTone<'a, 'd, O> {
+    /// Creates a new `PwmTone`.
+    ///
+    /// # Arguments
+    /// * `pwm` - The LEDC peripheral.
+    /// * `timer` - The timer driving the channel, a resolution of 10 bits
+    ///   covers the whole audible range.
+    /// * `channel` - The channel driving the buzzer.
+    ///
+    /// # Returns
+    /// A new `PwmTone` instance.
+    pub fn create_on_channel(
+        pwm: &'a Pwm<'d>,
+        timer: &'a PwmTimer<'d>,
+        channel: PwmChannel<'a, O>,
+    ) -> Self {
+        PwmTone {
+            pwm,
+            timer,
+            channel,
+        }
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<'a, 'd, O: OutputPin> ToneOutput for PwmTone<'a, 'd, O> {
+    fn play_tone(&mut self, frequency_hz: u32) -> Result<(), PeripheralError> {
+        if frequency_hz == 0 {
+            return self.stop_tone();
+        }
+        self.pwm.set_frequency(self.timer, frequency_hz)?;
+        let duty = self.channel.max_duty() / 2;
+        self.channel.set_duty(duty)
+    }
+
+    fn stop_tone(&mut self) -> Result<(), PeripheralError> {
+        self.channel.set_duty(0)
+    }
+}
+
+/// A passive buzzer playing tones and RTTTL melodies.
+pub struct Buzzer<'a, T: ToneOutput> {
+    /// The output driving the buzzer.
+    pub output: T,
+    player: MelodyPlayer<'a>,
+    /// End of a single tone started by `tone`.
+    tone_ends_ms: Option<u64>,
+}
+
+impl<'a, T: ToneOutput> Buzzer<'a, T> {
+    /// Creates a new silent `Buzzer`.
+    ///
+    /// # Arguments
+    /// * `output` - The output driving the buzzer.
+    ///
+    /// # Returns
+    /// A new `Buzzer` instance.
+    pub fn create_on_output(output: T) -> Self {
+        Buzzer {
+            output,
+            player: MelodyPlayer::new(),
+            tone_ends_ms: None,
+        }
+    }
+
+    /// Starts a single tone, stopping a playing melody.
+    ///
+    /// # Arguments
+    /// * `frequency_hz` - Frequency of the tone in hertz.
+    /// * `duration_ms` - Duration of the tone in milliseconds.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn tone(
+        &mut self,
+        frequency_hz: u32,
+        duration_ms: u32,
+        now_ms: u64,
+    ) -> Result<(), PeripheralError> {
+        self.player.stop();
+        self.tone_ends_ms = Some(now_ms + duration_ms as u64);
+        self.output.play_tone(frequency_hz)
+    }
+
+    /// Starts a melody, it progresses with every call of `update`.
+    ///
+    /// # Arguments
+    /// * `melody` - The melody to play.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn play(&mut self, melody: &Rtttl<'a>, now_ms: u64) -> Result<(), PeripheralError> {
+        self.tone_ends_ms = None;
+        self.player.start(melody, now_ms);
+        self.update(now_ms).map(|_| ())
+    }
+
+    /// Advances a melody or ends a single tone. Should be called at least every
+    /// few milliseconds while `is_playing` returns `true`.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(true)` while a melody or a tone is playing.
+    pub fn update(&mut self, now_ms: u64) -> Result<bool, PeripheralError> {
+        if let Some(ends_ms) = self.tone_ends_ms {
+            if now_ms >= ends_ms {
+                self.tone_ends_ms = None;
+                self.output.stop_tone()?;
+            }
+        }
+        match self.player.update(now_ms) {
+            Some(ToneChange::Tone(frequency_hz)) => self.output.play_tone(frequency_hz)?,
+            Some(ToneChange::Silence) => self.output.stop_tone()?,
+            None => {}
+        }
+        Ok(self.is_playing())
+    }
+
+    /// Returns `true` while a melody or a tone is playing.
+    pub fn is_playing(&self) -> bool {
+        self.player.is_playing() || self.tone_ends_ms.is_some()
+    }
+
+    /// Stops a melody or a tone.
+    pub fn stop(&mut self) -> Result<(), PeripheralError> {
+        self.player.stop();
+        self.tone_ends_ms = None;
+        self.output.stop_tone()
+    }
+
+    /// Plays a single tone and waits until it ends.
+    ///
+    /// # Arguments
+    /// * `frequency_hz` - Frequency of the tone in hertz.
+    /// * `duration_ms` - Duration of the tone in milliseconds.
+    /// * `delay` - The delay provider.
+    #[cfg(feature = "esp-hal")]
+    pub fn beep(
+        &mut self,
+        frequency_hz: u32,
+        duration_ms: u32,
+        delay: &mut Delay,
+    ) -> Result<(), PeripheralError> {
+        self.stop()?;
+        self.output.play_tone(frequency_hz)?;
+        delay.delay_ms(duration_ms);
+        self.output.stop_tone()
+    }
+
+    /// Plays a melody and waits until it ends.
+    ///
+    /// # Arguments
+    /// * `melody` - The melody to play.
+    /// * `delay` - The delay provider.
+    #[cfg(feature = "esp-hal")]
+    pub fn play_blocking(
+        &mut self,
+        melody: &Rtttl<'_>,
+        delay: &mut Delay,
+    ) -> Result<(), PeripheralError> {
+        self.stop()?;
+        for note in melody.notes().filter_map(Result::ok) {
+            let tone_ms = note.duration_ms * ARTICULATION / 100;
+            if note.frequency_hz != 0 {
+                self.output.play_tone(note.frequency_hz)?;
+            }
+            delay.delay_ms(tone_ms);
+            self.output.stop_tone()?;
+            delay.delay_ms(note.duration_ms - tone_ms);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: ToneOutput> Shutdown for Buzzer<'a, T> {
+    /// Stops a melody or a tone.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOKIA: &str = "Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a";
+    const SIMPSONS: &str = "The Simpsons:d=4,o=5,b=160:c.6,e6,f#6,8a6,g.6,e6,c6,8a,8f#,8f#,8f#,\
+                            2g,8p,8p,8f#,8f#,8f#,8g,a#.,8c6,8c6,8c6,c6";
+
+    fn note(rtttl: &Rtttl, index: usize) -> Note {
+        rtttl.notes().nth(index).unwrap().unwrap()
+    }
+
+    #[test]
+    fn note_frequencies() {
+        assert_eq!(note_frequency(9, 4), 440);
+        assert_eq!(note_frequency(0, 4), 262);
+        assert_eq!(note_frequency(9, 5), 880);
+        assert_eq!(note_frequency(11, 7), 3951);
+    }
+
+    #[test]
+    fn parses_defaults() {
+        let rtttl = Rtttl::parse(NOKIA).unwrap();
+        assert_eq!(rtttl.name, "Nokia");
+        assert_eq!((rtttl.duration, rtttl.octave, rtttl.bpm), (4, 5, 225));
+
+        let rtttl = Rtttl::parse("Empty::").unwrap();
+        assert_eq!((rtttl.duration, rtttl.octave, rtttl.bpm), (4, 6, 63));
+        assert_eq!(rtttl.notes().count(), 0);
+    }
+
+    #[test]
+    fn plays_nokia_tune() {
+        let rtttl = Rtttl::parse(NOKIA).unwrap();
+        assert_eq!(rtttl.notes().count(), 13);
+        // A whole note lasts 240000 / 225 = 1066 ms.
+        assert_eq!(
+            note(&rtttl, 0),
+            Note {
+                frequency_hz: 1319,
+                duration_ms: 133
+            }
+        );
+        assert_eq!(
+            note(&rtttl, 2),
+            Note {
+                frequency_hz: 740,
+                duration_ms: 266
+            }
+        );
+        assert_eq!(
+            note(&rtttl, 12),
+            Note {
+                frequency_hz: 880,
+                duration_ms: 533
+            }
+        );
+        assert_eq!(rtttl.duration_ms(), 6 * 133 + 6 * 266 + 533);
+    }
+
+    #[test]
+    fn plays_dotted_notes_and_pauses() {
+        let rtttl = Rtttl::parse(SIMPSONS).unwrap();
+        assert_eq!(rtttl.notes().count(), 23);
+        // Dotted quarter note at 160 bpm: 375 ms + 187 ms.
+        assert_eq!(
+            note(&rtttl, 0),
+            Note {
+                frequency_hz: 1047,
+                duration_ms: 562
+            }
+        );
+        assert_eq!(
+            note(&rtttl, 12),
+            Note {
+                frequency_hz: 0,
+                duration_ms: 187
+            }
+        );
+        assert_eq!(
+            note(&rtttl, 18),
+            Note {
+                frequency_hz: 932,
+                duration_ms: 562
+            }
+        );
+    }
+
+    #[test]
+    fn dot_before_or_after_octave() {
+        let rtttl = Rtttl::parse("Dots:d=4,o=5,b=60:c.6,c6.,8b#").unwrap();
+        assert_eq!(note(&rtttl, 0), note(&rtttl, 1));
+        assert_eq!(note(&rtttl, 0).duration_ms, 1500);
+        // B# is the C of the next octave.
+        assert_eq!(note(&rtttl, 2).frequency_hz, 1047);
+        assert_eq!(note(&rtttl, 2).duration_ms, 500);
+    }
+
+    #[test]
+    fn skips_empty_notes() {
+        let rtttl = Rtttl::parse("Beep:d=8,o=6,b=180: c , p,,c,").unwrap();
+        assert_eq!(rtttl.notes().count(), 3);
+    }
+
+    #[test]
+    fn rejects_malformed_melodies() {
+        assert_eq!(Rtttl::parse("Beep"), Err(RtttlError::MissingSection));
+        assert_eq!(Rtttl::parse("Beep:d=4"), Err(RtttlError::MissingSection));
+        for text in [
+            "Beep:d=3:c",
+            "Beep:o=3:c",
+            "Beep:o=8:c",
+            "Beep:b=0:c",
+            "Beep:x=1:c",
+            "Beep:d:c",
+            "Beep:d=four:c",
+        ] {
+            assert_eq!(
+                Rtttl::parse(text),
+                Err(RtttlError::InvalidDefault),
+                "{text}"
+            );
+        }
+        for text in [
+            "Beep::c,3c",
+            "Beep::c,128c",
+            "Beep::c,x",
+            "Beep::c,c8",
+            "Beep::c,c3",
+            "Beep::c,c#x",
+            "Beep::c,c..",
+            "Beep::c,#c",
+        ] {
+            assert_eq!(
+                Rtttl::parse(text),
+                Err(RtttlError::InvalidNote(1)),
+                "{text}"
+            );
+        }
+    }
+}
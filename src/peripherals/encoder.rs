@@ -0,0 +1,565 @@
+//! # Rotary Encoder Module
+//!
+//! This module provides support for incremental rotary encoders (like the
+//! common KY-040 module) with an integrated push button, which are handy for
+//! menu navigation.
+//!
+//! Quadrature signals can be decoded in two ways:
+//! - `RotaryEncoder` - polls two GPIOs and decodes them with a state table.
+//!   Works on every chip, but has to be updated often enough not to miss
+//!   transitions.
+//! - `PcntEncoder` - counts the edges in hardware with the pulse counter (PCNT)
+//!   peripheral, available on ESP32, ESP32-S2, ESP32-S3, ESP32-C6 and ESP32-H2.
+//!
+//! Both of them feed the decoded steps to an `EncoderCounter`, which takes care
+//! of detent scaling, acceleration and position limits. `QuadratureDecoder` and
+//! `EncoderCounter` do not touch any hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     button::Event,
+//!     encoder::{EncoderConfig, Limits, RotaryEncoder},
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let mut encoder = RotaryEncoder::create_on_pins(
+//!     pins.gpio4.into_pull_up_input(),
+//!     pins.gpio5.into_pull_up_input(),
+//!     pins.gpio6.into_pull_up_input(),
+//!     EncoderConfig {
+//!         limits: Limits::Wrap { min: 0, max: 9 },
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     if encoder.update(now) != 0 {
+//!         println!("Menu item: {}", encoder.position());
+//!     }
+//!     if let Event::Click = encoder.button.update(now) {
+//!         println!("Selected!");
+//!     }
+//! }
+//! ```
+
+use embedded_hal::digital::v2::InputPin;
+
+use super::button::{Button, ButtonConfig};
+
+/// Movement for every transition `previous_state << 2 | current_state`, where
+/// the state is `a << 1 | b`. Invalid transitions (both signals changed) and
+/// no movement are `0`.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Limits applied to the position of an encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limits {
+    /// The position is not limited.
+    None,
+    /// The position stops at `min` and `max`.
+    Clamp { min: i32, max: i32 },
+    /// The position wraps from `max` to `min` and vice versa.
+    Wrap { min: i32, max: i32 },
+}
+
+/// Acceleration of fast rotation: the quicker the detents follow each other,
+/// the bigger the step of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Acceleration {
+    /// Detents closer to each other than this time are accelerated.
+    pub threshold_ms: u32,
+    /// Maximum amount of position units per detent.
+    pub max_multiplier: u8,
+}
+
+/// Configuration of an encoder.
+#[derive(Debug, Clone, Copy)]
+pub struct EncoderConfig {
+    /// Amount of quadrature steps per one mechanical detent (usually 4, some
+    /// encoders have 2 or 1).
+    pub steps_per_detent: u8,
+    /// Swaps the direction of rotation.
+    pub reverse: bool,
+    /// Acceleration of fast rotation, `None` to disable it.
+    pub acceleration: Option<Acceleration>,
+    /// Limits of the position.
+    pub limits: Limits,
+    /// Configuration of the push button.
+    pub button: ButtonConfig,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        EncoderConfig {
+            steps_per_detent: 4,
+            reverse: false,
+            acceleration: None,
+            limits: Limits::None,
+            button: ButtonConfig::default(),
+        }
+    }
+}
+
+/// Decodes the quadrature signals of an encoder with a state table.
+pub struct QuadratureDecoder {
+    state: u8,
+}
+
+impl QuadratureDecoder {
+    /// Creates a new decoder with the given initial level of the signals.
+    pub const fn new(a: bool, b: bool) -> Self {
+        QuadratureDecoder {
+            state: ((a as u8) << 1) | b as u8,
+        }
+    }
+
+    /// Feeds the decoder with the current level of the signals.
+    ///
+    /// # Returns
+    /// `1` for a step clockwise (A leads B), `-1` for a step counter-clockwise
+    /// and `0` if there was no movement or the transition was invalid.
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let state = ((a as u8) << 1) | b as u8;
+        let step = TRANSITIONS[((self.state << 2) | state) as usize];
+        self.state = state;
+        step
+    }
+}
+
+/// Turns quadrature steps into the position of an encoder.
+pub struct EncoderCounter {
+    steps_per_detent: u8,
+    reverse: bool,
+    acceleration: Option<Acceleration>,
+    limits: Limits,
+    position: i32,
+    /// Steps not forming a whole detent yet.
+    steps: i32,
+    /// Time of the last detent, used for acceleration.
+    last_detent_ms: Option<u64>,
+}
+
+impl EncoderCounter {
+    /// Creates a new counter at position `0` (or the nearest limit).
+    pub fn new(config: &EncoderConfig) -> Self {
+        let mut counter = EncoderCounter {
+            steps_per_detent: config.steps_per_detent.max(1),
+            reverse: config.reverse,
+            acceleration: config.acceleration,
+            limits: config.limits,
+            position: 0,
+            steps: 0,
+            last_detent_ms: None,
+        };
+        counter.set_position(0);
+        counter
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Sets the current position, applying the limits.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = match self.limits {
+            Limits::None => position,
+            Limits::Clamp { min, max } => position.clamp(min, max),
+            Limits::Wrap { min, max } => wrap(position as i64, min, max),
+        };
+        self.steps = 0;
+    }
+
+    /// Feeds the counter with decoded quadrature steps.
+    ///
+    /// # Arguments
+    /// * `steps` - Signed amount of steps since the last call.
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// The signed amount of detents turned, times the acceleration multiplier.
+    /// The limits are not applied, read `position` for the new position.
+    pub fn feed(&mut self, steps: i32, now_ms: u64) -> i32 {
+        let steps = if self.reverse {
+            steps.saturating_neg()
+        } else {
+            steps
+        };
+        self.steps = self.steps.saturating_add(steps);
+
+        let per_detent = self.steps_per_detent as i32;
+        let detents = self.steps / per_detent;
+        if detents == 0 {
+            return 0;
+        }
+        self.steps -= detents * per_detent;
+
+        let multiplier = self.multiplier(now_ms);
+        self.last_detent_ms = Some(now_ms);
+
+        // Computed in i64, so limits close to the range of i32 cannot overflow.
+        let delta = detents as i64 * multiplier as i64;
+        let target = self.position as i64 + delta;
+        self.position = match self.limits {
+            Limits::None => saturate(target),
+            Limits::Clamp { min, max } => saturate(target.clamp(min as i64, max as i64)),
+            Limits::Wrap { min, max } => wrap(target, min, max),
+        };
+        saturate(delta)
+    }
+
+    fn multiplier(&self, now_ms: u64) -> i32 {
+        match (self.acceleration, self.last_detent_ms) {
+            (Some(acceleration), Some(last)) => {
+                let elapsed = now_ms.saturating_sub(last).max(1);
+                if elapsed >= acceleration.threshold_ms as u64 {
+                    1
+                } else {
+                    (acceleration.threshold_ms as u64 / elapsed)
+                        .clamp(1, acceleration.max_multiplier.max(1) as u64)
+                        as i32
+                }
+            }
+            _ => 1,
+        }
+    }
+}
+
+/// Wraps `value` into the `min..=max` range.
+fn wrap(value: i64, min: i32, max: i32) -> i32 {
+    let span = max as i64 - min as i64 + 1;
+    if span <= 0 {
+        return min;
+    }
+    (min as i64 + (value - min as i64).rem_euclid(span)) as i32
+}
+
+/// Limits `value` to the range of `i32`.
+fn saturate(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// Computes the steps between two readings of a pulse counter which resets
+/// to zero when it reaches `-limit` or `limit`, assuming the encoder moved
+/// less than `limit / 2` steps in between.
+fn counter_delta(last: i16, value: i16, limit: i16) -> i32 {
+    let limit = (limit as i32).max(1);
+    let delta = (value as i32 - last as i32).rem_euclid(limit);
+    if delta > limit / 2 {
+        delta - limit
+    } else {
+        delta
+    }
+}
+
+/// A rotary encoder decoded by polling two GPIOs, with a push button.
+pub struct RotaryEncoder<A: InputPin, B: InputPin, SW: InputPin> {
+    /// The input pin of the A (CLK) signal.
+    a: A,
+    /// The input pin of the B (DT) signal.
+    b: B,
+    decoder: QuadratureDecoder,
+    counter: EncoderCounter,
+    /// The push button of the encoder.
+    pub button: Button<SW>,
+}
+
+impl<A, B, SW> RotaryEncoder<A, B, SW>
+where
+    A: InputPin<Error = core::convert::Infallible>,
+    B: InputPin<Error = core::convert::Infallible>,
+    SW: InputPin<Error = core::convert::Infallible>,
+{
+    /// Creates a new `RotaryEncoder` on the given pins.
+    ///
+    /// # Arguments
+    /// * `a` - The input pin of the A (CLK) signal.
+    /// * `b` - The input pin of the B (DT) signal.
+    /// * `switch` - The input pin of the push button (SW).
+    /// * `config` - Configuration of the encoder.
+    ///
+    /// # Returns
+    /// A new `RotaryEncoder` instance.
+    pub fn create_on_pins(a: A, b: B, switch: SW, config: EncoderConfig) -> Self {
+        let decoder = QuadratureDecoder::new(a.is_high().unwrap(), b.is_high().unwrap());
+        RotaryEncoder {
+            a,
+            b,
+            decoder,
+            counter: EncoderCounter::new(&config),
+            button: Button::create_on_pins_with_config(switch, config.button),
+        }
+    }
+
+    /// Reads the signals and updates the position. Has to be called often
+    /// enough not to miss any transition.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// The signed amount of detents turned, see `EncoderCounter::feed`.
+    pub fn update(&mut self, now_ms: u64) -> i32 {
+        let step = self
+            .decoder
+            .update(self.a.is_high().unwrap(), self.b.is_high().unwrap());
+        self.counter.feed(step as i32, now_ms)
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> i32 {
+        self.counter.position()
+    }
+
+    /// Sets the current position, applying the limits.
+    pub fn set_position(&mut self, position: i32) {
+        self.counter.set_position(position);
+    }
+}
+
+/// A rotary encoder decoded by the pulse counter (PCNT) peripheral, with a
+/// push button.
+#[cfg(any(
+    feature = "esp32",
+    feature = "esp32s2",
+    feature = "esp32s3",
+    feature = "esp32c6",
+    feature = "esp32h2"
+))]
+pub struct PcntEncoder<SW: InputPin> {
+    /// The pulse counter unit with both channels configured for quadrature
+    /// decoding.
+    unit: esp_hal::pcnt::unit::Unit,
+    /// Last value read from the unit.
+    last: i16,
+    /// Limit at which the unit resets to zero.
+    limit: i16,
+    counter: EncoderCounter,
+    /// The push button of the encoder.
+    pub button: Button<SW>,
+}
+
+#[cfg(any(
+    feature = "esp32",
+    feature = "esp32s2",
+    feature = "esp32s3",
+    feature = "esp32c6",
+    feature = "esp32h2"
+))]
+impl<SW: InputPin<Error = core::convert::Infallible>> PcntEncoder<SW> {
+    /// Creates a new `PcntEncoder` on a configured pulse counter unit.
+    ///
+    /// Both channels of the unit should be configured to count the edges of
+    /// both signals (4 counts per quadrature cycle), as shown in the `esp-hal`
+    /// `pcnt_encoder` example, with a high limit of `limit` and a low limit of
+    /// `-limit`. The counter is never cleared, so no counts are lost; the
+    /// wrap-around at the limits is taken into account as long as the encoder
+    /// moves less than `limit / 2` counts between two updates.
+    ///
+    /// # Arguments
+    /// * `unit` - The configured pulse counter unit.
+    /// * `limit` - The high limit of the unit.
+    /// * `switch` - The input pin of the push button (SW).
+    /// * `config` - Configuration of the encoder.
+    ///
+    /// # Returns
+    /// A new `PcntEncoder` instance.
+    pub fn create_on_unit(
+        unit: esp_hal::pcnt::unit::Unit,
+        limit: i16,
+        switch: SW,
+        config: EncoderConfig,
+    ) -> Self {
+        unit.clear();
+        unit.resume();
+        PcntEncoder {
+            unit,
+            last: 0,
+            limit,
+            counter: EncoderCounter::new(&config),
+            button: Button::create_on_pins_with_config(switch, config.button),
+        }
+    }
+
+    /// Reads the hardware counter and updates the position.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// The signed amount of detents turned, see `EncoderCounter::feed`.
+    pub fn update(&mut self, now_ms: u64) -> i32 {
+        let value = self.unit.get_value();
+        let steps = counter_delta(self.last, value, self.limit);
+        self.last = value;
+        self.counter.feed(steps, now_ms)
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> i32 {
+        self.counter.position()
+    }
+
+    /// Sets the current position, applying the limits.
+    pub fn set_position(&mut self, position: i32) {
+        self.counter.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels of A and B over one clockwise quadrature cycle, A leading B.
+    const CW: [(bool, bool); 5] = [
+        (false, false),
+        (true, false),
+        (true, true),
+        (false, true),
+        (false, false),
+    ];
+
+    #[test]
+    fn clockwise_cycle_steps_forward() {
+        let mut decoder = QuadratureDecoder::new(false, false);
+        for &(a, b) in &CW[1..] {
+            assert_eq!(decoder.update(a, b), 1);
+        }
+    }
+
+    #[test]
+    fn counter_clockwise_cycle_steps_backward() {
+        let mut decoder = QuadratureDecoder::new(false, false);
+        for &(a, b) in CW[..4].iter().rev() {
+            assert_eq!(decoder.update(a, b), -1);
+        }
+    }
+
+    #[test]
+    fn unchanged_and_invalid_transitions_do_not_move() {
+        for state in 0..4 {
+            assert_eq!(TRANSITIONS[(state << 2) | state], 0);
+            // Both signals changed
+            assert_eq!(TRANSITIONS[(state << 2) | (state ^ 0b11)], 0);
+        }
+    }
+
+    #[test]
+    fn table_is_antisymmetric() {
+        for from in 0..4 {
+            for to in 0..4 {
+                assert_eq!(
+                    TRANSITIONS[(from << 2) | to],
+                    -TRANSITIONS[(to << 2) | from]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reversal_mid_cycle_cancels_out() {
+        let mut decoder = QuadratureDecoder::new(false, false);
+        let mut position = 0;
+        for (a, b) in [(true, false), (true, true), (true, false), (false, false)] {
+            position += decoder.update(a, b) as i32;
+        }
+        assert_eq!(position, 0);
+    }
+
+    #[test]
+    fn steps_are_grouped_into_detents() {
+        let mut counter = EncoderCounter::new(&EncoderConfig::default());
+        assert_eq!(counter.feed(3, 0), 0);
+        assert_eq!(counter.feed(1, 0), 1);
+        assert_eq!(counter.feed(-8, 0), -2);
+        assert_eq!(counter.position(), -1);
+    }
+
+    #[test]
+    fn wrap_and_clamp_limits() {
+        let config = EncoderConfig {
+            steps_per_detent: 1,
+            limits: Limits::Wrap { min: 0, max: 9 },
+            ..Default::default()
+        };
+        let mut counter = EncoderCounter::new(&config);
+        // One detent counter-clockwise, wrapping around.
+        assert_eq!(counter.feed(-1, 0), -1);
+        assert_eq!(counter.position(), 9);
+        counter.feed(12, 0);
+        assert_eq!(counter.position(), 1);
+
+        let config = EncoderConfig {
+            steps_per_detent: 1,
+            limits: Limits::Clamp { min: -2, max: 2 },
+            ..Default::default()
+        };
+        let mut counter = EncoderCounter::new(&config);
+        assert_eq!(counter.feed(5, 0), 5);
+        assert_eq!(counter.position(), 2);
+        assert_eq!(counter.feed(-9, 0), -9);
+        assert_eq!(counter.position(), -2);
+    }
+
+    #[test]
+    fn limits_at_the_range_of_i32_do_not_overflow() {
+        let config = EncoderConfig {
+            steps_per_detent: 1,
+            limits: Limits::Wrap {
+                min: i32::MIN,
+                max: i32::MAX,
+            },
+            ..Default::default()
+        };
+        let mut counter = EncoderCounter::new(&config);
+        counter.set_position(i32::MAX);
+        assert_eq!(counter.feed(1, 0), 1);
+        assert_eq!(counter.position(), i32::MIN);
+
+        let config = EncoderConfig {
+            steps_per_detent: 1,
+            limits: Limits::Clamp {
+                min: i32::MIN,
+                max: i32::MAX,
+            },
+            ..Default::default()
+        };
+        let mut counter = EncoderCounter::new(&config);
+        counter.set_position(i32::MAX - 1);
+        assert_eq!(counter.feed(i32::MAX, 0), i32::MAX);
+        assert_eq!(counter.position(), i32::MAX);
+    }
+
+    #[test]
+    fn fast_detents_are_accelerated() {
+        let config = EncoderConfig {
+            steps_per_detent: 1,
+            acceleration: Some(Acceleration {
+                threshold_ms: 100,
+                max_multiplier: 5,
+            }),
+            ..Default::default()
+        };
+        let mut counter = EncoderCounter::new(&config);
+        assert_eq!(counter.feed(1, 1000), 1);
+        assert_eq!(counter.feed(1, 1050), 2);
+        assert_eq!(counter.feed(1, 1060), 5);
+        assert_eq!(counter.feed(1, 1500), 1);
+    }
+
+    #[test]
+    fn pulse_counter_delta_wraps_at_the_limit() {
+        assert_eq!(counter_delta(0, 4, 16384), 4);
+        assert_eq!(counter_delta(5, -3, 16384), -8);
+        // Reset to zero when reaching the high limit
+        assert_eq!(counter_delta(16382, 2, 16384), 4);
+        // Reset to zero when reaching the low limit
+        assert_eq!(counter_delta(-16383, -1, 16384), -2);
+    }
+}
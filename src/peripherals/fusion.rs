@@ -0,0 +1,864 @@
+//! # Sensor Fusion Module
+//!
+//! This module estimates the orientation of a device from the readings of an
+//! accelerometer, a gyroscope and optionally a magnetometer (e.g.
+//! `peripherals::mpu6050`). The gyroscope tracks fast rotations but drifts,
+//! the accelerometer (gravity) and the magnetometer (magnetic north) are noisy
+//! but do not drift; the filters here combine them:
+//! - `Madgwick`: gradient descent AHRS filter, tuned by a single gain `beta`.
+//! - `Mahony`: proportional-integral AHRS filter, which also estimates the
+//!   gyroscope bias.
+//! - `Complementary`: blends integrated gyroscope angles with the angles from
+//!   the accelerometer and magnetometer, cheap and easy to understand.
+//!
+//! All filters take timestamped `MotionSample`s, with or without a magnetometer
+//! reading (6-DoF or 9-DoF), and report the orientation as a `Quaternion` or as
+//! `EulerAngles`. The first sample initializes the orientation from the
+//! accelerometer and magnetometer.
+//!
+//! ## Conventions
+//! Readings are expected in a right-handed sensor frame whose Z axis points
+//! up when the device lies flat, as with most IMU chips. Accelerometer and
+//! magnetometer units do not matter (only directions are used), the gyroscope
+//! must be in rad/s. The earth frame has X pointing to magnetic north and Z
+//! pointing up. Euler angles are in radians: roll around X, pitch around Y and
+//! yaw around Z (counterclockwise seen from above). The compass heading is
+//! measured clockwise from magnetic north, see `EulerAngles::heading`.
+//!
+//! This module is pure maths and does not touch the hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     fusion::{Madgwick, MotionSample, OrientationFilter},
+//!     mpu6050::Mpu6050,
+//!     I2cPeriph,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//! let mut imu = Mpu6050::create_on_i2c(i2c, delay).unwrap();
+//! let mut filter = Madgwick::new(0.1);
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1_000_000);
+//!     let sample = MotionSample::read_from(&mut imu, now).unwrap();
+//!     filter.update(&sample);
+//!     let angles = filter.euler().to_degrees();
+//!     println!(
+//!         "Roll {} Pitch {} Yaw {}",
+//!         angles.roll, angles.pitch, angles.yaw
+//!     );
+//! }
+//! ```
+
+use core::f32::consts::PI;
+
+use super::{AccelerationSensor, GyroSensor, PeripheralError, Vector3};
+
+/// A unit quaternion describing the rotation from the sensor frame to the
+/// earth frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+impl Quaternion {
+    /// No rotation: the sensor lies flat with X pointing to north.
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    /// Creates a new `Quaternion`, not normalized.
+    pub const fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    /// Creates a quaternion from Euler angles, see `EulerAngles`.
+    pub fn from_euler(angles: EulerAngles) -> Self {
+        let (sr, cr) = libm::sincosf(angles.roll / 2.0);
+        let (sp, cp) = libm::sincosf(angles.pitch / 2.0);
+        let (sy, cy) = libm::sincosf(angles.yaw / 2.0);
+        Quaternion {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    /// Returns the length of the quaternion.
+    pub fn norm(&self) -> f32 {
+        libm::sqrtf(self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z)
+    }
+
+    /// Returns the quaternion scaled to unit length.
+    pub fn normalized(&self) -> Self {
+        let norm = self.norm();
+        if norm == 0.0 {
+            return Quaternion::IDENTITY;
+        }
+        Quaternion::new(self.w / norm, self.x / norm, self.y / norm, self.z / norm)
+    }
+
+    /// Returns the inverse rotation.
+    pub fn conjugate(&self) -> Self {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Returns the Hamilton product `self * other`, the rotation `other`
+    /// followed by `self`.
+    pub fn mul(&self, other: &Quaternion) -> Self {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+
+    /// Rotates a vector from the sensor frame to the earth frame.
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        let Quaternion { w, x, y, z } = *self;
+        Vector3::new(
+            v.x * (1.0 - 2.0 * (y * y + z * z))
+                + v.y * 2.0 * (x * y - w * z)
+                + v.z * 2.0 * (x * z + w * y),
+            v.x * 2.0 * (x * y + w * z)
+                + v.y * (1.0 - 2.0 * (x * x + z * z))
+                + v.z * 2.0 * (y * z - w * x),
+            v.x * 2.0 * (x * z - w * y)
+                + v.y * 2.0 * (y * z + w * x)
+                + v.z * (1.0 - 2.0 * (x * x + y * y)),
+        )
+    }
+
+    /// Rotates a vector from the earth frame to the sensor frame.
+    pub fn rotate_inverse(&self, v: Vector3) -> Vector3 {
+        self.conjugate().rotate(v)
+    }
+
+    /// Converts the quaternion to Euler angles.
+    pub fn to_euler(&self) -> EulerAngles {
+        let Quaternion { w, x, y, z } = *self;
+        EulerAngles {
+            roll: libm::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y)),
+            pitch: libm::asinf((2.0 * (w * y - z * x)).clamp(-1.0, 1.0)),
+            yaw: libm::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z)),
+        }
+    }
+}
+
+/// Orientation as roll, pitch and yaw in radians, applied in the order yaw,
+/// pitch, roll.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EulerAngles {
+    /// Rotation around the X axis, -π..=π.
+    pub roll: f32,
+    /// Rotation around the Y axis, -π/2..=π/2.
+    pub pitch: f32,
+    /// Rotation around the Z axis, counterclockwise seen from above, -π..=π.
+    pub yaw: f32,
+}
+
+impl EulerAngles {
+    /// Returns the angles converted to degrees.
+    pub fn to_degrees(&self) -> Self {
+        EulerAngles {
+            roll: self.roll.to_degrees(),
+            pitch: self.pitch.to_degrees(),
+            yaw: self.yaw.to_degrees(),
+        }
+    }
+
+    /// Returns the compass heading of the sensor X axis in radians, clockwise
+    /// from magnetic north, 0..2π. Only meaningful with a magnetometer.
+    pub fn heading(&self) -> f32 {
+        wrap_positive(-self.yaw)
+    }
+}
+
+/// One reading of the motion sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MotionSample {
+    /// Acceleration, in any unit.
+    pub accel: Vector3,
+    /// Angular rate in rad/s.
+    pub gyro: Vector3,
+    /// Magnetic field, in any unit, for 9-DoF fusion.
+    pub mag: Option<Vector3>,
+    /// Time of the reading in microseconds.
+    pub timestamp_us: u64,
+}
+
+impl MotionSample {
+    /// Creates a new 6-DoF sample.
+    pub const fn new(accel: Vector3, gyro: Vector3, timestamp_us: u64) -> Self {
+        MotionSample {
+            accel,
+            gyro,
+            mag: None,
+            timestamp_us,
+        }
+    }
+
+    /// Adds a magnetometer reading, making it a 9-DoF sample. The
+    /// magnetometer axes must be aligned with the accelerometer axes.
+    pub const fn with_magnetometer(mut self, mag: Vector3) -> Self {
+        self.mag = Some(mag);
+        self
+    }
+
+    /// Reads a 6-DoF sample from a motion sensor.
+    ///
+    /// # Arguments
+    /// * `sensor` - Sensor with an accelerometer and a gyroscope.
+    /// * `timestamp_us` - Current time in microseconds.
+    pub fn read_from<S: AccelerationSensor + GyroSensor>(
+        sensor: &mut S,
+        timestamp_us: u64,
+    ) -> Result<Self, PeripheralError> {
+        Ok(MotionSample::new(
+            sensor.get_acceleration()?,
+            sensor.get_angular_rate()?,
+            timestamp_us,
+        ))
+    }
+}
+
+/// Common interface of the orientation filters.
+pub trait OrientationFilter {
+    /// Updates the orientation with a new sample. The first sample (also after
+    /// `reset`) initializes the orientation from the accelerometer and
+    /// magnetometer.
+    fn update(&mut self, sample: &MotionSample);
+
+    /// Returns the current orientation.
+    fn quaternion(&self) -> Quaternion;
+
+    /// Forgets the orientation, the next sample initializes it again.
+    fn reset(&mut self);
+
+    /// Returns the current orientation as Euler angles.
+    fn euler(&self) -> EulerAngles {
+        self.quaternion().to_euler()
+    }
+
+    /// Returns the compass heading in radians, see `EulerAngles::heading`.
+    fn heading(&self) -> f32 {
+        self.euler().heading()
+    }
+}
+
+fn dot(a: Vector3, b: Vector3) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    Vector3::new(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    )
+}
+
+fn normalize(v: Vector3) -> Option<Vector3> {
+    let magnitude = v.magnitude();
+    if magnitude == 0.0 || !magnitude.is_finite() {
+        return None;
+    }
+    Some(Vector3::new(
+        v.x / magnitude,
+        v.y / magnitude,
+        v.z / magnitude,
+    ))
+}
+
+/// Wraps an angle to -π..=π.
+fn wrap(angle: f32) -> f32 {
+    let wrapped = libm::remainderf(angle, 2.0 * PI);
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+/// Wraps an angle to 0..2π.
+fn wrap_positive(angle: f32) -> f32 {
+    let wrapped = libm::fmodf(angle, 2.0 * PI);
+    if wrapped < 0.0 {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// Computes the tilt-compensated compass heading of the sensor X axis.
+///
+/// # Arguments
+/// * `accel` - Accelerometer reading while not accelerating (gravity only).
+/// * `mag` - Magnetometer reading, axes aligned with the accelerometer.
+///
+/// # Returns
+/// The heading in radians, clockwise from magnetic north, 0..2π, or `None` if
+/// a reading is zero or both point the same way.
+pub fn tilt_compensated_heading(accel: Vector3, mag: Vector3) -> Option<f32> {
+    let up = normalize(accel)?;
+    // The horizontal component of the field points north, so up × field
+    // points west and west × up points north.
+    let west = normalize(cross(up, mag))?;
+    let north = cross(west, up);
+    Some(wrap_positive(libm::atan2f(-west.x, north.x)))
+}
+
+/// Computes roll and pitch from an accelerometer reading (gravity only).
+///
+/// # Returns
+/// Euler angles with a yaw of zero.
+pub fn tilt_from_accel(accel: Vector3) -> EulerAngles {
+    EulerAngles {
+        roll: libm::atan2f(accel.y, accel.z),
+        pitch: libm::atan2f(-accel.x, libm::sqrtf(accel.y * accel.y + accel.z * accel.z)),
+        yaw: 0.0,
+    }
+}
+
+/// Orientation from the accelerometer and magnetometer only.
+fn initial_orientation(sample: &MotionSample) -> EulerAngles {
+    let mut angles = tilt_from_accel(sample.accel);
+    if let Some(heading) = sample
+        .mag
+        .and_then(|mag| tilt_compensated_heading(sample.accel, mag))
+    {
+        angles.yaw = wrap(-heading);
+    }
+    angles
+}
+
+/// Time between samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Timestep {
+    last_us: Option<u64>,
+}
+
+impl Timestep {
+    /// Returns the seconds since the previous sample, or `None` for the first
+    /// sample.
+    fn advance(&mut self, now_us: u64) -> Option<f32> {
+        let last = self.last_us.replace(now_us)?;
+        Some(now_us.saturating_sub(last) as f32 / 1_000_000.0)
+    }
+}
+
+/// Integrates the angular rate into the orientation over `dt` seconds.
+fn integrate(q: Quaternion, gyro: Vector3, dt: f32) -> Quaternion {
+    let rate = q.mul(&Quaternion::new(0.0, gyro.x, gyro.y, gyro.z));
+    Quaternion::new(
+        q.w + 0.5 * rate.w * dt,
+        q.x + 0.5 * rate.x * dt,
+        q.y + 0.5 * rate.y * dt,
+        q.z + 0.5 * rate.z * dt,
+    )
+}
+
+/// Madgwick's gradient descent orientation filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Madgwick {
+    /// Weight of the accelerometer and magnetometer correction, higher
+    /// converges faster but is noisier. 0.1 is a good start; roughly the
+    /// expected gyroscope error in rad/s.
+    pub beta: f32,
+    q: Quaternion,
+    timestep: Timestep,
+}
+
+impl Madgwick {
+    /// Creates a new `Madgwick` filter.
+    ///
+    /// # Arguments
+    /// * `beta` - Gain of the correction, see `Madgwick::beta`.
+    pub const fn new(beta: f32) -> Self {
+        Madgwick {
+            beta,
+            q: Quaternion::IDENTITY,
+            timestep: Timestep { last_us: None },
+        }
+    }
+
+    /// Gradient of the error between the measured and the expected directions
+    /// of gravity and the magnetic field.
+    fn gradient(&self, accel: Vector3, mag: Option<Vector3>) -> [f32; 4] {
+        let Quaternion { w, x, y, z } = self.q;
+        // Gravity: expected direction in the sensor frame minus the measured.
+        let f = [
+            2.0 * (x * z - w * y) - accel.x,
+            2.0 * (w * x + y * z) - accel.y,
+            1.0 - 2.0 * (x * x + y * y) - accel.z,
+        ];
+        let j = [
+            [-2.0 * y, 2.0 * z, -2.0 * w, 2.0 * x],
+            [2.0 * x, 2.0 * w, 2.0 * z, 2.0 * y],
+            [0.0, -4.0 * x, -4.0 * y, 0.0],
+        ];
+        let mut gradient = [0.0f32; 4];
+        for row in 0..3 {
+            for col in 0..4 {
+                gradient[col] += j[row][col] * f[row];
+            }
+        }
+
+        if let Some(mag) = mag {
+            // Direction of the field in the earth frame, with the horizontal
+            // component moved onto the X axis.
+            let h = self.q.rotate(mag);
+            let bx = libm::sqrtf(h.x * h.x + h.y * h.y);
+            let bz = h.z;
+            let f = [
+                2.0 * bx * (0.5 - y * y - z * z) + 2.0 * bz * (x * z - w * y) - mag.x,
+                2.0 * bx * (x * y - w * z) + 2.0 * bz * (w * x + y * z) - mag.y,
+                2.0 * bx * (w * y + x * z) + 2.0 * bz * (0.5 - x * x - y * y) - mag.z,
+            ];
+            let j = [
+                [
+                    -2.0 * bz * y,
+                    2.0 * bz * z,
+                    -4.0 * bx * y - 2.0 * bz * w,
+                    -4.0 * bx * z + 2.0 * bz * x,
+                ],
+                [
+                    -2.0 * bx * z + 2.0 * bz * x,
+                    2.0 * bx * y + 2.0 * bz * w,
+                    2.0 * bx * x + 2.0 * bz * z,
+                    -2.0 * bx * w + 2.0 * bz * y,
+                ],
+                [
+                    2.0 * bx * y,
+                    2.0 * bx * z - 4.0 * bz * x,
+                    2.0 * bx * w - 4.0 * bz * y,
+                    2.0 * bx * x,
+                ],
+            ];
+            for row in 0..3 {
+                for col in 0..4 {
+                    gradient[col] += j[row][col] * f[row];
+                }
+            }
+        }
+        gradient
+    }
+}
+
+impl OrientationFilter for Madgwick {
+    fn update(&mut self, sample: &MotionSample) {
+        let dt = match self.timestep.advance(sample.timestamp_us) {
+            Some(dt) => dt,
+            None => {
+                self.q = Quaternion::from_euler(initial_orientation(sample));
+                return;
+            }
+        };
+
+        let mut q = integrate(self.q, sample.gyro, dt);
+        if let Some(accel) = normalize(sample.accel) {
+            let mag = sample.mag.and_then(normalize);
+            let [gw, gx, gy, gz] = self.gradient(accel, mag);
+            let step = Quaternion::new(gw, gx, gy, gz);
+            let norm = step.norm();
+            if norm > 0.0 {
+                let k = self.beta * dt / norm;
+                q = Quaternion::new(
+                    q.w - k * step.w,
+                    q.x - k * step.x,
+                    q.y - k * step.y,
+                    q.z - k * step.z,
+                );
+            }
+        }
+        self.q = q.normalized();
+    }
+
+    fn quaternion(&self) -> Quaternion {
+        self.q
+    }
+
+    fn reset(&mut self) {
+        self.q = Quaternion::IDENTITY;
+        self.timestep = Timestep::default();
+    }
+}
+
+/// Mahony's proportional-integral orientation filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mahony {
+    /// Proportional gain of the correction, e.g. 1.0.
+    pub kp: f32,
+    /// Integral gain estimating the gyroscope bias, `0.0` disables it.
+    pub ki: f32,
+    q: Quaternion,
+    integral: Vector3,
+    timestep: Timestep,
+}
+
+impl Mahony {
+    /// Creates a new `Mahony` filter.
+    ///
+    /// # Arguments
+    /// * `kp` - Proportional gain, see `Mahony::kp`.
+    /// * `ki` - Integral gain, see `Mahony::ki`.
+    pub const fn new(kp: f32, ki: f32) -> Self {
+        Mahony {
+            kp,
+            ki,
+            q: Quaternion::IDENTITY,
+            integral: Vector3::new(0.0, 0.0, 0.0),
+            timestep: Timestep { last_us: None },
+        }
+    }
+
+    /// Returns the estimated gyroscope bias in rad/s.
+    pub fn gyro_bias(&self) -> Vector3 {
+        Vector3::new(-self.integral.x, -self.integral.y, -self.integral.z)
+    }
+}
+
+impl OrientationFilter for Mahony {
+    fn update(&mut self, sample: &MotionSample) {
+        let dt = match self.timestep.advance(sample.timestamp_us) {
+            Some(dt) => dt,
+            None => {
+                self.q = Quaternion::from_euler(initial_orientation(sample));
+                return;
+            }
+        };
+
+        let mut gyro = sample.gyro;
+        if let Some(accel) = normalize(sample.accel) {
+            // Rotation needed to turn the expected directions into the
+            // measured ones.
+            let gravity = self.q.rotate_inverse(Vector3::new(0.0, 0.0, 1.0));
+            let mut error = cross(accel, gravity);
+            if let Some(mag) = sample.mag.and_then(normalize) {
+                let h = self.q.rotate(mag);
+                let b = Vector3::new(libm::sqrtf(h.x * h.x + h.y * h.y), 0.0, h.z);
+                let field = self.q.rotate_inverse(b);
+                let mag_error = cross(mag, field);
+                error = Vector3::new(
+                    error.x + mag_error.x,
+                    error.y + mag_error.y,
+                    error.z + mag_error.z,
+                );
+            }
+
+            if self.ki > 0.0 {
+                self.integral.x += self.ki * error.x * dt;
+                self.integral.y += self.ki * error.y * dt;
+                self.integral.z += self.ki * error.z * dt;
+            } else {
+                self.integral = Vector3::default();
+            }
+            gyro.x += self.kp * error.x + self.integral.x;
+            gyro.y += self.kp * error.y + self.integral.y;
+            gyro.z += self.kp * error.z + self.integral.z;
+        }
+        self.q = integrate(self.q, gyro, dt).normalized();
+    }
+
+    fn quaternion(&self) -> Quaternion {
+        self.q
+    }
+
+    fn reset(&mut self) {
+        self.q = Quaternion::IDENTITY;
+        self.integral = Vector3::default();
+        self.timestep = Timestep::default();
+    }
+}
+
+/// Complementary filter working directly on Euler angles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complementary {
+    /// Weight of the integrated gyroscope angles, 0.0..=1.0, e.g. 0.98. The
+    /// rest comes from the accelerometer and magnetometer.
+    pub alpha: f32,
+    angles: EulerAngles,
+    timestep: Timestep,
+}
+
+impl Complementary {
+    /// Creates a new `Complementary` filter.
+    ///
+    /// # Arguments
+    /// * `alpha` - Weight of the gyroscope, see `Complementary::alpha`.
+    pub const fn new(alpha: f32) -> Self {
+        Complementary {
+            alpha,
+            angles: EulerAngles {
+                roll: 0.0,
+                pitch: 0.0,
+                yaw: 0.0,
+            },
+            timestep: Timestep { last_us: None },
+        }
+    }
+}
+
+impl OrientationFilter for Complementary {
+    fn update(&mut self, sample: &MotionSample) {
+        let dt = match self.timestep.advance(sample.timestamp_us) {
+            Some(dt) => dt,
+            None => {
+                self.angles = initial_orientation(sample);
+                return;
+            }
+        };
+
+        // Angular rate around the sensor axes to rates of the Euler angles.
+        let EulerAngles { roll, pitch, yaw } = self.angles;
+        let (sr, cr) = libm::sincosf(roll);
+        let cp = libm::cosf(pitch).max(1e-3);
+        let tp = libm::sinf(pitch) / cp;
+        let g = sample.gyro;
+        let mut angles = EulerAngles {
+            roll: wrap(roll + (g.x + sr * tp * g.y + cr * tp * g.z) * dt),
+            pitch: pitch + (cr * g.y - sr * g.z) * dt,
+            yaw: wrap(yaw + (sr / cp * g.y + cr / cp * g.z) * dt),
+        };
+
+        let weight = 1.0 - self.alpha.clamp(0.0, 1.0);
+        if normalize(sample.accel).is_some() {
+            let measured = initial_orientation(sample);
+            angles.roll = wrap(angles.roll + weight * wrap(measured.roll - angles.roll));
+            angles.pitch += weight * (measured.pitch - angles.pitch);
+            if sample.mag.is_some() {
+                angles.yaw = wrap(angles.yaw + weight * wrap(measured.yaw - angles.yaw));
+            }
+        }
+        angles.pitch = angles.pitch.clamp(-PI / 2.0, PI / 2.0);
+        self.angles = angles;
+    }
+
+    fn quaternion(&self) -> Quaternion {
+        Quaternion::from_euler(self.angles)
+    }
+
+    fn reset(&mut self) {
+        self.angles = EulerAngles::default();
+        self.timestep = Timestep::default();
+    }
+
+    fn euler(&self) -> EulerAngles {
+        self.angles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Field in the earth frame pointing north and down, as in Europe.
+    const FIELD: Vector3 = Vector3::new(0.5, 0.0, -0.8);
+    const UP: Vector3 = Vector3::new(0.0, 0.0, 1.0);
+    const STILL: Vector3 = Vector3::new(0.0, 0.0, 0.0);
+
+    fn assert_angle(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            libm::fabsf(wrap(actual - expected)) <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    fn assert_angles(actual: EulerAngles, expected: EulerAngles, tolerance: f32) {
+        assert_angle(actual.roll, expected.roll, tolerance);
+        assert_angle(actual.pitch, expected.pitch, tolerance);
+        assert_angle(actual.yaw, expected.yaw, tolerance);
+    }
+
+    /// Readings of a device held still in the given orientation.
+    fn still_sample(angles: EulerAngles, timestamp_us: u64) -> MotionSample {
+        let q = Quaternion::from_euler(angles);
+        MotionSample::new(q.rotate_inverse(UP), STILL, timestamp_us)
+            .with_magnetometer(q.rotate_inverse(FIELD))
+    }
+
+    const TILTED: EulerAngles = EulerAngles {
+        roll: 0.4,
+        pitch: -0.3,
+        yaw: -1.0,
+    };
+
+    #[test]
+    fn euler_round_trip() {
+        for (roll, pitch, yaw) in [
+            (0.0, 0.0, 0.0),
+            (0.4, -0.3, -1.0),
+            (-2.5, 1.2, 3.0),
+            (3.0, -1.4, -2.9),
+            (1.0, 0.5, 1.7),
+        ] {
+            let angles = EulerAngles { roll, pitch, yaw };
+            let q = Quaternion::from_euler(angles);
+            assert!(libm::fabsf(q.norm() - 1.0) < 1e-6);
+            assert_angles(q.to_euler(), angles, 1e-4);
+        }
+        assert_eq!(
+            Quaternion::from_euler(EulerAngles::default()),
+            Quaternion::IDENTITY
+        );
+    }
+
+    #[test]
+    fn rotations() {
+        // Yawing by 90° turns the sensor X axis to the west.
+        let q = Quaternion::from_euler(EulerAngles {
+            roll: 0.0,
+            pitch: 0.0,
+            yaw: PI / 2.0,
+        });
+        let west = q.rotate(Vector3::new(1.0, 0.0, 0.0));
+        assert!(libm::fabsf(west.x) < 1e-6 && libm::fabsf(west.y - 1.0) < 1e-6);
+
+        let q = Quaternion::from_euler(TILTED);
+        let v = Vector3::new(0.3, -0.7, 0.2);
+        let back = q.rotate_inverse(q.rotate(v));
+        assert!(libm::fabsf(back.x - v.x) < 1e-6);
+        assert!(libm::fabsf(back.y - v.y) < 1e-6);
+        assert!(libm::fabsf(back.z - v.z) < 1e-6);
+    }
+
+    #[test]
+    fn heading_quadrants() {
+        // Flat device, the field is seen turned against the heading.
+        for (mag, heading) in [
+            (Vector3::new(0.5, 0.0, -0.8), 0.0),
+            (Vector3::new(0.35355, 0.35355, -0.8), PI / 4.0),
+            (Vector3::new(0.0, 0.5, -0.8), PI / 2.0),
+            (Vector3::new(-0.5, 0.0, -0.8), PI),
+            (Vector3::new(0.0, -0.5, -0.8), 3.0 * PI / 2.0),
+            (Vector3::new(0.35355, -0.35355, -0.8), 7.0 * PI / 4.0),
+        ] {
+            assert_angle(tilt_compensated_heading(UP, mag).unwrap(), heading, 1e-4);
+        }
+
+        // Tilted device in every quadrant.
+        for heading in [0.3, 1.9, 3.5, 5.2] {
+            let q = Quaternion::from_euler(EulerAngles {
+                roll: -0.5,
+                pitch: 0.6,
+                yaw: -heading,
+            });
+            let measured =
+                tilt_compensated_heading(q.rotate_inverse(UP), q.rotate_inverse(FIELD)).unwrap();
+            assert!((0.0..2.0 * PI).contains(&measured));
+            assert_angle(measured, heading, 1e-4);
+        }
+
+        assert_eq!(tilt_compensated_heading(STILL, FIELD), None);
+        assert_eq!(tilt_compensated_heading(UP, STILL), None);
+        assert_eq!(tilt_compensated_heading(UP, UP), None);
+    }
+
+    #[test]
+    fn first_sample_initializes() {
+        let mut filter = Madgwick::new(0.1);
+        filter.update(&still_sample(TILTED, 0));
+        assert_angles(filter.euler(), TILTED, 1e-4);
+        assert_angle(filter.heading(), 1.0, 1e-4);
+
+        filter.reset();
+        assert_eq!(filter.quaternion(), Quaternion::IDENTITY);
+    }
+
+    /// Starts the filter flat and feeds it a still, tilted device for 60 s at
+    /// 100 Hz.
+    fn converge(filter: &mut dyn OrientationFilter, with_magnetometer: bool) -> EulerAngles {
+        filter.update(&still_sample(EulerAngles::default(), 0));
+        for index in 1..=6000 {
+            let mut sample = still_sample(TILTED, index * 10_000);
+            if !with_magnetometer {
+                sample.mag = None;
+            }
+            filter.update(&sample);
+        }
+        filter.euler()
+    }
+
+    #[test]
+    fn filters_converge_to_a_static_orientation() {
+        let filters: [&mut dyn OrientationFilter; 3] = [
+            &mut Madgwick::new(0.5),
+            &mut Mahony::new(1.0, 0.0),
+            &mut Complementary::new(0.98),
+        ];
+        for filter in filters {
+            assert_angles(converge(filter, true), TILTED, 0.01);
+        }
+    }
+
+    #[test]
+    fn filters_converge_without_magnetometer() {
+        let filters: [&mut dyn OrientationFilter; 3] = [
+            &mut Madgwick::new(0.5),
+            &mut Mahony::new(1.0, 0.0),
+            &mut Complementary::new(0.98),
+        ];
+        for filter in filters {
+            let angles = converge(filter, false);
+            assert_angle(angles.roll, TILTED.roll, 0.01);
+            assert_angle(angles.pitch, TILTED.pitch, 0.01);
+        }
+    }
+
+    #[test]
+    fn integrates_constant_rate() {
+        let filters: [&mut dyn OrientationFilter; 3] = [
+            &mut Madgwick::new(0.1),
+            &mut Mahony::new(1.0, 0.1),
+            &mut Complementary::new(0.98),
+        ];
+        for filter in filters {
+            // Turning counterclockwise at 0.5 rad/s for 2 s while lying flat.
+            for index in 0..=200 {
+                filter.update(&MotionSample::new(
+                    UP,
+                    Vector3::new(0.0, 0.0, 0.5),
+                    index * 10_000,
+                ));
+            }
+            let angles = filter.euler();
+            assert_angle(angles.yaw, 1.0, 0.005);
+            assert_angle(angles.roll, 0.0, 0.001);
+            assert_angle(angles.pitch, 0.0, 0.001);
+        }
+    }
+
+    #[test]
+    fn mahony_estimates_gyro_bias() {
+        let mut filter = Mahony::new(1.0, 0.3);
+        let bias = Vector3::new(0.02, -0.01, 0.0);
+        for index in 0..=6000 {
+            let mut sample = still_sample(TILTED, index * 10_000);
+            sample.gyro = bias;
+            filter.update(&sample);
+        }
+        let estimate = filter.gyro_bias();
+        assert!(libm::fabsf(estimate.x - bias.x) < 0.001, "{estimate:?}");
+        assert!(libm::fabsf(estimate.y - bias.y) < 0.001, "{estimate:?}");
+        assert_angles(filter.euler(), TILTED, 0.01);
+    }
+}
@@ -0,0 +1,268 @@
+//! # GPIO Events Module
+//!
+//! This module provides an interrupt-driven alternative to polling of simple
+//! digital inputs like `Button` and `PirSensor`. Instead of reading the pin in
+//! a busy loop, the input registers a GPIO edge interrupt and the interrupt
+//! handler pushes timestamped `InputEvent`s into an `EventQueue`. The main loop
+//! (or an embassy task with the `async` feature) drains the queue whenever it
+//! has time, so short pulses are not missed and the CPU can sleep in between.
+//!
+//! `EventQueue` is a lock-free single-producer single-consumer ring buffer:
+//! events should be pushed only from one interrupt handler and popped only
+//! from one place in the program.
+//!
+//! ## Example
+//! ```no_run
+//! use core::cell::RefCell;
+//!
+//! use critical_section::Mutex;
+//! use esp_hal::{gpio::*, interrupt, peripherals, prelude::*, systimer::SystemTimer};
+//! use esp_ward::peripherals::{
+//!     button::Button,
+//!     gpio_events::{EventQueue, InputEventKind},
+//! };
+//!
+//! const BUTTON_ID: u8 = 0;
+//! static EVENTS: EventQueue<16> = EventQueue::new();
+//! static BUTTON: Mutex<RefCell<Option<Button<GpioPin<Input<PullUp>, 9>>>>> =
+//!     Mutex::new(RefCell::new(None));
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     let peripherals = esp_ward::take_periph!();
+//!     let system = esp_ward::take_system!(peripherals);
+//!     let (_, pins, _) = esp_ward::init_chip!(peripherals, system);
+//!
+//!     let mut button = Button::create_on_pins(pins.gpio9.into_pull_up_input());
+//!     button.listen();
+//!     critical_section::with(|cs| BUTTON.borrow_ref_mut(cs).replace(button));
+//!     interrupt::enable(peripherals::Interrupt::GPIO, interrupt::Priority::Priority2).unwrap();
+//!
+//!     loop {
+//!         while let Some(event) = EVENTS.pop() {
+//!             if event.kind == InputEventKind::Activated {
+//!                 println!("Button pressed at {} ms", event.timestamp_ms);
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     critical_section::with(|cs| {
+//!         if let Some(button) = BUTTON.borrow_ref_mut(cs).as_mut() {
+//!             button.handle_interrupt(&EVENTS, BUTTON_ID, now);
+//!         }
+//!     });
+//! }
+//! ```
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "async")]
+use embassy_sync::waitqueue::AtomicWaker;
+
+/// Kind of a change of a digital input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEventKind {
+    /// The input became active (button pressed, motion detected).
+    Activated,
+    /// The input became inactive (button released, motion ended).
+    Deactivated,
+}
+
+/// A change of a digital input captured in an interrupt handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// User defined identifier of the input which produced the event.
+    pub source: u8,
+    /// What happened with the input.
+    pub kind: InputEventKind,
+    /// Time of the event in milliseconds, as passed to the interrupt handler.
+    pub timestamp_ms: u64,
+}
+
+impl InputEvent {
+    const EMPTY: InputEvent = InputEvent {
+        source: 0,
+        kind: InputEventKind::Deactivated,
+        timestamp_ms: 0,
+    };
+}
+
+/// Lock-free queue of `InputEvent`s, able to hold `N - 1` events. `N` must be
+/// at least 2, which is checked at compile time.
+///
+/// Designed to be placed in a `static` and shared between one interrupt
+/// handler (producer) and one consumer.
+pub struct EventQueue<const N: usize> {
+    buffer: UnsafeCell<[InputEvent; N]>,
+    /// Index of the next event to be popped, written only by the consumer.
+    head: AtomicUsize,
+    /// Index of the next free slot, written only by the producer.
+    tail: AtomicUsize,
+    /// Amount of events lost because the queue was full.
+    dropped: AtomicUsize,
+    #[cfg(feature = "async")]
+    waker: AtomicWaker,
+}
+
+// Access to the buffer is synchronized with `head` and `tail`, see `push` and
+// `pop`.
+unsafe impl<const N: usize> Sync for EventQueue<N> {}
+
+impl<const N: usize> EventQueue<N> {
+    /// One slot always stays free to tell a full queue from an empty one.
+    const CAPACITY_CHECK: () = assert!(N >= 2, "EventQueue needs N >= 2 to hold any event");
+
+    /// Creates a new empty queue.
+    pub const fn new() -> Self {
+        let () = Self::CAPACITY_CHECK;
+        EventQueue {
+            buffer: UnsafeCell::new([InputEvent::EMPTY; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            #[cfg(feature = "async")]
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Pushes an event to the queue. Should be called only from a single
+    /// producer (usually the GPIO interrupt handler).
+    ///
+    /// # Returns
+    /// `true` if the event was stored, `false` if the queue was full and the
+    /// event was dropped.
+    pub fn push(&self, event: InputEvent) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % N;
+        if next == self.head.load(Ordering::Acquire) {
+            // Only the producer writes `dropped`, so no read-modify-write
+            // atomics are needed (not every chip has them).
+            let dropped = self.dropped.load(Ordering::Relaxed);
+            self.dropped
+                .store(dropped.wrapping_add(1), Ordering::Relaxed);
+            return false;
+        }
+        unsafe { (*self.buffer.get())[tail] = event };
+        self.tail.store(next, Ordering::Release);
+
+        #[cfg(feature = "async")]
+        self.waker.wake();
+
+        true
+    }
+
+    /// Pops the oldest event from the queue. Should be called only from a
+    /// single consumer.
+    ///
+    /// # Returns
+    /// `Some(InputEvent)` or `None` if the queue is empty.
+    pub fn pop(&self) -> Option<InputEvent> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let event = unsafe { (*self.buffer.get())[head] };
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(event)
+    }
+
+    /// Returns the amount of events the queue can hold, `N - 1`.
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Returns `true` if there are no events waiting.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+
+    /// Returns the amount of events dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits until an event is available and returns it.
+    ///
+    /// Available only with the `async` feature, intended to be awaited from a
+    /// single embassy task.
+    #[cfg(feature = "async")]
+    pub async fn wait_for_event(&self) -> InputEvent {
+        core::future::poll_fn(|cx| {
+            if let Some(event) = self.pop() {
+                return core::task::Poll::Ready(event);
+            }
+            self.waker.register(cx.waker());
+            // An event might have arrived before the waker was registered
+            match self.pop() {
+                Some(event) => core::task::Poll::Ready(event),
+                None => core::task::Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+fn kind(active: bool) -> InputEventKind {
+    if active {
+        InputEventKind::Activated
+    } else {
+        InputEventKind::Deactivated
+    }
+}
+
+/// Checks the interrupt status of `pin` and, if it was triggered, pushes the
+/// events of the input to `queue` and clears the interrupt.
+///
+/// The pin interrupt fires on any edge and does not tell which one, so the
+/// kind of the event follows from the level of the input compared to the last
+/// reported one. When the level did not change, a pulse ended before the
+/// interrupt was handled and both of its edges are pushed, with the same
+/// timestamp.
+///
+/// # Arguments
+/// * `pin` - The pin to check.
+/// * `active` - Whether the input is active right now.
+/// * `reported` - Whether the input was active in the last pushed event,
+///   updated by this function.
+/// * `queue` - The queue to push the events to.
+/// * `source` - Identifier of the input stored in the events.
+/// * `now_ms` - Timestamp of the events in milliseconds.
+///
+/// # Returns
+/// `true` if the interrupt was caused by `pin`.
+#[cfg(feature = "esp-hal")]
+pub(crate) fn handle_pin_interrupt<P: esp_hal::gpio::Pin, const N: usize>(
+    pin: &mut P,
+    active: bool,
+    reported: &mut bool,
+    queue: &EventQueue<N>,
+    source: u8,
+    now_ms: u64,
+) -> bool {
+    if !pin.is_pcore_interrupt_set() {
+        return false;
+    }
+    if active == *reported {
+        queue.push(InputEvent {
+            source,
+            kind: kind(!active),
+            timestamp_ms: now_ms,
+        });
+    }
+    queue.push(InputEvent {
+        source,
+        kind: kind(active),
+        timestamp_ms: now_ms,
+    });
+    *reported = active;
+    pin.clear_interrupt();
+    true
+}
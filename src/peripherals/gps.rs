@@ -0,0 +1,880 @@
+//! # GPS Module
+//!
+//! This module provides a streaming NMEA 0183 parser and a driver for GPS (or
+//! GNSS) receivers on a serial port, like the u-blox NEO-6M/NEO-M8N or the
+//! MediaTek based modules.
+//!
+//! The parser understands the sentences carrying the navigation data, from any
+//! talker (`GP`, `GN`, `GL`, `GA`, `BD`, ...):
+//! - `GGA`: time, position, fix quality, satellites used, altitude,
+//! - `RMC`: time, date, position, speed and course,
+//! - `GSA`: fix type, satellites used and dilution of precision,
+//! - `GSV`: satellites in view with their elevation, azimuth and signal,
+//! - `VTG`: course and speed over ground.
+//!
+//! Sentences with a wrong or missing checksum are dropped. `GpsFix` collects
+//! the sentences of the receiver into the current fix, and its `timestamp`
+//! gives the UTC time as a UNIX timestamp, usable with the helpers in
+//! `connectivity::wifi` (`timestamp_to_hms`, `weekday_from_timestamp`)
+//! without network access. `Gps` is also a `TimeSource`, e.g. to set a
+//! real-time clock from.
+//!
+//! Most receivers talk at 9600 baud, so the UART can be created with
+//! `init_uart_default!`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::{connectivity::wifi::timestamp_to_hms, peripherals::gps::Gps};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//!
+//! let mut gps = Gps::create_on_uart(uart);
+//! loop {
+//!     let now_ms = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     match gps.poll(now_ms) {
+//!         Ok(Some(_)) if gps.fix().has_fix() => {
+//!             let fix = gps.fix();
+//!             println!("{:?}, {:?}", fix.latitude, fix.longitude);
+//!             if let Some(timestamp) = fix.timestamp() {
+//!                 println!("UTC time: {:?}", timestamp_to_hms(timestamp));
+//!             }
+//!         }
+//!         Ok(_) => (),
+//!         Err(_) => println!("GPS receiver does not answer"),
+//!     }
+//! }
+//! ```
+
+use core::str::FromStr;
+
+use embedded_hal::serial::{Read, Write};
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+#[cfg(feature = "esp-hal")]
+use super::{uart::read_frame_blocking, UnifiedData};
+use super::{
+    uart::{send, xor8, FrameFormat, FrameLength, FrameReceiver, FrameSync},
+    DateTime,
+    PeripheralError,
+    TimeSource,
+};
+
+/// Longest sentence kept. The standard allows 82 characters, some receivers
+/// send longer proprietary sentences.
+pub const NMEA_MAX_LEN: usize = 96;
+
+/// Longest time between two sentences; receivers send at least one burst of
+/// sentences per second.
+const SENTENCE_TIMEOUT_MS: u64 = 3000;
+/// Sentences read by `UnifiedData::read` while waiting for the `RMC` sentence.
+const MAX_SENTENCES_PER_FIX: usize = 32;
+/// Most fields in a supported sentence (`GSV` with four satellites has 20).
+const MAX_FIELDS: usize = 24;
+/// Kilometres per hour in one knot.
+const KMH_PER_KNOT: f32 = 1.852;
+
+/// Checks the XOR checksum between `$` and `*` against the two hexadecimal
+/// digits following `*`.
+fn sentence_valid(frame: &[u8]) -> bool {
+    let star = match frame.iter().position(|b| *b == b'*') {
+        Some(star) => star,
+        None => return false,
+    };
+    let checksum = frame
+        .get(star + 1..star + 3)
+        .and_then(|digits| core::str::from_utf8(digits).ok())
+        .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+    checksum == Some(xor8(&frame[1..star]))
+}
+
+/// Frame format of NMEA sentences, from `$` to the line feed.
+pub const FORMAT: FrameFormat = FrameFormat {
+    header: b"$",
+    length: FrameLength::Terminator(b'\n'),
+    footer: &[],
+    validate: Some(sentence_valid),
+};
+
+/// Time of day in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UtcTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millisecond: u16,
+}
+
+/// Calendar date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// Fix quality reported by `GGA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixQuality {
+    Invalid,
+    Gps,
+    Dgps,
+    Pps,
+    Rtk,
+    FloatRtk,
+    /// Dead reckoning.
+    Estimated,
+    Manual,
+    Simulation,
+}
+
+/// Fix type reported by `GSA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixType {
+    NoFix,
+    Fix2D,
+    Fix3D,
+}
+
+/// Global positioning system fix data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gga {
+    pub time: Option<UtcTime>,
+    /// Latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    pub quality: FixQuality,
+    /// Number of satellites used for the fix.
+    pub satellites: u8,
+    /// Horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// Altitude above mean sea level in metres.
+    pub altitude: Option<f32>,
+    /// Height of the geoid above the WGS84 ellipsoid in metres.
+    pub geoid_separation: Option<f32>,
+}
+
+/// Recommended minimum navigation data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rmc {
+    pub time: Option<UtcTime>,
+    /// `false` if the receiver has no valid fix.
+    pub valid: bool,
+    /// Latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    /// Speed over ground in knots.
+    pub speed_knots: Option<f32>,
+    /// Course over ground in degrees from true north.
+    pub course: Option<f32>,
+    pub date: Option<Date>,
+    /// Magnetic variation in degrees, negative to the west.
+    pub magnetic_variation: Option<f32>,
+}
+
+/// Dilution of precision and active satellites.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gsa {
+    /// `true` if the receiver switches between 2D and 3D on its own.
+    pub automatic: bool,
+    pub fix_type: FixType,
+    /// PRNs of the satellites used for the fix.
+    pub satellites: [Option<u8>; 12],
+    pub pdop: Option<f32>,
+    pub hdop: Option<f32>,
+    pub vdop: Option<f32>,
+}
+
+/// A satellite in view, reported by `GSV`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SatelliteInfo {
+    pub prn: u8,
+    /// Elevation in degrees, 0 to 90.
+    pub elevation: Option<u8>,
+    /// Azimuth in degrees from true north, 0 to 359.
+    pub azimuth: Option<u16>,
+    /// Signal to noise ratio in dBHz, `None` if not tracked.
+    pub snr: Option<u8>,
+}
+
+/// Satellites in view; the list is split over `message_count` sentences of
+/// up to four satellites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gsv {
+    pub message_count: u8,
+    pub message_number: u8,
+    pub satellites_in_view: u8,
+    pub satellites: [Option<SatelliteInfo>; 4],
+}
+
+/// Course and speed over ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vtg {
+    /// Course in degrees from true north.
+    pub course_true: Option<f32>,
+    /// Course in degrees from magnetic north.
+    pub course_magnetic: Option<f32>,
+    pub speed_knots: Option<f32>,
+    pub speed_kmh: Option<f32>,
+}
+
+/// A parsed NMEA sentence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+    Gsa(Gsa),
+    Gsv(Gsv),
+    Vtg(Vtg),
+}
+
+/// Parses a number, `None` for an empty or malformed field.
+fn number<T: FromStr>(field: &str) -> Option<T> {
+    field.parse().ok()
+}
+
+/// Parses `hhmmss.sss`.
+fn parse_time(field: &str) -> Option<UtcTime> {
+    if field.len() < 6 || !field.is_ascii() {
+        return None;
+    }
+    let fraction: f32 = match &field[6..] {
+        "" => 0.0,
+        rest => number(rest)?,
+    };
+    Some(UtcTime {
+        hour: number(&field[0..2])?,
+        minute: number(&field[2..4])?,
+        second: number(&field[4..6])?,
+        millisecond: libm::roundf(fraction * 1000.0) as u16,
+    })
+}
+
+/// Parses `ddmmyy`, years are taken as 20yy.
+fn parse_date(field: &str) -> Option<Date> {
+    if field.len() != 6 || !field.is_ascii() {
+        return None;
+    }
+    Some(Date {
+        year: 2000 + number::<u16>(&field[4..6])?,
+        month: number(&field[2..4])?,
+        day: number(&field[0..2])?,
+    })
+}
+
+/// Parses `dddmm.mmmm` with its hemisphere into degrees.
+fn parse_coordinate(field: &str, hemisphere: &str) -> Option<f64> {
+    let point = field.find('.').unwrap_or(field.len());
+    if point < 3 || !field.is_ascii() {
+        return None;
+    }
+    let degrees: f64 = number(&field[..point - 2])?;
+    let minutes: f64 = number(&field[point - 2..])?;
+    let value = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(value),
+        "S" | "W" => Some(-value),
+        _ => None,
+    }
+}
+
+/// Parses a value followed by an `E` or `W` direction field.
+fn parse_variation(field: &str, direction: &str) -> Option<f32> {
+    let value: f32 = number(field)?;
+    match direction {
+        "E" => Some(value),
+        "W" => Some(-value),
+        _ => None,
+    }
+}
+
+fn parse_gga(fields: &[&str]) -> Option<Gga> {
+    if fields.len() < 14 {
+        return None;
+    }
+    let quality = match fields[6] {
+        "1" => FixQuality::Gps,
+        "2" => FixQuality::Dgps,
+        "3" => FixQuality::Pps,
+        "4" => FixQuality::Rtk,
+        "5" => FixQuality::FloatRtk,
+        "6" => FixQuality::Estimated,
+        "7" => FixQuality::Manual,
+        "8" => FixQuality::Simulation,
+        _ => FixQuality::Invalid,
+    };
+    Some(Gga {
+        time: parse_time(fields[1]),
+        latitude: parse_coordinate(fields[2], fields[3]),
+        longitude: parse_coordinate(fields[4], fields[5]),
+        quality,
+        satellites: number(fields[7]).unwrap_or(0),
+        hdop: number(fields[8]),
+        altitude: number(fields[9]),
+        geoid_separation: number(fields[11]),
+    })
+}
+
+fn parse_rmc(fields: &[&str]) -> Option<Rmc> {
+    if fields.len() < 10 {
+        return None;
+    }
+    Some(Rmc {
+        time: parse_time(fields[1]),
+        valid: fields[2] == "A",
+        latitude: parse_coordinate(fields[3], fields[4]),
+        longitude: parse_coordinate(fields[5], fields[6]),
+        speed_knots: number(fields[7]),
+        course: number(fields[8]),
+        date: parse_date(fields[9]),
+        magnetic_variation: fields
+            .get(10)
+            .zip(fields.get(11))
+            .and_then(|(value, direction)| parse_variation(value, direction)),
+    })
+}
+
+fn parse_gsa(fields: &[&str]) -> Option<Gsa> {
+    if fields.len() < 18 {
+        return None;
+    }
+    let fix_type = match fields[2] {
+        "2" => FixType::Fix2D,
+        "3" => FixType::Fix3D,
+        _ => FixType::NoFix,
+    };
+    let mut satellites = [None; 12];
+    for (satellite, field) in satellites.iter_mut().zip(&fields[3..15]) {
+        *satellite = number(field);
+    }
+    Some(Gsa {
+        automatic: fields[1] == "A",
+        fix_type,
+        satellites,
+        pdop: number(fields[15]),
+        hdop: number(fields[16]),
+        vdop: number(fields[17]),
+    })
+}
+
+fn parse_gsv(fields: &[&str]) -> Option<Gsv> {
+    if fields.len() < 4 {
+        return None;
+    }
+    // NMEA 4.10 adds a signal ID after the last satellite, which is left out
+    // by only taking complete groups of four fields.
+    let mut satellites = [None; 4];
+    for (satellite, info) in satellites.iter_mut().zip(fields[4..].chunks_exact(4)) {
+        *satellite = number(info[0]).map(|prn| SatelliteInfo {
+            prn,
+            elevation: number(info[1]),
+            azimuth: number(info[2]),
+            snr: number(info[3]),
+        });
+    }
+    Some(Gsv {
+        message_count: number(fields[1])?,
+        message_number: number(fields[2])?,
+        satellites_in_view: number(fields[3])?,
+        satellites,
+    })
+}
+
+fn parse_vtg(fields: &[&str]) -> Option<Vtg> {
+    if fields.len() < 9 {
+        return None;
+    }
+    Some(Vtg {
+        course_true: number(fields[1]),
+        course_magnetic: number(fields[3]),
+        speed_knots: number(fields[5]),
+        speed_kmh: number(fields[7]),
+    })
+}
+
+/// Parses a complete NMEA sentence, from `$` to the optional line ending.
+///
+/// # Returns
+/// The sentence, or `None` if it is malformed or of an unsupported type.
+/// The checksum is not checked, see `FORMAT`.
+pub fn parse_sentence(sentence: &[u8]) -> Option<Sentence> {
+    let text = core::str::from_utf8(sentence).ok()?;
+    let text = text.trim_end().strip_prefix('$')?;
+    let body = text.split('*').next()?;
+
+    let mut fields = [""; MAX_FIELDS];
+    let mut count = 0;
+    for field in body.split(',') {
+        if count == MAX_FIELDS {
+            return None;
+        }
+        fields[count] = field;
+        count += 1;
+    }
+    let fields = &fields[..count];
+
+    // Two letter talker ID followed by the sentence type, proprietary
+    // sentences start with `P`.
+    let address = fields[0];
+    if address.len() != 5 || address.starts_with('P') || !address.is_ascii() {
+        return None;
+    }
+    match &address[2..] {
+        "GGA" => parse_gga(fields).map(Sentence::Gga),
+        "RMC" => parse_rmc(fields).map(Sentence::Rmc),
+        "GSA" => parse_gsa(fields).map(Sentence::Gsa),
+        "GSV" => parse_gsv(fields).map(Sentence::Gsv),
+        "VTG" => parse_vtg(fields).map(Sentence::Vtg),
+        _ => None,
+    }
+}
+
+/// The navigation data collected from the sentences of a receiver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    pub quality: FixQuality,
+    pub fix_type: FixType,
+    /// Latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// Longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    /// Altitude above mean sea level in metres.
+    pub altitude: Option<f32>,
+    /// Speed over ground in km/h.
+    pub speed_kmh: Option<f32>,
+    /// Course over ground in degrees from true north.
+    pub course: Option<f32>,
+    pub satellites_used: u8,
+    pub satellites_in_view: u8,
+    pub hdop: Option<f32>,
+    pub pdop: Option<f32>,
+    pub vdop: Option<f32>,
+    pub time: Option<UtcTime>,
+    pub date: Option<Date>,
+}
+
+impl Default for GpsFix {
+    fn default() -> Self {
+        GpsFix {
+            quality: FixQuality::Invalid,
+            fix_type: FixType::NoFix,
+            latitude: None,
+            longitude: None,
+            altitude: None,
+            speed_kmh: None,
+            course: None,
+            satellites_used: 0,
+            satellites_in_view: 0,
+            hdop: None,
+            pdop: None,
+            vdop: None,
+            time: None,
+            date: None,
+        }
+    }
+}
+
+impl GpsFix {
+    /// Returns `true` if the position is valid.
+    pub fn has_fix(&self) -> bool {
+        self.quality != FixQuality::Invalid && self.latitude.is_some() && self.longitude.is_some()
+    }
+
+    /// Updates the fix with a sentence.
+    pub fn update(&mut self, sentence: &Sentence) {
+        match sentence {
+            Sentence::Gga(gga) => {
+                self.time = gga.time.or(self.time);
+                self.quality = gga.quality;
+                self.latitude = gga.latitude;
+                self.longitude = gga.longitude;
+                self.altitude = gga.altitude;
+                self.satellites_used = gga.satellites;
+                self.hdop = gga.hdop;
+            }
+            Sentence::Rmc(rmc) => {
+                self.time = rmc.time.or(self.time);
+                self.date = rmc.date.or(self.date);
+                if rmc.valid {
+                    self.latitude = rmc.latitude;
+                    self.longitude = rmc.longitude;
+                    self.speed_kmh = rmc.speed_knots.map(|knots| knots * KMH_PER_KNOT);
+                    self.course = rmc.course;
+                } else {
+                    self.quality = FixQuality::Invalid;
+                    self.speed_kmh = None;
+                    self.course = None;
+                }
+            }
+            Sentence::Gsa(gsa) => {
+                self.fix_type = gsa.fix_type;
+                self.pdop = gsa.pdop;
+                self.hdop = gsa.hdop;
+                self.vdop = gsa.vdop;
+            }
+            Sentence::Gsv(gsv) => self.satellites_in_view = gsv.satellites_in_view,
+            Sentence::Vtg(vtg) => {
+                self.course = vtg.course_true;
+                self.speed_kmh = vtg
+                    .speed_kmh
+                    .or(vtg.speed_knots.map(|knots| knots * KMH_PER_KNOT));
+            }
+        }
+    }
+
+    /// Returns the UTC date and time, once a `RMC` sentence reported the
+    /// date.
+    pub fn datetime(&self) -> Option<DateTime> {
+        let (date, time) = (self.date?, self.time?);
+        Some(DateTime::new(
+            date.year,
+            date.month,
+            date.day,
+            time.hour,
+            time.minute,
+            time.second,
+        ))
+    }
+
+    /// Returns the UTC date and time as a UNIX timestamp (seconds since
+    /// 1970-01-01), see `GpsFix::datetime`.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.datetime().map(|datetime| datetime.timestamp())
+    }
+}
+
+/// Represents a GPS receiver on a serial port.
+pub struct Gps<S> {
+    /// The serial port, usually 9600 baud 8N1.
+    pub serial: S,
+    receiver: FrameReceiver<NMEA_MAX_LEN>,
+    fix: GpsFix,
+}
+
+impl<S: Read<u8> + Write<u8>> Gps<S> {
+    /// Creates a new `Gps` on a serial port.
+    ///
+    /// # Arguments
+    /// * `serial` - The serial port the receiver is connected to.
+    ///
+    /// # Returns
+    /// A new `Gps` instance.
+    pub fn create_on_uart(serial: S) -> Self {
+        Gps {
+            serial,
+            receiver: FrameReceiver::new(FrameSync::new(FORMAT), SENTENCE_TIMEOUT_MS),
+            fix: GpsFix::default(),
+        }
+    }
+
+    /// Returns the current fix.
+    pub fn fix(&self) -> &GpsFix {
+        &self.fix
+    }
+
+    /// Returns the UTC time as a UNIX timestamp, see `GpsFix::timestamp`.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.fix.timestamp()
+    }
+
+    /// Reads the received bytes without blocking and updates the fix. Should
+    /// be called often enough for the receive FIFO not to overflow.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(Some(sentence))` with a new supported sentence, `Ok(None)` if none
+    /// is complete yet, or `Err(PeripheralError::ReadError)` on a serial error
+    /// or when no sentence arrived for 3 seconds.
+    pub fn poll(&mut self, now_ms: u64) -> Result<Option<Sentence>, PeripheralError> {
+        while let Some(frame) = self.receiver.poll(&mut self.serial, now_ms)? {
+            if let Some(sentence) = parse_sentence(frame) {
+                self.fix.update(&sentence);
+                return Ok(Some(sentence));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sends a sentence to configure the receiver, e.g. `PMTK220,1000` on
+    /// MediaTek receivers. The `$`, the checksum and the line ending are
+    /// added.
+    pub fn send_sentence(&mut self, body: &str) -> Result<(), PeripheralError> {
+        let checksum = xor8(body.as_bytes());
+        let hex = |nibble: u8| b"0123456789ABCDEF"[nibble as usize];
+        send(&mut self.serial, b"$")?;
+        send(&mut self.serial, body.as_bytes())?;
+        send(
+            &mut self.serial,
+            &[b'*', hex(checksum >> 4), hex(checksum & 0x0F), b'\r', b'\n'],
+        )
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> UnifiedData for Gps<S> {
+    type Output = GpsFix;
+    /// Reads sentences until the `RMC` sentence of the next position update
+    /// and returns the fix.
+    fn read(&mut self, mut delay: Delay) -> Result<Self::Output, PeripheralError> {
+        for _ in 0..MAX_SENTENCES_PER_FIX {
+            let frame = read_frame_blocking(
+                &mut self.serial,
+                &mut self.receiver.sync,
+                &mut delay,
+                SENTENCE_TIMEOUT_MS as u32,
+            )?;
+            if let Some(sentence) = parse_sentence(frame) {
+                self.fix.update(&sentence);
+                if let Sentence::Rmc(_) = sentence {
+                    return Ok(self.fix);
+                }
+            }
+        }
+        Err(PeripheralError::ReadError)
+    }
+}
+
+impl<S: Read<u8> + Write<u8>> TimeSource for Gps<S> {
+    /// Returns the UTC time of the last position update, `Gps::poll` has to
+    /// be called for it to advance.
+    ///
+    /// # Returns
+    /// The date and time, or `PeripheralError::ReadError` if the receiver has
+    /// not reported the date yet.
+    fn get_datetime(&mut self) -> Result<DateTime, PeripheralError> {
+        self.fix.datetime().ok_or(PeripheralError::ReadError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &[u8] = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+    const RMC: &[u8] = b"$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W*61\r\n";
+    const GSA: &[u8] = b"$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39\r\n";
+    const GSV: &[u8] = b"$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75\r\n";
+    const VTG: &[u8] = b"$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\r\n";
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn parses_gga() {
+        let gga = match parse_sentence(GGA) {
+            Some(Sentence::Gga(gga)) => gga,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            gga.time,
+            Some(UtcTime {
+                hour: 12,
+                minute: 35,
+                second: 19,
+                millisecond: 0
+            })
+        );
+        assert_close(gga.latitude, 48.0 + 7.038 / 60.0);
+        assert_close(gga.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(gga.quality, FixQuality::Gps);
+        assert_eq!(gga.satellites, 8);
+        assert_eq!(gga.hdop, Some(0.9));
+        assert_eq!(gga.altitude, Some(545.4));
+        assert_eq!(gga.geoid_separation, Some(46.9));
+    }
+
+    #[test]
+    fn parses_rmc() {
+        let rmc = match parse_sentence(RMC) {
+            Some(Sentence::Rmc(rmc)) => rmc,
+            other => panic!("{other:?}"),
+        };
+        assert!(rmc.valid);
+        assert_close(rmc.latitude, 48.0 + 7.038 / 60.0);
+        assert_close(rmc.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(rmc.speed_knots, Some(22.4));
+        assert_eq!(rmc.course, Some(84.4));
+        assert_eq!(
+            rmc.date,
+            Some(Date {
+                year: 2024,
+                month: 3,
+                day: 23
+            })
+        );
+        assert_eq!(rmc.magnetic_variation, Some(-3.1));
+    }
+
+    #[test]
+    fn parses_gsa() {
+        let gsa = match parse_sentence(GSA) {
+            Some(Sentence::Gsa(gsa)) => gsa,
+            other => panic!("{other:?}"),
+        };
+        assert!(gsa.automatic);
+        assert_eq!(gsa.fix_type, FixType::Fix3D);
+        assert_eq!(
+            gsa.satellites,
+            [
+                Some(4),
+                Some(5),
+                None,
+                Some(9),
+                Some(12),
+                None,
+                None,
+                Some(24),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        assert_eq!(
+            (gsa.pdop, gsa.hdop, gsa.vdop),
+            (Some(2.5), Some(1.3), Some(2.1))
+        );
+    }
+
+    #[test]
+    fn parses_gsv() {
+        let gsv = match parse_sentence(GSV) {
+            Some(Sentence::Gsv(gsv)) => gsv,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!((gsv.message_count, gsv.message_number), (2, 1));
+        assert_eq!(gsv.satellites_in_view, 8);
+        assert_eq!(
+            gsv.satellites[0],
+            Some(SatelliteInfo {
+                prn: 1,
+                elevation: Some(40),
+                azimuth: Some(83),
+                snr: Some(46)
+            })
+        );
+        assert_eq!(gsv.satellites[3].map(|satellite| satellite.prn), Some(14));
+    }
+
+    #[test]
+    fn gsv_signal_id_is_not_a_satellite() {
+        // NMEA 4.10, last message with two satellites, one not tracked, and
+        // the signal ID.
+        let sentence = b"$GPGSV,3,3,10,30,12,045,38,31,05,210,,1*6B\r\n";
+        let gsv = match parse_sentence(sentence) {
+            Some(Sentence::Gsv(gsv)) => gsv,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(gsv.satellites[0].map(|satellite| satellite.prn), Some(30));
+        assert_eq!(
+            gsv.satellites[1],
+            Some(SatelliteInfo {
+                prn: 31,
+                elevation: Some(5),
+                azimuth: Some(210),
+                snr: None
+            })
+        );
+        assert_eq!(gsv.satellites[2], None);
+        assert_eq!(gsv.satellites[3], None);
+    }
+
+    #[test]
+    fn parses_vtg() {
+        let vtg = match parse_sentence(VTG) {
+            Some(Sentence::Vtg(vtg)) => vtg,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(vtg.course_true, Some(54.7));
+        assert_eq!(vtg.course_magnetic, Some(34.4));
+        assert_eq!(vtg.speed_knots, Some(5.5));
+        assert_eq!(vtg.speed_kmh, Some(10.2));
+    }
+
+    #[test]
+    fn southern_and_western_coordinates_are_negative() {
+        let sentence =
+            b"$GPGGA,092750.000,5321.6802,S,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*6B\r\n";
+        let gga = match parse_sentence(sentence) {
+            Some(Sentence::Gga(gga)) => gga,
+            other => panic!("{other:?}"),
+        };
+        assert_close(gga.latitude, -(53.0 + 21.6802 / 60.0));
+        assert_close(gga.longitude, -(6.0 + 30.3372 / 60.0));
+        assert_eq!(gga.time.map(|time| time.second), Some(50));
+    }
+
+    #[test]
+    fn sentences_without_fix_have_empty_fields() {
+        let gga = match parse_sentence(b"$GPGGA,,,,,,0,00,99.99,,,,,,*48\r\n") {
+            Some(Sentence::Gga(gga)) => gga,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(gga.time, None);
+        assert_eq!((gga.latitude, gga.longitude), (None, None));
+        assert_eq!(gga.quality, FixQuality::Invalid);
+        assert_eq!(gga.satellites, 0);
+        assert_eq!(gga.altitude, None);
+
+        let rmc = match parse_sentence(b"$GPRMC,,V,,,,,,,,,,N*53\r\n") {
+            Some(Sentence::Rmc(rmc)) => rmc,
+            other => panic!("{other:?}"),
+        };
+        assert!(!rmc.valid);
+        assert_eq!((rmc.latitude, rmc.longitude), (None, None));
+        assert_eq!(rmc.date, None);
+        assert_eq!(rmc.magnetic_variation, None);
+
+        let mut fix = GpsFix::default();
+        fix.update(&Sentence::Gga(gga));
+        fix.update(&Sentence::Rmc(rmc));
+        assert!(!fix.has_fix());
+        assert_eq!(fix.timestamp(), None);
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut corrupted = [0; 67];
+        corrupted.copy_from_slice(GGA);
+        // One digit of the altitude changed.
+        corrupted[48] = b'6';
+        assert!(!sentence_valid(&corrupted));
+        assert!(sentence_valid(GGA));
+    }
+
+    #[test]
+    fn unsupported_sentences_are_skipped() {
+        assert_eq!(parse_sentence(b"$PMTK001,220,3*30\r\n"), None);
+        assert_eq!(
+            parse_sentence(b"$GPZDA,123519,23,03,2024,00,00*43\r\n"),
+            None
+        );
+        assert_eq!(parse_sentence(b"GPGGA,123519*00\r\n"), None);
+    }
+
+    #[test]
+    fn fix_from_burst() {
+        let mut fix = GpsFix::default();
+        for sentence in [GGA, GSA, GSV, RMC, VTG] {
+            fix.update(&parse_sentence(sentence).unwrap());
+        }
+        assert!(fix.has_fix());
+        assert_eq!(fix.fix_type, FixType::Fix3D);
+        assert_eq!(fix.satellites_used, 8);
+        assert_eq!(fix.satellites_in_view, 8);
+        assert_eq!(fix.course, Some(54.7));
+        assert_eq!(fix.speed_kmh, Some(10.2));
+        assert_eq!(fix.datetime(), Some(DateTime::new(2024, 3, 23, 12, 35, 19)));
+        assert_eq!(fix.timestamp(), Some(1_711_197_319));
+    }
+}
@@ -0,0 +1,325 @@
+//! # HX711 Load Cell Amplifier Module
+//!
+//! This module provides a bit-banged driver for the Avia HX711 24-bit ADC for
+//! load cells (weight scales), on a clock (SCK) output pin and a data (DOUT)
+//! input pin. The amplifier has two channels: channel A with a gain of 128 or
+//! 64, and channel B with a gain of 32.
+//!
+//! Raw readings are turned into weights with an offset (`tare`) and a scale
+//! (`calibrate` with a known weight); the weight is in the unit of the
+//! calibration weight, usually grams. The offset and scale should be stored
+//! and restored with `set_offset`/`set_scale`, so the scale does not need to
+//! be calibrated after every boot.
+//!
+//! The HX711 powers down when SCK stays high for more than 60 µs, so the
+//! bits of a reading are clocked with interrupts disabled, for about 60 µs.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     hx711::{Gain, Hx711},
+//!     Shutdown,
+//!     WeightSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let mut scale = Hx711::create_on_pins(
+//!     pins.gpio4.into_push_pull_output(),
+//!     pins.gpio5.into_floating_input(),
+//!     delay,
+//!     Gain::A128,
+//! )
+//! .unwrap();
+//! // Nothing on the scale
+//! scale.tare(10).unwrap();
+//! // Put a 500 g weight on the scale
+//! scale.calibrate(500.0, 10).unwrap();
+//!
+//! scale.set_averaging(4);
+//! loop {
+//!     println!("Weight: {} g", scale.get_weight().unwrap());
+//!     scale.shutdown().unwrap();
+//!     delay.delay_ms(60_000u32);
+//!     scale.power_up().unwrap();
+//! }
+//! ```
+
+use embedded_hal::{
+    blocking::delay::{DelayMs, DelayUs},
+    digital::v2::{InputPin, OutputPin},
+};
+use esp_hal::delay::Delay;
+
+use super::{PeripheralError, Shutdown, UnifiedData, WeightSensor};
+
+/// Longest wait for a conversion; at 10 samples per second a conversion
+/// takes 100 ms, after power up the output settles within 400 ms.
+const READY_TIMEOUT_MS: u32 = 500;
+/// SCK high time that powers the HX711 down.
+const POWER_DOWN_US: u32 = 100;
+
+/// Channel and gain of the next conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gain {
+    /// Channel A, gain 128 (±20 mV full scale at 5 V), the power-on default.
+    A128,
+    /// Channel A, gain 64 (±40 mV).
+    A64,
+    /// Channel B, gain 32 (±80 mV).
+    B32,
+}
+
+impl Gain {
+    /// Clock pulses after the 24 data bits that select the next conversion.
+    fn extra_pulses(self) -> u8 {
+        match self {
+            Gain::A128 => 1,
+            Gain::B32 => 2,
+            Gain::A64 => 3,
+        }
+    }
+}
+
+/// Sign-extends a 24-bit two's complement value.
+fn sign_extend(raw: u32) -> i32 {
+    ((raw << 8) as i32) >> 8
+}
+
+/// Represents an HX711 on a clock and a data pin.
+pub struct Hx711<SCK, DOUT>
+where
+    SCK: OutputPin<Error = core::convert::Infallible>,
+    DOUT: InputPin<Error = core::convert::Infallible>,
+{
+    sck: SCK,
+    dout: DOUT,
+    delay: Delay,
+    gain: Gain,
+    offset: f32,
+    scale: f32,
+    samples: u8,
+}
+
+impl<SCK, DOUT> Hx711<SCK, DOUT>
+where
+    SCK: OutputPin<Error = core::convert::Infallible>,
+    DOUT: InputPin<Error = core::convert::Infallible>,
+{
+    /// Creates a new `Hx711` and selects the channel and gain.
+    ///
+    /// # Arguments
+    /// * `sck` - The output pin connected to SCK.
+    /// * `dout` - The input pin connected to DOUT.
+    /// * `delay` - Delay provider for the clock pulses.
+    /// * `gain` - The channel and gain.
+    ///
+    /// # Returns
+    /// A new `Hx711` instance, or `PeripheralError::InitializationFailed` if
+    /// the HX711 does not answer.
+    pub fn create_on_pins(
+        mut sck: SCK,
+        dout: DOUT,
+        delay: Delay,
+        gain: Gain,
+    ) -> Result<Self, PeripheralError> {
+        sck.set_low().unwrap();
+        let mut hx711 = Hx711 {
+            sck,
+            dout,
+            delay,
+            gain,
+            offset: 0.0,
+            scale: 1.0,
+            samples: 1,
+        };
+        hx711
+            .set_gain(gain)
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(hx711)
+    }
+
+    /// Returns `true` if a conversion is ready to be read.
+    pub fn is_ready(&self) -> bool {
+        self.dout.is_low().unwrap()
+    }
+
+    fn wait_ready(&mut self) -> Result<(), PeripheralError> {
+        let mut waited_ms = 0;
+        while !self.is_ready() {
+            if waited_ms >= READY_TIMEOUT_MS {
+                return Err(PeripheralError::ReadError);
+            }
+            self.delay.delay_ms(1u32);
+            waited_ms += 1;
+        }
+        Ok(())
+    }
+
+    fn pulse(&mut self) -> bool {
+        self.sck.set_high().unwrap();
+        self.delay.delay_us(1u32);
+        let bit = self.dout.is_high().unwrap();
+        self.sck.set_low().unwrap();
+        self.delay.delay_us(1u32);
+        bit
+    }
+
+    /// Waits for a conversion and reads it.
+    ///
+    /// # Returns
+    /// The signed 24-bit conversion, or `PeripheralError::ReadError` if no
+    /// conversion is ready within 500 ms.
+    pub fn read_raw(&mut self) -> Result<i32, PeripheralError> {
+        self.wait_ready()?;
+        // An interrupt while SCK is high could power the HX711 down.
+        let raw = critical_section::with(|_| {
+            let mut raw = 0u32;
+            for _ in 0..24 {
+                raw = (raw << 1) | self.pulse() as u32;
+            }
+            for _ in 0..self.gain.extra_pulses() {
+                self.pulse();
+            }
+            raw
+        });
+        Ok(sign_extend(raw))
+    }
+
+    /// Reads the average of several conversions.
+    ///
+    /// # Arguments
+    /// * `samples` - The number of conversions, at least 1.
+    pub fn read_average(&mut self, samples: u8) -> Result<f32, PeripheralError> {
+        let samples = samples.max(1);
+        let mut sum = 0i64;
+        for _ in 0..samples {
+            sum += self.read_raw()? as i64;
+        }
+        Ok(sum as f32 / samples as f32)
+    }
+
+    /// Returns the channel and gain.
+    pub fn gain(&self) -> Gain {
+        self.gain
+    }
+
+    /// Selects the channel and gain. The conversion already running uses the
+    /// previous setting, so it is read and discarded.
+    pub fn set_gain(&mut self, gain: Gain) -> Result<(), PeripheralError> {
+        self.gain = gain;
+        self.read_raw().map(|_| ())
+    }
+
+    /// Sets the number of conversions averaged by `get_weight`.
+    pub fn set_averaging(&mut self, samples: u8) {
+        self.samples = samples.max(1);
+    }
+
+    /// Sets the current load as zero.
+    ///
+    /// # Arguments
+    /// * `samples` - The number of conversions averaged.
+    pub fn tare(&mut self, samples: u8) -> Result<(), PeripheralError> {
+        self.offset = self.read_average(samples)?;
+        Ok(())
+    }
+
+    /// Computes the scale from a known weight on the scale, after `tare`.
+    ///
+    /// # Arguments
+    /// * `known_weight` - The weight on the scale, in the unit the weights are
+    ///   reported in.
+    /// * `samples` - The number of conversions averaged.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::ReadError` if the reading did not
+    /// change from the tare.
+    pub fn calibrate(&mut self, known_weight: f32, samples: u8) -> Result<(), PeripheralError> {
+        let delta = self.read_average(samples)? - self.offset;
+        if delta == 0.0 || known_weight == 0.0 {
+            return Err(PeripheralError::ReadError);
+        }
+        self.scale = delta / known_weight;
+        Ok(())
+    }
+
+    /// Returns the raw reading of zero load.
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    /// Sets the raw reading of zero load, e.g. a stored tare.
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset;
+    }
+
+    /// Returns the raw counts per unit of weight.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Sets the raw counts per unit of weight, e.g. a stored calibration.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Measures the weight, averaged over `samples` conversions.
+    pub fn read_weight(&mut self, samples: u8) -> Result<f32, PeripheralError> {
+        Ok((self.read_average(samples)? - self.offset) / self.scale)
+    }
+
+    /// Powers the HX711 down by holding SCK high.
+    pub fn power_down(&mut self) {
+        self.sck.set_low().unwrap();
+        self.sck.set_high().unwrap();
+        self.delay.delay_us(POWER_DOWN_US);
+    }
+
+    /// Powers the HX711 up. It resets to channel A with gain 128, so another
+    /// gain is selected again.
+    pub fn power_up(&mut self) -> Result<(), PeripheralError> {
+        self.sck.set_low().unwrap();
+        if self.gain != Gain::A128 {
+            self.set_gain(self.gain)?;
+        }
+        Ok(())
+    }
+}
+
+impl<SCK, DOUT> WeightSensor for Hx711<SCK, DOUT>
+where
+    SCK: OutputPin<Error = core::convert::Infallible>,
+    DOUT: InputPin<Error = core::convert::Infallible>,
+{
+    /// Measures the weight, averaged as set by `Hx711::set_averaging`.
+    fn get_weight(&mut self) -> Result<f32, PeripheralError> {
+        self.read_weight(self.samples)
+    }
+}
+
+impl<SCK, DOUT> UnifiedData for Hx711<SCK, DOUT>
+where
+    SCK: OutputPin<Error = core::convert::Infallible>,
+    DOUT: InputPin<Error = core::convert::Infallible>,
+{
+    type Output = f32;
+    /// Measures the weight, see `WeightSensor::get_weight`.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_weight()
+    }
+}
+
+impl<SCK, DOUT> Shutdown for Hx711<SCK, DOUT>
+where
+    SCK: OutputPin<Error = core::convert::Infallible>,
+    DOUT: InputPin<Error = core::convert::Infallible>,
+{
+    /// Powers the HX711 down, see `Hx711::power_up`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.power_down();
+        Ok(())
+    }
+}
@@ -1,25 +1,64 @@
 //! # Joystick Module
 //!
 //! Provides an interface to a 2-axis joystick with an integrated select button.
-//! This module is configured to use specific pins for the X and Y axes and
-//! assumes that the select button uses a digital input pin.
+//! The axes can be connected to any ADC1-capable pins (see
+//! `create_joystick_on_pins!`), or to the default pins of the chip (see
+//! `create_joystick!`). The select button uses a digital input pin.
 //!
-//! ### ATTENTION: THIS MODULE IS SUPPOSED TO BE USED ONLY WITH X-AXIS AND Y-AXIS CONNECTED TO DEFAULT PINS!!!
-use embedded_hal::{adc::OneShot, digital::v2::InputPin};
+//! Besides raw ADC values, the joystick can be calibrated and report its
+//! position as normalized axes in the `-1.0..=1.0` range with a dead-zone, or
+//! as 4-way/8-way `Direction`s with repeat, which is handy for driving menus.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::joystick::{Direction, Joystick};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let (mut joystick, mut adc) = esp_ward::create_joystick_on_pins!(
+//!     peripherals,
+//!     pins.gpio2.into_analog(),
+//!     pins.gpio4.into_analog(),
+//!     pins.gpio9.into_pull_up_input()
+//! );
+//! // Stick must be left in the centre during the calibration
+//! joystick.calibrate_center(&mut adc, 16).unwrap();
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     match joystick.update(&mut adc, now).unwrap() {
+//!         Some(Direction::Up) => println!("Menu up"),
+//!         Some(Direction::Down) => println!("Menu down"),
+//!         _ => (),
+//!     }
+//! }
+//! ```
+#[cfg(feature = "esp-hal")]
+use embedded_hal::{
+    adc::{Channel, OneShot},
+    digital::v2::InputPin,
+};
+#[cfg(feature = "esp-hal")]
 use esp_hal::{
-    analog::adc::{AdcPin, ADC},
+    analog::adc::AdcPin,
     gpio::{Analog, GpioPin},
     prelude::*,
 };
 
-#[cfg(not(feature = "esp32"))]
+#[cfg(feature = "esp-hal")]
+use super::PeripheralError;
+
+#[cfg(all(feature = "esp-hal", not(feature = "esp32")))]
 type XPin = AdcPin<GpioPin<Analog, 1>, esp_hal::peripherals::ADC1>;
-#[cfg(feature = "esp32")]
+#[cfg(all(feature = "esp-hal", feature = "esp32"))]
 type XPin = AdcPin<GpioPin<Analog, 32>, esp_hal::peripherals::ADC1>;
 
-#[cfg(not(feature = "esp32"))]
+#[cfg(all(feature = "esp-hal", not(feature = "esp32")))]
 type YPin = AdcPin<GpioPin<Analog, 3>, esp_hal::peripherals::ADC1>;
-#[cfg(feature = "esp32")]
+#[cfg(all(feature = "esp-hal", feature = "esp32"))]
 type YPin = AdcPin<GpioPin<Analog, 35>, esp_hal::peripherals::ADC1>;
 
 #[cfg(not(feature = "esp32"))]
@@ -54,20 +93,294 @@ macro_rules! get_y_adc_pin {
     };
 }
 
+/// Maximum raw value of the 12-bit ADC.
+const ADC_MAX: u16 = 4095;
+
 /// Represents a joystick with two axes and a select button.
-pub struct Joystick<SELECT: InputPin> {
+#[cfg(feature = "esp-hal")]
+pub struct Joystick<SELECT: InputPin, X = XPin, Y = YPin> {
     /// The select button of the joystick, wrapped in a `Button` struct for
     /// debouncing.
     pub select: crate::peripherals::button::Button<SELECT>,
     /// The analog input pin for the X-axis.
-    pub x_axis: XPin,
+    pub x_axis: X,
     /// The analog input pin for the Y-axis.
-    pub y_axis: YPin,
+    pub y_axis: Y,
+    /// Calibration used for the normalized axes.
+    pub calibration: JoystickCalibration,
+    /// Dead-zone, direction and repeat settings.
+    pub config: JoystickConfig,
+    /// Direction of the last reading, used for the release threshold.
+    direction: Direction,
+    repeater: DirectionRepeater,
 }
 
 /// A threshold value to interpret the joystick's value in direction.
 pub const ROUGH_THRESHOLD: u16 = 2048;
 
+/// Raw ADC values of the centre and the extremes of both axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JoystickCalibration {
+    /// Raw values of the X-axis: `(min, center, max)`.
+    pub x: (u16, u16, u16),
+    /// Raw values of the Y-axis: `(min, center, max)`.
+    pub y: (u16, u16, u16),
+}
+
+impl Default for JoystickCalibration {
+    /// Full range of the 12-bit ADC with the centre in the middle.
+    fn default() -> Self {
+        JoystickCalibration {
+            x: (0, ROUGH_THRESHOLD, ADC_MAX),
+            y: (0, ROUGH_THRESHOLD, ADC_MAX),
+        }
+    }
+}
+
+impl JoystickCalibration {
+    /// Converts raw values of both axes into the `-1.0..=1.0` range.
+    pub fn normalize(&self, x: u16, y: u16) -> (f32, f32) {
+        (normalize_axis(x, self.x), normalize_axis(y, self.y))
+    }
+}
+
+/// Converts a raw value into the `-1.0..=1.0` range given the `(min, center,
+/// max)` of the axis. Each half of the axis is scaled separately, as the
+/// centre of cheap joysticks is rarely in the middle of the range.
+pub fn normalize_axis(raw: u16, (min, center, max): (u16, u16, u16)) -> f32 {
+    let value = if raw >= center {
+        let span = max.saturating_sub(center);
+        if span == 0 {
+            0.0
+        } else {
+            (raw - center) as f32 / span as f32
+        }
+    } else {
+        let span = center.saturating_sub(min);
+        if span == 0 {
+            0.0
+        } else {
+            -((center - raw) as f32 / span as f32)
+        }
+    };
+    value.clamp(-1.0, 1.0)
+}
+
+/// Applies a dead-zone to a normalized axis value. Values within the
+/// dead-zone become `0.0`, the rest is rescaled to keep the full range.
+pub fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    let magnitude = if value < 0.0 { -value } else { value };
+    if magnitude <= dead_zone || dead_zone >= 1.0 {
+        return 0.0;
+    }
+    let scaled = (magnitude - dead_zone) / (1.0 - dead_zone);
+    if value < 0.0 {
+        -scaled
+    } else {
+        scaled
+    }
+}
+
+/// Direction the joystick is pointing to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Center,
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+/// Amount of directions reported by the joystick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectionMode {
+    /// Only up, down, left and right.
+    FourWay,
+    /// Diagonals are reported as well.
+    EightWay,
+}
+
+/// Converts normalized axes into a `Direction`. Positive X is right, positive
+/// Y is up.
+///
+/// # Arguments
+/// * `x`, `y` - Normalized axes.
+/// * `threshold` - Minimum deflection (`0.0..=1.0`) to report a direction.
+/// * `mode` - 4-way or 8-way directions.
+pub fn direction_from_axes(x: f32, y: f32, threshold: f32, mode: DirectionMode) -> Direction {
+    let abs_x = if x < 0.0 { -x } else { x };
+    let abs_y = if y < 0.0 { -y } else { y };
+    if abs_x < threshold && abs_y < threshold {
+        return Direction::Center;
+    }
+
+    let horizontal = if x < 0.0 {
+        Direction::Left
+    } else {
+        Direction::Right
+    };
+    let vertical = if y < 0.0 {
+        Direction::Down
+    } else {
+        Direction::Up
+    };
+
+    match mode {
+        DirectionMode::FourWay => {
+            if abs_x >= abs_y {
+                horizontal
+            } else {
+                vertical
+            }
+        }
+        DirectionMode::EightWay => {
+            // tan(67.5°), splits the circle into 45° sectors
+            const SECTOR: f32 = 2.414;
+            if abs_x > abs_y * SECTOR {
+                horizontal
+            } else if abs_y > abs_x * SECTOR {
+                vertical
+            } else {
+                match (horizontal, vertical) {
+                    (Direction::Left, Direction::Up) => Direction::UpLeft,
+                    (Direction::Right, Direction::Up) => Direction::UpRight,
+                    (Direction::Left, _) => Direction::DownLeft,
+                    _ => Direction::DownRight,
+                }
+            }
+        }
+    }
+}
+
+/// Converts normalized axes into a `Direction` with hysteresis: a direction
+/// is entered at `press_threshold`, but held until the deflection drops below
+/// `release_threshold`, so ADC noise around the threshold does not report the
+/// direction again and again.
+///
+/// # Arguments
+/// * `x`, `y` - Normalized axes.
+/// * `current` - The direction of the previous reading.
+/// * `press_threshold` - Minimum deflection to enter a direction.
+/// * `release_threshold` - Deflection below which the direction is left, lower
+///   than `press_threshold`.
+/// * `mode` - 4-way or 8-way directions.
+pub fn direction_with_hysteresis(
+    x: f32,
+    y: f32,
+    current: Direction,
+    press_threshold: f32,
+    release_threshold: f32,
+    mode: DirectionMode,
+) -> Direction {
+    let direction = direction_from_axes(x, y, press_threshold, mode);
+    if direction != Direction::Center || current == Direction::Center {
+        return direction;
+    }
+    if direction_from_axes(x, y, release_threshold, mode) == current {
+        current
+    } else {
+        Direction::Center
+    }
+}
+
+/// Turns a stream of directions into events: a direction is reported once
+/// when entered and then repeatedly while it is held.
+pub struct DirectionRepeater {
+    current: Direction,
+    next_repeat: u64,
+}
+
+impl DirectionRepeater {
+    /// Creates a new repeater in the `Direction::Center` state.
+    pub const fn new() -> Self {
+        DirectionRepeater {
+            current: Direction::Center,
+            next_repeat: 0,
+        }
+    }
+
+    /// Feeds the repeater with the current direction.
+    ///
+    /// # Arguments
+    /// * `direction` - The current direction.
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    /// * `delay_ms` - Time before the first repeat.
+    /// * `interval_ms` - Time between repeats, `None` disables repeating.
+    ///
+    /// # Returns
+    /// `Some(Direction)` when a direction was entered or repeated, `None`
+    /// otherwise. `Direction::Center` is never reported.
+    pub fn update(
+        &mut self,
+        direction: Direction,
+        now_ms: u64,
+        delay_ms: u32,
+        interval_ms: Option<u32>,
+    ) -> Option<Direction> {
+        if direction != self.current {
+            self.current = direction;
+            self.next_repeat = now_ms + delay_ms as u64;
+            return match direction {
+                Direction::Center => None,
+                _ => Some(direction),
+            };
+        }
+
+        match (direction, interval_ms) {
+            (Direction::Center, _) | (_, None) => None,
+            (_, Some(interval)) => {
+                if now_ms >= self.next_repeat {
+                    self.next_repeat = now_ms + interval as u64;
+                    Some(direction)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Dead-zone, direction and repeat settings of a joystick.
+#[derive(Debug, Clone, Copy)]
+pub struct JoystickConfig {
+    /// Dead-zone around the centre (`0.0..1.0`) of the normalized axes.
+    pub dead_zone: f32,
+    /// Minimum deflection (`0.0..=1.0`) to report a direction.
+    pub direction_threshold: f32,
+    /// Deflection below which a reported direction is released, lower than
+    /// `direction_threshold` so noise around it is not reported repeatedly.
+    pub release_threshold: f32,
+    /// 4-way or 8-way directions.
+    pub mode: DirectionMode,
+    /// Inverts the X-axis.
+    pub invert_x: bool,
+    /// Inverts the Y-axis.
+    pub invert_y: bool,
+    /// Time before the first repeat of a held direction.
+    pub repeat_delay_ms: u32,
+    /// Time between repeats of a held direction, `None` disables repeating.
+    pub repeat_interval_ms: Option<u32>,
+}
+
+impl Default for JoystickConfig {
+    fn default() -> Self {
+        JoystickConfig {
+            dead_zone: 0.1,
+            direction_threshold: 0.5,
+            release_threshold: 0.4,
+            mode: DirectionMode::FourWay,
+            invert_x: false,
+            invert_y: false,
+            repeat_delay_ms: 500,
+            repeat_interval_ms: Some(150),
+        }
+    }
+}
+
 /// Macro for creating a `Joystick` instance.
 ///
 /// Unlike a function, this macro can take ownership of parts of the
@@ -81,6 +394,9 @@ pub const ROUGH_THRESHOLD: u16 = 2048;
 /// however, performs the setup inline where it's invoked and thus avoids the
 /// mentioned ownership issue.
 ///
+/// The axes are expected on the default pins (see `get_x_adc_pin!` and
+/// `get_y_adc_pin!`), use `create_joystick_on_pins!` for any other pins.
+///
 /// # Arguments
 /// * `$peripherals` - The `esp-hal` `Peripherals` instance.
 /// * `$pins` - The `esp-hal` GPIO pins split from `Peripherals`.
@@ -103,41 +419,94 @@ pub const ROUGH_THRESHOLD: u16 = 2048;
 #[macro_export]
 macro_rules! create_joystick {
     ($peripherals: expr, $pins: expr, $pin_select: expr ) => {{
-        let mut adc1_config = esp_hal::analog::adc::AdcConfig::<esp_hal::peripherals::ADC1>::new();
-        let mut select = esp_ward::peripherals::button::Button::create_on_pins($pin_select);
+        esp_ward::create_joystick_on_pins!(
+            $peripherals,
+            esp_ward::get_x_adc_pin!($pins),
+            esp_ward::get_y_adc_pin!($pins),
+            $pin_select
+        )
+    }};
+}
 
-        let x_axis_pin = esp_ward::get_x_adc_pin!($pins);
-        let y_axis_pin = esp_ward::get_y_adc_pin!($pins);
+pub use create_joystick;
 
-        let mut x_axis = adc1_config.enable_pin(
-            x_axis_pin,
-            esp_hal::analog::adc::Attenuation::Attenuation11dB,
-        );
+/// Macro for creating a `Joystick` instance with the axes on arbitrary
+/// ADC1-capable pins. See `create_joystick!` for why this is a macro.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$pin_x` - The GPIO pin used for the X-axis, in analog mode.
+/// * `$pin_y` - The GPIO pin used for the Y-axis, in analog mode.
+/// * `$pin_select` - The GPIO pin used for the joystick's select button.
+///
+/// # Returns
+/// A tuple containing the `Joystick` instance and the initialized ADC.
+///
+/// ```no_run
+/// let peripherals = take_periph!();
+/// let system = take_system!(peripherals);
+/// let (clocks, pins, delay) = init_chip!(peripherals, system);
+/// let (joystick, adc1) = create_joystick_on_pins!(
+///     peripherals,
+///     pins.gpio2.into_analog(),
+///     pins.gpio4.into_analog(),
+///     pins.gpio9.into_pull_up_input()
+/// );
+/// ```
+#[macro_export]
+macro_rules! create_joystick_on_pins {
+    ($peripherals: expr, $pin_x: expr, $pin_y: expr, $pin_select: expr ) => {{
+        let mut adc1_config = esp_hal::analog::adc::AdcConfig::<esp_hal::peripherals::ADC1>::new();
+        let select = esp_ward::peripherals::button::Button::create_on_pins($pin_select);
 
-        let mut y_axis = adc1_config.enable_pin(
-            y_axis_pin,
-            esp_hal::analog::adc::Attenuation::Attenuation11dB,
-        );
+        let x_axis =
+            adc1_config.enable_pin($pin_x, esp_hal::analog::adc::Attenuation::Attenuation11dB);
+
+        let y_axis =
+            adc1_config.enable_pin($pin_y, esp_hal::analog::adc::Attenuation::Attenuation11dB);
 
-        let mut adc1 = esp_hal::analog::adc::ADC::<esp_hal::peripherals::ADC1>::new(
+        let adc1 = esp_hal::analog::adc::ADC::<esp_hal::peripherals::ADC1>::new(
             $peripherals.ADC1,
             adc1_config,
         );
 
         (
-            Joystick {
-                select: select,
-                x_axis: x_axis,
-                y_axis: y_axis,
-            },
+            esp_ward::peripherals::joystick::Joystick::create_on_adc_pins(select, x_axis, y_axis),
             adc1,
         )
     }};
 }
 
-pub use create_joystick;
+pub use create_joystick_on_pins;
+
+#[cfg(feature = "esp-hal")]
+impl<SELECT: InputPin<Error = core::convert::Infallible>, X, Y> Joystick<SELECT, X, Y> {
+    /// Creates a new `Joystick` from already enabled ADC pins, with the
+    /// default calibration and configuration.
+    ///
+    /// # Arguments
+    /// * `select` - The select button of the joystick.
+    /// * `x_axis` - The analog input pin for the X-axis.
+    /// * `y_axis` - The analog input pin for the Y-axis.
+    ///
+    /// # Returns
+    /// A new `Joystick` instance.
+    pub fn create_on_adc_pins(
+        select: crate::peripherals::button::Button<SELECT>,
+        x_axis: X,
+        y_axis: Y,
+    ) -> Self {
+        Joystick {
+            select,
+            x_axis,
+            y_axis,
+            calibration: JoystickCalibration::default(),
+            config: JoystickConfig::default(),
+            direction: Direction::Center,
+            repeater: DirectionRepeater::new(),
+        }
+    }
 
-impl<SELECT: InputPin<Error = core::convert::Infallible>> Joystick<SELECT> {
     /// Retrieves the current positions of both axes.
     ///
     /// # Arguments
@@ -146,11 +515,16 @@ impl<SELECT: InputPin<Error = core::convert::Infallible>> Joystick<SELECT> {
     /// # Returns
     /// Returns a tuple `(u16, u16)` where the first element is the X-axis value
     /// and the second is the Y-axis value.
-    pub fn get_axes(&mut self, adc: &mut ADC<'_, esp_hal::peripherals::ADC1>) -> (u16, u16) {
-        (
-            nb::block!(adc.read(&mut self.x_axis)).unwrap(),
-            nb::block!(adc.read(&mut self.y_axis)).unwrap(),
-        )
+    ///
+    /// # Panics
+    /// If the ADC fails, see `try_get_axes`.
+    pub fn get_axes<ADCI, A>(&mut self, adc: &mut A) -> (u16, u16)
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        self.try_get_axes(adc).unwrap()
     }
 
     /// Retrieves the current position of the X-axis.
@@ -160,9 +534,16 @@ impl<SELECT: InputPin<Error = core::convert::Infallible>> Joystick<SELECT> {
     ///
     /// # Returns
     /// Returns a `u16` representing the X-axis value.
-    pub fn get_x(&mut self, adc: &mut ADC<'_, esp_hal::peripherals::ADC1>) -> u16 {
-        let (x, _) = self.get_axes(adc);
-        x
+    ///
+    /// # Panics
+    /// If the ADC fails, see `try_get_x`.
+    pub fn get_x<ADCI, A>(&mut self, adc: &mut A) -> u16
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        self.try_get_x(adc).unwrap()
     }
 
     /// Retrieves the current position of the Y-axis.
@@ -172,9 +553,215 @@ impl<SELECT: InputPin<Error = core::convert::Infallible>> Joystick<SELECT> {
     ///
     /// # Returns
     /// Returns a `u16` representing the Y-axis value.
-    pub fn get_y(&mut self, adc: &mut ADC<'_, esp_hal::peripherals::ADC1>) -> u16 {
-        let (_, y) = self.get_axes(adc);
-        y
+    ///
+    /// # Panics
+    /// If the ADC fails, see `try_get_y`.
+    pub fn get_y<ADCI, A>(&mut self, adc: &mut A) -> u16
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        self.try_get_y(adc).unwrap()
+    }
+
+    /// Retrieves the current positions of both axes.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    ///
+    /// # Returns
+    /// Returns a tuple `(u16, u16)` where the first element is the X-axis value
+    /// and the second is the Y-axis value, or `PeripheralError::ReadError` if
+    /// the ADC fails.
+    pub fn try_get_axes<ADCI, A>(&mut self, adc: &mut A) -> Result<(u16, u16), PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let x = nb::block!(OneShot::<ADCI, u16, X>::read(adc, &mut self.x_axis))
+            .map_err(|_| PeripheralError::ReadError)?;
+        let y = nb::block!(OneShot::<ADCI, u16, Y>::read(adc, &mut self.y_axis))
+            .map_err(|_| PeripheralError::ReadError)?;
+        Ok((x, y))
+    }
+
+    /// Retrieves the current position of the X-axis.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the value from the analog pin.
+    ///
+    /// # Returns
+    /// Returns a `u16` representing the X-axis value, or
+    /// `PeripheralError::ReadError` if the ADC fails.
+    pub fn try_get_x<ADCI, A>(&mut self, adc: &mut A) -> Result<u16, PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (x, _) = self.try_get_axes(adc)?;
+        Ok(x)
+    }
+
+    /// Retrieves the current position of the Y-axis.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the value from the analog pin.
+    ///
+    /// # Returns
+    /// Returns a `u16` representing the Y-axis value, or
+    /// `PeripheralError::ReadError` if the ADC fails.
+    pub fn try_get_y<ADCI, A>(&mut self, adc: &mut A) -> Result<u16, PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (_, y) = self.try_get_axes(adc)?;
+        Ok(y)
+    }
+
+    /// Calibrates the centre of both axes by averaging several readings. The
+    /// stick must not be touched during the calibration.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    /// * `samples` - Amount of readings to average.
+    pub fn calibrate_center<ADCI, A>(
+        &mut self,
+        adc: &mut A,
+        samples: u16,
+    ) -> Result<(), PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let samples = samples.max(1);
+        let (mut sum_x, mut sum_y) = (0u32, 0u32);
+        for _ in 0..samples {
+            let (x, y) = self.try_get_axes(adc)?;
+            sum_x += x as u32;
+            sum_y += y as u32;
+        }
+        self.calibration.x.1 = (sum_x / samples as u32) as u16;
+        self.calibration.y.1 = (sum_y / samples as u32) as u16;
+        Ok(())
+    }
+
+    /// Starts the calibration of the extremes: the range is reset to the
+    /// centre, so it can be widened by `calibrate_extremes` while the stick is
+    /// moved around.
+    pub fn reset_extremes(&mut self) {
+        self.calibration.x = (
+            self.calibration.x.1,
+            self.calibration.x.1,
+            self.calibration.x.1,
+        );
+        self.calibration.y = (
+            self.calibration.y.1,
+            self.calibration.y.1,
+            self.calibration.y.1,
+        );
+    }
+
+    /// Widens the calibrated range of both axes by the current reading. Should
+    /// be called repeatedly while the user moves the stick to all extremes,
+    /// after `reset_extremes`.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    pub fn calibrate_extremes<ADCI, A>(&mut self, adc: &mut A) -> Result<(), PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (x, y) = self.try_get_axes(adc)?;
+        self.calibration.x.0 = self.calibration.x.0.min(x);
+        self.calibration.x.2 = self.calibration.x.2.max(x);
+        self.calibration.y.0 = self.calibration.y.0.min(y);
+        self.calibration.y.2 = self.calibration.y.2.max(y);
+        Ok(())
+    }
+
+    /// Retrieves the calibrated position of both axes.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    ///
+    /// # Returns
+    /// Returns a tuple `(f32, f32)` with both axes in the `-1.0..=1.0` range,
+    /// with the dead-zone and inversion applied.
+    pub fn get_normalized<ADCI, A>(&mut self, adc: &mut A) -> Result<(f32, f32), PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (raw_x, raw_y) = self.try_get_axes(adc)?;
+        let (x, y) = self.calibration.normalize(raw_x, raw_y);
+        let x = apply_dead_zone(x, self.config.dead_zone);
+        let y = apply_dead_zone(y, self.config.dead_zone);
+        Ok((
+            if self.config.invert_x { -x } else { x },
+            if self.config.invert_y { -y } else { y },
+        ))
+    }
+
+    /// Retrieves the direction the joystick is pointing to. A direction is
+    /// held until the deflection drops below
+    /// `JoystickConfig::release_threshold`,
+    /// see `direction_with_hysteresis`.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    pub fn get_direction<ADCI, A>(&mut self, adc: &mut A) -> Result<Direction, PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (x, y) = self.get_normalized(adc)?;
+        self.direction = direction_with_hysteresis(
+            x,
+            y,
+            self.direction,
+            self.config.direction_threshold,
+            self.config.release_threshold,
+            self.config.mode,
+        );
+        Ok(self.direction)
+    }
+
+    /// Reads the joystick and reports direction events with repeat, see
+    /// `DirectionRepeater`. Should be called repeatedly.
+    ///
+    /// # Arguments
+    /// * `adc` - The ADC instance to read the values from the analog pins.
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// `Some(Direction)` when a direction was entered or repeated.
+    pub fn update<ADCI, A>(
+        &mut self,
+        adc: &mut A,
+        now_ms: u64,
+    ) -> Result<Option<Direction>, PeripheralError>
+    where
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let direction = self.get_direction(adc)?;
+        Ok(self.repeater.update(
+            direction,
+            now_ms,
+            self.config.repeat_delay_ms,
+            self.config.repeat_interval_ms,
+        ))
     }
 
     /// Checks if the select button is currently pressed.
@@ -195,3 +782,126 @@ impl<SELECT: InputPin<Error = core::convert::Infallible>> Joystick<SELECT> {
 
 // `UnifiedData` trait can not be implemented due to peculiarities of ADC
 // peripheral in esp-hal driver.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOUR: DirectionMode = DirectionMode::FourWay;
+    const EIGHT: DirectionMode = DirectionMode::EightWay;
+
+    #[test]
+    fn normalizes_each_half_separately() {
+        let axis = (100, 1000, 4000);
+        assert_eq!(normalize_axis(1000, axis), 0.0);
+        assert_eq!(normalize_axis(100, axis), -1.0);
+        assert_eq!(normalize_axis(550, axis), -0.5);
+        assert_eq!(normalize_axis(2500, axis), 0.5);
+        assert_eq!(normalize_axis(4000, axis), 1.0);
+        // Beyond the calibrated extremes.
+        assert_eq!(normalize_axis(0, axis), -1.0);
+        assert_eq!(normalize_axis(4095, axis), 1.0);
+    }
+
+    #[test]
+    fn normalizes_degenerate_calibration() {
+        // Right after `reset_extremes` the range is a single point.
+        let axis = (2000, 2000, 2000);
+        assert_eq!(normalize_axis(1000, axis), 0.0);
+        assert_eq!(normalize_axis(3000, axis), 0.0);
+
+        let calibration = JoystickCalibration::default();
+        assert_eq!(calibration.normalize(ROUGH_THRESHOLD, ADC_MAX), (0.0, 1.0));
+    }
+
+    #[test]
+    fn dead_zone_keeps_full_range() {
+        assert_eq!(apply_dead_zone(0.05, 0.1), 0.0);
+        assert_eq!(apply_dead_zone(-0.1, 0.1), 0.0);
+        assert_eq!(apply_dead_zone(1.0, 0.1), 1.0);
+        assert_eq!(apply_dead_zone(-1.0, 0.1), -1.0);
+        assert_eq!(apply_dead_zone(-0.625, 0.25), -0.5);
+        assert_eq!(apply_dead_zone(0.3, 0.0), 0.3);
+        assert_eq!(apply_dead_zone(0.9, 1.0), 0.0);
+    }
+
+    #[test]
+    fn four_way_directions() {
+        assert_eq!(direction_from_axes(0.3, -0.4, 0.5, FOUR), Direction::Center);
+        assert_eq!(direction_from_axes(0.0, 0.6, 0.5, FOUR), Direction::Up);
+        assert_eq!(direction_from_axes(0.0, -0.6, 0.5, FOUR), Direction::Down);
+        assert_eq!(direction_from_axes(-0.8, 0.6, 0.5, FOUR), Direction::Left);
+        assert_eq!(direction_from_axes(0.6, -0.8, 0.5, FOUR), Direction::Down);
+        // Ties go to the horizontal axis.
+        assert_eq!(direction_from_axes(0.7, 0.7, 0.5, FOUR), Direction::Right);
+    }
+
+    #[test]
+    fn eight_way_directions() {
+        assert_eq!(
+            direction_from_axes(0.7, 0.7, 0.5, EIGHT),
+            Direction::UpRight
+        );
+        assert_eq!(
+            direction_from_axes(-0.7, 0.7, 0.5, EIGHT),
+            Direction::UpLeft
+        );
+        assert_eq!(
+            direction_from_axes(-0.7, -0.7, 0.5, EIGHT),
+            Direction::DownLeft
+        );
+        assert_eq!(
+            direction_from_axes(0.7, -0.7, 0.5, EIGHT),
+            Direction::DownRight
+        );
+        // Within 22.5° of an axis.
+        assert_eq!(direction_from_axes(0.9, 0.3, 0.5, EIGHT), Direction::Right);
+        assert_eq!(direction_from_axes(-0.3, -0.9, 0.5, EIGHT), Direction::Down);
+    }
+
+    #[test]
+    fn hysteresis_holds_direction() {
+        let mut direction = Direction::Center;
+        for (x, expected) in [
+            (0.45, Direction::Center),
+            (0.55, Direction::Right),
+            (0.45, Direction::Right),
+            (0.5, Direction::Right),
+            (0.35, Direction::Center),
+            (0.45, Direction::Center),
+        ] {
+            direction = direction_with_hysteresis(x, 0.0, direction, 0.5, 0.4, FOUR);
+            assert_eq!(direction, expected, "{x}");
+        }
+
+        // A different direction above the release threshold is not held.
+        let direction = direction_with_hysteresis(0.0, 0.45, Direction::Right, 0.5, 0.4, FOUR);
+        assert_eq!(direction, Direction::Center);
+    }
+
+    #[test]
+    fn repeater_reports_entered_and_held_directions() {
+        let mut repeater = DirectionRepeater::new();
+        let some_up = Some(Direction::Up);
+        assert_eq!(repeater.update(Direction::Center, 0, 500, Some(150)), None);
+        assert_eq!(repeater.update(Direction::Up, 100, 500, Some(150)), some_up);
+        assert_eq!(repeater.update(Direction::Up, 599, 500, Some(150)), None);
+        assert_eq!(repeater.update(Direction::Up, 600, 500, Some(150)), some_up);
+        assert_eq!(repeater.update(Direction::Up, 749, 500, Some(150)), None);
+        assert_eq!(repeater.update(Direction::Up, 750, 500, Some(150)), some_up);
+
+        assert_eq!(
+            repeater.update(Direction::Center, 800, 500, Some(150)),
+            None
+        );
+        assert_eq!(
+            repeater.update(Direction::Center, 5000, 500, Some(150)),
+            None
+        );
+        assert_eq!(
+            repeater.update(Direction::Left, 5000, 500, None),
+            Some(Direction::Left)
+        );
+        assert_eq!(repeater.update(Direction::Left, 9000, 500, None), None);
+    }
+}
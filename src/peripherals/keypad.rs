@@ -0,0 +1,647 @@
+//! # Matrix Keypad Module
+//!
+//! This module provides a scanner for matrix keypads (like the common 3x4 and
+//! 4x4 membrane keypads). Rows are connected to output pins, columns to input
+//! pins with pull-ups. During a scan every row is driven low one by one and
+//! the columns reading low are the pressed keys of that row.
+//!
+//! Every key is debounced separately and reports press, release and long
+//! press events. Keypads without diodes can show "ghost" keys when three keys
+//! forming corners of a rectangle are pressed, such scans are detected and
+//! ignored. The maximum amount of simultaneously pressed keys (rollover) can be
+//! limited as well.
+//!
+//! `KeyMatrix` and `PinEntry` do not touch any hardware and can be fed with
+//! synthetic scans and timestamps.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::keypad::{
+//!     KeyEventKind,
+//!     Keypad,
+//!     KeypadConfig,
+//!     PinEntry,
+//!     PinStatus,
+//!     KEYMAP_4X4,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let mut keypad = Keypad::create_on_pins(
+//!     [
+//!         pins.gpio0.into_push_pull_output().degrade(),
+//!         pins.gpio1.into_push_pull_output().degrade(),
+//!         pins.gpio2.into_push_pull_output().degrade(),
+//!         pins.gpio3.into_push_pull_output().degrade(),
+//!     ],
+//!     [
+//!         pins.gpio4.into_pull_up_input().degrade(),
+//!         pins.gpio5.into_pull_up_input().degrade(),
+//!         pins.gpio6.into_pull_up_input().degrade(),
+//!         pins.gpio7.into_pull_up_input().degrade(),
+//!     ],
+//!     KEYMAP_4X4,
+//!     delay,
+//!     KeypadConfig::default(),
+//! );
+//! let mut pin_entry: PinEntry<6> = PinEntry::new(10_000);
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     if let Some(event) = keypad.update(now) {
+//!         if event.kind == KeyEventKind::Pressed {
+//!             if let PinStatus::Complete = pin_entry.feed(event.key, now) {
+//!                 if pin_entry.matches("1234") {
+//!                     println!("Door open");
+//!                 }
+//!                 pin_entry.clear();
+//!             }
+//!         }
+//!     }
+//!     if let PinStatus::TimedOut = pin_entry.update(now) {
+//!         println!("PIN entry timed out");
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::{
+    blocking::delay::DelayUs,
+    digital::v2::{InputPin, OutputPin},
+};
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+/// Key map of a common 3x4 telephone-style keypad.
+pub const KEYMAP_3X4: [[char; 3]; 4] = [
+    ['1', '2', '3'],
+    ['4', '5', '6'],
+    ['7', '8', '9'],
+    ['*', '0', '#'],
+];
+
+/// Key map of a common 4x4 keypad.
+pub const KEYMAP_4X4: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// Kind of a key event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEventKind {
+    Pressed,
+    Released,
+    /// The key has been held for at least `KeypadConfig::long_press_ms`.
+    LongPress,
+}
+
+/// An event of a single key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Character of the key from the key map.
+    pub key: char,
+    /// Row of the key.
+    pub row: usize,
+    /// Column of the key.
+    pub col: usize,
+    /// What happened with the key.
+    pub kind: KeyEventKind,
+}
+
+/// Configuration of the keypad timings and limits. All timings are in
+/// milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct KeypadConfig {
+    /// Time the key state has to stay unchanged to be accepted.
+    pub debounce_ms: u32,
+    /// Hold time after which `KeyEventKind::LongPress` is reported.
+    pub long_press_ms: u32,
+    /// Maximum amount of simultaneously pressed keys, further presses are
+    /// ignored.
+    pub max_keys: usize,
+    /// Time in microseconds the column lines get to settle after a row is
+    /// driven low. Long cables and weak pull-ups need more.
+    pub settle_us: u32,
+}
+
+impl Default for KeypadConfig {
+    fn default() -> Self {
+        KeypadConfig {
+            debounce_ms: 20,
+            long_press_ms: 1000,
+            max_keys: 2,
+            settle_us: 10,
+        }
+    }
+}
+
+/// Debouncing state of a single key.
+#[derive(Clone, Copy)]
+struct KeyState {
+    raw: bool,
+    raw_since: u64,
+    stable: bool,
+    long_fired: bool,
+}
+
+impl KeyState {
+    const RELEASED: KeyState = KeyState {
+        raw: false,
+        raw_since: 0,
+        stable: false,
+        long_fired: false,
+    };
+}
+
+/// Hardware independent state of a keypad, turning raw scans into debounced
+/// `KeyEvent`s.
+pub struct KeyMatrix<const ROWS: usize, const COLS: usize> {
+    keymap: [[char; COLS]; ROWS],
+    config: KeypadConfig,
+    keys: [[KeyState; COLS]; ROWS],
+    ghosting: bool,
+}
+
+impl<const ROWS: usize, const COLS: usize> KeyMatrix<ROWS, COLS> {
+    /// Creates a new key matrix with all keys released.
+    pub const fn new(keymap: [[char; COLS]; ROWS], config: KeypadConfig) -> Self {
+        KeyMatrix {
+            keymap,
+            config,
+            keys: [[KeyState::RELEASED; COLS]; ROWS],
+            ghosting: false,
+        }
+    }
+
+    /// Returns `true` if the last scan was ignored because of ghosting.
+    pub fn is_ghosting(&self) -> bool {
+        self.ghosting
+    }
+
+    /// Returns `true` if the key at `row` and `col` is pressed (debounced).
+    pub fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.keys[row][col].stable
+    }
+
+    /// Feeds the matrix with a raw scan of the keypad.
+    ///
+    /// At most one event is reported per call, the rest of them is reported
+    /// on the following calls.
+    ///
+    /// # Arguments
+    /// * `scan` - `true` for every key which is pressed right now.
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// `Some(KeyEvent)` or `None` if nothing happened.
+    pub fn update(&mut self, scan: &[[bool; COLS]; ROWS], now_ms: u64) -> Option<KeyEvent> {
+        self.ghosting = Self::has_ghosting(scan);
+        if !self.ghosting {
+            self.apply_scan(scan, now_ms);
+        }
+
+        let debounce = self.config.debounce_ms as u64;
+        let long_press = self.config.long_press_ms as u64;
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                let key = &mut self.keys[row][col];
+                let kind =
+                    if key.raw != key.stable && now_ms.saturating_sub(key.raw_since) >= debounce {
+                        key.stable = key.raw;
+                        key.long_fired = false;
+                        if key.stable {
+                            KeyEventKind::Pressed
+                        } else {
+                            KeyEventKind::Released
+                        }
+                    } else if key.stable
+                        && key.raw
+                        && !key.long_fired
+                        && now_ms.saturating_sub(key.raw_since) >= long_press
+                    {
+                        key.long_fired = true;
+                        KeyEventKind::LongPress
+                    } else {
+                        continue;
+                    };
+
+                return Some(KeyEvent {
+                    key: self.keymap[row][col],
+                    row,
+                    col,
+                    kind,
+                });
+            }
+        }
+        None
+    }
+
+    /// Updates the raw state of the keys, respecting the rollover limit.
+    fn apply_scan(&mut self, scan: &[[bool; COLS]; ROWS], now_ms: u64) {
+        let mut pressed = self.keys.iter().flatten().filter(|key| key.raw).count();
+
+        for (keys, raws) in self.keys.iter_mut().zip(scan) {
+            for (key, &raw) in keys.iter_mut().zip(raws) {
+                if raw == key.raw {
+                    continue;
+                }
+                if raw {
+                    if pressed >= self.config.max_keys {
+                        continue;
+                    }
+                    pressed += 1;
+                } else {
+                    pressed -= 1;
+                }
+                key.raw = raw;
+                key.raw_since = now_ms;
+            }
+        }
+    }
+
+    /// Detects keys which may be "ghosts": two rows sharing two or more pressed
+    /// columns form a rectangle where one of the corners can be phantom.
+    fn has_ghosting(scan: &[[bool; COLS]; ROWS]) -> bool {
+        for first in 0..ROWS {
+            for second in (first + 1)..ROWS {
+                let shared = (0..COLS)
+                    .filter(|&col| scan[first][col] && scan[second][col])
+                    .count();
+                if shared >= 2 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// A matrix keypad with `ROWS` output pins and `COLS` input pins.
+#[cfg(feature = "esp-hal")]
+pub struct Keypad<R: OutputPin, C: InputPin, const ROWS: usize, const COLS: usize> {
+    /// Row pins, driven low one by one during a scan.
+    rows: [R; ROWS],
+    /// Column pins with pull-ups, reading low when a key is pressed.
+    cols: [C; COLS],
+    /// Delay provider for the settle time of the columns.
+    delay: Delay,
+    settle_us: u32,
+    matrix: KeyMatrix<ROWS, COLS>,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<R, C, const ROWS: usize, const COLS: usize> Keypad<R, C, ROWS, COLS>
+where
+    R: OutputPin<Error = core::convert::Infallible>,
+    C: InputPin<Error = core::convert::Infallible>,
+{
+    /// Creates a new `Keypad` on the given pins.
+    ///
+    /// # Arguments
+    /// * `rows` - Output pins connected to the rows of the keypad.
+    /// * `cols` - Input pins with pull-ups connected to the columns.
+    /// * `keymap` - Characters of the keys, see `KEYMAP_3X4` and `KEYMAP_4X4`.
+    /// * `delay` - Delay provider for the settle time of the columns.
+    /// * `config` - Timings and limits of the keypad.
+    ///
+    /// # Returns
+    /// A new `Keypad` instance.
+    pub fn create_on_pins(
+        mut rows: [R; ROWS],
+        cols: [C; COLS],
+        keymap: [[char; COLS]; ROWS],
+        delay: Delay,
+        config: KeypadConfig,
+    ) -> Self {
+        for row in rows.iter_mut() {
+            row.set_high().unwrap();
+        }
+        Keypad {
+            rows,
+            cols,
+            delay,
+            settle_us: config.settle_us,
+            matrix: KeyMatrix::new(keymap, config),
+        }
+    }
+
+    /// Scans the whole keypad.
+    ///
+    /// # Returns
+    /// `true` for every key which is pressed right now (not debounced).
+    pub fn scan(&mut self) -> [[bool; COLS]; ROWS] {
+        let mut scan = [[false; COLS]; ROWS];
+        for (row, pin) in self.rows.iter_mut().enumerate() {
+            pin.set_low().unwrap();
+            // The pull-ups need some time to charge the line capacitance
+            self.delay.delay_us(self.settle_us);
+            for (col, input) in self.cols.iter().enumerate() {
+                scan[row][col] = input.is_low().unwrap();
+            }
+            pin.set_high().unwrap();
+        }
+        scan
+    }
+
+    /// Scans the keypad and reports the next key event. Should be called
+    /// repeatedly, at least once per `debounce_ms`.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    ///
+    /// # Returns
+    /// `Some(KeyEvent)` or `None` if nothing happened.
+    pub fn update(&mut self, now_ms: u64) -> Option<KeyEvent> {
+        let scan = self.scan();
+        self.matrix.update(&scan, now_ms)
+    }
+
+    /// Returns `true` if the last scan was ignored because of ghosting.
+    pub fn is_ghosting(&self) -> bool {
+        self.matrix.is_ghosting()
+    }
+}
+
+/// Status of a PIN entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinStatus {
+    /// Nothing happened.
+    Idle,
+    /// A digit was added, the PIN has the given length now.
+    Digit(usize),
+    /// The entry was cleared with the clear key.
+    Cleared,
+    /// The submit key was pressed or the maximum length was reached.
+    Complete,
+    /// No key was pressed for the timeout and the entry was cleared.
+    TimedOut,
+}
+
+/// Collects a PIN of up to `N` digits from key presses.
+///
+/// `*` clears the entry and `#` submits it (see `PinEntry::with_keys`).
+pub struct PinEntry<const N: usize> {
+    digits: [u8; N],
+    len: usize,
+    timeout_ms: u64,
+    last_key_ms: Option<u64>,
+    clear_key: char,
+    submit_key: char,
+}
+
+impl<const N: usize> PinEntry<N> {
+    /// Creates a new empty PIN entry.
+    ///
+    /// # Arguments
+    /// * `timeout_ms` - Time without a key press after which the entry is
+    ///   cleared.
+    pub const fn new(timeout_ms: u64) -> Self {
+        Self::with_keys(timeout_ms, '*', '#')
+    }
+
+    /// Creates a new empty PIN entry with custom clear and submit keys.
+    pub const fn with_keys(timeout_ms: u64, clear_key: char, submit_key: char) -> Self {
+        PinEntry {
+            digits: [0; N],
+            len: 0,
+            timeout_ms,
+            last_key_ms: None,
+            clear_key,
+            submit_key,
+        }
+    }
+
+    /// Feeds a pressed key to the entry. Keys other than digits, clear and
+    /// submit keys are ignored. An entry which has timed out is cleared
+    /// first, so `key` starts a new one.
+    ///
+    /// # Arguments
+    /// * `key` - The pressed key.
+    /// * `now_ms` - Current monotonic time in milliseconds.
+    pub fn feed(&mut self, key: char, now_ms: u64) -> PinStatus {
+        self.update(now_ms);
+
+        if key == self.clear_key {
+            self.clear();
+            return PinStatus::Cleared;
+        }
+        if key == self.submit_key {
+            self.last_key_ms = None;
+            return PinStatus::Complete;
+        }
+        if !key.is_ascii_digit() || self.len >= N {
+            return PinStatus::Idle;
+        }
+
+        self.digits[self.len] = key as u8;
+        self.len += 1;
+        self.last_key_ms = Some(now_ms);
+        if self.len == N {
+            self.last_key_ms = None;
+            PinStatus::Complete
+        } else {
+            PinStatus::Digit(self.len)
+        }
+    }
+
+    /// Checks the timeout of the entry. Should be called periodically.
+    ///
+    /// # Returns
+    /// `PinStatus::TimedOut` once the entry has timed out and was cleared,
+    /// `PinStatus::Idle` otherwise.
+    pub fn update(&mut self, now_ms: u64) -> PinStatus {
+        match self.last_key_ms {
+            Some(last) if now_ms.saturating_sub(last) >= self.timeout_ms => {
+                self.clear();
+                PinStatus::TimedOut
+            }
+            _ => PinStatus::Idle,
+        }
+    }
+
+    /// Returns the entered digits.
+    pub fn pin(&self) -> &str {
+        // Only ASCII digits are ever stored
+        core::str::from_utf8(&self.digits[..self.len]).unwrap_or("")
+    }
+
+    /// Compares the entered digits with `expected`.
+    pub fn matches(&self, expected: &str) -> bool {
+        self.pin() == expected
+    }
+
+    /// Clears the entry.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.last_key_ms = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: KeypadConfig = KeypadConfig {
+        debounce_ms: 20,
+        long_press_ms: 1000,
+        max_keys: 2,
+        settle_us: 10,
+    };
+
+    /// Builds a scan of a 4x4 keypad with the given keys pressed.
+    fn scan(pressed: &[(usize, usize)]) -> [[bool; 4]; 4] {
+        let mut scan = [[false; 4]; 4];
+        for (row, col) in pressed {
+            scan[*row][*col] = true;
+        }
+        scan
+    }
+
+    fn event(key: char, row: usize, col: usize, kind: KeyEventKind) -> Option<KeyEvent> {
+        Some(KeyEvent {
+            key,
+            row,
+            col,
+            kind,
+        })
+    }
+
+    #[test]
+    fn debounces_press_and_release() {
+        let mut matrix = KeyMatrix::new(KEYMAP_4X4, CONFIG);
+        assert_eq!(matrix.update(&scan(&[(1, 2)]), 0), None);
+        // Bounces back before the debounce time.
+        assert_eq!(matrix.update(&scan(&[]), 5), None);
+        assert_eq!(matrix.update(&scan(&[(1, 2)]), 10), None);
+        assert_eq!(matrix.update(&scan(&[(1, 2)]), 29), None);
+        assert_eq!(
+            matrix.update(&scan(&[(1, 2)]), 30),
+            event('6', 1, 2, KeyEventKind::Pressed)
+        );
+        assert!(matrix.is_pressed(1, 2));
+
+        assert_eq!(matrix.update(&scan(&[]), 100), None);
+        assert_eq!(
+            matrix.update(&scan(&[]), 120),
+            event('6', 1, 2, KeyEventKind::Released)
+        );
+        assert!(!matrix.is_pressed(1, 2));
+    }
+
+    #[test]
+    fn reports_long_press_once() {
+        let mut matrix = KeyMatrix::new(KEYMAP_4X4, CONFIG);
+        matrix.update(&scan(&[(3, 1)]), 0);
+        assert_eq!(
+            matrix.update(&scan(&[(3, 1)]), 20),
+            event('0', 3, 1, KeyEventKind::Pressed)
+        );
+        assert_eq!(matrix.update(&scan(&[(3, 1)]), 999), None);
+        assert_eq!(
+            matrix.update(&scan(&[(3, 1)]), 1000),
+            event('0', 3, 1, KeyEventKind::LongPress)
+        );
+        assert_eq!(matrix.update(&scan(&[(3, 1)]), 5000), None);
+    }
+
+    #[test]
+    fn reports_one_event_per_update() {
+        let mut matrix = KeyMatrix::new(KEYMAP_4X4, CONFIG);
+        let both = scan(&[(0, 0), (2, 3)]);
+        matrix.update(&both, 0);
+        assert_eq!(
+            matrix.update(&both, 20),
+            event('1', 0, 0, KeyEventKind::Pressed)
+        );
+        assert_eq!(
+            matrix.update(&both, 21),
+            event('C', 2, 3, KeyEventKind::Pressed)
+        );
+        assert_eq!(matrix.update(&both, 22), None);
+    }
+
+    #[test]
+    fn ignores_ghosting_scans() {
+        let mut matrix = KeyMatrix::new(KEYMAP_4X4, CONFIG);
+        // Three real keys and the phantom fourth corner of the rectangle.
+        let ghost = scan(&[(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(matrix.update(&ghost, 0), None);
+        assert!(matrix.is_ghosting());
+        assert_eq!(matrix.update(&ghost, 100), None);
+        assert!(!matrix.is_pressed(0, 0));
+
+        assert_eq!(matrix.update(&scan(&[(0, 0)]), 200), None);
+        assert!(!matrix.is_ghosting());
+    }
+
+    #[test]
+    fn limits_rollover() {
+        let mut matrix = KeyMatrix::new(KEYMAP_4X4, CONFIG);
+        let three = scan(&[(0, 0), (1, 1), (2, 2)]);
+        matrix.update(&three, 0);
+        matrix.update(&three, 20);
+        matrix.update(&three, 20);
+        assert_eq!(matrix.update(&three, 20), None);
+        assert!(matrix.is_pressed(0, 0));
+        assert!(matrix.is_pressed(1, 1));
+        assert!(!matrix.is_pressed(2, 2));
+
+        // Releasing a key makes room for the third one.
+        let two = scan(&[(1, 1), (2, 2)]);
+        matrix.update(&two, 30);
+        assert_eq!(
+            matrix.update(&two, 50),
+            event('1', 0, 0, KeyEventKind::Released)
+        );
+        assert_eq!(
+            matrix.update(&two, 50),
+            event('9', 2, 2, KeyEventKind::Pressed)
+        );
+    }
+
+    #[test]
+    fn collects_pin() {
+        let mut entry: PinEntry<4> = PinEntry::new(5000);
+        assert_eq!(entry.feed('1', 0), PinStatus::Digit(1));
+        assert_eq!(entry.feed('A', 10), PinStatus::Idle);
+        assert_eq!(entry.feed('2', 20), PinStatus::Digit(2));
+        assert_eq!(entry.feed('#', 30), PinStatus::Complete);
+        assert!(entry.matches("12"));
+
+        entry.clear();
+        for (time, key) in ['4', '3', '2'].iter().enumerate() {
+            entry.feed(*key, time as u64);
+        }
+        assert_eq!(entry.feed('1', 3), PinStatus::Complete);
+        assert_eq!(entry.pin(), "4321");
+        assert_eq!(entry.feed('5', 4), PinStatus::Idle);
+
+        assert_eq!(entry.feed('*', 5), PinStatus::Cleared);
+        assert_eq!(entry.pin(), "");
+    }
+
+    #[test]
+    fn times_out() {
+        let mut entry: PinEntry<4> = PinEntry::new(5000);
+        assert_eq!(entry.update(10_000), PinStatus::Idle);
+        entry.feed('1', 0);
+        assert_eq!(entry.update(4999), PinStatus::Idle);
+        assert_eq!(entry.update(5000), PinStatus::TimedOut);
+        assert_eq!(entry.pin(), "");
+        assert_eq!(entry.update(20_000), PinStatus::Idle);
+    }
+
+    #[test]
+    fn key_after_timeout_starts_new_entry() {
+        let mut entry: PinEntry<4> = PinEntry::new(5000);
+        entry.feed('1', 0);
+        entry.feed('2', 1000);
+        assert_eq!(entry.feed('3', 7000), PinStatus::Digit(1));
+        assert_eq!(entry.pin(), "3");
+    }
+}
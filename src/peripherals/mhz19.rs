@@ -0,0 +1,379 @@
+//! # MH-Z19 CO2 Sensor Module
+//!
+//! This module provides a UART driver for the Winsen MH-Z19B and MH-Z19C NDIR
+//! CO2 sensors. Besides the CO2 concentration, the sensor reports a coarse
+//! temperature (1 °C resolution) measured inside the module.
+//!
+//! The sensor talks at 9600 baud, so the UART can be created with
+//! `init_uart_default!`. It only answers when asked, every command is a 9 byte
+//! frame checked with the negated 8-bit sum.
+//!
+//! The detection range (2000 or 5000 ppm) and the automatic baseline
+//! correction (ABC) are set on creation, see `Mhz19Config`. ABC assumes the
+//! sensor sees fresh air (about 400 ppm) at least once a day, so it should be
+//! disabled for greenhouses or rooms that are never aired. After power on, the
+//! readings are not valid for `WARM_UP_MS`, see `Mhz19::is_warming_up`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     mhz19::{Mhz19, Mhz19Config},
+//!     CO2Sensor,
+//!     TemperatureSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//!
+//! let now_ms = || SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//! let mut sensor = Mhz19::create_on_uart(uart, delay, Mhz19Config::default(), now_ms()).unwrap();
+//! loop {
+//!     let now = now_ms();
+//!     if !sensor.is_warming_up(now) {
+//!         println!("CO2: {} ppm", sensor.get_co2().unwrap());
+//!         println!("Temperature: {} °C", sensor.get_temperature().unwrap());
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::serial::{Read, Write};
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+use super::uart::{negated_sum8, FrameFormat, FrameLength};
+#[cfg(feature = "esp-hal")]
+use super::{
+    uart::{read_frame_blocking, send, FrameSync},
+    CO2Sensor,
+    PeripheralError,
+    TemperatureSensor,
+    UnifiedData,
+};
+
+/// Time after power on until the readings are valid, in milliseconds.
+pub const WARM_UP_MS: u32 = 180_000;
+
+const START: u8 = 0xFF;
+/// Sensor number of the command frames, the sensor ignores other values.
+const SENSOR_NUMBER: u8 = 0x01;
+/// Length of commands and responses.
+const FRAME_LEN: usize = 9;
+
+const CMD_SET_ABC: u8 = 0x79;
+const CMD_READ_CO2: u8 = 0x86;
+const CMD_ZERO_CALIBRATION: u8 = 0x87;
+const CMD_SPAN_CALIBRATION: u8 = 0x88;
+const CMD_SET_RANGE: u8 = 0x99;
+
+/// Value of the ABC command enabling the correction.
+const ABC_ON: u8 = 0xA0;
+/// Offset of the temperature byte of the CO2 response.
+const TEMPERATURE_OFFSET: i16 = 40;
+
+/// Longest time until the sensor answers a command.
+const RESPONSE_TIMEOUT_MS: u32 = 1000;
+/// Frames skipped while waiting for the response, e.g. late answers to
+/// previous commands.
+const MAX_SKIPPED_FRAMES: usize = 4;
+
+/// Checks the negated sum of the bytes between the start byte and the
+/// checksum.
+fn frame_valid(frame: &[u8]) -> bool {
+    negated_sum8(&frame[1..8]) == frame[8]
+}
+
+/// Frame format of the responses.
+pub const FORMAT: FrameFormat = FrameFormat {
+    header: &[START],
+    length: FrameLength::Fixed(FRAME_LEN),
+    footer: &[],
+    validate: Some(frame_valid),
+};
+
+/// Detection range of the sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mhz19Range {
+    /// 0 to 2000 ppm, more accurate indoors.
+    Ppm2000,
+    /// 0 to 5000 ppm, the factory setting.
+    Ppm5000,
+}
+
+impl Mhz19Range {
+    /// Returns the upper end of the range in ppm.
+    pub fn ppm(&self) -> u16 {
+        match self {
+            Mhz19Range::Ppm2000 => 2000,
+            Mhz19Range::Ppm5000 => 5000,
+        }
+    }
+}
+
+/// Configuration of an MH-Z19 sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mhz19Config {
+    pub range: Mhz19Range,
+    /// Enables the automatic baseline correction.
+    pub abc: bool,
+}
+
+impl Default for Mhz19Config {
+    fn default() -> Self {
+        Mhz19Config {
+            range: Mhz19Range::Ppm5000,
+            abc: true,
+        }
+    }
+}
+
+/// One measurement of the MH-Z19.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Mhz19Reading {
+    /// CO2 concentration in ppm.
+    pub co2: u16,
+    /// Temperature inside the module in degrees Celsius.
+    pub temperature: i16,
+}
+
+/// Builds a command frame.
+///
+/// # Arguments
+/// * `command` - The command.
+/// * `data` - The five data bytes.
+pub fn command(command: u8, data: [u8; 5]) -> [u8; FRAME_LEN] {
+    let mut frame = [0u8; FRAME_LEN];
+    frame[0] = START;
+    frame[1] = SENSOR_NUMBER;
+    frame[2] = command;
+    frame[3..8].copy_from_slice(&data);
+    frame[8] = negated_sum8(&frame[1..8]);
+    frame
+}
+
+/// Parses the response to the CO2 read command.
+///
+/// # Returns
+/// The measurement, or `None` if the frame is another response. The checksum
+/// is not checked, see `FORMAT`.
+pub fn parse_frame(frame: &[u8]) -> Option<Mhz19Reading> {
+    if frame.len() != FRAME_LEN || frame[0] != START || frame[1] != CMD_READ_CO2 {
+        return None;
+    }
+    Some(Mhz19Reading {
+        co2: u16::from_be_bytes([frame[2], frame[3]]),
+        temperature: frame[4] as i16 - TEMPERATURE_OFFSET,
+    })
+}
+
+/// Represents an MH-Z19 sensor on a serial port.
+#[cfg(feature = "esp-hal")]
+pub struct Mhz19<S> {
+    /// The serial port, 9600 baud 8N1.
+    pub serial: S,
+    /// Delay provider for timeouts.
+    pub delay: Delay,
+    sync: FrameSync<FRAME_LEN>,
+    config: Mhz19Config,
+    /// Time the sensor was powered on.
+    started_ms: u64,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> Mhz19<S> {
+    /// Creates a new `Mhz19` and applies the configuration.
+    ///
+    /// # Arguments
+    /// * `serial` - The serial port the sensor is connected to.
+    /// * `delay` - A delay provider for timeouts.
+    /// * `config` - The range and ABC setting.
+    /// * `now_ms` - Current time in milliseconds, taken as the power on time of
+    ///   the sensor for `is_warming_up`.
+    ///
+    /// # Returns
+    /// A new `Mhz19` instance, or `PeripheralError::InitializationFailed` if
+    /// the configuration can not be sent.
+    pub fn create_on_uart(
+        serial: S,
+        delay: Delay,
+        config: Mhz19Config,
+        now_ms: u64,
+    ) -> Result<Self, PeripheralError> {
+        let mut sensor = Mhz19 {
+            serial,
+            delay,
+            sync: FrameSync::new(FORMAT),
+            config,
+            started_ms: now_ms,
+        };
+        sensor
+            .set_range(config.range)
+            .and_then(|_| sensor.set_abc(config.abc))
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(sensor)
+    }
+
+    /// Sends a command; the settings are acknowledged inconsistently across
+    /// firmware versions, so no response is awaited.
+    fn write_command(&mut self, command_id: u8, data: [u8; 5]) -> Result<(), PeripheralError> {
+        send(&mut self.serial, &command(command_id, data))
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &Mhz19Config {
+        &self.config
+    }
+
+    /// Sets the detection range.
+    pub fn set_range(&mut self, range: Mhz19Range) -> Result<(), PeripheralError> {
+        let [high, low] = range.ppm().to_be_bytes();
+        self.write_command(CMD_SET_RANGE, [0, 0, 0, high, low])?;
+        self.config.range = range;
+        Ok(())
+    }
+
+    /// Enables or disables the automatic baseline correction.
+    pub fn set_abc(&mut self, enabled: bool) -> Result<(), PeripheralError> {
+        let value = if enabled { ABC_ON } else { 0 };
+        self.write_command(CMD_SET_ABC, [value, 0, 0, 0, 0])?;
+        self.config.abc = enabled;
+        Ok(())
+    }
+
+    /// Calibrates the zero point to 400 ppm.
+    ///
+    /// The sensor has to be in fresh air (outdoors, or by an open window) for
+    /// at least 20 minutes before.
+    pub fn calibrate_zero(&mut self) -> Result<(), PeripheralError> {
+        self.write_command(CMD_ZERO_CALIBRATION, [0; 5])
+    }
+
+    /// Calibrates the span point, after `calibrate_zero`.
+    ///
+    /// # Arguments
+    /// * `ppm` - The known CO2 concentration the sensor has been in for at
+    ///   least 20 minutes, 1000 ppm or more is recommended.
+    pub fn calibrate_span(&mut self, ppm: u16) -> Result<(), PeripheralError> {
+        let [high, low] = ppm.to_be_bytes();
+        self.write_command(CMD_SPAN_CALIBRATION, [high, low, 0, 0, 0])
+    }
+
+    /// Restarts the warm-up, e.g. after switching the supply of the sensor
+    /// back on.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn start_warm_up(&mut self, now_ms: u64) {
+        self.started_ms = now_ms;
+    }
+
+    /// Checks whether the sensor is still warming up, `WARM_UP_MS` after it
+    /// was created or after `start_warm_up`.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn is_warming_up(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.started_ms) < WARM_UP_MS as u64
+    }
+
+    /// Requests a measurement and waits for it.
+    ///
+    /// While warming up, the sensor answers with a placeholder concentration
+    /// (410 or 500 ppm depending on the firmware) which can not be told apart
+    /// from a real one, so `is_warming_up` should be checked first.
+    pub fn measure(&mut self) -> Result<Mhz19Reading, PeripheralError> {
+        self.sync.clear();
+        self.write_command(CMD_READ_CO2, [0; 5])?;
+        for _ in 0..MAX_SKIPPED_FRAMES {
+            let frame = read_frame_blocking(
+                &mut self.serial,
+                &mut self.sync,
+                &mut self.delay,
+                RESPONSE_TIMEOUT_MS,
+            )?;
+            if let Some(reading) = parse_frame(frame) {
+                return Ok(reading);
+            }
+        }
+        Err(PeripheralError::ReadError)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> CO2Sensor for Mhz19<S> {
+    /// Measures the CO2 concentration in ppm.
+    ///
+    /// The trait has no notion of time, so the warm-up is not checked and a
+    /// placeholder value is returned while the sensor warms up, see
+    /// `Mhz19::is_warming_up`.
+    fn get_co2(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.measure()?.co2 as f32)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> TemperatureSensor for Mhz19<S> {
+    /// Measures the temperature inside the module, which runs a few degrees
+    /// above the ambient temperature.
+    fn get_temperature(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.measure()?.temperature as f32)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> UnifiedData for Mhz19<S> {
+    type Output = Mhz19Reading;
+    /// Requests a measurement, see `Mhz19::measure`. The warm-up is not
+    /// checked, see `Mhz19::is_warming_up`.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.measure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response to the CO2 read command: 608 ppm at 31 °C.
+    const MEASUREMENT: [u8; 9] = [0xFF, 0x86, 0x02, 0x60, 0x47, 0x00, 0x00, 0x00, 0xD1];
+
+    #[test]
+    fn parses_measurement() {
+        assert!(frame_valid(&MEASUREMENT));
+        assert_eq!(
+            parse_frame(&MEASUREMENT),
+            Some(Mhz19Reading {
+                co2: 608,
+                temperature: 31,
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_other_responses() {
+        // Response to the range command.
+        let response = [0xFF, 0x99, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66];
+        assert!(frame_valid(&response));
+        assert_eq!(parse_frame(&response), None);
+        assert_eq!(parse_frame(&MEASUREMENT[..8]), None);
+    }
+
+    #[test]
+    fn builds_commands() {
+        assert_eq!(
+            command(CMD_READ_CO2, [0; 5]),
+            [0xFF, 0x01, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79]
+        );
+        assert_eq!(
+            command(CMD_SET_RANGE, [0, 0, 0, 0x07, 0xD0]),
+            [0xFF, 0x01, 0x99, 0x00, 0x00, 0x00, 0x07, 0xD0, 0x8F]
+        );
+        assert_eq!(
+            command(CMD_SET_ABC, [ABC_ON, 0, 0, 0, 0]),
+            [0xFF, 0x01, 0x79, 0xA0, 0x00, 0x00, 0x00, 0x00, 0xE6]
+        );
+    }
+}
@@ -22,24 +22,88 @@
 //!
 //! ## Features
 //! - Button input handling.
+//! - Interrupt-driven input events with an event queue.
+//! - Rotary encoders with push button.
+//! - Matrix keypads with PIN entry.
 //! - Support for a range of environmental sensors (temperature, humidity,
 //!   pressure, movement).
 //! - Distance measurement capabilities.
-//! - Light intensity sensing.
+//! - Light intensity sensing (TSL2591, BH1750, VEML7700).
 //! - Gas sensing for CO2 and VOCs.
+//! - NDIR CO2 sensing over UART.
+//! - Particulate matter sensing (PM1.0, PM2.5, PM10).
+//! - Air quality indices (US EPA AQI, EU CAQI) and CO2/TVOC comfort bands.
+//! - Generic analog (ADC) sensors and NTC thermistors.
+//! - Battery voltage and state of charge monitoring.
+//! - Voltage, current and power monitoring with energy accumulation.
+//! - Weight measurement with HX711 load cell amplifiers.
+//! - Digital outputs: relays, LEDs and switches.
+//! - PWM outputs: dimmable LEDs and servos.
+//! - Buzzer tones and RTTTL melodies.
+//! - WS2812/SK6812 addressable LEDs with effects.
+//! - Stepper motors with acceleration profiles.
+//! - Motion sensing with accelerometers, gyroscopes and magnetometers.
+//! - Orientation estimation (sensor fusion) from motion sensors.
+//! - Framing, checksums and timeouts for serial (UART) sensor protocols.
+//! - GPS receivers with a streaming NMEA 0183 parser.
+//! - Real-time clocks (DS3231, PCF8563) and other time sources.
+//!
+//! ## Host Tests
+//! Without a chip feature, `esp-hal` is left out and only the hardware
+//! independent parts build: protocol parsers, state machines and maths. They
+//! are tested on the host with `cargo test --lib`.
 
 // Include sub-modules for different peripherals.
+pub mod actuators;
+#[cfg(feature = "esp-hal")]
 pub mod aht20;
+#[cfg(feature = "esp-hal")]
+pub mod analog;
+pub mod aqi;
+#[cfg(feature = "esp-hal")]
+pub mod battery;
+#[cfg(feature = "esp-hal")]
+pub mod bh1750;
+#[cfg(feature = "esp-hal")]
 pub mod bme280;
 pub mod button;
+pub mod buzzer;
+pub mod encoder;
+pub mod fusion;
+pub mod gpio_events;
+pub mod gps;
+#[cfg(feature = "esp-hal")]
+pub mod hx711;
 pub mod joystick;
+pub mod keypad;
+pub mod mhz19;
+pub mod motor;
+#[cfg(feature = "esp-hal")]
+pub mod mpu6050;
+#[cfg(feature = "esp-hal")]
 pub mod pir;
+pub mod pms5003;
+#[cfg(feature = "esp-hal")]
+pub mod power_monitor;
+pub mod pwm;
+pub mod rtc;
+pub mod sds011;
+#[cfg(feature = "esp-hal")]
 pub mod sgp30;
+#[cfg(feature = "esp-hal")]
 pub mod tsl2591;
-#[cfg(any(not(feature = "esp32"), all(feature = "esp32", feature = "wifi")))]
+pub mod uart;
+#[cfg(all(
+    feature = "esp-hal",
+    any(not(feature = "esp32"), all(feature = "esp32", feature = "wifi"))
+))]
 pub mod ultrasonic_distance;
+#[cfg(feature = "esp-hal")]
+pub mod veml7700;
+pub mod ws2812;
 
 // Internal use of `esp-hal` components.
+#[cfg(feature = "esp-hal")]
 use esp_hal::{
     delay::Delay,
     i2c::I2C,
@@ -51,11 +115,13 @@ use esp_hal::{
 pub enum PeripheralError {
     InitializationFailed,
     ReadError,
+    WriteError,
 }
 
 /// Trait for peripherals that communicate over I2C.
 /// Implementation should provide a method for creating an instance of the
 /// peripheral on the I2C bus.
+#[cfg(feature = "esp-hal")]
 pub trait I2cPeriph {
     type Returnable;
     fn create_on_i2c(
@@ -67,6 +133,7 @@ pub trait I2cPeriph {
 /// Trait for peripherals that communicate over SPI.
 /// Implementation should provide a method for creating an instance of the
 /// peripheral on the I2C bus.
+#[cfg(feature = "esp-hal")]
 pub trait SpiPeriph {
     type Returnable;
     fn create_on_spi(
@@ -81,6 +148,7 @@ pub trait Shutdown {
 }
 
 /// Trait for peripherals capable of returning data (which would be serialized).
+#[cfg(feature = "esp-hal")]
 pub trait UnifiedData {
     type Output;
     fn read(&mut self, delay: Delay) -> Result<Self::Output, PeripheralError>;
@@ -134,3 +202,276 @@ pub trait LumiSensor {
     /// Measures the ambient light intensity in lux.
     fn get_lux(&mut self) -> Result<f32, PeripheralError>;
 }
+
+/// Trait for peripherals that can monitor a battery.
+pub trait BatterySensor {
+    /// Reads the battery voltage in volts.
+    fn get_battery_voltage(&mut self) -> Result<f32, PeripheralError>;
+
+    /// Estimates the state of charge of the battery as a percentage.
+    fn get_state_of_charge(&mut self) -> Result<f32, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure voltage.
+pub trait VoltageSensor {
+    /// Reads the voltage in volts.
+    fn get_voltage(&mut self) -> Result<f32, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure current.
+pub trait CurrentSensor {
+    /// Reads the current in amperes, negative when it flows backwards.
+    fn get_current(&mut self) -> Result<f32, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure power.
+pub trait PowerSensor {
+    /// Reads the power in watts, negative when the current flows backwards.
+    fn get_power(&mut self) -> Result<f32, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure weight.
+pub trait WeightSensor {
+    /// Reads the weight in the unit the sensor was calibrated in.
+    fn get_weight(&mut self) -> Result<f32, PeripheralError>;
+}
+
+/// A color with an optional white channel, used by RGBW LEDs only.
+///
+/// Shared by `ws2812` and the air quality categories of `aqi`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+
+    /// Creates a color without white.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, w: 0 }
+    }
+
+    /// Creates a color with white.
+    pub const fn rgbw(r: u8, g: u8, b: u8, w: u8) -> Self {
+        Color { r, g, b, w }
+    }
+
+    /// Creates a color from hue (0-359 degrees), saturation and value.
+    pub fn hsv(hue: u16, saturation: u8, value: u8) -> Self {
+        let hue = hue % 360;
+        let sector = hue / 60;
+        let fraction = ((hue % 60) * 255 / 60) as u32;
+        let value32 = value as u32;
+        let saturation = saturation as u32;
+
+        let p = (value32 * (255 - saturation) / 255) as u8;
+        let q = (value32 * (255 - saturation * fraction / 255) / 255) as u8;
+        let t = (value32 * (255 - saturation * (255 - fraction) / 255) / 255) as u8;
+        match sector {
+            0 => Color::rgb(value, t, p),
+            1 => Color::rgb(q, value, p),
+            2 => Color::rgb(p, value, t),
+            3 => Color::rgb(p, q, value),
+            4 => Color::rgb(t, p, value),
+            _ => Color::rgb(value, p, q),
+        }
+    }
+
+    /// Scales all channels by `factor` / 255.
+    pub fn scale(&self, factor: u8) -> Self {
+        let scale = |channel: u8| (channel as u16 * factor as u16 / 255) as u8;
+        Color::rgbw(scale(self.r), scale(self.g), scale(self.b), scale(self.w))
+    }
+}
+
+/// A three-axis vector, as measured by motion sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    /// Creates a new `Vector3`.
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vector3 { x, y, z }
+    }
+
+    /// Returns the length of the vector.
+    pub fn magnitude(&self) -> f32 {
+        libm::sqrtf(self.x * self.x + self.y * self.y + self.z * self.z)
+    }
+}
+
+/// Trait for peripherals that can measure acceleration.
+pub trait AccelerationSensor {
+    /// Reads the acceleration on all three axes in m/s², including gravity.
+    fn get_acceleration(&mut self) -> Result<Vector3, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure angular rate.
+pub trait GyroSensor {
+    /// Reads the angular rate around all three axes in radians per second.
+    fn get_angular_rate(&mut self) -> Result<Vector3, PeripheralError>;
+}
+
+/// Trait for peripherals that can measure magnetic field.
+pub trait MagnetometerSensor {
+    /// Reads the magnetic field on all three axes in microtesla (µT).
+    fn get_magnetic_field(&mut self) -> Result<Vector3, PeripheralError>;
+}
+
+/// Particulate matter mass concentrations in µg/m³.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ParticulateMatter {
+    /// PM1.0, `None` if the sensor does not measure it.
+    pub pm1_0: Option<f32>,
+    pub pm2_5: f32,
+    pub pm10: f32,
+}
+
+/// Number of particles larger than the given diameter in 0.1 L of air.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParticleCounts {
+    pub over_0_3um: u16,
+    pub over_0_5um: u16,
+    pub over_1_0um: u16,
+    pub over_2_5um: u16,
+    pub over_5_0um: u16,
+    pub over_10um: u16,
+}
+
+/// Trait for peripherals that can measure particulate matter.
+pub trait ParticulateSensor {
+    /// Measures the PM1.0, PM2.5 and PM10 mass concentrations.
+    fn get_particulate_matter(&mut self) -> Result<ParticulateMatter, PeripheralError>;
+
+    /// Counts the particles by size, `None` if the sensor does not count
+    /// particles.
+    fn get_particle_counts(&mut self) -> Result<Option<ParticleCounts>, PeripheralError> {
+        Ok(None)
+    }
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    // Months counted from March, so that the leap day is the last one.
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of the proleptic Gregorian calendar from the days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u8;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u8;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// A calendar date and time of day, usually in UTC. Ordered chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    /// Month, 1 to 12.
+    pub month: u8,
+    /// Day of the month, 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Creates a new `DateTime`.
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    /// Converts a UNIX timestamp (seconds since 1970-01-01) to a `DateTime`.
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+        let seconds = timestamp % 86_400;
+        DateTime {
+            year: year as u16,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds % 3600 / 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    /// Returns the UNIX timestamp, usable with the helpers in
+    /// `connectivity::wifi`. Dates before 1970 give 0.
+    pub fn timestamp(&self) -> u64 {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (days * 86_400 + seconds).max(0) as u64
+    }
+
+    /// Returns the day of the week, 0 for Sunday to 6 for Saturday.
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 was a Thursday.
+        (days_from_civil(self.year as i64, self.month, self.day) + 4).rem_euclid(7) as u8
+    }
+
+    /// Checks that all fields are in range, leap years included.
+    pub fn is_valid(&self) -> bool {
+        let leap_year = self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0);
+        let days_in_month = match self.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap_year => 29,
+            2 => 28,
+            _ => return false,
+        };
+        (1..=days_in_month).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+/// Trait for peripherals that keep or receive the wall-clock time, like
+/// real-time clocks and GPS receivers.
+pub trait TimeSource {
+    /// Reads the current date and time.
+    fn get_datetime(&mut self) -> Result<DateTime, PeripheralError>;
+
+    /// Reads the current time as a UNIX timestamp.
+    fn get_timestamp(&mut self) -> Result<u64, PeripheralError> {
+        Ok(self.get_datetime()?.timestamp())
+    }
+}
@@ -0,0 +1,458 @@
+//! # DC Motor Module
+//!
+//! This module drives brushed DC motors through an H-bridge such as L298N or
+//! TB6612FNG: two direction inputs select the direction and a PWM output (see
+//! `peripherals::pwm`) on the enable input sets the speed.
+//!
+//! Speeds are signed, from -1.0 (full reverse) to 1.0 (full forward). A motor
+//! can either coast (both direction inputs low, the motor spins out freely) or
+//! brake (both inputs high, the motor is shorted). Speed changes can be ramped
+//! to protect gearboxes and power supplies, ramping is driven by timestamps you
+//! pass in.
+//!
+//! `DifferentialDrive` steers a two-wheeled robot from a pair of axes, e.g.
+//! the normalized axes of a `Joystick`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::motor::dc::{DcMotor, DcMotorConfig, DifferentialDrive};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let pwm = esp_ward::init_pwm_custom!(peripherals, clocks);
+//! let timer = pwm
+//!     .timer(0, 20_000, esp_hal::ledc::timer::config::Duty::Duty10Bit)
+//!     .unwrap();
+//! let config = DcMotorConfig {
+//!     ramp_per_second: 2.0,
+//!     ..Default::default()
+//! };
+//! let left = DcMotor::create_on_pins(
+//!     pins.gpio2.into_push_pull_output(),
+//!     pins.gpio3.into_push_pull_output(),
+//!     pwm.channel(0, &timer, pins.gpio4).unwrap(),
+//!     config,
+//! );
+//! let right = DcMotor::create_on_pins(
+//!     pins.gpio5.into_push_pull_output(),
+//!     pins.gpio6.into_push_pull_output(),
+//!     pwm.channel(1, &timer, pins.gpio7).unwrap(),
+//!     config,
+//! );
+//! let mut drive = DifferentialDrive::create_on_motors(left, right);
+//! let (mut joystick, mut adc) = esp_ward::create_joystick_on_pins!(
+//!     peripherals,
+//!     pins.gpio0.into_analog(),
+//!     pins.gpio1.into_analog(),
+//!     pins.gpio9.into_pull_up_input()
+//! );
+//! joystick.calibrate_center(&mut adc, 16).unwrap();
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     drive
+//!         .drive_from_joystick(&mut joystick, &mut adc, now)
+//!         .unwrap();
+//! }
+//! ```
+
+use embedded_hal::digital::v2::OutputPin;
+#[cfg(feature = "esp-hal")]
+use embedded_hal::{
+    adc::{Channel, OneShot},
+    digital::v2::InputPin,
+};
+
+#[cfg(feature = "esp-hal")]
+use super::super::joystick::Joystick;
+use super::super::{pwm::PwmOutput, PeripheralError, Shutdown, Writable};
+
+/// Configuration of a DC motor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DcMotorConfig {
+    /// Maximum speed change per second, e.g. 2.0 goes from stop to full speed
+    /// in 0.5 s. `0.0` disables ramping.
+    pub ramp_per_second: f32,
+    /// Duty cycle (0.0 to 1.0) below which the motor does not turn. Speeds
+    /// above zero are mapped to `min_duty..=1.0`.
+    pub min_duty: f32,
+    /// Swaps the direction, e.g. for the left motor of a robot.
+    pub invert: bool,
+}
+
+impl Default for DcMotorConfig {
+    fn default() -> Self {
+        DcMotorConfig {
+            ramp_per_second: 0.0,
+            min_duty: 0.0,
+            invert: false,
+        }
+    }
+}
+
+/// Hardware independent speed ramp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedRamp {
+    /// Maximum speed change per second, `0.0` disables ramping.
+    pub rate_per_second: f32,
+    current: f32,
+    target: f32,
+    last_ms: Option<u64>,
+}
+
+impl SpeedRamp {
+    /// Creates a new ramp standing still.
+    pub const fn new(rate_per_second: f32) -> Self {
+        SpeedRamp {
+            rate_per_second,
+            current: 0.0,
+            target: 0.0,
+            last_ms: None,
+        }
+    }
+
+    /// Sets the speed to ramp to, clamped to -1.0..=1.0.
+    pub fn set_target(&mut self, target: f32, now_ms: u64) {
+        // Do not count the time spent at the previous target.
+        if self.current == self.target {
+            self.last_ms = Some(now_ms);
+        }
+        self.target = target.clamp(-1.0, 1.0);
+    }
+
+    /// Sets the speed immediately.
+    pub fn reset(&mut self, speed: f32) {
+        self.current = speed.clamp(-1.0, 1.0);
+        self.target = self.current;
+        self.last_ms = None;
+    }
+
+    /// Returns the target speed.
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Returns the current speed.
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Advances the ramp.
+    ///
+    /// # Returns
+    /// The current speed.
+    pub fn update(&mut self, now_ms: u64) -> f32 {
+        let elapsed_ms = now_ms.saturating_sub(self.last_ms.unwrap_or(now_ms));
+        self.last_ms = Some(now_ms);
+        if self.rate_per_second <= 0.0 {
+            self.current = self.target;
+            return self.current;
+        }
+
+        let max_change = self.rate_per_second * elapsed_ms as f32 / 1000.0;
+        let change = (self.target - self.current).clamp(-max_change, max_change);
+        self.current += change;
+        self.current
+    }
+}
+
+/// Mixes a steering axis and a throttle axis into the speeds of the left and
+/// right wheel of a differential drive ("arcade drive").
+///
+/// # Arguments
+/// * `x` - Steering from -1.0 (left) to 1.0 (right).
+/// * `y` - Throttle from -1.0 (backwards) to 1.0 (forwards).
+///
+/// # Returns
+/// The `(left, right)` speeds, scaled down together to stay within -1.0..=1.0.
+pub fn mix_differential(x: f32, y: f32) -> (f32, f32) {
+    let left = y + x;
+    let right = y - x;
+    let scale = libm::fabsf(left).max(libm::fabsf(right)).max(1.0);
+    (left / scale, right / scale)
+}
+
+/// A DC motor on an H-bridge.
+pub struct DcMotor<IN1, IN2, P: PwmOutput> {
+    pub in1: IN1,
+    pub in2: IN2,
+    /// PWM output on the enable input of the bridge.
+    pub pwm: P,
+    /// Minimum duty and direction inversion.
+    pub config: DcMotorConfig,
+    ramp: SpeedRamp,
+    braking: bool,
+}
+
+impl<IN1, IN2, P> DcMotor<IN1, IN2, P>
+where
+    IN1: OutputPin<Error = core::convert::Infallible>,
+    IN2: OutputPin<Error = core::convert::Infallible>,
+    P: PwmOutput,
+{
+    /// Creates a new coasting `DcMotor`.
+    ///
+    /// # Arguments
+    /// * `in1`, `in2` - Outputs to the direction inputs of the bridge.
+    /// * `pwm` - PWM output to the enable input of the bridge.
+    /// * `config` - Ramping, minimum duty and direction inversion.
+    ///
+    /// # Returns
+    /// A new `DcMotor` instance.
+    pub fn create_on_pins(in1: IN1, in2: IN2, pwm: P, config: DcMotorConfig) -> Self {
+        let mut motor = DcMotor {
+            in1,
+            in2,
+            pwm,
+            config,
+            ramp: SpeedRamp::new(config.ramp_per_second),
+            braking: false,
+        };
+        // A duty of zero is always within range.
+        let _ = motor.coast();
+        motor
+    }
+
+    /// Sets the speed to ramp to, see `update`.
+    ///
+    /// # Arguments
+    /// * `speed` - Speed from -1.0 (full reverse) to 1.0 (full forward).
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn set_speed(&mut self, speed: f32, now_ms: u64) -> Result<(), PeripheralError> {
+        self.braking = false;
+        self.ramp.rate_per_second = self.config.ramp_per_second;
+        self.ramp.set_target(speed, now_ms);
+        self.update(now_ms)
+    }
+
+    /// Advances the ramp towards the target speed. Should be called
+    /// periodically while ramping.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn update(&mut self, now_ms: u64) -> Result<(), PeripheralError> {
+        if self.braking {
+            return Ok(());
+        }
+        let speed = self.ramp.update(now_ms);
+        self.apply(speed)
+    }
+
+    /// Returns the current speed.
+    pub fn speed(&self) -> f32 {
+        self.ramp.current()
+    }
+
+    /// Returns `true` while the speed ramps towards the target.
+    pub fn is_ramping(&self) -> bool {
+        !self.braking && self.ramp.current() != self.ramp.target()
+    }
+
+    /// Lets the motor spin out freely, immediately.
+    pub fn coast(&mut self) -> Result<(), PeripheralError> {
+        self.braking = false;
+        self.ramp.reset(0.0);
+        self.apply(0.0)
+    }
+
+    /// Shorts the motor to stop it quickly and hold it, immediately.
+    pub fn brake(&mut self) -> Result<(), PeripheralError> {
+        self.braking = true;
+        self.ramp.reset(0.0);
+        self.in1.set_high().unwrap();
+        self.in2.set_high().unwrap();
+        let duty = self.pwm.max_duty();
+        self.pwm.set_duty(duty)
+    }
+
+    fn apply(&mut self, speed: f32) -> Result<(), PeripheralError> {
+        let speed = if self.config.invert { -speed } else { speed };
+        if speed > 0.0 {
+            self.in1.set_high().unwrap();
+            self.in2.set_low().unwrap();
+        } else if speed < 0.0 {
+            self.in1.set_low().unwrap();
+            self.in2.set_high().unwrap();
+        } else {
+            self.in1.set_low().unwrap();
+            self.in2.set_low().unwrap();
+        }
+
+        let magnitude = libm::fabsf(speed);
+        let duty = if magnitude > 0.0 {
+            self.config.min_duty + (1.0 - self.config.min_duty) * magnitude
+        } else {
+            0.0
+        };
+        let max_duty = self.pwm.max_duty();
+        self.pwm
+            .set_duty(libm::roundf(duty * max_duty as f32) as u32)
+    }
+}
+
+impl<IN1, IN2, P> Writable for DcMotor<IN1, IN2, P>
+where
+    IN1: OutputPin<Error = core::convert::Infallible>,
+    IN2: OutputPin<Error = core::convert::Infallible>,
+    P: PwmOutput,
+{
+    type Input = f32;
+    /// Sets the speed immediately, without ramping.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        self.braking = false;
+        self.ramp.reset(data);
+        self.apply(self.ramp.current())
+    }
+}
+
+impl<IN1, IN2, P> Shutdown for DcMotor<IN1, IN2, P>
+where
+    IN1: OutputPin<Error = core::convert::Infallible>,
+    IN2: OutputPin<Error = core::convert::Infallible>,
+    P: PwmOutput,
+{
+    /// Lets the motor coast.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.coast()
+    }
+}
+
+/// Two motors of a differential drive robot.
+pub struct DifferentialDrive<L, R> {
+    pub left: L,
+    pub right: R,
+}
+
+impl<L1, L2, LP, R1, R2, RP> DifferentialDrive<DcMotor<L1, L2, LP>, DcMotor<R1, R2, RP>>
+where
+    L1: OutputPin<Error = core::convert::Infallible>,
+    L2: OutputPin<Error = core::convert::Infallible>,
+    LP: PwmOutput,
+    R1: OutputPin<Error = core::convert::Infallible>,
+    R2: OutputPin<Error = core::convert::Infallible>,
+    RP: PwmOutput,
+{
+    /// Creates a new `DifferentialDrive`.
+    ///
+    /// # Arguments
+    /// * `left`, `right` - The motors, both turning forward with a positive
+    ///   speed (use `DcMotorConfig::invert` for a mirrored motor).
+    ///
+    /// # Returns
+    /// A new `DifferentialDrive` instance.
+    pub fn create_on_motors(left: DcMotor<L1, L2, LP>, right: DcMotor<R1, R2, RP>) -> Self {
+        DifferentialDrive { left, right }
+    }
+
+    /// Drives from a steering and a throttle axis, e.g. the normalized axes of
+    /// a `Joystick`.
+    ///
+    /// # Arguments
+    /// * `x` - Steering from -1.0 (left) to 1.0 (right).
+    /// * `y` - Throttle from -1.0 (backwards) to 1.0 (forwards).
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn drive(&mut self, x: f32, y: f32, now_ms: u64) -> Result<(), PeripheralError> {
+        let (left, right) = mix_differential(x, y);
+        self.left.set_speed(left, now_ms)?;
+        self.right.set_speed(right, now_ms)
+    }
+
+    /// Drives from the normalized axes of a `Joystick`: up drives forwards,
+    /// left and right steer.
+    ///
+    /// # Arguments
+    /// * `joystick` - The joystick, calibrated beforehand.
+    /// * `adc` - The ADC instance to read the axes.
+    /// * `now_ms` - Current time in milliseconds.
+    #[cfg(feature = "esp-hal")]
+    pub fn drive_from_joystick<SELECT, X, Y, ADCI, A>(
+        &mut self,
+        joystick: &mut Joystick<SELECT, X, Y>,
+        adc: &mut A,
+        now_ms: u64,
+    ) -> Result<(), PeripheralError>
+    where
+        SELECT: InputPin<Error = core::convert::Infallible>,
+        X: Channel<ADCI>,
+        Y: Channel<ADCI>,
+        A: OneShot<ADCI, u16, X> + OneShot<ADCI, u16, Y>,
+    {
+        let (x, y) = joystick.get_normalized(adc)?;
+        self.drive(x, y, now_ms)
+    }
+
+    /// Advances the ramps of both motors.
+    pub fn update(&mut self, now_ms: u64) -> Result<(), PeripheralError> {
+        self.left.update(now_ms)?;
+        self.right.update(now_ms)
+    }
+
+    /// Lets both motors coast.
+    pub fn coast(&mut self) -> Result<(), PeripheralError> {
+        self.left.coast()?;
+        self.right.coast()
+    }
+
+    /// Brakes both motors.
+    pub fn brake(&mut self) -> Result<(), PeripheralError> {
+        self.left.brake()?;
+        self.right.brake()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp_limits_speed_change() {
+        let mut ramp = SpeedRamp::new(2.0);
+        ramp.set_target(1.0, 1000);
+        assert_eq!(ramp.update(1000), 0.0);
+        assert_eq!(ramp.update(1250), 0.5);
+        assert_eq!(ramp.update(1500), 1.0);
+        assert_eq!(ramp.update(2000), 1.0);
+
+        // Reversing goes through zero at the same rate.
+        ramp.set_target(-1.0, 3000);
+        assert_eq!(ramp.update(3500), 0.0);
+        assert_eq!(ramp.update(4000), -1.0);
+    }
+
+    #[test]
+    fn ramp_ignores_time_spent_at_target() {
+        let mut ramp = SpeedRamp::new(1.0);
+        ramp.update(0);
+        ramp.set_target(0.5, 10_000);
+        assert_eq!(ramp.update(10_250), 0.25);
+    }
+
+    #[test]
+    fn ramp_without_rate_and_reset_jump() {
+        let mut ramp = SpeedRamp::new(0.0);
+        ramp.set_target(2.0, 0);
+        assert_eq!(ramp.target(), 1.0);
+        assert_eq!(ramp.update(0), 1.0);
+
+        let mut ramp = SpeedRamp::new(1.0);
+        ramp.set_target(1.0, 0);
+        ramp.reset(-0.5);
+        assert_eq!(ramp.current(), -0.5);
+        assert_eq!(ramp.update(5000), -0.5);
+    }
+
+    #[test]
+    fn mixes_steering_and_throttle() {
+        assert_eq!(mix_differential(0.0, 1.0), (1.0, 1.0));
+        assert_eq!(mix_differential(0.0, -0.5), (-0.5, -0.5));
+        // Turning on the spot.
+        assert_eq!(mix_differential(1.0, 0.0), (1.0, -1.0));
+        assert_eq!(mix_differential(0.5, 0.0), (0.5, -0.5));
+        // Scaled down together, keeping the ratio.
+        assert_eq!(mix_differential(1.0, 1.0), (1.0, 0.0));
+        assert_eq!(mix_differential(-0.5, 1.0), (0.5 / 1.5, 1.0));
+    }
+}
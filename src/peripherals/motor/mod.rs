@@ -0,0 +1,12 @@
+//! # Motor Module
+//!
+//! This module groups drivers for motors.
+//!
+//! ## Currently Supported Motors
+//! - `dc`: Brushed DC motors on H-bridges (L298N, TB6612FNG) with signed PWM
+//!   speed, coasting, braking, ramping and a differential drive helper.
+//! - `stepper`: Unipolar steppers on ULN2003 style drivers (e.g. 28BYJ-48) and
+//!   STEP/DIR drivers (A4988, DRV8825) with acceleration profiles and homing.
+
+pub mod dc;
+pub mod stepper;
@@ -0,0 +1,720 @@
+//! # Stepper Motor Module
+//!
+//! This module drives stepper motors either directly through four coil outputs
+//! (ULN2003 boards with 28BYJ-48 motors, full or half stepping) or through
+//! STEP/DIR drivers like A4988 and DRV8825.
+//!
+//! `Stepper` moves to absolute or relative positions with a trapezoidal speed
+//! profile: it accelerates up to the maximum speed, cruises and decelerates to
+//! stop exactly at the target. `Stepper::run` is non-blocking, it takes a step
+//! whenever one is due and should be called as often as possible. Timing is in
+//! microseconds.
+//!
+//! The profile maths lives in `MotionPlanner`, which does not touch any
+//! hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::{
+//!     button::{ActiveLevel, Button, ButtonConfig},
+//!     motor::stepper::{FourWire, StepMode, Stepper, StepperConfig},
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! let driver = FourWire::create_on_pins(
+//!     pins.gpio4.into_push_pull_output(),
+//!     pins.gpio5.into_push_pull_output(),
+//!     pins.gpio6.into_push_pull_output(),
+//!     pins.gpio7.into_push_pull_output(),
+//!     StepMode::Half,
+//! );
+//! let mut stepper = Stepper::create_on_driver(
+//!     driver,
+//!     StepperConfig {
+//!         max_speed: 800.0,
+//!         acceleration: 400.0,
+//!         ..Default::default()
+//!     },
+//! );
+//! let mut limit = Button::create_on_pins_with_config(
+//!     pins.gpio10.into_pull_up_input(),
+//!     ButtonConfig {
+//!         active_level: ActiveLevel::Low,
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! let now_us = || SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1_000_000);
+//! stepper.start_homing(-1);
+//! while stepper.run_homing(&mut limit, now_us()) {}
+//!
+//! // One turn of a 28BYJ-48 in half steps
+//! stepper.move_to(4096);
+//! while stepper.run(now_us()) {}
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::OutputPin;
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+use crate::peripherals::button::Button;
+
+/// Coil sequence for full stepping with two coils on at a time.
+pub const FULL_STEP: [[bool; 4]; 4] = [
+    [true, true, false, false],
+    [false, true, true, false],
+    [false, false, true, true],
+    [true, false, false, true],
+];
+
+/// Coil sequence for half stepping.
+pub const HALF_STEP: [[bool; 4]; 8] = [
+    [true, false, false, false],
+    [true, true, false, false],
+    [false, true, false, false],
+    [false, true, true, false],
+    [false, false, true, false],
+    [false, false, true, true],
+    [false, false, false, true],
+    [true, false, false, true],
+];
+
+/// Stepping mode of a four-wire motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// Full steps with two coils on, more torque.
+    Full,
+    /// Half steps, twice the resolution and smoother movement.
+    Half,
+}
+
+impl StepMode {
+    /// Returns the coil sequence of the mode.
+    pub fn sequence(&self) -> &'static [[bool; 4]] {
+        match self {
+            StepMode::Full => &FULL_STEP,
+            StepMode::Half => &HALF_STEP,
+        }
+    }
+}
+
+/// Hardware performing single steps.
+pub trait StepperDriver {
+    /// Takes one step, `direction` is `1` or `-1`.
+    fn step(&mut self, direction: i8);
+    /// Releases the motor, so it no longer holds its position.
+    fn release(&mut self);
+}
+
+/// A unipolar motor driven through four coil outputs, e.g. a 28BYJ-48 on a
+/// ULN2003 board.
+pub struct FourWire<A, B, C, D> {
+    pub coil_a: A,
+    pub coil_b: B,
+    pub coil_c: C,
+    pub coil_d: D,
+    mode: StepMode,
+    phase: usize,
+}
+
+impl<A, B, C, D> FourWire<A, B, C, D>
+where
+    A: OutputPin<Error = core::convert::Infallible>,
+    B: OutputPin<Error = core::convert::Infallible>,
+    C: OutputPin<Error = core::convert::Infallible>,
+    D: OutputPin<Error = core::convert::Infallible>,
+{
+    /// Creates a new `FourWire` driver with all coils off.
+    ///
+    /// # Arguments
+    /// * `coil_a` - `coil_d` - Outputs to IN1 - IN4 of the driver board.
+    /// * `mode` - Full or half stepping.
+    ///
+    /// # Returns
+    /// A new `FourWire` instance.
+    pub fn create_on_pins(coil_a: A, coil_b: B, coil_c: C, coil_d: D, mode: StepMode) -> Self {
+        let mut driver = FourWire {
+            coil_a,
+            coil_b,
+            coil_c,
+            coil_d,
+            mode,
+            phase: 0,
+        };
+        driver.release();
+        driver
+    }
+
+    fn set_coils(&mut self, coils: [bool; 4]) {
+        set_level(&mut self.coil_a, coils[0]);
+        set_level(&mut self.coil_b, coils[1]);
+        set_level(&mut self.coil_c, coils[2]);
+        set_level(&mut self.coil_d, coils[3]);
+    }
+}
+
+fn set_level<P: OutputPin<Error = core::convert::Infallible>>(pin: &mut P, high: bool) {
+    if high {
+        pin.set_high().unwrap();
+    } else {
+        pin.set_low().unwrap();
+    }
+}
+
+impl<A, B, C, D> StepperDriver for FourWire<A, B, C, D>
+where
+    A: OutputPin<Error = core::convert::Infallible>,
+    B: OutputPin<Error = core::convert::Infallible>,
+    C: OutputPin<Error = core::convert::Infallible>,
+    D: OutputPin<Error = core::convert::Infallible>,
+{
+    fn step(&mut self, direction: i8) {
+        let sequence = self.mode.sequence();
+        self.phase = if direction >= 0 {
+            (self.phase + 1) % sequence.len()
+        } else {
+            (self.phase + sequence.len() - 1) % sequence.len()
+        };
+        self.set_coils(sequence[self.phase]);
+    }
+
+    fn release(&mut self) {
+        self.set_coils([false; 4]);
+    }
+}
+
+/// A STEP/DIR driver like A4988 or DRV8825.
+///
+/// The enable input of the driver is not managed: tie it to its active level
+/// or drive it yourself, as `StepperDriver::release` does nothing here.
+#[cfg(feature = "esp-hal")]
+pub struct StepDir<STEP, DIR> {
+    pub step: STEP,
+    pub dir: DIR,
+    /// Whether a high DIR level moves in the negative direction.
+    pub invert_direction: bool,
+    delay: Delay,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<STEP, DIR> StepDir<STEP, DIR>
+where
+    STEP: OutputPin<Error = core::convert::Infallible>,
+    DIR: OutputPin<Error = core::convert::Infallible>,
+{
+    /// Creates a new `StepDir` driver.
+    ///
+    /// # Arguments
+    /// * `step` - Output connected to the STEP input.
+    /// * `dir` - Output connected to the DIR input.
+    /// * `delay` - Delay used for the step pulse width.
+    ///
+    /// # Returns
+    /// A new `StepDir` instance.
+    pub fn create_on_pins(mut step: STEP, dir: DIR, delay: Delay) -> Self {
+        step.set_low().unwrap();
+        StepDir {
+            step,
+            dir,
+            invert_direction: false,
+            delay,
+        }
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<STEP, DIR> StepperDriver for StepDir<STEP, DIR>
+where
+    STEP: OutputPin<Error = core::convert::Infallible>,
+    DIR: OutputPin<Error = core::convert::Infallible>,
+{
+    fn step(&mut self, direction: i8) {
+        set_level(&mut self.dir, (direction >= 0) != self.invert_direction);
+        // Both A4988 and DRV8825 need about 2 us of setup and pulse width.
+        self.delay.delay_us(2u32);
+        self.step.set_high().unwrap();
+        self.delay.delay_us(2u32);
+        self.step.set_low().unwrap();
+    }
+
+    /// Does nothing, the enable pin is not managed so the motor keeps holding.
+    /// Drive the EN input high (A4988, DRV8825) to release it.
+    fn release(&mut self) {}
+}
+
+/// Speed limits of a stepper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepperConfig {
+    /// Maximum speed in steps per second.
+    pub max_speed: f32,
+    /// Acceleration in steps per second squared, `0.0` starts and stops at
+    /// full speed.
+    pub acceleration: f32,
+    /// Constant speed in steps per second while homing.
+    pub homing_speed: f32,
+}
+
+impl Default for StepperConfig {
+    fn default() -> Self {
+        StepperConfig {
+            max_speed: 500.0,
+            acceleration: 1000.0,
+            homing_speed: 200.0,
+        }
+    }
+}
+
+/// Hardware independent trapezoidal motion profile, deciding when the next
+/// step is due.
+#[derive(Debug, Clone)]
+pub struct MotionPlanner {
+    /// Speed limits.
+    pub config: StepperConfig,
+    position: i64,
+    target: i64,
+    /// Square of the current speed in steps per second. Kept squared, so
+    /// accelerating and decelerating by one step are exact additions.
+    speed_squared: f32,
+    /// Direction of the current movement, `1` or `-1`.
+    direction: i8,
+    next_step_us: u64,
+}
+
+impl MotionPlanner {
+    /// Creates a new planner standing at position 0.
+    pub const fn new(config: StepperConfig) -> Self {
+        MotionPlanner {
+            config,
+            position: 0,
+            target: 0,
+            speed_squared: 0.0,
+            direction: 1,
+            next_step_us: 0,
+        }
+    }
+
+    /// Returns the current position in steps.
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    /// Returns the target position in steps.
+    pub fn target(&self) -> i64 {
+        self.target
+    }
+
+    /// Returns the current speed in steps per second, negative when moving
+    /// backwards.
+    pub fn speed(&self) -> f32 {
+        libm::sqrtf(self.speed_squared) * self.direction as f32
+    }
+
+    /// Returns `true` while the motor moves or has a target to reach.
+    pub fn is_running(&self) -> bool {
+        self.speed_squared > 0.0 || self.position != self.target
+    }
+
+    /// Sets the target position. A running movement is continued, reversing
+    /// with deceleration if needed.
+    pub fn move_to(&mut self, target: i64) {
+        self.target = target;
+    }
+
+    /// Redefines the current position without moving, stopping immediately.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position;
+        self.target = position;
+        self.speed_squared = 0.0;
+    }
+
+    /// Returns the amount of steps needed to stop from the current speed.
+    pub fn stopping_distance(&self) -> i64 {
+        if self.config.acceleration <= 0.0 {
+            return 0;
+        }
+        (self.speed_squared / (2.0 * self.config.acceleration)) as i64
+    }
+
+    /// Decelerates to a stop as fast as the acceleration allows.
+    pub fn stop(&mut self) {
+        if self.speed_squared > 0.0 {
+            self.target = self.position + self.stopping_distance() * self.direction as i64;
+        } else {
+            self.target = self.position;
+        }
+    }
+
+    /// Decides whether a step is due and advances the position if so.
+    ///
+    /// # Arguments
+    /// * `now_us` - Current time in microseconds.
+    ///
+    /// # Returns
+    /// The direction of the step to take now, `None` if no step is due.
+    pub fn poll(&mut self, now_us: u64) -> Option<i8> {
+        if self.speed_squared <= 0.0 {
+            self.update_speed();
+            if self.speed_squared <= 0.0 {
+                return None;
+            }
+            self.next_step_us = now_us;
+        }
+        if now_us < self.next_step_us {
+            return None;
+        }
+
+        let direction = self.direction;
+        self.position += direction as i64;
+        self.update_speed();
+        if self.speed_squared > 0.0 {
+            let interval_us = (1_000_000.0 / libm::sqrtf(self.speed_squared)) as u64;
+            // Do not try to catch up when polled more than a step too late, it
+            // would jerk.
+            let next_step_us = self.next_step_us + interval_us;
+            self.next_step_us = if next_step_us < now_us {
+                now_us + interval_us
+            } else {
+                next_step_us
+            };
+        }
+        Some(direction)
+    }
+
+    /// Computes the speed for the next step from the distance to go.
+    fn update_speed(&mut self) {
+        let distance = self.target - self.position;
+        if self.speed_squared <= 0.0 {
+            if distance == 0 {
+                return;
+            }
+            self.direction = if distance > 0 { 1 } else { -1 };
+        }
+        // Steps left in the current direction, negative if the target is behind.
+        let ahead = distance * self.direction as i64;
+        let max_squared = self.config.max_speed * self.config.max_speed;
+
+        if self.config.acceleration <= 0.0 {
+            self.speed_squared = if ahead > 0 { max_squared } else { 0.0 };
+            return;
+        }
+
+        // Speed change of a single step: v1^2 = v0^2 + 2 * a * 1 step.
+        let step_squared = 2.0 * self.config.acceleration;
+        if ahead <= 0 || ahead <= self.stopping_distance() {
+            self.speed_squared = (self.speed_squared - step_squared).max(0.0);
+            // Keep crawling if the target was not reached yet.
+            if ahead > 0 && self.speed_squared < step_squared {
+                self.speed_squared = step_squared.min(max_squared);
+            }
+        } else if self.speed_squared < max_squared {
+            self.speed_squared = (self.speed_squared + step_squared).min(max_squared);
+        } else {
+            // The maximum speed was lowered while running.
+            self.speed_squared = (self.speed_squared - step_squared).max(max_squared);
+        }
+    }
+}
+
+/// A stepper motor with a motion profile.
+pub struct Stepper<D: StepperDriver> {
+    /// The driver performing the steps.
+    pub driver: D,
+    planner: MotionPlanner,
+    /// Constant speed planner used while homing, kept apart so the speed
+    /// limits of the regular movements are untouched.
+    homing: Option<MotionPlanner>,
+}
+
+impl<D: StepperDriver> Stepper<D> {
+    /// Creates a new `Stepper` standing at position 0.
+    ///
+    /// # Arguments
+    /// * `driver` - The driver performing the steps.
+    /// * `config` - Speed limits.
+    ///
+    /// # Returns
+    /// A new `Stepper` instance.
+    pub fn create_on_driver(driver: D, config: StepperConfig) -> Self {
+        Stepper {
+            driver,
+            planner: MotionPlanner::new(config),
+            homing: None,
+        }
+    }
+
+    /// Returns the motion planner, e.g. to change the speed limits.
+    pub fn planner(&mut self) -> &mut MotionPlanner {
+        &mut self.planner
+    }
+
+    /// Moves to an absolute position in steps.
+    pub fn move_to(&mut self, position: i64) {
+        self.homing = None;
+        self.planner.move_to(position);
+    }
+
+    /// Moves by a number of steps relative to the current target.
+    pub fn move_by(&mut self, steps: i64) {
+        let target = self.planner.target() + steps;
+        self.move_to(target);
+    }
+
+    /// Returns the current position in steps.
+    pub fn position(&self) -> i64 {
+        self.planner.position()
+    }
+
+    /// Redefines the current position without moving.
+    pub fn set_position(&mut self, position: i64) {
+        self.planner.set_position(position);
+    }
+
+    /// Returns `true` while the motor moves or has a target to reach.
+    pub fn is_running(&self) -> bool {
+        self.planner.is_running()
+    }
+
+    /// Decelerates to a stop.
+    pub fn stop(&mut self) {
+        self.homing = None;
+        self.planner.stop();
+    }
+
+    /// Releases the motor, so it no longer holds its position.
+    pub fn release(&mut self) {
+        self.driver.release();
+    }
+
+    /// Takes a step if one is due. Should be called as often as possible, at
+    /// least once per step interval.
+    ///
+    /// # Arguments
+    /// * `now_us` - Current time in microseconds.
+    ///
+    /// # Returns
+    /// `true` while the motor is moving.
+    pub fn run(&mut self, now_us: u64) -> bool {
+        if let Some(direction) = self.planner.poll(now_us) {
+            self.driver.step(direction);
+        }
+        self.planner.is_running()
+    }
+
+    /// Starts moving towards the limit switch at the homing speed, see
+    /// `run_homing`.
+    ///
+    /// # Arguments
+    /// * `direction` - Direction of the limit switch, `1` or `-1`.
+    pub fn start_homing(&mut self, direction: i8) {
+        let direction: i8 = if direction >= 0 { 1 } else { -1 };
+        let mut homing = MotionPlanner::new(StepperConfig {
+            max_speed: self.planner.config.homing_speed,
+            acceleration: 0.0,
+            ..self.planner.config
+        });
+        homing.move_to(i64::MAX / 2 * direction as i64);
+        self.homing = Some(homing);
+        self.planner.set_position(0);
+    }
+
+    /// Moves towards the limit switch until it is pressed, then stops and sets
+    /// the position to 0. The switch is debounced by `Button::update` with its
+    /// `ButtonConfig::debounce_ms`, so a bouncing contact or a glitch does not
+    /// end the homing early. Should be called as often as possible.
+    ///
+    /// # Arguments
+    /// * `limit` - The limit switch.
+    /// * `now_us` - Current time in microseconds.
+    ///
+    /// # Returns
+    /// `true` while homing.
+    pub fn run_homing<T>(&mut self, limit: &mut Button<T>, now_us: u64) -> bool
+    where
+        T: embedded_hal::digital::v2::InputPin<Error = core::convert::Infallible>,
+    {
+        let Some(homing) = self.homing.as_mut() else {
+            return false;
+        };
+        limit.update(now_us / 1000);
+        if limit.is_pressed() {
+            self.homing = None;
+            self.planner.set_position(0);
+            return false;
+        }
+
+        if let Some(direction) = homing.poll(now_us) {
+            self.driver.step(direction);
+        }
+        true
+    }
+
+    /// Returns `true` while homing.
+    pub fn is_homing(&self) -> bool {
+        self.homing.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Statistics of a simulated movement.
+    #[derive(Debug, Default)]
+    struct Run {
+        steps: u32,
+        reversals: u32,
+        last_step_us: u64,
+        min_interval_us: u64,
+        max_speed: f32,
+    }
+
+    /// Polls the planner every 10 µs until it stops.
+    fn simulate(planner: &mut MotionPlanner, start_us: u64) -> Run {
+        let mut run = Run {
+            min_interval_us: u64::MAX,
+            ..Default::default()
+        };
+        let mut last_direction = None;
+        let mut now_us = start_us;
+        while planner.is_running() {
+            if let Some(direction) = planner.poll(now_us) {
+                if run.steps > 0 {
+                    run.min_interval_us = run.min_interval_us.min(now_us - run.last_step_us);
+                }
+                if last_direction.is_some_and(|last| last != direction) {
+                    run.reversals += 1;
+                }
+                last_direction = Some(direction);
+                run.steps += 1;
+                run.last_step_us = now_us;
+                run.max_speed = run.max_speed.max(libm::fabsf(planner.speed()));
+            }
+            now_us += 10;
+            assert!(now_us - start_us < 60_000_000, "the movement never ends");
+        }
+        run
+    }
+
+    fn planner(max_speed: f32, acceleration: f32) -> MotionPlanner {
+        MotionPlanner::new(StepperConfig {
+            max_speed,
+            acceleration,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn trapezoid_profile() {
+        let mut planner = planner(100.0, 100.0);
+        planner.move_to(1000);
+        let run = simulate(&mut planner, 0);
+
+        assert_eq!(planner.position(), 1000);
+        assert_eq!(planner.speed(), 0.0);
+        assert_eq!(run.steps, 1000);
+        assert_eq!(run.reversals, 0);
+        assert_eq!(run.max_speed, 100.0);
+        // Cruising at 100 steps/s, one step every 10 ms.
+        assert!((10_000..10_010).contains(&run.min_interval_us));
+        // About 1 s accelerating, 9 s cruising and 1 s decelerating.
+        assert!(
+            (10_500_000..11_200_000).contains(&run.last_step_us),
+            "{}",
+            run.last_step_us
+        );
+    }
+
+    #[test]
+    fn triangle_profile() {
+        let mut planner = planner(100.0, 100.0);
+        planner.move_to(-40);
+        let run = simulate(&mut planner, 0);
+
+        assert_eq!(planner.position(), -40);
+        assert_eq!(run.steps, 40);
+        // Accelerating over half of the distance reaches sqrt(2 * 100 * 20).
+        assert!(run.max_speed < 100.0);
+        assert!(
+            run.max_speed > 55.0 && run.max_speed <= 64.0,
+            "{}",
+            run.max_speed
+        );
+        // About 2 * sqrt(2 * 20 / 100) s, the first step is taken right away.
+        assert!(
+            (1_000_000..1_400_000).contains(&run.last_step_us),
+            "{}",
+            run.last_step_us
+        );
+    }
+
+    #[test]
+    fn constant_speed_step_timing() {
+        let mut planner = planner(500.0, 0.0);
+        planner.move_to(5);
+        let mut steps = [0; 5];
+        let mut count = 0;
+        let mut now_us = 1000;
+        while planner.is_running() {
+            if planner.poll(now_us).is_some() {
+                steps[count] = now_us;
+                count += 1;
+            }
+            now_us += 1;
+        }
+        assert_eq!(steps, [1000, 3000, 5000, 7000, 9000]);
+        assert_eq!(planner.poll(now_us + 10_000), None);
+    }
+
+    #[test]
+    fn late_polls_do_not_catch_up() {
+        let mut planner = planner(500.0, 0.0);
+        planner.move_to(3);
+        assert_eq!(planner.poll(0), Some(1));
+        // Polled 10 ms late: one step now, the next one a full interval later.
+        assert_eq!(planner.poll(12_000), Some(1));
+        assert_eq!(planner.poll(12_001), None);
+        assert_eq!(planner.poll(13_999), None);
+        assert_eq!(planner.poll(14_000), Some(1));
+        assert!(!planner.is_running());
+    }
+
+    #[test]
+    fn reverses_with_deceleration() {
+        let mut planner = planner(100.0, 100.0);
+        planner.move_to(1000);
+        let mut now_us = 0;
+        while planner.position() < 200 {
+            planner.poll(now_us);
+            now_us += 10;
+        }
+        planner.move_to(0);
+        let run = simulate(&mut planner, now_us);
+
+        assert_eq!(planner.position(), 0);
+        assert_eq!(run.reversals, 1);
+        // Stopping from full speed overshoots by 50 steps.
+        assert_eq!(run.steps, 50 + 250);
+    }
+
+    #[test]
+    fn stops_within_the_stopping_distance() {
+        let mut planner = planner(100.0, 100.0);
+        planner.move_to(1000);
+        let mut now_us = 0;
+        while planner.position() < 200 {
+            planner.poll(now_us);
+            now_us += 10;
+        }
+        assert_eq!(planner.stopping_distance(), 50);
+        planner.stop();
+        assert_eq!(planner.target(), 250);
+        simulate(&mut planner, now_us);
+        assert_eq!(planner.position(), 250);
+    }
+}
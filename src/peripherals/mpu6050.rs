@@ -0,0 +1,781 @@
+//! # MPU6050 Inertial Measurement Unit Module
+//!
+//! This module provides a driver for the InvenSense MPU6050 6-axis IMU
+//! (3-axis accelerometer and 3-axis gyroscope) over I2C. Besides single
+//! readings through the `AccelerationSensor`, `GyroSensor` and
+//! `TemperatureSensor` traits, the driver supports:
+//! - accelerometer and gyroscope range and digital low-pass filter
+//!   configuration,
+//! - burst reads of buffered samples from the on-chip FIFO,
+//! - offset calibration while the sensor lies still and flat,
+//! - data-ready and motion interrupts on the INT pin, see `ImuInterruptPin`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     mpu6050::{AccelRange, GyroRange, ImuSample, Mpu6050, Mpu6050Config},
+//!     AccelerationSensor,
+//!     GyroSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let config = Mpu6050Config {
+//!     accel_range: AccelRange::G4,
+//!     gyro_range: GyroRange::Dps500,
+//!     ..Default::default()
+//! };
+//! let mut imu = Mpu6050::create_on_i2c_with_config(i2c, delay, config).unwrap();
+//! // The sensor must lie still and flat during the calibration
+//! imu.calibrate(100).unwrap();
+//!
+//! let acceleration = imu.get_acceleration().unwrap();
+//! println!("Acceleration: {:?} m/s²", acceleration);
+//!
+//! imu.enable_fifo().unwrap();
+//! let mut samples = [ImuSample::default(); 16];
+//! loop {
+//!     let count = imu.read_fifo(&mut samples).unwrap();
+//!     for sample in &samples[..count] {
+//!         println!("Gyro: {:?} rad/s", sample.gyro);
+//!     }
+//! }
+//! ```
+
+use embedded_hal::{
+    blocking::{
+        delay::DelayMs,
+        i2c::{Write, WriteRead},
+    },
+    digital::v2::InputPin,
+};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{
+    gpio_events::{handle_pin_interrupt, EventQueue},
+    AccelerationSensor,
+    GyroSensor,
+    I2cPeriph,
+    PeripheralError,
+    Shutdown,
+    TemperatureSensor,
+    UnifiedData,
+    Vector3,
+};
+
+/// I2C address with the AD0 pin low.
+pub const DEFAULT_ADDRESS: u8 = 0x68;
+/// I2C address with the AD0 pin high.
+pub const ALTERNATE_ADDRESS: u8 = 0x69;
+
+/// Standard gravity in m/s².
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+const REG_SMPLRT_DIV: u8 = 0x19;
+const REG_CONFIG: u8 = 0x1A;
+const REG_GYRO_CONFIG: u8 = 0x1B;
+const REG_ACCEL_CONFIG: u8 = 0x1C;
+const REG_MOT_THR: u8 = 0x1F;
+const REG_MOT_DUR: u8 = 0x20;
+const REG_FIFO_EN: u8 = 0x23;
+const REG_INT_PIN_CFG: u8 = 0x37;
+const REG_INT_ENABLE: u8 = 0x38;
+const REG_INT_STATUS: u8 = 0x3A;
+const REG_ACCEL_XOUT_H: u8 = 0x3B;
+const REG_TEMP_OUT_H: u8 = 0x41;
+const REG_GYRO_XOUT_H: u8 = 0x43;
+const REG_USER_CTRL: u8 = 0x6A;
+const REG_PWR_MGMT_1: u8 = 0x6B;
+const REG_FIFO_COUNT_H: u8 = 0x72;
+const REG_FIFO_R_W: u8 = 0x74;
+const REG_WHO_AM_I: u8 = 0x75;
+
+const WHO_AM_I: u8 = 0x68;
+
+const PWR_RESET: u8 = 0x80;
+const PWR_SLEEP: u8 = 0x40;
+/// Clock source: PLL with the X axis gyroscope as reference.
+const PWR_CLOCK_PLL: u8 = 0x01;
+
+const FIFO_EN_ACCEL_GYRO: u8 = 0x78;
+const USER_CTRL_FIFO_EN: u8 = 0x40;
+const USER_CTRL_FIFO_RESET: u8 = 0x04;
+
+const INT_MOTION: u8 = 0x40;
+const INT_FIFO_OVERFLOW: u8 = 0x10;
+const INT_DATA_READY: u8 = 0x01;
+
+/// Size of the on-chip FIFO in bytes.
+const FIFO_SIZE: u16 = 1024;
+/// Bytes of one FIFO sample: accelerometer followed by gyroscope.
+const FIFO_SAMPLE_LEN: usize = 12;
+/// Samples read in one I2C transaction, the I2C peripheral of the ESP32
+/// family transfers at most 32 bytes at once.
+const FIFO_BURST_SAMPLES: usize = 2;
+
+/// Full scale range of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelRange {
+    /// ±2 g
+    G2,
+    /// ±4 g
+    G4,
+    /// ±8 g
+    G8,
+    /// ±16 g
+    G16,
+}
+
+impl AccelRange {
+    fn bits(self) -> u8 {
+        (self as u8) << 3
+    }
+
+    /// Returns the sensitivity in LSB per g.
+    pub fn lsb_per_g(self) -> f32 {
+        match self {
+            AccelRange::G2 => 16384.0,
+            AccelRange::G4 => 8192.0,
+            AccelRange::G8 => 4096.0,
+            AccelRange::G16 => 2048.0,
+        }
+    }
+}
+
+/// Full scale range of the gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GyroRange {
+    /// ±250 °/s
+    Dps250,
+    /// ±500 °/s
+    Dps500,
+    /// ±1000 °/s
+    Dps1000,
+    /// ±2000 °/s
+    Dps2000,
+}
+
+impl GyroRange {
+    fn bits(self) -> u8 {
+        (self as u8) << 3
+    }
+
+    /// Returns the sensitivity in LSB per degree per second.
+    pub fn lsb_per_dps(self) -> f32 {
+        match self {
+            GyroRange::Dps250 => 131.0,
+            GyroRange::Dps500 => 65.5,
+            GyroRange::Dps1000 => 32.8,
+            GyroRange::Dps2000 => 16.4,
+        }
+    }
+}
+
+/// Bandwidth of the digital low-pass filter of the accelerometer and
+/// gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigitalFilter {
+    /// Filter disabled, the gyroscope is sampled at 8 kHz.
+    Off,
+    Hz184,
+    Hz94,
+    Hz44,
+    Hz21,
+    Hz10,
+    Hz5,
+}
+
+impl DigitalFilter {
+    fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the internal sample rate in Hz.
+    fn base_rate_hz(self) -> u32 {
+        match self {
+            DigitalFilter::Off => 8000,
+            _ => 1000,
+        }
+    }
+}
+
+/// Configuration of the MPU6050.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mpu6050Config {
+    /// I2C address, `DEFAULT_ADDRESS` or `ALTERNATE_ADDRESS`.
+    pub address: u8,
+    pub accel_range: AccelRange,
+    pub gyro_range: GyroRange,
+    pub filter: DigitalFilter,
+    /// The sample rate is the internal rate (1 kHz with the filter enabled)
+    /// divided by `1 + sample_rate_divider`.
+    pub sample_rate_divider: u8,
+}
+
+impl Default for Mpu6050Config {
+    fn default() -> Self {
+        Mpu6050Config {
+            address: DEFAULT_ADDRESS,
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::Dps250,
+            filter: DigitalFilter::Hz44,
+            // 100 Hz
+            sample_rate_divider: 9,
+        }
+    }
+}
+
+impl Mpu6050Config {
+    /// Returns the sample rate in Hz.
+    pub fn sample_rate_hz(&self) -> u32 {
+        self.filter.base_rate_hz() / (1 + self.sample_rate_divider as u32)
+    }
+}
+
+/// Electrical configuration of the INT pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InterruptPinConfig {
+    /// The pin is low while an interrupt is pending.
+    pub active_low: bool,
+    /// The pin is open drain instead of push-pull.
+    pub open_drain: bool,
+    /// The pin stays active until the interrupt is cleared, instead of a 50 µs
+    /// pulse.
+    pub latch: bool,
+    /// Any read clears the interrupt, instead of reading the interrupt status
+    /// only.
+    pub clear_on_any_read: bool,
+}
+
+impl InterruptPinConfig {
+    fn bits(self) -> u8 {
+        (self.active_low as u8) << 7
+            | (self.open_drain as u8) << 6
+            | (self.latch as u8) << 5
+            | (self.clear_on_any_read as u8) << 4
+    }
+}
+
+/// Pending interrupts, see `Mpu6050::interrupt_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InterruptStatus {
+    pub data_ready: bool,
+    pub motion: bool,
+    pub fifo_overflow: bool,
+}
+
+impl InterruptStatus {
+    fn from_bits(bits: u8) -> Self {
+        InterruptStatus {
+            data_ready: bits & INT_DATA_READY != 0,
+            motion: bits & INT_MOTION != 0,
+            fifo_overflow: bits & INT_FIFO_OVERFLOW != 0,
+        }
+    }
+}
+
+/// Offsets subtracted from the readings, see `Mpu6050::calibrate`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImuOffsets {
+    /// Accelerometer offset in m/s².
+    pub accel: Vector3,
+    /// Gyroscope offset in rad/s.
+    pub gyro: Vector3,
+}
+
+/// One accelerometer and gyroscope sample.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImuSample {
+    /// Acceleration in m/s².
+    pub accel: Vector3,
+    /// Angular rate in rad/s.
+    pub gyro: Vector3,
+}
+
+fn raw_vector(bytes: &[u8]) -> [i16; 3] {
+    [
+        i16::from_be_bytes([bytes[0], bytes[1]]),
+        i16::from_be_bytes([bytes[2], bytes[3]]),
+        i16::from_be_bytes([bytes[4], bytes[5]]),
+    ]
+}
+
+/// Converts big-endian raw accelerometer data to m/s².
+fn convert_accel(bytes: &[u8], range: AccelRange, offset: Vector3) -> Vector3 {
+    let [x, y, z] = raw_vector(bytes);
+    let scale = STANDARD_GRAVITY / range.lsb_per_g();
+    Vector3::new(
+        x as f32 * scale - offset.x,
+        y as f32 * scale - offset.y,
+        z as f32 * scale - offset.z,
+    )
+}
+
+/// Converts big-endian raw gyroscope data to rad/s.
+fn convert_gyro(bytes: &[u8], range: GyroRange, offset: Vector3) -> Vector3 {
+    let [x, y, z] = raw_vector(bytes);
+    let scale = core::f32::consts::PI / 180.0 / range.lsb_per_dps();
+    Vector3::new(
+        x as f32 * scale - offset.x,
+        y as f32 * scale - offset.y,
+        z as f32 * scale - offset.z,
+    )
+}
+
+/// Converts big-endian raw temperature data to degrees Celsius.
+fn convert_temperature(bytes: &[u8]) -> f32 {
+    i16::from_be_bytes([bytes[0], bytes[1]]) as f32 / 340.0 + 36.53
+}
+
+/// Represents an MPU6050 IMU.
+pub struct Mpu6050<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the sensor is connected to.
+    pub bus: BUS,
+    /// Delay provider for timing-sensitive operations.
+    pub delay: Delay,
+    config: Mpu6050Config,
+    offsets: ImuOffsets,
+}
+
+impl<BUS, E> Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Resets, wakes up and configures the MPU6050.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the sensor is connected to.
+    /// * `delay` - A delay provider for timing-sensitive operations.
+    /// * `config` - Address, ranges, filter and sample rate.
+    ///
+    /// # Returns
+    /// A new `Mpu6050` instance, or `PeripheralError::InitializationFailed` if
+    /// the sensor does not respond or is not an MPU6050.
+    pub fn create_on_i2c_with_config(
+        bus: BUS,
+        delay: Delay,
+        config: Mpu6050Config,
+    ) -> Result<Self, PeripheralError> {
+        let mut sensor = Mpu6050 {
+            bus,
+            delay,
+            config,
+            offsets: ImuOffsets::default(),
+        };
+        sensor
+            .init()
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(sensor)
+    }
+
+    fn init(&mut self) -> Result<(), PeripheralError> {
+        if self.read_register(REG_WHO_AM_I)? != WHO_AM_I {
+            return Err(PeripheralError::InitializationFailed);
+        }
+        self.write_register(REG_PWR_MGMT_1, PWR_RESET)?;
+        self.delay.delay_ms(100u32);
+        self.write_register(REG_PWR_MGMT_1, PWR_CLOCK_PLL)?;
+        self.write_register(REG_CONFIG, self.config.filter.bits())?;
+        self.write_register(REG_SMPLRT_DIV, self.config.sample_rate_divider)?;
+        self.write_register(REG_GYRO_CONFIG, self.config.gyro_range.bits())?;
+        self.write_register(REG_ACCEL_CONFIG, self.config.accel_range.bits())
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), PeripheralError> {
+        self.bus
+            .write(self.config.address, &[register, value])
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), PeripheralError> {
+        self.bus
+            .write_read(self.config.address, &[register], buffer)
+            .map_err(|_| PeripheralError::ReadError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, PeripheralError> {
+        let mut buffer = [0u8];
+        self.read_registers(register, &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    /// Returns the current configuration.
+    pub fn config(&self) -> &Mpu6050Config {
+        &self.config
+    }
+
+    /// Sets the full scale range of the accelerometer.
+    pub fn set_accel_range(&mut self, range: AccelRange) -> Result<(), PeripheralError> {
+        // Keeps the high-pass filter used for motion detection.
+        let hpf = self.read_register(REG_ACCEL_CONFIG)? & 0x07;
+        self.write_register(REG_ACCEL_CONFIG, range.bits() | hpf)?;
+        self.config.accel_range = range;
+        Ok(())
+    }
+
+    /// Sets the full scale range of the gyroscope.
+    pub fn set_gyro_range(&mut self, range: GyroRange) -> Result<(), PeripheralError> {
+        self.write_register(REG_GYRO_CONFIG, range.bits())?;
+        self.config.gyro_range = range;
+        Ok(())
+    }
+
+    /// Sets the digital low-pass filter and the sample rate divider.
+    pub fn set_sample_rate(
+        &mut self,
+        filter: DigitalFilter,
+        sample_rate_divider: u8,
+    ) -> Result<(), PeripheralError> {
+        self.write_register(REG_CONFIG, filter.bits())?;
+        self.write_register(REG_SMPLRT_DIV, sample_rate_divider)?;
+        self.config.filter = filter;
+        self.config.sample_rate_divider = sample_rate_divider;
+        Ok(())
+    }
+
+    /// Returns the offsets subtracted from the readings.
+    pub fn offsets(&self) -> &ImuOffsets {
+        &self.offsets
+    }
+
+    /// Sets the offsets subtracted from the readings, e.g. from a previous
+    /// `calibrate` stored in flash.
+    pub fn set_offsets(&mut self, offsets: ImuOffsets) {
+        self.offsets = offsets;
+    }
+
+    /// Measures the offsets of both sensors. The sensor must lie still with
+    /// the Z axis pointing up while calibrating.
+    ///
+    /// # Arguments
+    /// * `samples` - Number of samples to average, taken at the sample rate.
+    ///
+    /// # Returns
+    /// The new offsets, which are also applied to all further readings.
+    pub fn calibrate(&mut self, samples: u16) -> Result<ImuOffsets, PeripheralError> {
+        let samples = samples.max(1);
+        let period_ms = (1000 / self.config.sample_rate_hz()).max(1);
+        self.offsets = ImuOffsets::default();
+
+        let mut accel = Vector3::default();
+        let mut gyro = Vector3::default();
+        for _ in 0..samples {
+            let sample = self.read_sample()?;
+            accel.x += sample.accel.x;
+            accel.y += sample.accel.y;
+            accel.z += sample.accel.z;
+            gyro.x += sample.gyro.x;
+            gyro.y += sample.gyro.y;
+            gyro.z += sample.gyro.z;
+            self.delay.delay_ms(period_ms);
+        }
+
+        let n = samples as f32;
+        self.offsets = ImuOffsets {
+            accel: Vector3::new(accel.x / n, accel.y / n, accel.z / n - STANDARD_GRAVITY),
+            gyro: Vector3::new(gyro.x / n, gyro.y / n, gyro.z / n),
+        };
+        Ok(self.offsets)
+    }
+
+    /// Reads the accelerometer, temperature and gyroscope in one burst.
+    ///
+    /// # Returns
+    /// The sample and the temperature in degrees Celsius.
+    pub fn read_all(&mut self) -> Result<(ImuSample, f32), PeripheralError> {
+        let mut buffer = [0u8; 14];
+        self.read_registers(REG_ACCEL_XOUT_H, &mut buffer)?;
+        let sample = ImuSample {
+            accel: convert_accel(&buffer[0..6], self.config.accel_range, self.offsets.accel),
+            gyro: convert_gyro(&buffer[8..14], self.config.gyro_range, self.offsets.gyro),
+        };
+        Ok((sample, convert_temperature(&buffer[6..8])))
+    }
+
+    /// Reads the accelerometer and the gyroscope.
+    pub fn read_sample(&mut self) -> Result<ImuSample, PeripheralError> {
+        self.read_all().map(|(sample, _)| sample)
+    }
+
+    /// Resets the FIFO and starts buffering accelerometer and gyroscope
+    /// samples in it at the sample rate.
+    pub fn enable_fifo(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_FIFO_EN, FIFO_EN_ACCEL_GYRO)?;
+        self.reset_fifo()
+    }
+
+    /// Stops buffering samples in the FIFO.
+    pub fn disable_fifo(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_FIFO_EN, 0)?;
+        self.write_register(REG_USER_CTRL, 0)
+    }
+
+    /// Discards all samples in the FIFO.
+    pub fn reset_fifo(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_USER_CTRL, USER_CTRL_FIFO_RESET)?;
+        self.write_register(REG_USER_CTRL, USER_CTRL_FIFO_EN)
+    }
+
+    /// Returns the number of bytes in the FIFO.
+    pub fn fifo_count(&mut self) -> Result<u16, PeripheralError> {
+        let mut buffer = [0u8; 2];
+        self.read_registers(REG_FIFO_COUNT_H, &mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    /// Reads buffered samples from the FIFO, oldest first.
+    ///
+    /// If the FIFO overflowed, the samples are no longer aligned: the FIFO is
+    /// reset and `PeripheralError::ReadError` is returned. Read often enough
+    /// to avoid this, the FIFO holds 85 samples.
+    ///
+    /// # Arguments
+    /// * `samples` - Buffer for the samples.
+    ///
+    /// # Returns
+    /// The number of samples stored in `samples`.
+    pub fn read_fifo(&mut self, samples: &mut [ImuSample]) -> Result<usize, PeripheralError> {
+        let count = self.fifo_count()?;
+        if count >= FIFO_SIZE {
+            self.reset_fifo()?;
+            return Err(PeripheralError::ReadError);
+        }
+
+        let available = (count as usize / FIFO_SAMPLE_LEN).min(samples.len());
+        let mut buffer = [0u8; FIFO_SAMPLE_LEN * FIFO_BURST_SAMPLES];
+        let mut read = 0;
+        while read < available {
+            let burst = (available - read).min(FIFO_BURST_SAMPLES);
+            let bytes = &mut buffer[..burst * FIFO_SAMPLE_LEN];
+            self.read_registers(REG_FIFO_R_W, bytes)?;
+            for chunk in bytes.chunks_exact(FIFO_SAMPLE_LEN) {
+                samples[read] = ImuSample {
+                    accel: convert_accel(&chunk[0..6], self.config.accel_range, self.offsets.accel),
+                    gyro: convert_gyro(&chunk[6..12], self.config.gyro_range, self.offsets.gyro),
+                };
+                read += 1;
+            }
+        }
+        Ok(read)
+    }
+
+    /// Configures the INT pin and enables the interrupts driving it.
+    ///
+    /// # Arguments
+    /// * `pin` - Electrical configuration of the INT pin.
+    /// * `data_ready` - Interrupt whenever a new sample is available.
+    /// * `motion` - Interrupt on motion, see `configure_motion_detection`.
+    pub fn enable_interrupts(
+        &mut self,
+        pin: InterruptPinConfig,
+        data_ready: bool,
+        motion: bool,
+    ) -> Result<(), PeripheralError> {
+        self.write_register(REG_INT_PIN_CFG, pin.bits())?;
+        let mut enable = 0;
+        if data_ready {
+            enable |= INT_DATA_READY;
+        }
+        if motion {
+            enable |= INT_MOTION;
+        }
+        self.write_register(REG_INT_ENABLE, enable)
+    }
+
+    /// Configures when the motion interrupt fires.
+    ///
+    /// # Arguments
+    /// * `threshold_mg` - Acceleration change in milli-g, in steps of 2 mg.
+    /// * `duration_ms` - How long the threshold must be exceeded.
+    pub fn configure_motion_detection(
+        &mut self,
+        threshold_mg: u16,
+        duration_ms: u8,
+    ) -> Result<(), PeripheralError> {
+        self.write_register(REG_MOT_THR, (threshold_mg / 2).min(255) as u8)?;
+        self.write_register(REG_MOT_DUR, duration_ms)?;
+        // Motion is detected on the output of the 5 Hz high-pass filter.
+        self.write_register(REG_ACCEL_CONFIG, self.config.accel_range.bits() | 0x01)
+    }
+
+    /// Reads and clears the pending interrupts.
+    pub fn interrupt_status(&mut self) -> Result<InterruptStatus, PeripheralError> {
+        Ok(InterruptStatus::from_bits(
+            self.read_register(REG_INT_STATUS)?,
+        ))
+    }
+
+    /// Puts the sensor to sleep or wakes it up.
+    pub fn set_sleep(&mut self, sleep: bool) -> Result<(), PeripheralError> {
+        let value = if sleep {
+            PWR_SLEEP | PWR_CLOCK_PLL
+        } else {
+            PWR_CLOCK_PLL
+        };
+        self.write_register(REG_PWR_MGMT_1, value)
+    }
+}
+
+impl I2cPeriph for Mpu6050 {
+    type Returnable = Self;
+
+    /// Initializes the MPU6050 on the default address with the default
+    /// configuration (±2 g, ±250 °/s, 100 Hz).
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the sensor.
+    /// * `delay` - A delay provider for timing-sensitive operations.
+    ///
+    /// # Returns
+    /// A result containing the initialized `Mpu6050` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Mpu6050::create_on_i2c_with_config(bus, delay, Mpu6050Config::default())
+    }
+}
+
+impl<BUS, E> AccelerationSensor for Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the acceleration in m/s², with the offsets applied.
+    fn get_acceleration(&mut self) -> Result<Vector3, PeripheralError> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(REG_ACCEL_XOUT_H, &mut buffer)?;
+        Ok(convert_accel(
+            &buffer,
+            self.config.accel_range,
+            self.offsets.accel,
+        ))
+    }
+}
+
+impl<BUS, E> GyroSensor for Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the angular rate in rad/s, with the offsets applied.
+    fn get_angular_rate(&mut self) -> Result<Vector3, PeripheralError> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(REG_GYRO_XOUT_H, &mut buffer)?;
+        Ok(convert_gyro(
+            &buffer,
+            self.config.gyro_range,
+            self.offsets.gyro,
+        ))
+    }
+}
+
+impl<BUS, E> TemperatureSensor for Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the die temperature in degrees Celsius.
+    fn get_temperature(&mut self) -> Result<f32, PeripheralError> {
+        let mut buffer = [0u8; 2];
+        self.read_registers(REG_TEMP_OUT_H, &mut buffer)?;
+        Ok(convert_temperature(&buffer))
+    }
+}
+
+impl<BUS, E> UnifiedData for Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = (Vector3, Vector3, f32);
+    /// Reads the acceleration (m/s²), angular rate (rad/s) and temperature
+    /// (°C) in one burst.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        let (sample, temperature) = self.read_all()?;
+        Ok((sample.accel, sample.gyro, temperature))
+    }
+}
+
+impl<BUS, E> Shutdown for Mpu6050<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Puts the sensor to sleep, see `Mpu6050::set_sleep` to wake it up.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.set_sleep(true)
+    }
+}
+
+/// The GPIO connected to the INT pin of an IMU.
+pub struct ImuInterruptPin<PIN> {
+    pub pin: PIN,
+    active_low: bool,
+}
+
+impl<PIN: InputPin<Error = core::convert::Infallible>> ImuInterruptPin<PIN> {
+    /// Creates a new `ImuInterruptPin`.
+    ///
+    /// # Arguments
+    /// * `pin` - The input pin connected to INT.
+    /// * `config` - The same configuration as passed to
+    ///   `Mpu6050::enable_interrupts`.
+    ///
+    /// # Returns
+    /// A new `ImuInterruptPin` instance.
+    pub fn create_on_pins(pin: PIN, config: InterruptPinConfig) -> Self {
+        ImuInterruptPin {
+            pin,
+            active_low: config.active_low,
+        }
+    }
+
+    /// Returns `true` while an interrupt is signalled. Only reliable with a
+    /// latched INT pin.
+    pub fn is_active(&self) -> bool {
+        self.pin.is_high().unwrap() != self.active_low
+    }
+}
+
+impl<PIN> ImuInterruptPin<PIN>
+where
+    PIN: InputPin<Error = core::convert::Infallible> + esp_hal::gpio::Pin,
+{
+    /// Enables the GPIO interrupt on the active edge of the INT pin, see
+    /// `peripherals::gpio_events`.
+    pub fn listen(&mut self) {
+        self.pin.listen(if self.active_low {
+            esp_hal::gpio::Event::FallingEdge
+        } else {
+            esp_hal::gpio::Event::RisingEdge
+        });
+    }
+
+    /// Disables the GPIO interrupt of the INT pin.
+    pub fn unlisten(&mut self) {
+        self.pin.unlisten();
+    }
+
+    /// Handles the GPIO interrupt for this pin. Should be called from the
+    /// `GPIO` interrupt handler. Read `Mpu6050::interrupt_status` afterwards
+    /// to find out which interrupt fired.
+    ///
+    /// # Arguments
+    /// * `queue` - The queue the event is pushed to.
+    /// * `source` - Identifier of this IMU stored in the event.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the interrupt was caused by this pin.
+    pub fn handle_interrupt<const N: usize>(
+        &mut self,
+        queue: &EventQueue<N>,
+        source: u8,
+        now_ms: u64,
+    ) -> bool {
+        // Only the active edge is listened to, the pulse may already be over,
+        // so every interrupt is a single activation.
+        let mut reported = false;
+        handle_pin_interrupt(&mut self.pin, true, &mut reported, queue, source, now_ms)
+    }
+}
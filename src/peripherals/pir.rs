@@ -21,16 +21,57 @@
 //!     println!("Motion detected!");
 //! }
 //! ```
+//!
+//! ## Occupancy
+//! `OccupancySensor` builds on `PirSensor` and keeps a room occupied until no
+//! motion was seen for a hold time. The first seconds after power-on are
+//! ignored while the sensor warms up, and new triggers are ignored for a
+//! cooldown after the room becomes vacant.
+//!
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::pir::{
+//!     OccupancyConfig,
+//!     OccupancyEventKind,
+//!     OccupancySensor,
+//!     PirSensor,
+//! };
+//!
+//! let pir = PirSensor::create_on_pins(pins.gpio5.into_pull_down_input());
+//! let mut room = OccupancySensor::create_on_sensor(
+//!     pir,
+//!     OccupancyConfig {
+//!         hold_ms: 120_000,
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! loop {
+//!     let now_ms = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     if let Some(event) = room.update(now_ms) {
+//!         match event.kind {
+//!             OccupancyEventKind::Occupied => println!("Occupied at {}", event.timestamp_ms),
+//!             OccupancyEventKind::Vacant => println!("Vacant at {}", event.timestamp_ms),
+//!         }
+//!     }
+//! }
+//! ```
 
 use embedded_hal::digital::v2::InputPin;
 use esp_hal::delay::Delay;
 
-use super::{PeripheralError, UnifiedData};
+use super::{
+    gpio_events::{handle_pin_interrupt, EventQueue},
+    PeripheralError,
+    UnifiedData,
+};
 
 /// Represents a PIR motion sensor connected to a single digital input pin.
 pub struct PirSensor<PIN: InputPin> {
     /// The digital input pin connected to the PIR sensor.
     inner: PIN,
+    /// State of the sensor in the last interrupt event.
+    reported_motion: bool,
 }
 
 impl<PIN: InputPin<Error = core::convert::Infallible>> PirSensor<PIN> {
@@ -41,7 +82,61 @@ impl<PIN: InputPin<Error = core::convert::Infallible>> PirSensor<PIN> {
     ///  # Returns
     /// A new `PirSensor` instance.
     pub fn create_on_pins(pin: PIN) -> Self {
-        PirSensor { inner: pin }
+        PirSensor {
+            inner: pin,
+            reported_motion: false,
+        }
+    }
+
+    /// Returns `true` if the sensor currently detects motion.
+    pub fn motion_detected(&mut self) -> bool {
+        self.inner.is_high().unwrap()
+    }
+}
+
+impl<PIN> PirSensor<PIN>
+where
+    PIN: InputPin<Error = core::convert::Infallible> + esp_hal::gpio::Pin,
+{
+    /// Enables the GPIO interrupt on both edges of the sensor pin, so the
+    /// start and the end of motion can be captured, see
+    /// `peripherals::gpio_events`.
+    pub fn listen(&mut self) {
+        self.reported_motion = self.motion_detected();
+        self.inner.listen(esp_hal::gpio::Event::AnyEdge);
+    }
+
+    /// Disables the GPIO interrupt of the sensor pin.
+    pub fn unlisten(&mut self) {
+        self.inner.unlisten();
+    }
+
+    /// Handles the GPIO interrupt for this sensor. Should be called from the
+    /// `GPIO` interrupt handler. A pulse that ended before the interrupt was
+    /// handled is reported as both edges.
+    ///
+    /// # Arguments
+    /// * `queue` - The queue the events are pushed to.
+    /// * `source` - Identifier of this sensor stored in the events.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `true` if the interrupt was caused by this sensor.
+    pub fn handle_interrupt<const N: usize>(
+        &mut self,
+        queue: &EventQueue<N>,
+        source: u8,
+        now_ms: u64,
+    ) -> bool {
+        let motion = self.inner.is_high().unwrap();
+        handle_pin_interrupt(
+            &mut self.inner,
+            motion,
+            &mut self.reported_motion,
+            queue,
+            source,
+            now_ms,
+        )
     }
 }
 
@@ -55,3 +150,232 @@ impl<PIN: InputPin<Error = core::convert::Infallible>> UnifiedData for PirSensor
         Ok(self.inner.is_high().unwrap())
     }
 }
+
+/// Configuration of the occupancy state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccupancyConfig {
+    /// Time in milliseconds the room stays occupied after the last motion.
+    pub hold_ms: u32,
+    /// Whether new motion while occupied extends the hold time. If `false`,
+    /// the room becomes vacant `hold_ms` after it became occupied.
+    pub retrigger: bool,
+    /// Time in milliseconds after power-on during which motion is ignored,
+    /// PIR sensors usually trigger randomly while warming up.
+    pub warm_up_ms: u32,
+    /// Time in milliseconds after becoming vacant during which motion is
+    /// ignored.
+    pub cooldown_ms: u32,
+    /// Length in milliseconds of the window motion events are counted in.
+    pub window_ms: u32,
+}
+
+impl Default for OccupancyConfig {
+    fn default() -> Self {
+        OccupancyConfig {
+            hold_ms: 60_000,
+            retrigger: true,
+            warm_up_ms: 30_000,
+            cooldown_ms: 2_000,
+            window_ms: 60_000,
+        }
+    }
+}
+
+/// State of the occupancy state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccupancyState {
+    /// The sensor is warming up, motion is ignored.
+    WarmingUp,
+    /// No motion was seen for the hold time.
+    Vacant,
+    /// Motion was seen within the hold time.
+    Occupied,
+    /// The room just became vacant, motion is ignored.
+    Cooldown,
+}
+
+/// Kind of an occupancy transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccupancyEventKind {
+    Occupied,
+    Vacant,
+}
+
+/// An occupancy transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccupancyEvent {
+    /// Kind of the transition.
+    pub kind: OccupancyEventKind,
+    /// Time in milliseconds the transition happened at.
+    pub timestamp_ms: u64,
+}
+
+/// Hardware independent occupancy state machine, fed with the motion level
+/// of a PIR sensor.
+pub struct OccupancyTracker {
+    config: OccupancyConfig,
+    state: OccupancyState,
+    started_ms: Option<u64>,
+    /// Occupied until this time, or the end of the cooldown.
+    deadline_ms: u64,
+    motion: bool,
+    window_start_ms: u64,
+    window_count: u32,
+    last_window_count: u32,
+}
+
+impl OccupancyTracker {
+    /// Creates a new tracker. The warm-up starts with the first update.
+    pub const fn new(config: OccupancyConfig) -> Self {
+        OccupancyTracker {
+            config,
+            state: OccupancyState::WarmingUp,
+            started_ms: None,
+            deadline_ms: 0,
+            motion: false,
+            window_start_ms: 0,
+            window_count: 0,
+            last_window_count: 0,
+        }
+    }
+
+    /// Updates the state machine with the current motion level.
+    ///
+    /// # Arguments
+    /// * `motion` - `true` if the sensor currently detects motion.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Some(OccupancyEvent)` if the room became occupied or vacant, `None`
+    /// otherwise.
+    pub fn update(&mut self, motion: bool, now_ms: u64) -> Option<OccupancyEvent> {
+        let started_ms = *self.started_ms.get_or_insert(now_ms);
+        let rising = motion && !self.motion;
+        self.motion = motion;
+
+        if self.state == OccupancyState::WarmingUp {
+            if now_ms.saturating_sub(started_ms) < self.config.warm_up_ms as u64 {
+                return None;
+            }
+            self.state = OccupancyState::Vacant;
+            self.window_start_ms = now_ms;
+        }
+
+        let window_ms = self.config.window_ms.max(1) as u64;
+        if now_ms.saturating_sub(self.window_start_ms) >= window_ms {
+            let elapsed_windows = (now_ms - self.window_start_ms) / window_ms;
+            self.last_window_count = if elapsed_windows == 1 {
+                self.window_count
+            } else {
+                0
+            };
+            self.window_count = 0;
+            self.window_start_ms += elapsed_windows * window_ms;
+        }
+        if rising {
+            self.window_count = self.window_count.saturating_add(1);
+        }
+
+        match self.state {
+            OccupancyState::Cooldown if now_ms >= self.deadline_ms => {
+                self.state = OccupancyState::Vacant;
+            }
+            OccupancyState::Occupied => {
+                if motion && self.config.retrigger {
+                    self.deadline_ms = now_ms + self.config.hold_ms as u64;
+                } else if now_ms >= self.deadline_ms {
+                    self.state = OccupancyState::Cooldown;
+                    self.deadline_ms = now_ms + self.config.cooldown_ms as u64;
+                    return Some(OccupancyEvent {
+                        kind: OccupancyEventKind::Vacant,
+                        timestamp_ms: now_ms,
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        if self.state == OccupancyState::Vacant && motion {
+            self.state = OccupancyState::Occupied;
+            self.deadline_ms = now_ms + self.config.hold_ms as u64;
+            return Some(OccupancyEvent {
+                kind: OccupancyEventKind::Occupied,
+                timestamp_ms: now_ms,
+            });
+        }
+        None
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> OccupancyState {
+        self.state
+    }
+
+    /// Returns `true` if the room is occupied.
+    pub fn is_occupied(&self) -> bool {
+        self.state == OccupancyState::Occupied
+    }
+
+    /// Returns the amount of motion events in the current window.
+    pub fn motion_count(&self) -> u32 {
+        self.window_count
+    }
+
+    /// Returns the amount of motion events in the last completed window.
+    pub fn last_window_count(&self) -> u32 {
+        self.last_window_count
+    }
+
+    /// Returns the configuration of the tracker.
+    pub fn config(&self) -> &OccupancyConfig {
+        &self.config
+    }
+}
+
+/// Occupancy detection on top of a `PirSensor`.
+pub struct OccupancySensor<PIN: InputPin> {
+    /// The underlying PIR sensor.
+    pub sensor: PirSensor<PIN>,
+    tracker: OccupancyTracker,
+}
+
+impl<PIN: InputPin<Error = core::convert::Infallible>> OccupancySensor<PIN> {
+    /// Creates a new `OccupancySensor`.
+    ///
+    /// # Arguments
+    /// * `sensor` - The PIR sensor.
+    /// * `config` - Configuration of the occupancy state machine.
+    ///
+    /// # Returns
+    /// A new `OccupancySensor` instance.
+    pub fn create_on_sensor(sensor: PirSensor<PIN>, config: OccupancyConfig) -> Self {
+        OccupancySensor {
+            sensor,
+            tracker: OccupancyTracker::new(config),
+        }
+    }
+
+    /// Reads the sensor and updates the occupancy state, should be called
+    /// periodically.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Some(OccupancyEvent)` if the room became occupied or vacant, `None`
+    /// otherwise.
+    pub fn update(&mut self, now_ms: u64) -> Option<OccupancyEvent> {
+        let motion = self.sensor.motion_detected();
+        self.tracker.update(motion, now_ms)
+    }
+
+    /// Returns the occupancy state machine.
+    pub fn tracker(&self) -> &OccupancyTracker {
+        &self.tracker
+    }
+
+    /// Returns `true` if the room is occupied.
+    pub fn is_occupied(&self) -> bool {
+        self.tracker.is_occupied()
+    }
+}
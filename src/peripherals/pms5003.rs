@@ -0,0 +1,353 @@
+//! # PMS5003 Particulate Matter Sensor Module
+//!
+//! This module provides a UART driver for the Plantower PMS5003 laser
+//! particulate matter sensor (and the protocol compatible PMS7003 and
+//! PMSA003). It measures PM1.0, PM2.5 and PM10 mass concentrations and counts
+//! particles by size.
+//!
+//! The sensor talks at 9600 baud, so the UART can be created with
+//! `init_uart_default!`. In `ReportingMode::Active` (the power-on default) it
+//! sends a measurement every 200 ms to 2.3 s, in `ReportingMode::Passive` only
+//! when asked. The fan and laser can be switched off with `sleep` (or
+//! `Shutdown`), after `wake` the readings need `WARM_UP_MS` to stabilize.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{pms5003::Pms5003, uart::ReportingMode, ParticulateSensor};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//!
+//! let mut sensor = Pms5003::create_on_uart(uart, delay, ReportingMode::Passive).unwrap();
+//! let pm = sensor.get_particulate_matter().unwrap();
+//! println!("PM2.5: {} µg/m³, PM10: {} µg/m³", pm.pm2_5, pm.pm10);
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    serial::{Read, Write},
+};
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+#[cfg(feature = "esp-hal")]
+use super::{
+    uart::{read_frame_blocking, send, FrameReceiver, FrameSync, ReportingMode},
+    ParticulateSensor,
+    PeripheralError,
+    Shutdown,
+    UnifiedData,
+};
+use super::{
+    uart::{sum16, FrameFormat, FrameLength},
+    ParticleCounts,
+    ParticulateMatter,
+};
+
+/// Time after waking up until the readings are stable, in milliseconds.
+pub const WARM_UP_MS: u32 = 30_000;
+
+const HEADER: [u8; 2] = [0x42, 0x4D];
+/// Length of a measurement frame.
+const DATA_FRAME_LEN: usize = 32;
+/// Value of the length field of a measurement frame.
+const DATA_LENGTH: u16 = 28;
+
+const CMD_READ: u8 = 0xE2;
+const CMD_MODE: u8 = 0xE1;
+const CMD_SLEEP: u8 = 0xE4;
+
+/// Longest time between two measurements in active mode.
+const ACTIVE_TIMEOUT_MS: u64 = 3000;
+/// Longest time until a measurement is sent in passive mode.
+const PASSIVE_TIMEOUT_MS: u32 = 1000;
+/// Frames skipped while waiting for a measurement, e.g. command responses.
+const MAX_SKIPPED_FRAMES: usize = 4;
+
+/// Checks the 16-bit sum at the end of a frame.
+fn frame_valid(frame: &[u8]) -> bool {
+    let (data, checksum) = frame.split_at(frame.len() - 2);
+    sum16(data) == u16::from_be_bytes([checksum[0], checksum[1]])
+}
+
+/// Frame format of measurements and command responses.
+pub const FORMAT: FrameFormat = FrameFormat {
+    header: &HEADER,
+    length: FrameLength::Field {
+        offset: 2,
+        big_endian: true,
+        wide: true,
+        extra: 4,
+    },
+    footer: &[],
+    validate: Some(frame_valid),
+};
+
+/// One measurement of the PMS5003.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PmsReading {
+    /// Concentrations under standard particle conditions (CF=1), meant for
+    /// calibration in the factory.
+    pub standard: ParticulateMatter,
+    /// Concentrations under atmospheric environment, to use for ambient air.
+    pub atmospheric: ParticulateMatter,
+    /// Particle counts in 0.1 L of air.
+    pub counts: ParticleCounts,
+}
+
+/// Parses a measurement frame.
+///
+/// # Returns
+/// The measurement, or `None` if the frame is not a measurement (e.g. a
+/// command response). The checksum is not checked, see `FORMAT`.
+pub fn parse_frame(frame: &[u8]) -> Option<PmsReading> {
+    if frame.len() != DATA_FRAME_LEN
+        || frame[..2] != HEADER
+        || u16::from_be_bytes([frame[2], frame[3]]) != DATA_LENGTH
+    {
+        return None;
+    }
+    let word = |index: usize| u16::from_be_bytes([frame[4 + 2 * index], frame[5 + 2 * index]]);
+    let mass = |first: usize| ParticulateMatter {
+        pm1_0: Some(word(first) as f32),
+        pm2_5: word(first + 1) as f32,
+        pm10: word(first + 2) as f32,
+    };
+    Some(PmsReading {
+        standard: mass(0),
+        atmospheric: mass(3),
+        counts: ParticleCounts {
+            over_0_3um: word(6),
+            over_0_5um: word(7),
+            over_1_0um: word(8),
+            over_2_5um: word(9),
+            over_5_0um: word(10),
+            over_10um: word(11),
+        },
+    })
+}
+
+/// Builds a command frame.
+pub fn command(command: u8, data: u16) -> [u8; 7] {
+    let [data_high, data_low] = data.to_be_bytes();
+    let mut frame = [HEADER[0], HEADER[1], command, data_high, data_low, 0, 0];
+    let [sum_high, sum_low] = sum16(&frame[..5]).to_be_bytes();
+    frame[5] = sum_high;
+    frame[6] = sum_low;
+    frame
+}
+
+/// Represents a PMS5003 sensor on a serial port.
+#[cfg(feature = "esp-hal")]
+pub struct Pms5003<S> {
+    /// The serial port, 9600 baud 8N1.
+    pub serial: S,
+    /// Delay provider for timeouts.
+    pub delay: Delay,
+    receiver: FrameReceiver<DATA_FRAME_LEN>,
+    mode: ReportingMode,
+    sleeping: bool,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> Pms5003<S> {
+    /// Creates a new `Pms5003` and switches it to the given reporting mode.
+    ///
+    /// # Arguments
+    /// * `serial` - The serial port the sensor is connected to.
+    /// * `delay` - A delay provider for timeouts.
+    /// * `mode` - The reporting mode.
+    ///
+    /// # Returns
+    /// A new `Pms5003` instance, or `PeripheralError::WriteError` if the mode
+    /// can not be sent.
+    pub fn create_on_uart(
+        serial: S,
+        delay: Delay,
+        mode: ReportingMode,
+    ) -> Result<Self, PeripheralError> {
+        let mut sensor = Pms5003 {
+            serial,
+            delay,
+            receiver: FrameReceiver::new(FrameSync::new(FORMAT), ACTIVE_TIMEOUT_MS),
+            mode,
+            sleeping: false,
+        };
+        sensor.set_mode(mode)?;
+        Ok(sensor)
+    }
+
+    /// Returns the reporting mode.
+    pub fn mode(&self) -> ReportingMode {
+        self.mode
+    }
+
+    /// Switches the reporting mode.
+    pub fn set_mode(&mut self, mode: ReportingMode) -> Result<(), PeripheralError> {
+        let data = match mode {
+            ReportingMode::Passive => 0,
+            ReportingMode::Active => 1,
+        };
+        send(&mut self.serial, &command(CMD_MODE, data))?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Switches the fan and the laser off.
+    pub fn sleep(&mut self) -> Result<(), PeripheralError> {
+        send(&mut self.serial, &command(CMD_SLEEP, 0))?;
+        self.sleeping = true;
+        Ok(())
+    }
+
+    /// Switches the fan and the laser on, see `WARM_UP_MS`.
+    pub fn wake(&mut self) -> Result<(), PeripheralError> {
+        send(&mut self.serial, &command(CMD_SLEEP, 1))?;
+        self.sleeping = false;
+        self.receiver.sync.clear();
+        Ok(())
+    }
+
+    /// Returns `true` after `sleep`.
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Waits for a measurement; in passive mode it is requested first.
+    pub fn measure(&mut self) -> Result<PmsReading, PeripheralError> {
+        let timeout_ms = match self.mode {
+            ReportingMode::Active => ACTIVE_TIMEOUT_MS as u32,
+            ReportingMode::Passive => {
+                self.receiver.sync.clear();
+                send(&mut self.serial, &command(CMD_READ, 0))?;
+                PASSIVE_TIMEOUT_MS
+            }
+        };
+        for _ in 0..MAX_SKIPPED_FRAMES {
+            let frame = read_frame_blocking(
+                &mut self.serial,
+                &mut self.receiver.sync,
+                &mut self.delay,
+                timeout_ms,
+            )?;
+            if let Some(reading) = parse_frame(frame) {
+                return Ok(reading);
+            }
+        }
+        Err(PeripheralError::ReadError)
+    }
+
+    /// Reads the received bytes without blocking, for active mode. Should be
+    /// called periodically.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(Some(reading))` if a new measurement was completed, `Ok(None)` if
+    /// none is complete yet, or `Err(PeripheralError::ReadError)` on a serial
+    /// error or when no frame arrived for 3 seconds.
+    pub fn poll(&mut self, now_ms: u64) -> Result<Option<PmsReading>, PeripheralError> {
+        while let Some(frame) = self.receiver.poll(&mut self.serial, now_ms)? {
+            if let Some(reading) = parse_frame(frame) {
+                return Ok(Some(reading));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Switches the sensor back on after `sleep` and waits for the readings to
+    /// stabilize.
+    pub fn wake_blocking(&mut self) -> Result<(), PeripheralError> {
+        self.wake()?;
+        self.delay.delay_ms(WARM_UP_MS);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> ParticulateSensor for Pms5003<S> {
+    /// Measures the concentrations under atmospheric environment.
+    fn get_particulate_matter(&mut self) -> Result<ParticulateMatter, PeripheralError> {
+        Ok(self.measure()?.atmospheric)
+    }
+
+    /// Counts the particles in 0.1 L of air.
+    fn get_particle_counts(&mut self) -> Result<Option<ParticleCounts>, PeripheralError> {
+        Ok(Some(self.measure()?.counts))
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> UnifiedData for Pms5003<S> {
+    type Output = PmsReading;
+    /// Waits for a complete measurement, see `Pms5003::measure`.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.measure()
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> Shutdown for Pms5003<S> {
+    /// Puts the sensor to sleep, see `Pms5003::wake`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.sleep()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Measurement sent by a PMS5003 in clean indoor air.
+    const MEASUREMENT: [u8; 32] = [
+        0x42, 0x4D, 0x00, 0x1C, 0x00, 0x05, 0x00, 0x08, 0x00, 0x0A, 0x00, 0x05, 0x00, 0x08, 0x00,
+        0x0A, 0x03, 0xB4, 0x01, 0x1C, 0x00, 0x3A, 0x00, 0x0A, 0x00, 0x02, 0x00, 0x00, 0x97, 0x00,
+        0x02, 0x8A,
+    ];
+    /// Response to the command switching to passive mode.
+    const MODE_RESPONSE: [u8; 8] = [0x42, 0x4D, 0x00, 0x04, 0xE1, 0x00, 0x01, 0x74];
+
+    #[test]
+    fn parses_measurement() {
+        let reading = parse_frame(&MEASUREMENT).unwrap();
+        let mass = ParticulateMatter {
+            pm1_0: Some(5.0),
+            pm2_5: 8.0,
+            pm10: 10.0,
+        };
+        assert_eq!(reading.standard, mass);
+        assert_eq!(reading.atmospheric, mass);
+        assert_eq!(
+            reading.counts,
+            ParticleCounts {
+                over_0_3um: 948,
+                over_0_5um: 284,
+                over_1_0um: 58,
+                over_2_5um: 10,
+                over_5_0um: 2,
+                over_10um: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn builds_commands() {
+        assert_eq!(
+            command(CMD_MODE, 0),
+            [0x42, 0x4D, 0xE1, 0x00, 0x00, 0x01, 0x70]
+        );
+        assert_eq!(
+            command(CMD_READ, 0),
+            [0x42, 0x4D, 0xE2, 0x00, 0x00, 0x01, 0x71]
+        );
+    }
+
+    #[test]
+    fn ignores_command_responses() {
+        assert_eq!(parse_frame(&MODE_RESPONSE), None);
+    }
+}
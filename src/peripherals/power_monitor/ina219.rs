@@ -0,0 +1,376 @@
+//! # INA219 Power Monitor Module
+//!
+//! This module provides a driver for the TI INA219 current and power monitor
+//! over I2C. It measures bus voltages up to 26 V and the voltage across the
+//! shunt resistor with 12-bit resolution, optionally averaged over up to 128
+//! samples. Common breakout boards carry a 0.1 Ω shunt, which is the default
+//! configuration (up to 3.2 A).
+//!
+//! The shunt voltage range (PGA gain) is chosen from the calibration, so the
+//! range fits the largest expected current.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     power_monitor::ina219::Ina219,
+//!     CurrentSensor,
+//!     I2cPeriph,
+//!     VoltageSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let mut monitor = Ina219::create_on_i2c(i2c, delay).unwrap();
+//! println!("{} V", monitor.get_voltage().unwrap());
+//! println!("{} A", monitor.get_current().unwrap());
+//! ```
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{calibration, PowerReading};
+use crate::peripherals::{
+    CurrentSensor,
+    I2cPeriph,
+    PeripheralError,
+    PowerSensor,
+    Shutdown,
+    UnifiedData,
+    VoltageSensor,
+};
+
+/// Default I2C address of the INA219, with A0 and A1 tied to ground.
+pub const DEFAULT_ADDRESS: u8 = 0x40;
+
+const REG_CONFIG: u8 = 0x00;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+const REG_POWER: u8 = 0x03;
+const REG_CURRENT: u8 = 0x04;
+const REG_CALIBRATION: u8 = 0x05;
+
+const CONFIG_RESET: u16 = 0x8000;
+const CONFIG_BUS_32V: u16 = 0x2000;
+const CONFIG_PGA_SHIFT: u16 = 11;
+const CONFIG_BADC_SHIFT: u16 = 7;
+const CONFIG_SADC_SHIFT: u16 = 3;
+/// Shunt and bus voltage, continuous.
+const MODE_CONTINUOUS: u16 = 0x07;
+const MODE_POWER_DOWN: u16 = 0x00;
+
+/// Bus voltage register: conversion ready.
+const BUS_CONVERSION_READY: u16 = 0x02;
+/// Bus voltage register: the power or current calculation overflowed.
+const BUS_OVERFLOW: u16 = 0x01;
+
+/// Shunt voltage LSB in volts.
+const SHUNT_LSB: f32 = 10e-6;
+/// Bus voltage LSB in volts.
+const BUS_LSB: f32 = 4e-3;
+/// Constant of the calibration formula.
+const CALIBRATION_SCALE: f32 = 0.04096;
+/// Power LSB in current LSBs.
+const POWER_LSB_RATIO: f32 = 20.0;
+/// Largest calibration register value, bit 0 is unused.
+const MAX_CALIBRATION: u16 = 0xFFFE;
+
+/// Full-scale range of the bus voltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusVoltageRange {
+    V16,
+    V32,
+}
+
+/// Number of samples averaged per conversion, all with 12-bit resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Averaging {
+    /// 532 µs per conversion.
+    Samples1,
+    Samples2,
+    Samples4,
+    Samples8,
+    Samples16,
+    Samples32,
+    Samples64,
+    /// 68.1 ms per conversion.
+    Samples128,
+}
+
+impl Averaging {
+    fn bits(self) -> u16 {
+        match self {
+            Averaging::Samples1 => 0b0011,
+            other => 0b1000 | other as u16,
+        }
+    }
+}
+
+/// Configuration of an INA219.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ina219Config {
+    pub address: u8,
+    /// Resistance of the shunt in ohms.
+    pub shunt_ohms: f32,
+    /// Largest expected current in amperes.
+    pub max_current: f32,
+    pub bus_range: BusVoltageRange,
+    pub averaging: Averaging,
+}
+
+impl Default for Ina219Config {
+    fn default() -> Self {
+        Ina219Config {
+            address: DEFAULT_ADDRESS,
+            shunt_ohms: 0.1,
+            max_current: 3.2,
+            bus_range: BusVoltageRange::V32,
+            averaging: Averaging::Samples1,
+        }
+    }
+}
+
+impl Ina219Config {
+    /// Returns the PGA setting covering the largest shunt voltage.
+    fn pga_bits(&self) -> u16 {
+        let max_shunt_voltage = self.max_current * self.shunt_ohms;
+        match max_shunt_voltage {
+            v if v <= 0.04 => 0,
+            v if v <= 0.08 => 1,
+            v if v <= 0.16 => 2,
+            _ => 3,
+        }
+    }
+
+    fn register(&self, mode: u16) -> u16 {
+        let bus_range = match self.bus_range {
+            BusVoltageRange::V16 => 0,
+            BusVoltageRange::V32 => CONFIG_BUS_32V,
+        };
+        bus_range
+            | (self.pga_bits() << CONFIG_PGA_SHIFT)
+            | (self.averaging.bits() << CONFIG_BADC_SHIFT)
+            | (self.averaging.bits() << CONFIG_SADC_SHIFT)
+            | mode
+    }
+}
+
+/// Computes the calibration register and the current LSB in amperes.
+fn calibration_for(shunt_ohms: f32, max_current: f32) -> (u16, f32) {
+    let (register, _) = calibration(CALIBRATION_SCALE, shunt_ohms, max_current, MAX_CALIBRATION);
+    // Bit 0 of the register is not used.
+    let register = (register & MAX_CALIBRATION).max(2);
+    (register, CALIBRATION_SCALE / (register as f32 * shunt_ohms))
+}
+
+/// Represents an INA219 power monitor.
+pub struct Ina219<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the monitor is connected to.
+    pub bus: BUS,
+    config: Ina219Config,
+    calibration: u16,
+    current_lsb: f32,
+}
+
+impl<BUS, E> Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Resets, configures and calibrates the INA219.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the monitor is connected to.
+    /// * `config` - Address, shunt, largest current, range and averaging.
+    ///
+    /// # Returns
+    /// A new `Ina219` instance, or `PeripheralError::InitializationFailed` if
+    /// the monitor does not respond.
+    pub fn create_on_i2c_with_config(
+        bus: BUS,
+        config: Ina219Config,
+    ) -> Result<Self, PeripheralError> {
+        let (calibration, current_lsb) = calibration_for(config.shunt_ohms, config.max_current);
+        let mut monitor = Ina219 {
+            bus,
+            config,
+            calibration,
+            current_lsb,
+        };
+        monitor
+            .write_register(REG_CONFIG, CONFIG_RESET)
+            .and_then(|_| monitor.configure())
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(monitor)
+    }
+
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), PeripheralError> {
+        let [high, low] = value.to_be_bytes();
+        self.bus
+            .write(self.config.address, &[register, high, low])
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, PeripheralError> {
+        let mut data = [0u8; 2];
+        self.bus
+            .write_read(self.config.address, &[register], &mut data)
+            .map_err(|_| PeripheralError::ReadError)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes the configuration and calibration registers.
+    fn configure(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_CALIBRATION, self.calibration)?;
+        self.write_register(REG_CONFIG, self.config.register(MODE_CONTINUOUS))
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &Ina219Config {
+        &self.config
+    }
+
+    /// Calibrates the monitor for another shunt or largest current.
+    ///
+    /// # Arguments
+    /// * `shunt_ohms` - Resistance of the shunt in ohms.
+    /// * `max_current` - Largest expected current in amperes.
+    pub fn calibrate(&mut self, shunt_ohms: f32, max_current: f32) -> Result<(), PeripheralError> {
+        let (calibration, current_lsb) = calibration_for(shunt_ohms, max_current);
+        self.config.shunt_ohms = shunt_ohms;
+        self.config.max_current = max_current;
+        self.calibration = calibration;
+        self.current_lsb = current_lsb;
+        self.configure()
+    }
+
+    /// Returns the current resolution in amperes.
+    pub fn current_lsb(&self) -> f32 {
+        self.current_lsb
+    }
+
+    /// Reads the bus voltage in volts.
+    pub fn bus_voltage(&mut self) -> Result<f32, PeripheralError> {
+        let value = self.read_register(REG_BUS_VOLTAGE)?;
+        Ok((value >> 3) as f32 * BUS_LSB)
+    }
+
+    /// Reads the voltage across the shunt in volts.
+    pub fn shunt_voltage(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_register(REG_SHUNT_VOLTAGE)? as i16 as f32 * SHUNT_LSB)
+    }
+
+    /// Reads the current in amperes.
+    pub fn current(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_register(REG_CURRENT)? as i16 as f32 * self.current_lsb)
+    }
+
+    /// Reads the power in watts, negative when the current flows backwards.
+    pub fn power(&mut self) -> Result<f32, PeripheralError> {
+        let power = self.read_register(REG_POWER)? as f32 * POWER_LSB_RATIO * self.current_lsb;
+        Ok(if self.current()? < 0.0 { -power } else { power })
+    }
+
+    /// Checks whether the current or power calculation overflowed, i.e. the
+    /// current exceeds the calibrated range.
+    pub fn is_overflowed(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_BUS_VOLTAGE)? & BUS_OVERFLOW != 0)
+    }
+
+    /// Checks whether a new conversion completed since the power was last
+    /// read.
+    pub fn is_conversion_ready(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_BUS_VOLTAGE)? & BUS_CONVERSION_READY != 0)
+    }
+
+    /// Reads all measurements.
+    pub fn read_all(&mut self) -> Result<PowerReading, PeripheralError> {
+        Ok(PowerReading {
+            bus_voltage: self.bus_voltage()?,
+            shunt_voltage: self.shunt_voltage()?,
+            current: self.current()?,
+            power: self.power()?,
+        })
+    }
+
+    /// Stops or restarts the conversions.
+    pub fn set_power_down(&mut self, power_down: bool) -> Result<(), PeripheralError> {
+        let mode = if power_down {
+            MODE_POWER_DOWN
+        } else {
+            MODE_CONTINUOUS
+        };
+        self.write_register(REG_CONFIG, self.config.register(mode))
+    }
+}
+
+impl I2cPeriph for Ina219 {
+    type Returnable = Self;
+
+    /// Initializes the INA219 on the default address for a 0.1 Ω shunt and
+    /// up to 3.2 A.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the monitor.
+    /// * `_delay` - Not used, the monitor needs no delays.
+    ///
+    /// # Returns
+    /// A result containing the `Ina219` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        _delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Ina219::create_on_i2c_with_config(bus, Ina219Config::default())
+    }
+}
+
+impl<BUS, E> VoltageSensor for Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the bus voltage.
+    fn get_voltage(&mut self) -> Result<f32, PeripheralError> {
+        self.bus_voltage()
+    }
+}
+
+impl<BUS, E> CurrentSensor for Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_current(&mut self) -> Result<f32, PeripheralError> {
+        self.current()
+    }
+}
+
+impl<BUS, E> PowerSensor for Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_power(&mut self) -> Result<f32, PeripheralError> {
+        self.power()
+    }
+}
+
+impl<BUS, E> UnifiedData for Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = PowerReading;
+    /// Reads the bus voltage, shunt voltage, current and power.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.read_all()
+    }
+}
+
+impl<BUS, E> Shutdown for Ina219<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Powers the monitor down, see `Ina219::set_power_down`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.set_power_down(true)
+    }
+}
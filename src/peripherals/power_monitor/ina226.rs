@@ -0,0 +1,440 @@
+//! # INA226 Power Monitor Module
+//!
+//! This module provides a driver for the TI INA226 current and power monitor
+//! over I2C. It measures bus voltages up to 36 V and the voltage across the
+//! shunt resistor (±81.92 mV) with 16-bit resolution. The conversion time and
+//! the number of averaged samples set the trade-off between noise and update
+//! rate.
+//!
+//! The ALERT pin (open drain, active low) can signal an over or under
+//! current, bus voltage or power, or completed conversions, see
+//! `Ina226::set_alert`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     power_monitor::ina226::{Alert, Averaging, Ina226, Ina226Config},
+//!     PowerSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let config = Ina226Config {
+//!     shunt_ohms: 0.002,
+//!     max_current: 20.0,
+//!     averaging: Averaging::Samples16,
+//!     ..Default::default()
+//! };
+//! let mut monitor = Ina226::create_on_i2c_with_config(i2c, config).unwrap();
+//! monitor.set_alert(Some(Alert::OverCurrent(15.0))).unwrap();
+//! println!("{} W", monitor.get_power().unwrap());
+//! ```
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{calibration, PowerReading};
+use crate::peripherals::{
+    CurrentSensor,
+    I2cPeriph,
+    PeripheralError,
+    PowerSensor,
+    Shutdown,
+    UnifiedData,
+    VoltageSensor,
+};
+
+/// Default I2C address of the INA226, with A0 and A1 tied to ground.
+pub const DEFAULT_ADDRESS: u8 = 0x40;
+
+const REG_CONFIG: u8 = 0x00;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+const REG_POWER: u8 = 0x03;
+const REG_CURRENT: u8 = 0x04;
+const REG_CALIBRATION: u8 = 0x05;
+const REG_MASK_ENABLE: u8 = 0x06;
+const REG_ALERT_LIMIT: u8 = 0x07;
+const REG_MANUFACTURER_ID: u8 = 0xFE;
+
+/// Manufacturer ID of Texas Instruments.
+const MANUFACTURER_ID: u16 = 0x5449;
+
+const CONFIG_RESET: u16 = 0x8000;
+/// Bits 14 to 12 read as `100`.
+const CONFIG_FIXED: u16 = 0x4000;
+const CONFIG_AVG_SHIFT: u16 = 9;
+const CONFIG_VBUSCT_SHIFT: u16 = 6;
+const CONFIG_VSHCT_SHIFT: u16 = 3;
+/// Shunt and bus voltage, continuous.
+const MODE_CONTINUOUS: u16 = 0x07;
+const MODE_POWER_DOWN: u16 = 0x00;
+
+const MASK_SHUNT_OVER: u16 = 0x8000;
+const MASK_SHUNT_UNDER: u16 = 0x4000;
+const MASK_BUS_OVER: u16 = 0x2000;
+const MASK_BUS_UNDER: u16 = 0x1000;
+const MASK_POWER_OVER: u16 = 0x0800;
+const MASK_CONVERSION_READY: u16 = 0x0400;
+/// The alert limit was exceeded.
+const MASK_ALERT_FLAG: u16 = 0x0010;
+/// A conversion completed.
+const MASK_CONVERSION_FLAG: u16 = 0x0008;
+/// The power calculation overflowed.
+const MASK_OVERFLOW_FLAG: u16 = 0x0004;
+
+/// Shunt voltage LSB in volts.
+const SHUNT_LSB: f32 = 2.5e-6;
+/// Bus voltage LSB in volts.
+const BUS_LSB: f32 = 1.25e-3;
+/// Constant of the calibration formula.
+const CALIBRATION_SCALE: f32 = 0.00512;
+/// Power LSB in current LSBs.
+const POWER_LSB_RATIO: f32 = 25.0;
+/// Largest calibration register value.
+const MAX_CALIBRATION: u16 = 0x7FFF;
+
+/// Number of samples averaged per measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Averaging {
+    Samples1,
+    Samples4,
+    Samples16,
+    Samples64,
+    Samples128,
+    Samples256,
+    Samples512,
+    Samples1024,
+}
+
+/// Conversion time of the bus and shunt voltages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionTime {
+    Us140,
+    Us204,
+    Us332,
+    Us588,
+    Us1100,
+    Us2116,
+    Us4156,
+    Us8244,
+}
+
+/// Condition signalled on the ALERT pin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alert {
+    /// The current exceeds the limit in amperes.
+    OverCurrent(f32),
+    /// The current falls below the limit in amperes.
+    UnderCurrent(f32),
+    /// The bus voltage exceeds the limit in volts.
+    BusOverVoltage(f32),
+    /// The bus voltage falls below the limit in volts.
+    BusUnderVoltage(f32),
+    /// The power exceeds the limit in watts.
+    OverPower(f32),
+    /// A conversion completed.
+    ConversionReady,
+}
+
+/// Configuration of an INA226.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ina226Config {
+    pub address: u8,
+    /// Resistance of the shunt in ohms.
+    pub shunt_ohms: f32,
+    /// Largest expected current in amperes, at most 81.92 mV over the shunt.
+    pub max_current: f32,
+    pub averaging: Averaging,
+    pub conversion_time: ConversionTime,
+}
+
+impl Default for Ina226Config {
+    fn default() -> Self {
+        Ina226Config {
+            address: DEFAULT_ADDRESS,
+            shunt_ohms: 0.1,
+            max_current: 0.8,
+            averaging: Averaging::Samples1,
+            conversion_time: ConversionTime::Us1100,
+        }
+    }
+}
+
+impl Ina226Config {
+    fn register(&self, mode: u16) -> u16 {
+        CONFIG_FIXED
+            | ((self.averaging as u16) << CONFIG_AVG_SHIFT)
+            | ((self.conversion_time as u16) << CONFIG_VBUSCT_SHIFT)
+            | ((self.conversion_time as u16) << CONFIG_VSHCT_SHIFT)
+            | mode
+    }
+}
+
+/// Represents an INA226 power monitor.
+pub struct Ina226<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the monitor is connected to.
+    pub bus: BUS,
+    config: Ina226Config,
+    calibration: u16,
+    current_lsb: f32,
+}
+
+impl<BUS, E> Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Resets, configures and calibrates the INA226.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the monitor is connected to.
+    /// * `config` - Address, shunt, largest current, averaging and conversion
+    ///   time.
+    ///
+    /// # Returns
+    /// A new `Ina226` instance, or `PeripheralError::InitializationFailed` if
+    /// the monitor does not respond or is not an INA226.
+    pub fn create_on_i2c_with_config(
+        bus: BUS,
+        config: Ina226Config,
+    ) -> Result<Self, PeripheralError> {
+        let (calibration, current_lsb) = calibration(
+            CALIBRATION_SCALE,
+            config.shunt_ohms,
+            config.max_current,
+            MAX_CALIBRATION,
+        );
+        let mut monitor = Ina226 {
+            bus,
+            config,
+            calibration,
+            current_lsb,
+        };
+        match monitor.read_register(REG_MANUFACTURER_ID) {
+            Ok(MANUFACTURER_ID) => (),
+            _ => return Err(PeripheralError::InitializationFailed),
+        }
+        monitor
+            .write_register(REG_CONFIG, CONFIG_RESET)
+            .and_then(|_| monitor.configure())
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(monitor)
+    }
+
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), PeripheralError> {
+        let [high, low] = value.to_be_bytes();
+        self.bus
+            .write(self.config.address, &[register, high, low])
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, PeripheralError> {
+        let mut data = [0u8; 2];
+        self.bus
+            .write_read(self.config.address, &[register], &mut data)
+            .map_err(|_| PeripheralError::ReadError)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Writes the configuration and calibration registers.
+    fn configure(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_CALIBRATION, self.calibration)?;
+        self.write_register(REG_CONFIG, self.config.register(MODE_CONTINUOUS))
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &Ina226Config {
+        &self.config
+    }
+
+    /// Calibrates the monitor for another shunt or largest current.
+    ///
+    /// # Arguments
+    /// * `shunt_ohms` - Resistance of the shunt in ohms.
+    /// * `max_current` - Largest expected current in amperes.
+    pub fn calibrate(&mut self, shunt_ohms: f32, max_current: f32) -> Result<(), PeripheralError> {
+        let (calibration, current_lsb) =
+            calibration(CALIBRATION_SCALE, shunt_ohms, max_current, MAX_CALIBRATION);
+        self.config.shunt_ohms = shunt_ohms;
+        self.config.max_current = max_current;
+        self.calibration = calibration;
+        self.current_lsb = current_lsb;
+        self.configure()
+    }
+
+    /// Sets the averaging and conversion time.
+    pub fn set_sampling(
+        &mut self,
+        averaging: Averaging,
+        conversion_time: ConversionTime,
+    ) -> Result<(), PeripheralError> {
+        self.config.averaging = averaging;
+        self.config.conversion_time = conversion_time;
+        self.write_register(REG_CONFIG, self.config.register(MODE_CONTINUOUS))
+    }
+
+    /// Returns the current resolution in amperes.
+    pub fn current_lsb(&self) -> f32 {
+        self.current_lsb
+    }
+
+    /// Reads the bus voltage in volts.
+    pub fn bus_voltage(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_register(REG_BUS_VOLTAGE)? as f32 * BUS_LSB)
+    }
+
+    /// Reads the voltage across the shunt in volts.
+    pub fn shunt_voltage(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_register(REG_SHUNT_VOLTAGE)? as i16 as f32 * SHUNT_LSB)
+    }
+
+    /// Reads the current in amperes.
+    pub fn current(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.read_register(REG_CURRENT)? as i16 as f32 * self.current_lsb)
+    }
+
+    /// Reads the power in watts, negative when the current flows backwards.
+    pub fn power(&mut self) -> Result<f32, PeripheralError> {
+        let power = self.read_register(REG_POWER)? as f32 * POWER_LSB_RATIO * self.current_lsb;
+        Ok(if self.current()? < 0.0 { -power } else { power })
+    }
+
+    /// Reads all measurements.
+    pub fn read_all(&mut self) -> Result<PowerReading, PeripheralError> {
+        Ok(PowerReading {
+            bus_voltage: self.bus_voltage()?,
+            shunt_voltage: self.shunt_voltage()?,
+            current: self.current()?,
+            power: self.power()?,
+        })
+    }
+
+    /// Selects the condition signalled on the ALERT pin, or disables it with
+    /// `None`.
+    pub fn set_alert(&mut self, alert: Option<Alert>) -> Result<(), PeripheralError> {
+        let shunt_limit = |current: f32, shunt_ohms: f32| {
+            ((current * shunt_ohms / SHUNT_LSB) as i32).clamp(i16::MIN as i32, i16::MAX as i32)
+                as i16 as u16
+        };
+        let shunt_ohms = self.config.shunt_ohms;
+        let (mask, limit) = match alert {
+            None => (0, 0),
+            Some(Alert::OverCurrent(current)) => {
+                (MASK_SHUNT_OVER, shunt_limit(current, shunt_ohms))
+            }
+            Some(Alert::UnderCurrent(current)) => {
+                (MASK_SHUNT_UNDER, shunt_limit(current, shunt_ohms))
+            }
+            Some(Alert::BusOverVoltage(voltage)) => (MASK_BUS_OVER, (voltage / BUS_LSB) as u16),
+            Some(Alert::BusUnderVoltage(voltage)) => (MASK_BUS_UNDER, (voltage / BUS_LSB) as u16),
+            Some(Alert::OverPower(power)) => (
+                MASK_POWER_OVER,
+                (power / (POWER_LSB_RATIO * self.current_lsb)) as u16,
+            ),
+            Some(Alert::ConversionReady) => (MASK_CONVERSION_READY, 0),
+        };
+        self.write_register(REG_ALERT_LIMIT, limit)?;
+        self.write_register(REG_MASK_ENABLE, mask)
+    }
+
+    /// Checks whether the alert limit was exceeded. Reading the flags
+    /// releases the ALERT pin.
+    pub fn is_alert_triggered(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_MASK_ENABLE)? & MASK_ALERT_FLAG != 0)
+    }
+
+    /// Checks whether a new conversion completed since the flags were last
+    /// read.
+    pub fn is_conversion_ready(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_MASK_ENABLE)? & MASK_CONVERSION_FLAG != 0)
+    }
+
+    /// Checks whether the power calculation overflowed, i.e. the current
+    /// exceeds the calibrated range.
+    pub fn is_overflowed(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_MASK_ENABLE)? & MASK_OVERFLOW_FLAG != 0)
+    }
+
+    /// Stops or restarts the conversions.
+    pub fn set_power_down(&mut self, power_down: bool) -> Result<(), PeripheralError> {
+        let mode = if power_down {
+            MODE_POWER_DOWN
+        } else {
+            MODE_CONTINUOUS
+        };
+        self.write_register(REG_CONFIG, self.config.register(mode))
+    }
+}
+
+impl I2cPeriph for Ina226 {
+    type Returnable = Self;
+
+    /// Initializes the INA226 on the default address for a 0.1 Ω shunt and
+    /// up to 0.8 A.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the monitor.
+    /// * `_delay` - Not used, the monitor needs no delays.
+    ///
+    /// # Returns
+    /// A result containing the `Ina226` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        _delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Ina226::create_on_i2c_with_config(bus, Ina226Config::default())
+    }
+}
+
+impl<BUS, E> VoltageSensor for Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the bus voltage.
+    fn get_voltage(&mut self) -> Result<f32, PeripheralError> {
+        self.bus_voltage()
+    }
+}
+
+impl<BUS, E> CurrentSensor for Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_current(&mut self) -> Result<f32, PeripheralError> {
+        self.current()
+    }
+}
+
+impl<BUS, E> PowerSensor for Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_power(&mut self) -> Result<f32, PeripheralError> {
+        self.power()
+    }
+}
+
+impl<BUS, E> UnifiedData for Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = PowerReading;
+    /// Reads the bus voltage, shunt voltage, current and power.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.read_all()
+    }
+}
+
+impl<BUS, E> Shutdown for Ina226<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Powers the monitor down, see `Ina226::set_power_down`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.set_power_down(true)
+    }
+}
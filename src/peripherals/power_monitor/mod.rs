@@ -0,0 +1,161 @@
+//! # Power Monitor Module
+//!
+//! This module groups drivers for current and power monitors, which measure
+//! the voltage across a shunt resistor and the bus voltage, e.g. to measure
+//! the power draw of a load or the output of a solar panel. Every monitor
+//! implements `VoltageSensor` (bus voltage), `CurrentSensor` and
+//! `PowerSensor`.
+//!
+//! The monitors are calibrated from the shunt resistance and the largest
+//! expected current, which sets the current resolution. `EnergyAccumulator`
+//! integrates the power over time into watt-hours.
+//!
+//! ## Currently Supported Monitors
+//! - `ina219`: TI INA219, bus voltages up to 26 V, 12-bit.
+//! - `ina226`: TI INA226, bus voltages up to 36 V, 16-bit, more accurate.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::power_monitor::{
+//!     ina226::{Ina226, Ina226Config},
+//!     EnergyAccumulator,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! // R010 shunt, up to 5 A
+//! let config = Ina226Config {
+//!     shunt_ohms: 0.01,
+//!     max_current: 5.0,
+//!     ..Default::default()
+//! };
+//! let mut monitor = Ina226::create_on_i2c_with_config(i2c, config).unwrap();
+//! let mut energy = EnergyAccumulator::new();
+//! loop {
+//!     let now_ms = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     let power = energy.measure(&mut monitor, now_ms).unwrap();
+//!     println!("{} W, {} Wh", power, energy.energy_wh());
+//!     delay.delay_ms(1000u32);
+//! }
+//! ```
+
+use super::{PeripheralError, PowerSensor};
+
+pub mod ina219;
+pub mod ina226;
+
+/// One measurement of a power monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PowerReading {
+    /// Voltage between the bus and ground in volts.
+    pub bus_voltage: f32,
+    /// Voltage across the shunt resistor in volts.
+    pub shunt_voltage: f32,
+    /// Current in amperes.
+    pub current: f32,
+    /// Power in watts.
+    pub power: f32,
+}
+
+/// Computes the calibration register of a monitor.
+///
+/// # Arguments
+/// * `scale` - The constant of the calibration formula of the monitor.
+/// * `shunt_ohms` - The shunt resistance.
+/// * `max_current` - The largest expected current in amperes.
+/// * `max_register` - The largest calibration register value.
+///
+/// # Returns
+/// The register value and the resulting current LSB in amperes.
+pub(crate) fn calibration(
+    scale: f32,
+    shunt_ohms: f32,
+    max_current: f32,
+    max_register: u16,
+) -> (u16, f32) {
+    // The current register is a signed 16-bit value.
+    let current_lsb = max_current / 32768.0;
+    let register = (scale / (current_lsb * shunt_ohms)).clamp(1.0, max_register as f32) as u16;
+    // The register is truncated, the actual LSB follows from it.
+    (register, scale / (register as f32 * shunt_ohms))
+}
+
+/// Integrates power over time into energy.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EnergyAccumulator {
+    energy_wh: f64,
+    elapsed_ms: u64,
+    last: Option<(u64, f32)>,
+}
+
+impl EnergyAccumulator {
+    /// Creates a new `EnergyAccumulator`.
+    pub const fn new() -> Self {
+        EnergyAccumulator {
+            energy_wh: 0.0,
+            elapsed_ms: 0,
+            last: None,
+        }
+    }
+
+    /// Adds a power sample, integrated with the previous one by the
+    /// trapezoidal rule.
+    ///
+    /// # Arguments
+    /// * `power` - The power in watts.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn add_sample(&mut self, power: f32, now_ms: u64) {
+        if let Some((last_ms, last_power)) = self.last {
+            let elapsed_ms = now_ms.saturating_sub(last_ms);
+            let average = (power as f64 + last_power as f64) / 2.0;
+            self.energy_wh += average * elapsed_ms as f64 / 3_600_000.0;
+            self.elapsed_ms += elapsed_ms;
+        }
+        self.last = Some((now_ms, power));
+    }
+
+    /// Measures the power and adds it as a sample.
+    ///
+    /// # Arguments
+    /// * `sensor` - The power sensor.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// The measured power in watts.
+    pub fn measure<S: PowerSensor>(
+        &mut self,
+        sensor: &mut S,
+        now_ms: u64,
+    ) -> Result<f32, PeripheralError> {
+        let power = sensor.get_power()?;
+        self.add_sample(power, now_ms);
+        Ok(power)
+    }
+
+    /// Returns the accumulated energy in watt-hours.
+    pub fn energy_wh(&self) -> f32 {
+        self.energy_wh as f32
+    }
+
+    /// Returns the time covered by the samples in milliseconds.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ms
+    }
+
+    /// Returns the average power in watts, `None` before two samples.
+    pub fn average_power(&self) -> Option<f32> {
+        if self.elapsed_ms == 0 {
+            return None;
+        }
+        Some((self.energy_wh * 3_600_000.0 / self.elapsed_ms as f64) as f32)
+    }
+
+    /// Clears the accumulated energy, the next sample starts anew.
+    pub fn reset(&mut self) {
+        *self = EnergyAccumulator::new();
+    }
+}
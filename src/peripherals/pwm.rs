@@ -0,0 +1,664 @@
+//! # PWM Module
+//!
+//! This module wraps the LEDC peripheral of `esp-hal` to drive PWM outputs:
+//! `DimmableLed` with gamma-corrected brightness and timed fades, and `Servo`
+//! with angle-to-pulse mapping.
+//!
+//! LEDC has four timers, each generating a frequency with a duty resolution,
+//! and six or eight channels, each driving one pin from one of the timers.
+//! `Pwm` keeps track of the timers and channels in use, so two devices cannot
+//! silently share a channel or reconfigure the timer of another device.
+//!
+//! Fades are driven by timestamps you pass in, so `DimmableLed::update` should
+//! be called periodically while a fade is running.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::pwm::{DimmableLed, Servo};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! // Timer 0 at 5 kHz for LEDs, timer 1 at 50 Hz for servos
+//! let (pwm, led_timer, servo_timer) = esp_ward::init_pwm_default!(peripherals, clocks);
+//!
+//! let mut led = DimmableLed::create_on_channel(pwm.channel(0, &led_timer, pins.gpio8).unwrap());
+//! let mut servo = Servo::create_on_channel(pwm.channel(1, &servo_timer, pins.gpio9).unwrap());
+//!
+//! servo.set_angle(90.0).unwrap();
+//! let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//! led.fade_to(1.0, 2000, now).unwrap();
+//!
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     led.update(now).unwrap();
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use core::cell::Cell;
+
+#[cfg(feature = "esp-hal")]
+use esp_hal::{
+    gpio::OutputPin,
+    ledc::{
+        channel::{self, ChannelHW, ChannelIFace},
+        timer::{self, config::Duty, TimerIFace},
+        LowSpeed,
+        LEDC,
+    },
+    peripheral::Peripheral,
+};
+#[cfg(feature = "esp-hal")]
+use fugit::RateExtU32;
+
+use super::{PeripheralError, Shutdown, Writable};
+
+/// Amount of LEDC channels of the chip.
+#[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+pub const CHANNELS: u8 = 8;
+/// Amount of LEDC channels of the chip.
+#[cfg(not(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3")))]
+pub const CHANNELS: u8 = 6;
+
+/// Amount of LEDC timers of the chip.
+pub const TIMERS: u8 = 4;
+
+/// A PWM output with a fixed frequency and duty resolution.
+pub trait PwmOutput {
+    /// Returns the duty value for a 100% duty cycle.
+    fn max_duty(&self) -> u32;
+    /// Returns the frequency of the output in hertz.
+    fn frequency_hz(&self) -> u32;
+    /// Sets the duty value, from 0 to `max_duty`.
+    fn set_duty(&mut self, duty: u32) -> Result<(), PeripheralError>;
+}
+
+/// Hardware independent bookkeeping of the timers and channels in use.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PwmAllocator {
+    timers: u8,
+    channels: u8,
+}
+
+impl PwmAllocator {
+    /// Creates a new allocator with everything free.
+    pub const fn new() -> Self {
+        PwmAllocator {
+            timers: 0,
+            channels: 0,
+        }
+    }
+
+    /// Marks a timer as used.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::InitializationFailed)` if the timer does not
+    /// exist or is already used.
+    pub fn claim_timer(&mut self, number: u8) -> Result<(), PeripheralError> {
+        Self::claim(&mut self.timers, number, TIMERS)
+    }
+
+    /// Marks a channel as used.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::InitializationFailed)` if the channel does not
+    /// exist or is already used.
+    pub fn claim_channel(&mut self, number: u8) -> Result<(), PeripheralError> {
+        Self::claim(&mut self.channels, number, CHANNELS)
+    }
+
+    /// Returns `true` if the timer is used.
+    pub fn is_timer_used(&self, number: u8) -> bool {
+        number < TIMERS && self.timers & (1 << number) != 0
+    }
+
+    /// Returns `true` if the channel is used.
+    pub fn is_channel_used(&self, number: u8) -> bool {
+        number < CHANNELS && self.channels & (1 << number) != 0
+    }
+
+    fn claim(used: &mut u8, number: u8, count: u8) -> Result<(), PeripheralError> {
+        if number >= count || *used & (1 << number) != 0 {
+            return Err(PeripheralError::InitializationFailed);
+        }
+        *used |= 1 << number;
+        Ok(())
+    }
+}
+
+/// A configured LEDC timer.
+#[cfg(feature = "esp-hal")]
+pub struct PwmTimer<'d> {
+    /// The `esp-hal` timer.
+    pub inner: timer::Timer<'d, LowSpeed>,
+    number: u8,
+    frequency_hz: Cell<u32>,
+    duty: Duty,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<'d> PwmTimer<'d> {
+    /// Returns the number of the timer.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    /// Returns the frequency of the timer in hertz.
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz.get()
+    }
+
+    /// Returns the duty resolution of the timer in bits.
+    pub fn resolution_bits(&self) -> u8 {
+        self.duty as u8
+    }
+}
+
+/// A configured LEDC channel driving one pin.
+#[cfg(feature = "esp-hal")]
+pub struct PwmChannel<'a, O: OutputPin> {
+    /// The `esp-hal` channel.
+    pub inner: channel::Channel<'a, LowSpeed, O>,
+    max_duty: u32,
+    frequency_hz: &'a Cell<u32>,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<'a, O: OutputPin> PwmOutput for PwmChannel<'a, O> {
+    fn max_duty(&self) -> u32 {
+        self.max_duty
+    }
+
+    fn frequency_hz(&self) -> u32 {
+        self.frequency_hz.get()
+    }
+
+    fn set_duty(&mut self, duty: u32) -> Result<(), PeripheralError> {
+        self.inner.set_duty_hw(duty.min(self.max_duty));
+        Ok(())
+    }
+}
+
+/// The LEDC peripheral together with the bookkeeping of its timers and
+/// channels.
+#[cfg(feature = "esp-hal")]
+pub struct Pwm<'d> {
+    /// The `esp-hal` LEDC driver.
+    pub ledc: LEDC<'d>,
+    allocator: Cell<PwmAllocator>,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<'d> Pwm<'d> {
+    /// Creates a new `Pwm`, selecting the APB clock as the clock of the
+    /// low-speed timers.
+    ///
+    /// # Arguments
+    /// * `ledc` - The `esp-hal` LEDC driver.
+    ///
+    /// # Returns
+    /// A new `Pwm` instance.
+    pub fn create_on_ledc(#[allow(unused_mut)] mut ledc: LEDC<'d>) -> Self {
+        // The ESP32 low-speed timers are always clocked from APB.
+        #[cfg(not(feature = "esp32"))]
+        ledc.set_global_slow_clock(esp_hal::ledc::LSGlobalClkSource::APBClk);
+        Pwm {
+            ledc,
+            allocator: Cell::new(PwmAllocator::new()),
+        }
+    }
+
+    /// Configures a timer.
+    ///
+    /// # Arguments
+    /// * `number` - Number of the timer, from 0 to 3.
+    /// * `frequency_hz` - Frequency of the timer in hertz.
+    /// * `duty` - Duty resolution. Higher frequencies allow lower resolutions
+    ///   only, e.g. 5 kHz allows up to 13 bits.
+    ///
+    /// # Returns
+    /// The configured timer, or `Err(PeripheralError::InitializationFailed)`
+    /// if the timer is already used or cannot generate the frequency.
+    pub fn timer(
+        &self,
+        number: u8,
+        frequency_hz: u32,
+        duty: Duty,
+    ) -> Result<PwmTimer<'d>, PeripheralError> {
+        // The timer is only marked as used once it is configured, so a failed
+        // configuration can be retried.
+        let mut allocator = self.allocator.get();
+        allocator.claim_timer(number)?;
+        let inner = self.configure_timer(number, frequency_hz, duty)?;
+        self.allocator.set(allocator);
+
+        Ok(PwmTimer {
+            inner,
+            number,
+            frequency_hz: Cell::new(frequency_hz),
+            duty,
+        })
+    }
+
+    /// Changes the frequency of a timer, keeping its duty resolution. All
+    /// channels driven by the timer change their frequency.
+    ///
+    /// # Arguments
+    /// * `timer` - The timer to change.
+    /// * `frequency_hz` - New frequency of the timer in hertz.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::WriteError)` if the timer cannot generate the
+    /// frequency with its resolution.
+    pub fn set_frequency(
+        &self,
+        timer: &PwmTimer<'d>,
+        frequency_hz: u32,
+    ) -> Result<(), PeripheralError> {
+        self.configure_timer(timer.number, frequency_hz, timer.duty)
+            .map_err(|_| PeripheralError::WriteError)?;
+        timer.frequency_hz.set(frequency_hz);
+        Ok(())
+    }
+
+    fn configure_timer(
+        &self,
+        number: u8,
+        frequency_hz: u32,
+        duty: Duty,
+    ) -> Result<timer::Timer<'d, LowSpeed>, PeripheralError> {
+        let timer_number = match number {
+            0 => timer::Number::Timer0,
+            1 => timer::Number::Timer1,
+            2 => timer::Number::Timer2,
+            3 => timer::Number::Timer3,
+            _ => return Err(PeripheralError::InitializationFailed),
+        };
+
+        let mut inner = self.ledc.get_timer::<LowSpeed>(timer_number);
+        inner
+            .configure(timer::config::Config {
+                duty,
+                clock_source: timer::LSClockSource::APBClk,
+                frequency: frequency_hz.Hz(),
+            })
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(inner)
+    }
+
+    /// Configures a channel driving a pin from a timer. The output starts with
+    /// a 0% duty cycle.
+    ///
+    /// # Arguments
+    /// * `number` - Number of the channel, from 0 to `CHANNELS - 1`.
+    /// * `timer` - The timer the channel is driven by, several channels may
+    ///   share one timer.
+    /// * `pin` - The output pin.
+    ///
+    /// # Returns
+    /// The configured channel, or `Err(PeripheralError::InitializationFailed)`
+    /// if the channel is already used.
+    pub fn channel<'a, O: OutputPin>(
+        &self,
+        number: u8,
+        timer: &'a PwmTimer<'d>,
+        pin: impl Peripheral<P = O> + 'd,
+    ) -> Result<PwmChannel<'a, O>, PeripheralError>
+    where
+        'd: 'a,
+    {
+        let channel_number = match number {
+            0 => channel::Number::Channel0,
+            1 => channel::Number::Channel1,
+            2 => channel::Number::Channel2,
+            3 => channel::Number::Channel3,
+            4 => channel::Number::Channel4,
+            5 => channel::Number::Channel5,
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            6 => channel::Number::Channel6,
+            #[cfg(any(feature = "esp32", feature = "esp32s2", feature = "esp32s3"))]
+            7 => channel::Number::Channel7,
+            _ => return Err(PeripheralError::InitializationFailed),
+        };
+        let mut allocator = self.allocator.get();
+        allocator.claim_channel(number)?;
+
+        let mut inner = self.ledc.get_channel(channel_number, pin);
+        inner
+            .configure(channel::config::Config {
+                timer: &timer.inner as &dyn TimerIFace<LowSpeed>,
+                duty_pct: 0,
+                pin_config: channel::config::PinConfig::PushPull,
+            })
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        self.allocator.set(allocator);
+
+        Ok(PwmChannel {
+            inner,
+            max_duty: (1u32 << timer.resolution_bits()) - 1,
+            frequency_hz: &timer.frequency_hz,
+        })
+    }
+
+    /// Returns the bookkeeping of the timers and channels in use.
+    pub fn allocator(&self) -> PwmAllocator {
+        self.allocator.get()
+    }
+}
+
+/// Converts a perceived brightness to a duty value.
+///
+/// # Arguments
+/// * `brightness` - Brightness from 0.0 to 1.0.
+/// * `gamma` - Gamma of the correction, 1.0 disables it.
+/// * `max_duty` - Duty value for a 100% duty cycle.
+pub fn gamma_correct(brightness: f32, gamma: f32, max_duty: u32) -> u32 {
+    let brightness = brightness.clamp(0.0, 1.0);
+    libm::roundf(libm::powf(brightness, gamma) * max_duty as f32) as u32
+}
+
+/// A linear transition between two values over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fade {
+    pub from: f32,
+    pub to: f32,
+    pub start_ms: u64,
+    pub duration_ms: u32,
+}
+
+impl Fade {
+    /// Returns the value of the transition at the given time.
+    pub fn value(&self, now_ms: u64) -> f32 {
+        let elapsed = now_ms.saturating_sub(self.start_ms);
+        if self.duration_ms == 0 || elapsed >= self.duration_ms as u64 {
+            return self.to;
+        }
+        let progress = elapsed as f32 / self.duration_ms as f32;
+        self.from + (self.to - self.from) * progress
+    }
+
+    /// Returns `true` once the transition reached its end.
+    pub fn is_finished(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.start_ms) >= self.duration_ms as u64
+    }
+}
+
+/// A LED dimmed by PWM. Brightness values are perceived brightness from 0.0 to
+/// 1.0, the duty cycle is gamma corrected.
+pub struct DimmableLed<P: PwmOutput> {
+    /// The PWM output driving the LED.
+    pub output: P,
+    /// Gamma of the correction, 2.2 by default. 1.0 makes the duty cycle
+    /// linear to the brightness.
+    pub gamma: f32,
+    brightness: f32,
+    fade: Option<Fade>,
+}
+
+impl<P: PwmOutput> DimmableLed<P> {
+    /// Creates a new `DimmableLed`, switched off.
+    ///
+    /// # Arguments
+    /// * `output` - The PWM output driving the LED.
+    ///
+    /// # Returns
+    /// A new `DimmableLed` instance.
+    pub fn create_on_channel(output: P) -> Self {
+        DimmableLed {
+            output,
+            gamma: 2.2,
+            brightness: 0.0,
+            fade: None,
+        }
+    }
+
+    /// Sets the brightness, stopping a running fade.
+    ///
+    /// # Arguments
+    /// * `brightness` - Brightness from 0.0 to 1.0.
+    pub fn set_brightness(&mut self, brightness: f32) -> Result<(), PeripheralError> {
+        self.fade = None;
+        self.apply(brightness)
+    }
+
+    /// Returns the current brightness.
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Starts a fade from the current brightness.
+    ///
+    /// # Arguments
+    /// * `brightness` - Target brightness from 0.0 to 1.0.
+    /// * `duration_ms` - Duration of the fade in milliseconds.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn fade_to(
+        &mut self,
+        brightness: f32,
+        duration_ms: u32,
+        now_ms: u64,
+    ) -> Result<(), PeripheralError> {
+        self.fade = Some(Fade {
+            from: self.brightness,
+            to: brightness.clamp(0.0, 1.0),
+            start_ms: now_ms,
+            duration_ms,
+        });
+        self.update(now_ms).map(|_| ())
+    }
+
+    /// Advances a running fade. Should be called periodically while
+    /// `is_fading` returns `true`.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(true)` while the fade is running.
+    pub fn update(&mut self, now_ms: u64) -> Result<bool, PeripheralError> {
+        let Some(fade) = self.fade else {
+            return Ok(false);
+        };
+        self.apply(fade.value(now_ms))?;
+        if fade.is_finished(now_ms) {
+            self.fade = None;
+        }
+        Ok(self.fade.is_some())
+    }
+
+    /// Returns `true` while a fade is running.
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    fn apply(&mut self, brightness: f32) -> Result<(), PeripheralError> {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        let duty = gamma_correct(self.brightness, self.gamma, self.output.max_duty());
+        self.output.set_duty(duty)
+    }
+}
+
+impl<P: PwmOutput> Writable for DimmableLed<P> {
+    type Input = f32;
+    /// Sets the brightness from 0.0 to 1.0.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        self.set_brightness(data)
+    }
+}
+
+impl<P: PwmOutput> Shutdown for DimmableLed<P> {
+    /// Switches the LED off.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.set_brightness(0.0)
+    }
+}
+
+/// Pulse range of a servo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoConfig {
+    /// Pulse width in microseconds at 0 degrees.
+    pub min_pulse_us: u32,
+    /// Pulse width in microseconds at `max_angle`.
+    pub max_pulse_us: u32,
+    /// Angle in degrees reached at `max_pulse_us`.
+    pub max_angle: f32,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        ServoConfig {
+            min_pulse_us: 500,
+            max_pulse_us: 2500,
+            max_angle: 180.0,
+        }
+    }
+}
+
+impl ServoConfig {
+    /// Converts an angle to a pulse width in microseconds. The angle is
+    /// clamped to the range of the servo.
+    pub fn angle_to_pulse_us(&self, angle: f32) -> u32 {
+        let angle = angle.clamp(0.0, self.max_angle);
+        let span = self.max_pulse_us as f32 - self.min_pulse_us as f32;
+        libm::roundf(self.min_pulse_us as f32 + span * angle / self.max_angle) as u32
+    }
+}
+
+/// Converts a pulse width to a duty value.
+///
+/// # Arguments
+/// * `pulse_us` - Pulse width in microseconds.
+/// * `frequency_hz` - Frequency of the PWM output in hertz.
+/// * `max_duty` - Duty value for a 100% duty cycle.
+pub fn pulse_to_duty(pulse_us: u32, frequency_hz: u32, max_duty: u32) -> u32 {
+    let duty = pulse_us as u64 * frequency_hz as u64 * max_duty as u64 / 1_000_000;
+    duty.min(max_duty as u64) as u32
+}
+
+/// A hobby servo driven by PWM. The output should run at 50 Hz with at least
+/// 12 bits of resolution for a smooth movement.
+pub struct Servo<P: PwmOutput> {
+    /// The PWM output driving the servo.
+    pub output: P,
+    /// Pulse range of the servo.
+    pub config: ServoConfig,
+    angle: Option<f32>,
+}
+
+impl<P: PwmOutput> Servo<P> {
+    /// Creates a new `Servo` with a 500-2500 us pulse range over 180 degrees.
+    /// No pulses are sent until the first angle is set.
+    ///
+    /// # Arguments
+    /// * `output` - The PWM output driving the servo.
+    ///
+    /// # Returns
+    /// A new `Servo` instance.
+    pub fn create_on_channel(output: P) -> Self {
+        Self::create_on_channel_with_config(output, ServoConfig::default())
+    }
+
+    /// Creates a new `Servo` with the given pulse range.
+    ///
+    /// # Arguments
+    /// * `output` - The PWM output driving the servo.
+    /// * `config` - Pulse range of the servo.
+    ///
+    /// # Returns
+    /// A new `Servo` instance.
+    pub fn create_on_channel_with_config(output: P, config: ServoConfig) -> Self {
+        Servo {
+            output,
+            config,
+            angle: None,
+        }
+    }
+
+    /// Moves the servo to an angle in degrees, clamped to the range of the
+    /// servo.
+    pub fn set_angle(&mut self, angle: f32) -> Result<(), PeripheralError> {
+        let angle = angle.clamp(0.0, self.config.max_angle);
+        self.set_pulse_us(self.config.angle_to_pulse_us(angle))?;
+        self.angle = Some(angle);
+        Ok(())
+    }
+
+    /// Sends pulses of the given width in microseconds.
+    pub fn set_pulse_us(&mut self, pulse_us: u32) -> Result<(), PeripheralError> {
+        let duty = pulse_to_duty(pulse_us, self.output.frequency_hz(), self.output.max_duty());
+        self.angle = None;
+        self.output.set_duty(duty)
+    }
+
+    /// Returns the last angle set, `None` if no angle was set or the servo is
+    /// detached.
+    pub fn angle(&self) -> Option<f32> {
+        self.angle
+    }
+}
+
+impl<P: PwmOutput> Writable for Servo<P> {
+    type Input = f32;
+    /// Moves the servo to an angle in degrees.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        self.set_angle(data)
+    }
+}
+
+impl<P: PwmOutput> Shutdown for Servo<P> {
+    /// Stops sending pulses, so the servo no longer holds its position.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.angle = None;
+        self.output.set_duty(0)
+    }
+}
+
+/// Initializes the LEDC peripheral with two default timers: timer 0 at 5 kHz
+/// with 13-bit resolution for LEDs and timer 1 at 50 Hz with 14-bit resolution
+/// for servos.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$clocks` - The system clocks initialized beforehand.
+///
+/// # Returns
+/// A tuple of the `Pwm` instance, the LED timer and the servo timer.
+#[macro_export]
+macro_rules! init_pwm_default {
+    ($peripherals:ident, $clocks:ident) => {{
+        let pwm = esp_ward::init_pwm_custom!($peripherals, $clocks);
+        let led_timer = pwm
+            .timer(0, 5_000, esp_hal::ledc::timer::config::Duty::Duty13Bit)
+            .unwrap();
+        let servo_timer = pwm
+            .timer(1, 50, esp_hal::ledc::timer::config::Duty::Duty14Bit)
+            .unwrap();
+        (pwm, led_timer, servo_timer)
+    }};
+}
+
+pub use init_pwm_default;
+
+/// Initializes the LEDC peripheral without configuring any timer, use
+/// `Pwm::timer` to configure them.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$clocks` - The system clocks initialized beforehand.
+///
+/// # Returns
+/// A new `Pwm` instance.
+#[macro_export]
+macro_rules! init_pwm_custom {
+    ($peripherals:ident, $clocks:ident) => {
+        esp_ward::peripherals::pwm::Pwm::create_on_ledc(esp_hal::ledc::LEDC::new(
+            $peripherals.LEDC,
+            &$clocks,
+        ))
+    };
+}
+
+pub use init_pwm_custom;
@@ -0,0 +1,446 @@
+//! # DS3231 Real-Time Clock Module
+//!
+//! This module provides a driver for the Maxim DS3231 real-time clock over
+//! I2C. Its crystal is temperature compensated, so it drifts by about a
+//! minute per year, and the temperature sensor used for the compensation can
+//! be read through `TemperatureSensor`.
+//!
+//! The INT/SQW pin either signals the two alarms (active low, open drain) or
+//! outputs a square wave, see `Ds3231::set_square_wave`. The 32K pin outputs
+//! a 32.768 kHz clock, see `Ds3231::set_32khz_output`.
+//!
+//! The clock uses the address 0x68, like the MPU6050.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     rtc::{ds3231::Ds3231, Alarm},
+//!     DateTime,
+//!     I2cPeriph,
+//!     TimeSource,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let mut rtc = Ds3231::create_on_i2c(i2c, delay).unwrap();
+//! if !rtc.is_time_valid().unwrap() {
+//!     // The battery was removed, set the time again, e.g. from a GPS receiver
+//!     rtc.set_datetime(&DateTime::new(2024, 6, 1, 12, 0, 0))
+//!         .unwrap();
+//! }
+//! println!("{:?}", rtc.get_datetime().unwrap());
+//!
+//! rtc.set_alarm1(&Alarm::daily(7, 30)).unwrap();
+//! rtc.enable_alarm_interrupts(true, false).unwrap();
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+#[cfg(feature = "esp-hal")]
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{bcd_decode, bcd_encode, AlarmDay};
+#[cfg(feature = "esp-hal")]
+use super::{storable, Alarm};
+#[cfg(feature = "esp-hal")]
+use crate::peripherals::{
+    DateTime,
+    I2cPeriph,
+    PeripheralError,
+    TemperatureSensor,
+    TimeSource,
+    UnifiedData,
+};
+
+/// I2C address of the DS3231.
+pub const ADDRESS: u8 = 0x68;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_ALARM1: u8 = 0x07;
+const REG_ALARM2: u8 = 0x0B;
+const REG_CONTROL: u8 = 0x0E;
+const REG_STATUS: u8 = 0x0F;
+const REG_TEMPERATURE: u8 = 0x11;
+
+/// Hour register: 12-hour mode.
+const HOUR_12H: u8 = 0x40;
+/// Hour register in 12-hour mode: PM.
+const HOUR_PM: u8 = 0x20;
+/// Month register: the year counter rolled over from 99.
+const MONTH_CENTURY: u8 = 0x80;
+/// Alarm registers: the field matches any value.
+const ALARM_ANY: u8 = 0x80;
+/// Alarm day register: day of the week instead of the month.
+const ALARM_WEEKDAY: u8 = 0x40;
+
+const CONTROL_INTCN: u8 = 0x04;
+const CONTROL_A2IE: u8 = 0x02;
+const CONTROL_A1IE: u8 = 0x01;
+const CONTROL_RATE_SHIFT: u8 = 3;
+const CONTROL_RATE_MASK: u8 = 0x18;
+
+const STATUS_OSF: u8 = 0x80;
+const STATUS_EN32KHZ: u8 = 0x08;
+const STATUS_A2F: u8 = 0x02;
+const STATUS_A1F: u8 = 0x01;
+
+/// Frequency of the square wave on the INT/SQW pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquareWave {
+    Hz1,
+    Hz1024,
+    Hz4096,
+    Hz8192,
+}
+
+impl SquareWave {
+    fn bits(self) -> u8 {
+        (self as u8) << CONTROL_RATE_SHIFT
+    }
+}
+
+/// Encodes an alarm field, or marks it as matching any value.
+fn alarm_field(value: Option<u8>) -> u8 {
+    value.map_or(ALARM_ANY, bcd_encode)
+}
+
+/// Encodes the day field of an alarm, which must be in range; the DS3231
+/// counts weekdays from 1.
+fn alarm_day(day: Option<AlarmDay>) -> u8 {
+    match day {
+        None => ALARM_ANY,
+        Some(AlarmDay::Date(date)) => bcd_encode(date),
+        Some(AlarmDay::Weekday(weekday)) => ALARM_WEEKDAY | (weekday + 1),
+    }
+}
+
+/// Checks the fields an alarm matches, from the lowest one up. The DS3231 can
+/// only match the lowest fields and ignore the ones above, e.g. it cannot
+/// match the hour with any minute.
+fn alarm_fields_valid(fields: &[bool]) -> bool {
+    fields.windows(2).all(|pair| pair[0] || !pair[1])
+}
+
+/// Decodes the hour register, in 12 or 24-hour mode.
+fn decode_hour(value: u8) -> u8 {
+    if value & HOUR_12H == 0 {
+        return bcd_decode(value & 0x3F);
+    }
+    let hour = bcd_decode(value & 0x1F) % 12;
+    if value & HOUR_PM != 0 {
+        hour + 12
+    } else {
+        hour
+    }
+}
+
+/// Represents a DS3231 real-time clock.
+#[cfg(feature = "esp-hal")]
+pub struct Ds3231<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the clock is connected to.
+    pub bus: BUS,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<BUS, E> Ds3231<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Creates a new `Ds3231` and checks that it responds.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the clock is connected to.
+    ///
+    /// # Returns
+    /// A new `Ds3231` instance, or `PeripheralError::InitializationFailed` if
+    /// the clock does not respond.
+    pub fn create_on_bus(bus: BUS) -> Result<Self, PeripheralError> {
+        let mut rtc = Ds3231 { bus };
+        rtc.read_register(REG_STATUS)
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(rtc)
+    }
+
+    fn write_registers(&mut self, data: &[u8]) -> Result<(), PeripheralError> {
+        self.bus
+            .write(ADDRESS, data)
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), PeripheralError> {
+        self.bus
+            .write_read(ADDRESS, &[register], buffer)
+            .map_err(|_| PeripheralError::ReadError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, PeripheralError> {
+        let mut value = [0u8];
+        self.read_registers(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn update_register(&mut self, register: u8, clear: u8, set: u8) -> Result<(), PeripheralError> {
+        let value = self.read_register(register)?;
+        self.write_registers(&[register, (value & !clear) | set])
+    }
+
+    /// Checks that the oscillator has not stopped since the time was last
+    /// set, e.g. because the battery was removed.
+    pub fn is_time_valid(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_STATUS)? & STATUS_OSF == 0)
+    }
+
+    /// Reads the date and time.
+    pub fn get_datetime(&mut self) -> Result<DateTime, PeripheralError> {
+        let mut data = [0u8; 7];
+        self.read_registers(REG_SECONDS, &mut data)?;
+        let century = if data[5] & MONTH_CENTURY != 0 { 100 } else { 0 };
+        Ok(DateTime {
+            year: 2000 + century + bcd_decode(data[6]) as u16,
+            month: bcd_decode(data[5] & 0x1F),
+            day: bcd_decode(data[4] & 0x3F),
+            hour: decode_hour(data[2]),
+            minute: bcd_decode(data[1] & 0x7F),
+            second: bcd_decode(data[0] & 0x7F),
+        })
+    }
+
+    /// Sets the date and time, in 24-hour mode, and marks the time as valid.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::WriteError` if the date is invalid or
+    /// outside of the years 2000 to 2199.
+    pub fn set_datetime(&mut self, datetime: &DateTime) -> Result<(), PeripheralError> {
+        if !storable(datetime, 2199) {
+            return Err(PeripheralError::WriteError);
+        }
+        let century = if datetime.year >= 2100 {
+            MONTH_CENTURY
+        } else {
+            0
+        };
+        self.write_registers(&[
+            REG_SECONDS,
+            bcd_encode(datetime.second),
+            bcd_encode(datetime.minute),
+            bcd_encode(datetime.hour),
+            datetime.weekday() + 1,
+            bcd_encode(datetime.day),
+            century | bcd_encode(datetime.month),
+            bcd_encode((datetime.year % 100) as u8),
+        ])?;
+        self.update_register(REG_STATUS, STATUS_OSF, 0)
+    }
+
+    /// Sets alarm 1, which has a one second resolution. The alarm flag is
+    /// cleared.
+    ///
+    /// The alarm fires every second with no field set, or when the set
+    /// fields match: the second, the minute and second, the hour, minute and
+    /// second, or all of them.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::WriteError` for any other combination
+    /// of fields or a field out of range, see `Alarm::is_valid`.
+    pub fn set_alarm1(&mut self, alarm: &Alarm) -> Result<(), PeripheralError> {
+        if !alarm.is_valid()
+            || !alarm_fields_valid(&[
+                alarm.second.is_some(),
+                alarm.minute.is_some(),
+                alarm.hour.is_some(),
+                alarm.day.is_some(),
+            ])
+        {
+            return Err(PeripheralError::WriteError);
+        }
+        self.write_registers(&[
+            REG_ALARM1,
+            alarm_field(alarm.second),
+            alarm_field(alarm.minute),
+            alarm_field(alarm.hour),
+            alarm_day(alarm.day),
+        ])?;
+        self.update_register(REG_STATUS, STATUS_A1F, 0)
+    }
+
+    /// Sets alarm 2, which fires at the start of a minute, so the second of
+    /// the alarm is ignored. The alarm flag is cleared.
+    ///
+    /// The alarm fires every minute with no field set, or when the set
+    /// fields match: the minute, the hour and minute, or all of them.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::WriteError` for any other combination
+    /// of fields or a field out of range, see `Alarm::is_valid`.
+    pub fn set_alarm2(&mut self, alarm: &Alarm) -> Result<(), PeripheralError> {
+        if !alarm.is_valid()
+            || !alarm_fields_valid(&[
+                alarm.minute.is_some(),
+                alarm.hour.is_some(),
+                alarm.day.is_some(),
+            ])
+        {
+            return Err(PeripheralError::WriteError);
+        }
+        self.write_registers(&[
+            REG_ALARM2,
+            alarm_field(alarm.minute),
+            alarm_field(alarm.hour),
+            alarm_day(alarm.day),
+        ])?;
+        self.update_register(REG_STATUS, STATUS_A2F, 0)
+    }
+
+    /// Enables the alarm interrupts on the INT/SQW pin, which stops the
+    /// square wave. The pin stays low until the alarm flags are cleared.
+    pub fn enable_alarm_interrupts(
+        &mut self,
+        alarm1: bool,
+        alarm2: bool,
+    ) -> Result<(), PeripheralError> {
+        let mut enable = CONTROL_INTCN;
+        if alarm1 {
+            enable |= CONTROL_A1IE;
+        }
+        if alarm2 {
+            enable |= CONTROL_A2IE;
+        }
+        self.update_register(REG_CONTROL, CONTROL_A1IE | CONTROL_A2IE, enable)
+    }
+
+    /// Checks which alarms fired, even when their interrupts are disabled.
+    ///
+    /// # Returns
+    /// The flags of alarm 1 and alarm 2.
+    pub fn alarms_fired(&mut self) -> Result<(bool, bool), PeripheralError> {
+        let status = self.read_register(REG_STATUS)?;
+        Ok((status & STATUS_A1F != 0, status & STATUS_A2F != 0))
+    }
+
+    /// Clears both alarm flags, which releases the INT/SQW pin.
+    pub fn clear_alarms(&mut self) -> Result<(), PeripheralError> {
+        self.update_register(REG_STATUS, STATUS_A1F | STATUS_A2F, 0)
+    }
+
+    /// Outputs a square wave on the INT/SQW pin, or with `None` uses the pin
+    /// for the alarm interrupts.
+    pub fn set_square_wave(
+        &mut self,
+        frequency: Option<SquareWave>,
+    ) -> Result<(), PeripheralError> {
+        match frequency {
+            Some(frequency) => self.update_register(
+                REG_CONTROL,
+                CONTROL_INTCN | CONTROL_RATE_MASK,
+                frequency.bits(),
+            ),
+            None => self.update_register(REG_CONTROL, 0, CONTROL_INTCN),
+        }
+    }
+
+    /// Enables or disables the 32.768 kHz output on the 32K pin.
+    pub fn set_32khz_output(&mut self, enabled: bool) -> Result<(), PeripheralError> {
+        if enabled {
+            self.update_register(REG_STATUS, 0, STATUS_EN32KHZ)
+        } else {
+            self.update_register(REG_STATUS, STATUS_EN32KHZ, 0)
+        }
+    }
+
+    /// Reads the temperature, updated every 64 seconds, in 0.25 °C steps.
+    pub fn read_temperature(&mut self) -> Result<f32, PeripheralError> {
+        let mut data = [0u8; 2];
+        self.read_registers(REG_TEMPERATURE, &mut data)?;
+        Ok(data[0] as i8 as f32 + (data[1] >> 6) as f32 * 0.25)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl I2cPeriph for Ds3231 {
+    type Returnable = Self;
+
+    /// Creates a new `Ds3231` on the I2C bus.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the clock.
+    /// * `_delay` - Not used, the clock needs no delays.
+    ///
+    /// # Returns
+    /// A result containing the `Ds3231` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        _delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Ds3231::create_on_bus(bus)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<BUS, E> TimeSource for Ds3231<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_datetime(&mut self) -> Result<DateTime, PeripheralError> {
+        Ds3231::get_datetime(self)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<BUS, E> TemperatureSensor for Ds3231<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Reads the temperature of the clock chip, see
+    /// `Ds3231::read_temperature`.
+    fn get_temperature(&mut self) -> Result<f32, PeripheralError> {
+        self.read_temperature()
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<BUS, E> UnifiedData for Ds3231<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = DateTime;
+    /// Reads the date and time.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_datetime()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alarm_fields_from_the_lowest_up() {
+        // Every second, then the datasheet combinations of alarm 1.
+        assert!(alarm_fields_valid(&[false, false, false, false]));
+        assert!(alarm_fields_valid(&[true, false, false, false]));
+        assert!(alarm_fields_valid(&[true, true, false, false]));
+        assert!(alarm_fields_valid(&[true, true, true, false]));
+        assert!(alarm_fields_valid(&[true, true, true, true]));
+
+        // The hour with any second, any minute.
+        assert!(!alarm_fields_valid(&[false, false, true, false]));
+        assert!(!alarm_fields_valid(&[true, false, true, false]));
+        assert!(!alarm_fields_valid(&[false, true, false, false]));
+        assert!(!alarm_fields_valid(&[true, true, false, true]));
+        assert!(!alarm_fields_valid(&[false, false, false, true]));
+    }
+
+    #[test]
+    fn encodes_alarm_fields() {
+        assert_eq!(alarm_field(None), ALARM_ANY);
+        assert_eq!(alarm_field(Some(59)), 0x59);
+        assert_eq!(alarm_day(None), ALARM_ANY);
+        assert_eq!(alarm_day(Some(AlarmDay::Date(31))), 0x31);
+        // Saturday, counted from 1 for Sunday.
+        assert_eq!(alarm_day(Some(AlarmDay::Weekday(6))), ALARM_WEEKDAY | 7);
+    }
+}
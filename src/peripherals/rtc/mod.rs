@@ -0,0 +1,118 @@
+//! # Real-Time Clock Module
+//!
+//! This module groups drivers for battery-backed real-time clocks, which keep
+//! the time across reboots and power loss, so offline devices do not depend on
+//! `connectivity::wifi::get_timestamp`. Every clock implements `TimeSource`.
+//!
+//! ## Currently Supported Clocks
+//! - `ds3231`: Maxim DS3231, a temperature compensated clock (±2 ppm) with a
+//!   temperature sensor, two alarms and a square-wave output.
+//! - `pcf8563`: NXP PCF8563, a low-power clock with an alarm, a programmable
+//!   clock output and a low-voltage detector.
+
+use super::DateTime;
+
+pub mod ds3231;
+#[cfg(feature = "esp-hal")]
+pub mod pcf8563;
+
+/// Day an alarm matches on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmDay {
+    /// Day of the month, 1 to 31.
+    Date(u8),
+    /// Day of the week, 0 for Sunday to 6 for Saturday.
+    Weekday(u8),
+}
+
+/// Time an alarm fires at. Fields set to `None` match any value, so e.g. only
+/// `minute: Some(30)` fires every hour at half past. Some clocks only support
+/// part of the combinations, see `ds3231::Ds3231::set_alarm1` and
+/// `pcf8563::Pcf8563::set_alarm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Alarm {
+    pub day: Option<AlarmDay>,
+    pub hour: Option<u8>,
+    pub minute: Option<u8>,
+    /// Ignored by clocks with a one minute alarm resolution, which fire at
+    /// the start of the minute.
+    pub second: Option<u8>,
+}
+
+impl Alarm {
+    /// Creates an alarm firing every day at the given time.
+    pub const fn daily(hour: u8, minute: u8) -> Self {
+        Alarm {
+            day: None,
+            hour: Some(hour),
+            minute: Some(minute),
+            second: Some(0),
+        }
+    }
+
+    /// Returns `true` if every set field is in range: hours 0 to 23, minutes
+    /// and seconds 0 to 59 and the day as described by `AlarmDay`.
+    pub fn is_valid(&self) -> bool {
+        let day_valid = match self.day {
+            None => true,
+            Some(AlarmDay::Date(date)) => (1..=31).contains(&date),
+            Some(AlarmDay::Weekday(weekday)) => weekday <= 6,
+        };
+        day_valid
+            && self.hour.unwrap_or(0) < 24
+            && self.minute.unwrap_or(0) < 60
+            && self.second.unwrap_or(0) < 60
+    }
+}
+
+/// Decodes a binary-coded decimal register value.
+pub(crate) fn bcd_decode(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Encodes a value below 100 as binary-coded decimal.
+pub(crate) fn bcd_encode(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+/// Checks that a date and time can be stored in a clock counting the years
+/// from 2000 up to `last_year`.
+pub(crate) fn storable(datetime: &DateTime, last_year: u16) -> bool {
+    datetime.is_valid() && (2000..=last_year).contains(&datetime.year)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alarm_fields_in_range() {
+        assert!(Alarm::default().is_valid());
+        assert!(Alarm::daily(23, 59).is_valid());
+        assert!(!Alarm::daily(24, 0).is_valid());
+        assert!(!Alarm::daily(7, 60).is_valid());
+
+        let alarm = Alarm {
+            second: Some(60),
+            ..Alarm::default()
+        };
+        assert!(!alarm.is_valid());
+
+        for (day, valid) in [
+            (AlarmDay::Date(1), true),
+            (AlarmDay::Date(31), true),
+            (AlarmDay::Date(0), false),
+            (AlarmDay::Date(32), false),
+            (AlarmDay::Weekday(0), true),
+            (AlarmDay::Weekday(6), true),
+            (AlarmDay::Weekday(7), false),
+            (AlarmDay::Weekday(255), false),
+        ] {
+            let alarm = Alarm {
+                day: Some(day),
+                ..Alarm::daily(7, 30)
+            };
+            assert_eq!(alarm.is_valid(), valid, "{day:?}");
+        }
+    }
+}
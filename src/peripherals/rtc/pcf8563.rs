@@ -0,0 +1,271 @@
+//! # PCF8563 Real-Time Clock Module
+//!
+//! This module provides a driver for the NXP PCF8563 (and the compatible
+//! BM8563 used on M5Stack boards) real-time clock over I2C. It draws very
+//! little current from its backup battery and detects when the battery voltage
+//! dropped too low to trust the time, see `Pcf8563::is_time_valid`.
+//!
+//! The INT pin (active low, open drain) signals the alarm, which has a one
+//! minute resolution. The CLKOUT pin outputs a programmable clock, see
+//! `Pcf8563::set_clock_output`.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     rtc::{pcf8563::Pcf8563, Alarm},
+//!     DateTime,
+//!     I2cPeriph,
+//!     TimeSource,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! let mut rtc = Pcf8563::create_on_i2c(i2c, delay).unwrap();
+//! if !rtc.is_time_valid().unwrap() {
+//!     rtc.set_datetime(&DateTime::new(2024, 6, 1, 12, 0, 0))
+//!         .unwrap();
+//! }
+//! println!("{:?}", rtc.get_datetime().unwrap());
+//!
+//! rtc.set_alarm(&Alarm::daily(7, 30)).unwrap();
+//! rtc.enable_alarm_interrupt(true).unwrap();
+//! ```
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{bcd_decode, bcd_encode, storable, Alarm, AlarmDay};
+use crate::peripherals::{DateTime, I2cPeriph, PeripheralError, TimeSource, UnifiedData};
+
+/// I2C address of the PCF8563.
+pub const ADDRESS: u8 = 0x51;
+
+const REG_CONTROL1: u8 = 0x00;
+const REG_CONTROL2: u8 = 0x01;
+const REG_SECONDS: u8 = 0x02;
+const REG_ALARM_MINUTE: u8 = 0x09;
+const REG_CLKOUT: u8 = 0x0D;
+
+/// Control 1: the clock is stopped.
+const CONTROL1_STOP: u8 = 0x20;
+/// Control 2: the alarm fired.
+const CONTROL2_AF: u8 = 0x08;
+/// Control 2: the timer fired, written as 1 to leave it untouched.
+const CONTROL2_TF: u8 = 0x04;
+/// Control 2: alarm interrupt enabled.
+const CONTROL2_AIE: u8 = 0x02;
+/// Seconds register: the voltage dropped, the time may be wrong.
+const SECONDS_VL: u8 = 0x80;
+/// Alarm registers: the field is disabled and matches any value.
+const ALARM_DISABLED: u8 = 0x80;
+/// CLKOUT register: output enabled.
+const CLKOUT_ENABLE: u8 = 0x80;
+
+/// Frequency of the CLKOUT pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockOutput {
+    Hz32768,
+    Hz1024,
+    Hz32,
+    Hz1,
+}
+
+/// Encodes an alarm field, or disables it.
+fn alarm_field(value: Option<u8>) -> u8 {
+    value.map_or(ALARM_DISABLED, bcd_encode)
+}
+
+/// Represents a PCF8563 real-time clock.
+pub struct Pcf8563<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the clock is connected to.
+    pub bus: BUS,
+}
+
+impl<BUS, E> Pcf8563<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Creates a new `Pcf8563` and makes sure the clock is running.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the clock is connected to.
+    ///
+    /// # Returns
+    /// A new `Pcf8563` instance, or `PeripheralError::InitializationFailed`
+    /// if the clock does not respond.
+    pub fn create_on_bus(bus: BUS) -> Result<Self, PeripheralError> {
+        let mut rtc = Pcf8563 { bus };
+        rtc.update_register(REG_CONTROL1, CONTROL1_STOP, 0)
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(rtc)
+    }
+
+    fn write_registers(&mut self, data: &[u8]) -> Result<(), PeripheralError> {
+        self.bus
+            .write(ADDRESS, data)
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), PeripheralError> {
+        self.bus
+            .write_read(ADDRESS, &[register], buffer)
+            .map_err(|_| PeripheralError::ReadError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, PeripheralError> {
+        let mut value = [0u8];
+        self.read_registers(register, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn update_register(&mut self, register: u8, clear: u8, set: u8) -> Result<(), PeripheralError> {
+        let value = self.read_register(register)?;
+        self.write_registers(&[register, (value & !clear) | set])
+    }
+
+    /// Checks that the supply voltage has not dropped too low since the time
+    /// was last set.
+    pub fn is_time_valid(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_SECONDS)? & SECONDS_VL == 0)
+    }
+
+    /// Reads the date and time, the years 2000 to 2099.
+    pub fn get_datetime(&mut self) -> Result<DateTime, PeripheralError> {
+        let mut data = [0u8; 7];
+        self.read_registers(REG_SECONDS, &mut data)?;
+        Ok(DateTime {
+            year: 2000 + bcd_decode(data[6]) as u16,
+            month: bcd_decode(data[5] & 0x1F),
+            day: bcd_decode(data[3] & 0x3F),
+            hour: bcd_decode(data[2] & 0x3F),
+            minute: bcd_decode(data[1] & 0x7F),
+            second: bcd_decode(data[0] & 0x7F),
+        })
+    }
+
+    /// Sets the date and time and marks the time as valid.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::WriteError` if the date is invalid or
+    /// outside of the years 2000 to 2099.
+    pub fn set_datetime(&mut self, datetime: &DateTime) -> Result<(), PeripheralError> {
+        if !storable(datetime, 2099) {
+            return Err(PeripheralError::WriteError);
+        }
+        // Writing the seconds clears the low voltage flag.
+        self.write_registers(&[
+            REG_SECONDS,
+            bcd_encode(datetime.second),
+            bcd_encode(datetime.minute),
+            bcd_encode(datetime.hour),
+            bcd_encode(datetime.day),
+            datetime.weekday(),
+            bcd_encode(datetime.month),
+            bcd_encode((datetime.year % 100) as u8),
+        ])
+    }
+
+    /// Sets the alarm, which fires at the start of a minute, so the second of
+    /// the alarm is ignored. The alarm flag is cleared.
+    ///
+    /// Unlike the DS3231, the PCF8563 never fires with no field set, so at
+    /// least the minute, the hour or the day has to be set.
+    ///
+    /// # Returns
+    /// `Ok(())`, or `PeripheralError::WriteError` if no field is set or a
+    /// field is out of range, see `Alarm::is_valid`.
+    pub fn set_alarm(&mut self, alarm: &Alarm) -> Result<(), PeripheralError> {
+        let any_field = alarm.minute.is_some() || alarm.hour.is_some() || alarm.day.is_some();
+        if !any_field || !alarm.is_valid() {
+            return Err(PeripheralError::WriteError);
+        }
+        let (date, weekday) = match alarm.day {
+            None => (None, None),
+            Some(AlarmDay::Date(date)) => (Some(date), None),
+            Some(AlarmDay::Weekday(weekday)) => (None, Some(weekday)),
+        };
+        self.write_registers(&[
+            REG_ALARM_MINUTE,
+            alarm_field(alarm.minute),
+            alarm_field(alarm.hour),
+            alarm_field(date),
+            alarm_field(weekday),
+        ])?;
+        self.clear_alarm()
+    }
+
+    /// Enables or disables the alarm interrupt on the INT pin. The pin stays
+    /// low until the alarm flag is cleared.
+    pub fn enable_alarm_interrupt(&mut self, enabled: bool) -> Result<(), PeripheralError> {
+        if enabled {
+            self.update_register(REG_CONTROL2, 0, CONTROL2_AIE | CONTROL2_TF)
+        } else {
+            self.update_register(REG_CONTROL2, CONTROL2_AIE, CONTROL2_TF)
+        }
+    }
+
+    /// Checks whether the alarm fired, even when its interrupt is disabled.
+    pub fn alarm_fired(&mut self) -> Result<bool, PeripheralError> {
+        Ok(self.read_register(REG_CONTROL2)? & CONTROL2_AF != 0)
+    }
+
+    /// Clears the alarm flag, which releases the INT pin.
+    pub fn clear_alarm(&mut self) -> Result<(), PeripheralError> {
+        self.update_register(REG_CONTROL2, CONTROL2_AF, CONTROL2_TF)
+    }
+
+    /// Outputs a clock on the CLKOUT pin, or disables it with `None`.
+    pub fn set_clock_output(
+        &mut self,
+        frequency: Option<ClockOutput>,
+    ) -> Result<(), PeripheralError> {
+        let value = match frequency {
+            Some(frequency) => CLKOUT_ENABLE | frequency as u8,
+            None => 0,
+        };
+        self.write_registers(&[REG_CLKOUT, value])
+    }
+}
+
+impl I2cPeriph for Pcf8563 {
+    type Returnable = Self;
+
+    /// Creates a new `Pcf8563` on the I2C bus.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the clock.
+    /// * `_delay` - Not used, the clock needs no delays.
+    ///
+    /// # Returns
+    /// A result containing the `Pcf8563` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        _delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Pcf8563::create_on_bus(bus)
+    }
+}
+
+impl<BUS, E> TimeSource for Pcf8563<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_datetime(&mut self) -> Result<DateTime, PeripheralError> {
+        Pcf8563::get_datetime(self)
+    }
+}
+
+impl<BUS, E> UnifiedData for Pcf8563<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = DateTime;
+    /// Reads the date and time.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_datetime()
+    }
+}
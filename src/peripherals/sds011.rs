@@ -0,0 +1,342 @@
+//! # SDS011 Particulate Matter Sensor Module
+//!
+//! This module provides a UART driver for the Nova Fitness SDS011 laser
+//! particulate matter sensor, which measures PM2.5 and PM10 mass
+//! concentrations.
+//!
+//! The sensor talks at 9600 baud, so the UART can be created with
+//! `init_uart_default!`. In `ReportingMode::Active` (the factory default) it
+//! sends a measurement every second, in `ReportingMode::Passive` (called query
+//! mode in the datasheet) only when asked. The fan and laser wear out, so the
+//! sensor should sleep between measurements: see `sleep` (or `Shutdown`) and
+//! `wake`. After waking up the readings need `WARM_UP_MS` to stabilize.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{sds011::Sds011, uart::ReportingMode, ParticulateSensor, Shutdown};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//! let uart = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//!
+//! let mut sensor = Sds011::create_on_uart(uart, delay, ReportingMode::Passive).unwrap();
+//! loop {
+//!     sensor.wake_blocking().unwrap();
+//!     let pm = sensor.get_particulate_matter().unwrap();
+//!     println!("PM2.5: {} µg/m³, PM10: {} µg/m³", pm.pm2_5, pm.pm10);
+//!     sensor.shutdown().unwrap();
+//!     delay.delay_ms(300_000u32);
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    serial::{Read, Write},
+};
+#[cfg(feature = "esp-hal")]
+use esp_hal::delay::Delay;
+
+#[cfg(feature = "esp-hal")]
+use super::{
+    uart::{read_frame_blocking, send, FrameReceiver, FrameSync, ReportingMode},
+    ParticulateSensor,
+    PeripheralError,
+    Shutdown,
+    UnifiedData,
+};
+use super::{
+    uart::{sum8, FrameFormat, FrameLength},
+    ParticulateMatter,
+};
+
+/// Time after waking up until the readings are stable, in milliseconds.
+pub const WARM_UP_MS: u32 = 30_000;
+
+const HEAD: u8 = 0xAA;
+const TAIL: u8 = 0xAB;
+/// Length of frames sent by the sensor.
+const FRAME_LEN: usize = 10;
+
+/// Command ID of measurement frames.
+const ID_MEASUREMENT: u8 = 0xC0;
+/// Command ID of command response frames.
+const ID_RESPONSE: u8 = 0xC5;
+/// Command ID of frames sent to the sensor.
+const ID_COMMAND: u8 = 0xB4;
+
+const CMD_REPORTING_MODE: u8 = 2;
+const CMD_QUERY: u8 = 4;
+const CMD_SLEEP: u8 = 6;
+
+/// Device ID addressing all sensors on the line.
+const ALL_DEVICES: u16 = 0xFFFF;
+
+/// Longest time between two measurements in active mode.
+const ACTIVE_TIMEOUT_MS: u64 = 2000;
+/// Longest time until the sensor answers a command.
+const RESPONSE_TIMEOUT_MS: u32 = 1000;
+/// Frames skipped while waiting for a frame of a given kind.
+const MAX_SKIPPED_FRAMES: usize = 4;
+
+/// Checks the sum of the six data bytes.
+fn frame_valid(frame: &[u8]) -> bool {
+    sum8(&frame[2..8]) == frame[8]
+}
+
+/// Frame format of measurements and command responses.
+pub const FORMAT: FrameFormat = FrameFormat {
+    header: &[HEAD],
+    length: FrameLength::Fixed(FRAME_LEN),
+    footer: &[TAIL],
+    validate: Some(frame_valid),
+};
+
+/// Parses a measurement frame.
+///
+/// # Returns
+/// The PM2.5 and PM10 concentrations, or `None` if the frame is not a
+/// measurement. The checksum is not checked, see `FORMAT`.
+pub fn parse_frame(frame: &[u8]) -> Option<ParticulateMatter> {
+    if frame.len() != FRAME_LEN || frame[0] != HEAD || frame[1] != ID_MEASUREMENT {
+        return None;
+    }
+    Some(ParticulateMatter {
+        pm1_0: None,
+        pm2_5: u16::from_le_bytes([frame[2], frame[3]]) as f32 / 10.0,
+        pm10: u16::from_le_bytes([frame[4], frame[5]]) as f32 / 10.0,
+    })
+}
+
+/// Builds a command frame for all sensors on the line.
+///
+/// # Arguments
+/// * `command` - The command, first data byte.
+/// * `data` - The following data bytes, the rest is zero.
+pub fn command(command: u8, data: &[u8]) -> [u8; 19] {
+    let mut frame = [0u8; 19];
+    frame[0] = HEAD;
+    frame[1] = ID_COMMAND;
+    frame[2] = command;
+    frame[3..3 + data.len()].copy_from_slice(data);
+    frame[15..17].copy_from_slice(&ALL_DEVICES.to_be_bytes());
+    frame[17] = sum8(&frame[2..17]);
+    frame[18] = TAIL;
+    frame
+}
+
+/// Represents an SDS011 sensor on a serial port.
+#[cfg(feature = "esp-hal")]
+pub struct Sds011<S> {
+    /// The serial port, 9600 baud 8N1.
+    pub serial: S,
+    /// Delay provider for timeouts.
+    pub delay: Delay,
+    receiver: FrameReceiver<FRAME_LEN>,
+    mode: ReportingMode,
+    sleeping: bool,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> Sds011<S> {
+    /// Creates a new `Sds011` and switches it to the given reporting mode.
+    ///
+    /// # Arguments
+    /// * `serial` - The serial port the sensor is connected to.
+    /// * `delay` - A delay provider for timeouts.
+    /// * `mode` - The reporting mode.
+    ///
+    /// # Returns
+    /// A new `Sds011` instance, or `PeripheralError::InitializationFailed` if
+    /// the sensor does not answer.
+    pub fn create_on_uart(
+        serial: S,
+        delay: Delay,
+        mode: ReportingMode,
+    ) -> Result<Self, PeripheralError> {
+        let mut sensor = Sds011 {
+            serial,
+            delay,
+            receiver: FrameReceiver::new(FrameSync::new(FORMAT), ACTIVE_TIMEOUT_MS),
+            mode,
+            sleeping: false,
+        };
+        sensor
+            .set_mode(mode)
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(sensor)
+    }
+
+    /// Sends a command and waits for the response.
+    fn execute(&mut self, command_id: u8, data: &[u8]) -> Result<(), PeripheralError> {
+        self.receiver.sync.clear();
+        send(&mut self.serial, &command(command_id, data))?;
+        for _ in 0..MAX_SKIPPED_FRAMES {
+            let frame = read_frame_blocking(
+                &mut self.serial,
+                &mut self.receiver.sync,
+                &mut self.delay,
+                RESPONSE_TIMEOUT_MS,
+            )?;
+            if frame[1] == ID_RESPONSE && frame[2] == command_id {
+                return Ok(());
+            }
+        }
+        Err(PeripheralError::ReadError)
+    }
+
+    /// Returns the reporting mode.
+    pub fn mode(&self) -> ReportingMode {
+        self.mode
+    }
+
+    /// Switches the reporting mode.
+    pub fn set_mode(&mut self, mode: ReportingMode) -> Result<(), PeripheralError> {
+        let value = match mode {
+            ReportingMode::Active => 0,
+            ReportingMode::Passive => 1,
+        };
+        self.execute(CMD_REPORTING_MODE, &[1, value])?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Switches the fan and the laser off.
+    pub fn sleep(&mut self) -> Result<(), PeripheralError> {
+        self.execute(CMD_SLEEP, &[1, 0])?;
+        self.sleeping = true;
+        Ok(())
+    }
+
+    /// Switches the fan and the laser on, see `WARM_UP_MS`.
+    pub fn wake(&mut self) -> Result<(), PeripheralError> {
+        // A sleeping sensor may lose the first bytes while waking up.
+        self.execute(CMD_SLEEP, &[1, 1])
+            .or_else(|_| self.execute(CMD_SLEEP, &[1, 1]))?;
+        self.sleeping = false;
+        Ok(())
+    }
+
+    /// Switches the sensor on and waits for the readings to stabilize.
+    pub fn wake_blocking(&mut self) -> Result<(), PeripheralError> {
+        self.wake()?;
+        self.delay.delay_ms(WARM_UP_MS);
+        Ok(())
+    }
+
+    /// Returns `true` after `sleep`.
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Waits for a measurement; in passive mode it is requested first.
+    pub fn measure(&mut self) -> Result<ParticulateMatter, PeripheralError> {
+        let timeout_ms = match self.mode {
+            ReportingMode::Active => ACTIVE_TIMEOUT_MS as u32,
+            ReportingMode::Passive => {
+                self.receiver.sync.clear();
+                send(&mut self.serial, &command(CMD_QUERY, &[]))?;
+                RESPONSE_TIMEOUT_MS
+            }
+        };
+        for _ in 0..MAX_SKIPPED_FRAMES {
+            let frame = read_frame_blocking(
+                &mut self.serial,
+                &mut self.receiver.sync,
+                &mut self.delay,
+                timeout_ms,
+            )?;
+            if let Some(reading) = parse_frame(frame) {
+                return Ok(reading);
+            }
+        }
+        Err(PeripheralError::ReadError)
+    }
+
+    /// Reads the received bytes without blocking, for active mode. Should be
+    /// called periodically.
+    ///
+    /// # Arguments
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(Some(reading))` if a new measurement was completed, `Ok(None)` if
+    /// none is complete yet, or `Err(PeripheralError::ReadError)` on a serial
+    /// error or when no frame arrived for 2 seconds.
+    pub fn poll(&mut self, now_ms: u64) -> Result<Option<ParticulateMatter>, PeripheralError> {
+        while let Some(frame) = self.receiver.poll(&mut self.serial, now_ms)? {
+            if let Some(reading) = parse_frame(frame) {
+                return Ok(Some(reading));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> ParticulateSensor for Sds011<S> {
+    /// Measures the PM2.5 and PM10 concentrations, PM1.0 is not measured.
+    fn get_particulate_matter(&mut self) -> Result<ParticulateMatter, PeripheralError> {
+        self.measure()
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> UnifiedData for Sds011<S> {
+    type Output = (f32, f32);
+    /// Waits for a measurement and returns the PM2.5 and PM10
+    /// concentrations.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        let reading = self.measure()?;
+        Ok((reading.pm2_5, reading.pm10))
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<S: Read<u8> + Write<u8>> Shutdown for Sds011<S> {
+    /// Puts the sensor to sleep, see `Sds011::wake`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.sleep()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Measurement from the SDS011 datasheet.
+    const MEASUREMENT: [u8; 10] = [0xAA, 0xC0, 0xD4, 0x04, 0x3A, 0x0A, 0xA1, 0x60, 0x1D, 0xAB];
+    /// Response to the command switching to passive mode.
+    const MODE_RESPONSE: [u8; 10] = [0xAA, 0xC5, 0x02, 0x01, 0x01, 0x00, 0xA1, 0x60, 0x05, 0xAB];
+
+    #[test]
+    fn parses_measurement() {
+        assert_eq!(
+            parse_frame(&MEASUREMENT),
+            Some(ParticulateMatter {
+                pm1_0: None,
+                pm2_5: 123.6,
+                pm10: 261.8,
+            })
+        );
+    }
+
+    #[test]
+    fn builds_commands() {
+        let mut query = [0u8; 19];
+        query[..3].copy_from_slice(&[0xAA, 0xB4, 0x04]);
+        query[15..].copy_from_slice(&[0xFF, 0xFF, 0x02, 0xAB]);
+        assert_eq!(command(CMD_QUERY, &[]), query);
+
+        let mut passive = query;
+        // The checksum does not change: 0x02 + 0x01 + 0x01 replaces 0x04.
+        passive[2..5].copy_from_slice(&[0x02, 0x01, 0x01]);
+        assert_eq!(command(CMD_REPORTING_MODE, &[1, 1]), passive);
+    }
+
+    #[test]
+    fn ignores_command_responses() {
+        assert_eq!(parse_frame(&MODE_RESPONSE), None);
+    }
+}
@@ -0,0 +1,646 @@
+//! # UART Sensor Framework Module
+//!
+//! Many sensors (particulate matter sensors, CO2 sensors, GPS receivers) talk
+//! over a serial line in frames: a fixed header, a payload of a fixed or
+//! announced length and a checksum. This module provides the building blocks
+//! for drivers of such sensors:
+//! - `FrameSync`: reassembles frames from a byte stream, resynchronizing after
+//!   garbage, lost bytes or corrupted frames,
+//! - checksum helpers used by common protocols,
+//! - `Timeout` and `FrameReceiver`, which report sensors that stopped
+//!   answering,
+//! - `send` and `read_frame_blocking` for request-response protocols.
+//!
+//! Everything works on top of the `embedded-hal` serial traits, so a `Uart`
+//! created with `init_uart_default!` or `init_uart_custom!` can be used
+//! directly.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::systimer::SystemTimer;
+//! use esp_ward::peripherals::uart::{sum16, FrameFormat, FrameLength, FrameReceiver, FrameSync};
+//!
+//! fn valid(frame: &[u8]) -> bool {
+//!     let (data, checksum) = frame.split_at(frame.len() - 2);
+//!     sum16(data) == u16::from_be_bytes([checksum[0], checksum[1]])
+//! }
+//!
+//! // Header 0x42 0x4D followed by a big-endian length of the rest of the frame
+//! const FORMAT: FrameFormat = FrameFormat {
+//!     header: &[0x42, 0x4D],
+//!     length: FrameLength::Field {
+//!         offset: 2,
+//!         big_endian: true,
+//!         wide: true,
+//!         extra: 4,
+//!     },
+//!     footer: &[],
+//!     validate: Some(valid),
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, delay) = init_chip!(peripherals, system);
+//! let mut serial = esp_ward::init_uart_default!(peripherals, pins, clocks);
+//!
+//! let mut receiver = FrameReceiver::new(FrameSync::<32>::new(FORMAT), 3000);
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     match receiver.poll(&mut serial, now) {
+//!         Ok(Some(frame)) => println!("Frame: {:?}", frame),
+//!         Ok(None) => (),
+//!         Err(_) => println!("Sensor does not answer"),
+//!     }
+//! }
+//! ```
+
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    serial::{Read, Write},
+};
+
+use super::PeripheralError;
+
+/// How a serial sensor reports its measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportingMode {
+    /// The sensor sends measurements on its own, periodically.
+    Active,
+    /// The sensor sends a measurement only when asked to.
+    Passive,
+}
+
+/// Sum of all bytes, truncated to 8 bits.
+pub fn sum8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Sum of all bytes, truncated to 16 bits.
+pub fn sum16(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16))
+}
+
+/// Two's complement of the 8-bit sum, so that the sum of the data and the
+/// checksum is zero.
+pub fn negated_sum8(data: &[u8]) -> u8 {
+    sum8(data).wrapping_neg()
+}
+
+/// XOR of all bytes, e.g. NMEA sentences.
+pub fn xor8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |checksum, byte| checksum ^ byte)
+}
+
+/// CRC-16/MODBUS (polynomial 0xA001 reflected, initial value 0xFFFF).
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// How the length of a frame is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLength {
+    /// All frames have the same total length, header included.
+    Fixed(usize),
+    /// The frame announces its length in a field.
+    Field {
+        /// Position of the field from the start of the frame.
+        offset: usize,
+        /// Byte order of a two byte field.
+        big_endian: bool,
+        /// The field has two bytes instead of one.
+        wide: bool,
+        /// Added to the field value to get the total frame length.
+        extra: usize,
+    },
+    /// The frame ends with the given byte, e.g. `b'\n'`.
+    Terminator(u8),
+}
+
+/// Description of a frame format.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameFormat {
+    /// Bytes every frame starts with.
+    pub header: &'static [u8],
+    pub length: FrameLength,
+    /// Bytes every frame ends with, may be empty.
+    pub footer: &'static [u8],
+    /// Checks a complete frame, usually its checksum.
+    pub validate: Option<fn(&[u8]) -> bool>,
+}
+
+/// Reassembles frames of a `FrameFormat` from a byte stream. Frames longer
+/// than `N` bytes are discarded.
+pub struct FrameSync<const N: usize> {
+    format: FrameFormat,
+    buffer: [u8; N],
+    len: usize,
+    /// Length of the frame returned by the last `push`, removed on the next.
+    ready: usize,
+    dropped: usize,
+}
+
+impl<const N: usize> FrameSync<N> {
+    /// Creates a new `FrameSync`.
+    pub const fn new(format: FrameFormat) -> Self {
+        FrameSync {
+            format,
+            buffer: [0; N],
+            len: 0,
+            ready: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the frame format.
+    pub fn format(&self) -> &FrameFormat {
+        &self.format
+    }
+
+    /// Discards a partially received frame.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.ready = 0;
+    }
+
+    /// Returns the number of bytes discarded while searching for frames.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Feeds one received byte.
+    ///
+    /// # Returns
+    /// A complete and valid frame, header and footer included, if this byte
+    /// completed one.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if self.ready > 0 {
+            self.shift(self.ready);
+            self.ready = 0;
+        }
+        if self.len == N {
+            self.discard(1);
+        }
+        self.buffer[self.len] = byte;
+        self.len += 1;
+
+        loop {
+            match self.check() {
+                Check::Incomplete => return None,
+                Check::Invalid => self.discard(1),
+                Check::Complete(len) => {
+                    self.ready = len;
+                    return Some(&self.buffer[..len]);
+                }
+            }
+            if self.len == 0 {
+                return None;
+            }
+        }
+    }
+
+    fn check(&self) -> Check {
+        let data = &self.buffer[..self.len];
+        let header = self.format.header;
+        let prefix = data.len().min(header.len());
+        if data[..prefix] != header[..prefix] {
+            return Check::Invalid;
+        }
+
+        let total = match self.format.length {
+            FrameLength::Fixed(len) => len,
+            FrameLength::Field {
+                offset,
+                big_endian,
+                wide,
+                extra,
+            } => {
+                let size = if wide { 2 } else { 1 };
+                if data.len() < offset + size {
+                    return Check::Incomplete;
+                }
+                let value = if !wide {
+                    data[offset] as usize
+                } else if big_endian {
+                    u16::from_be_bytes([data[offset], data[offset + 1]]) as usize
+                } else {
+                    u16::from_le_bytes([data[offset], data[offset + 1]]) as usize
+                };
+                value + extra
+            }
+            FrameLength::Terminator(terminator) => {
+                match data
+                    .iter()
+                    .skip(header.len())
+                    .position(|b| *b == terminator)
+                {
+                    Some(position) => header.len() + position + 1,
+                    None if data.len() < N => return Check::Incomplete,
+                    // Too long, no room left for the terminator.
+                    None => return Check::Invalid,
+                }
+            }
+        };
+
+        if total > N || total < header.len() + self.format.footer.len() {
+            return Check::Invalid;
+        }
+        if data.len() < total {
+            return Check::Incomplete;
+        }
+
+        let frame = &data[..total];
+        if !frame.ends_with(self.format.footer) {
+            return Check::Invalid;
+        }
+        if let Some(validate) = self.format.validate {
+            if !validate(frame) {
+                return Check::Invalid;
+            }
+        }
+        Check::Complete(total)
+    }
+
+    fn discard(&mut self, count: usize) {
+        self.dropped += count;
+        self.shift(count);
+    }
+
+    fn shift(&mut self, count: usize) {
+        self.buffer.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
+
+enum Check {
+    Incomplete,
+    Invalid,
+    Complete(usize),
+}
+
+/// A timeout measured with timestamps passed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    /// Duration of the timeout in milliseconds.
+    pub duration_ms: u64,
+    start_ms: Option<u64>,
+}
+
+impl Timeout {
+    /// Creates a new `Timeout`, started by the first `is_expired` call.
+    pub const fn new(duration_ms: u64) -> Self {
+        Timeout {
+            duration_ms,
+            start_ms: None,
+        }
+    }
+
+    /// Starts the timeout again.
+    pub fn restart(&mut self, now_ms: u64) {
+        self.start_ms = Some(now_ms);
+    }
+
+    /// Returns `true` once the duration passed since the last restart.
+    pub fn is_expired(&mut self, now_ms: u64) -> bool {
+        let start = *self.start_ms.get_or_insert(now_ms);
+        now_ms.saturating_sub(start) >= self.duration_ms
+    }
+
+    /// Returns the milliseconds left before the timeout expires.
+    pub fn remaining_ms(&self, now_ms: u64) -> u64 {
+        match self.start_ms {
+            Some(start) => (start + self.duration_ms).saturating_sub(now_ms),
+            None => self.duration_ms,
+        }
+    }
+}
+
+/// Receives frames from a serial port without blocking, and reports when no
+/// valid frame arrived for a while.
+pub struct FrameReceiver<const N: usize> {
+    pub sync: FrameSync<N>,
+    pub timeout: Timeout,
+}
+
+impl<const N: usize> FrameReceiver<N> {
+    /// Creates a new `FrameReceiver`.
+    ///
+    /// # Arguments
+    /// * `sync` - The frame synchronizer.
+    /// * `timeout_ms` - Longest expected time between two frames.
+    pub const fn new(sync: FrameSync<N>, timeout_ms: u64) -> Self {
+        FrameReceiver {
+            sync,
+            timeout: Timeout::new(timeout_ms),
+        }
+    }
+
+    /// Reads all received bytes until a frame is complete. Should be called
+    /// periodically, often enough for the receive FIFO not to overflow.
+    ///
+    /// # Arguments
+    /// * `serial` - The serial port.
+    /// * `now_ms` - Current time in milliseconds.
+    ///
+    /// # Returns
+    /// `Ok(Some(frame))` with a new frame, `Ok(None)` if no frame is complete
+    /// yet, or `Err(PeripheralError::ReadError)` on a serial error or when no
+    /// frame arrived within the timeout (the timeout then restarts).
+    pub fn poll<S: Read<u8>>(
+        &mut self,
+        serial: &mut S,
+        now_ms: u64,
+    ) -> Result<Option<&[u8]>, PeripheralError> {
+        loop {
+            match serial.read() {
+                Ok(byte) => {
+                    if self.sync.push(byte).is_some() {
+                        self.timeout.restart(now_ms);
+                        break;
+                    }
+                }
+                Err(nb::Error::WouldBlock) => {
+                    if self.timeout.is_expired(now_ms) {
+                        self.timeout.restart(now_ms);
+                        return Err(PeripheralError::ReadError);
+                    }
+                    return Ok(None);
+                }
+                Err(nb::Error::Other(_)) => return Err(PeripheralError::ReadError),
+            }
+        }
+        // The frame is still in the buffer until the next byte is pushed.
+        let len = self.sync.ready;
+        Ok(Some(&self.sync.buffer[..len]))
+    }
+}
+
+/// Writes all bytes to a serial port and waits until they are sent.
+pub fn send<S: Write<u8>>(serial: &mut S, data: &[u8]) -> Result<(), PeripheralError> {
+    for byte in data {
+        nb::block!(serial.write(*byte)).map_err(|_| PeripheralError::WriteError)?;
+    }
+    nb::block!(serial.flush()).map_err(|_| PeripheralError::WriteError)
+}
+
+/// Waits for a frame, e.g. the response to a command sent with `send`.
+///
+/// # Arguments
+/// * `serial` - The serial port.
+/// * `sync` - The frame synchronizer.
+/// * `delay` - Delay provider used to measure the timeout.
+/// * `timeout_ms` - How long to wait for the frame.
+///
+/// # Returns
+/// The frame, or `Err(PeripheralError::ReadError)` on a serial error or
+/// timeout.
+pub fn read_frame_blocking<'a, S: Read<u8>, D: DelayMs<u32>, const N: usize>(
+    serial: &mut S,
+    sync: &'a mut FrameSync<N>,
+    delay: &mut D,
+    timeout_ms: u32,
+) -> Result<&'a [u8], PeripheralError> {
+    let mut waited_ms = 0;
+    loop {
+        match serial.read() {
+            Ok(byte) => {
+                if sync.push(byte).is_some() {
+                    break;
+                }
+            }
+            Err(nb::Error::WouldBlock) => {
+                if waited_ms >= timeout_ms {
+                    return Err(PeripheralError::ReadError);
+                }
+                delay.delay_ms(1);
+                waited_ms += 1;
+            }
+            Err(nb::Error::Other(_)) => return Err(PeripheralError::ReadError),
+        }
+    }
+    let len = sync.ready;
+    Ok(&sync.buffer[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid(frame: &[u8]) -> bool {
+        let (data, checksum) = frame.split_at(frame.len() - 2);
+        sum16(data) == u16::from_be_bytes([checksum[0], checksum[1]])
+    }
+
+    /// Header, one byte announcing the payload length, payload and a 16-bit
+    /// sum of everything before it.
+    const FORMAT: FrameFormat = FrameFormat {
+        header: &[0x42, 0x4D],
+        length: FrameLength::Field {
+            offset: 2,
+            big_endian: true,
+            wide: false,
+            extra: 5,
+        },
+        footer: &[],
+        validate: Some(valid),
+    };
+    const FRAME: [u8; 8] = [0x42, 0x4D, 0x03, 0x01, 0x02, 0x03, 0x00, 0x98];
+
+    const LINE: FrameFormat = FrameFormat {
+        header: b"$",
+        length: FrameLength::Terminator(b'\n'),
+        footer: b"\r\n",
+        validate: None,
+    };
+
+    /// Serial port returning the given bytes, then `WouldBlock`.
+    struct MockSerial<'a> {
+        rx: &'a [u8],
+        tx: [u8; 8],
+        sent: usize,
+    }
+
+    impl<'a> MockSerial<'a> {
+        fn new(rx: &'a [u8]) -> Self {
+            MockSerial {
+                rx,
+                tx: [0; 8],
+                sent: 0,
+            }
+        }
+    }
+
+    impl Read<u8> for MockSerial<'_> {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            let (byte, rest) = self.rx.split_first().ok_or(nb::Error::WouldBlock)?;
+            self.rx = rest;
+            Ok(*byte)
+        }
+    }
+
+    impl Write<u8> for MockSerial<'_> {
+        type Error = ();
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), ()> {
+            self.tx[self.sent] = byte;
+            self.sent += 1;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct MockDelay {
+        waited_ms: u32,
+    }
+
+    impl DelayMs<u32> for MockDelay {
+        fn delay_ms(&mut self, ms: u32) {
+            self.waited_ms += ms;
+        }
+    }
+
+    /// Feeds the bytes one by one.
+    ///
+    /// # Returns
+    /// The amount of frames completed.
+    fn feed<const N: usize>(sync: &mut FrameSync<N>, bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .filter(|byte| sync.push(**byte).is_some())
+            .count()
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(sum8(&[0xFF, 0x02]), 0x01);
+        assert_eq!(sum16(&[0xFF, 0x02]), 0x0101);
+        assert_eq!(negated_sum8(&[0x01, 0x02]), 0xFD);
+        assert_eq!(xor8(b"GPGSA"), 0x42);
+        assert_eq!(crc16_modbus(b"123456789"), 0x4B37);
+    }
+
+    #[test]
+    fn returns_complete_frame() {
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        for byte in &FRAME[..FRAME.len() - 1] {
+            assert_eq!(sync.push(*byte), None);
+        }
+        assert_eq!(sync.push(FRAME[FRAME.len() - 1]), Some(&FRAME[..]));
+        // Consecutive frames follow without loss.
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+        assert_eq!(sync.dropped(), 0);
+    }
+
+    #[test]
+    fn drops_bad_checksum() {
+        let mut corrupted = FRAME;
+        corrupted[4] ^= 0x01;
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        assert_eq!(feed(&mut sync, &corrupted), 0);
+        assert!(sync.dropped() > 0);
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+    }
+
+    #[test]
+    fn resyncs_after_garbage() {
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        // Includes a false header announcing a frame too long for the buffer.
+        let garbage = [0x00, 0x42, 0x13, 0x42, 0x4D, 0xFF, 0x4D];
+        assert_eq!(feed(&mut sync, &garbage), 0);
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+        assert_eq!(sync.dropped(), garbage.len());
+    }
+
+    #[test]
+    fn resyncs_after_truncated_frame() {
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        assert_eq!(feed(&mut sync, &FRAME[..5]), 0);
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+        assert_eq!(sync.dropped(), 5);
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+    }
+
+    #[test]
+    fn clear_discards_partial_frame() {
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        feed(&mut sync, &FRAME[..5]);
+        sync.clear();
+        assert_eq!(feed(&mut sync, &FRAME), 1);
+        assert_eq!(sync.dropped(), 0);
+    }
+
+    #[test]
+    fn splits_terminated_frames() {
+        let mut sync = FrameSync::<16>::new(LINE);
+        assert_eq!(feed(&mut sync, b"xx$A,1\r\n$B\r\n"), 2);
+        assert_eq!(sync.dropped(), 2);
+        // Missing carriage return.
+        assert_eq!(feed(&mut sync, b"$C\n$D\r\n"), 1);
+    }
+
+    #[test]
+    fn drops_terminated_frame_longer_than_buffer() {
+        let mut sync = FrameSync::<8>::new(LINE);
+        assert_eq!(feed(&mut sync, b"$0123456789\r\n"), 0);
+        assert_eq!(feed(&mut sync, b"$A\r\n"), 1);
+        assert_eq!(sync.dropped(), 13);
+    }
+
+    #[test]
+    fn timeout_starts_on_first_check() {
+        let mut timeout = Timeout::new(100);
+        assert_eq!(timeout.remaining_ms(50), 100);
+        assert!(!timeout.is_expired(50));
+        assert_eq!(timeout.remaining_ms(120), 30);
+        assert!(!timeout.is_expired(149));
+        assert!(timeout.is_expired(150));
+        timeout.restart(150);
+        assert!(!timeout.is_expired(200));
+        assert_eq!(timeout.remaining_ms(300), 0);
+    }
+
+    #[test]
+    fn receiver_reports_frames_and_silence() {
+        let mut receiver = FrameReceiver::new(FrameSync::<16>::new(FORMAT), 1000);
+        let mut serial = MockSerial::new(&FRAME[..5]);
+        assert!(matches!(receiver.poll(&mut serial, 0), Ok(None)));
+        serial.rx = &FRAME[5..];
+        assert!(matches!(receiver.poll(&mut serial, 500), Ok(Some(frame)) if frame == FRAME));
+        // The timeout restarted with the frame.
+        assert!(matches!(receiver.poll(&mut serial, 1499), Ok(None)));
+        assert!(receiver.poll(&mut serial, 1500).is_err());
+        assert!(matches!(receiver.poll(&mut serial, 1501), Ok(None)));
+    }
+
+    #[test]
+    fn sends_and_waits_for_response() {
+        let mut serial = MockSerial::new(&FRAME);
+        send(&mut serial, &[0x01, 0x02]).unwrap();
+        assert_eq!(&serial.tx[..serial.sent], &[0x01, 0x02]);
+
+        let mut sync = FrameSync::<16>::new(FORMAT);
+        let mut delay = MockDelay { waited_ms: 0 };
+        let frame = read_frame_blocking(&mut serial, &mut sync, &mut delay, 10).unwrap();
+        assert_eq!(frame, &FRAME[..]);
+        assert_eq!(delay.waited_ms, 0);
+
+        assert!(read_frame_blocking(&mut serial, &mut sync, &mut delay, 10).is_err());
+        assert_eq!(delay.waited_ms, 10);
+    }
+}
@@ -0,0 +1,380 @@
+//! # VEML7700 Light Sensor Module
+//!
+//! This module provides a driver for the Vishay VEML7700 ambient light sensor
+//! over I2C. The gain (1/8 to 2) and the integration time (25 to 800 ms) set
+//! the resolution, from 0.0042 lux at gain 2 and 800 ms up to 2.1504 lux at
+//! gain 1/8 and 25 ms, where the sensor measures up to about 140000 lux.
+//!
+//! The response of the sensor is not linear above about 1000 lux; the
+//! correction from the Vishay application note is applied to these readings
+//! unless `Veml7700Config::correct_nonlinearity` is disabled.
+//!
+//! In `Veml7700Mode::OneShot` every reading powers the sensor up, waits for
+//! one integration and powers it down again; in `Veml7700Mode::Continuous` the
+//! sensor measures all the time and readings return the latest result. After
+//! a configuration change in continuous mode, the next result is ready after
+//! one integration time.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_ward::peripherals::{
+//!     veml7700::{Gain, IntegrationTime, Veml7700, Veml7700Config},
+//!     LumiSensor,
+//! };
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//! let i2c = esp_ward::init_i2c_default!(peripherals, pins, clocks);
+//!
+//! // Outdoors, in direct sunlight
+//! let config = Veml7700Config {
+//!     gain: Gain::Eighth,
+//!     integration_time: IntegrationTime::Ms25,
+//!     ..Default::default()
+//! };
+//! let mut sensor = Veml7700::create_on_i2c_with_config(i2c, delay, config).unwrap();
+//! loop {
+//!     println!("Light: {} lx", sensor.get_lux().unwrap());
+//!     delay.delay_ms(1000u32);
+//! }
+//! ```
+
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Write, WriteRead},
+};
+use esp_hal::{delay::Delay, i2c::I2C};
+
+use super::{I2cPeriph, LumiSensor, PeripheralError, Shutdown, UnifiedData};
+
+/// I2C address of the VEML7700.
+pub const ADDRESS: u8 = 0x10;
+
+const REG_ALS_CONF: u8 = 0x00;
+const REG_ALS: u8 = 0x04;
+const REG_WHITE: u8 = 0x05;
+const REG_ID: u8 = 0x07;
+
+/// Low byte of the ID register.
+const DEVICE_ID: u8 = 0x81;
+/// Configuration register: the sensor is shut down.
+const ALS_SD: u16 = 0x0001;
+const ALS_GAIN_SHIFT: u16 = 11;
+const ALS_IT_SHIFT: u16 = 6;
+
+/// Resolution in lux per count at gain 2 and 800 ms.
+const MAX_RESOLUTION: f32 = 0.0042;
+/// Time to wake up from shutdown in milliseconds.
+const WAKE_UP_MS: u32 = 3;
+/// Readings above this are corrected for the non-linear response.
+const NONLINEAR_LUX: f32 = 1000.0;
+
+/// Gain of the sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gain {
+    /// Gain 1/8, for the brightest light.
+    Eighth,
+    /// Gain 1/4.
+    Quarter,
+    /// Gain 1.
+    One,
+    /// Gain 2, for the dimmest light.
+    Two,
+}
+
+impl Gain {
+    /// Returns the gain factor.
+    pub fn factor(self) -> f32 {
+        match self {
+            Gain::Eighth => 0.125,
+            Gain::Quarter => 0.25,
+            Gain::One => 1.0,
+            Gain::Two => 2.0,
+        }
+    }
+
+    fn bits(self) -> u16 {
+        match self {
+            Gain::One => 0b00,
+            Gain::Two => 0b01,
+            Gain::Eighth => 0b10,
+            Gain::Quarter => 0b11,
+        }
+    }
+}
+
+/// Integration time of a measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrationTime {
+    Ms25,
+    Ms50,
+    Ms100,
+    Ms200,
+    Ms400,
+    Ms800,
+}
+
+impl IntegrationTime {
+    /// Returns the integration time in milliseconds.
+    pub fn ms(self) -> u32 {
+        match self {
+            IntegrationTime::Ms25 => 25,
+            IntegrationTime::Ms50 => 50,
+            IntegrationTime::Ms100 => 100,
+            IntegrationTime::Ms200 => 200,
+            IntegrationTime::Ms400 => 400,
+            IntegrationTime::Ms800 => 800,
+        }
+    }
+
+    fn bits(self) -> u16 {
+        match self {
+            IntegrationTime::Ms25 => 0b1100,
+            IntegrationTime::Ms50 => 0b1000,
+            IntegrationTime::Ms100 => 0b0000,
+            IntegrationTime::Ms200 => 0b0001,
+            IntegrationTime::Ms400 => 0b0010,
+            IntegrationTime::Ms800 => 0b0011,
+        }
+    }
+}
+
+/// When the sensor measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Veml7700Mode {
+    /// Measures all the time.
+    Continuous,
+    /// Measures on every reading and shuts down in between.
+    OneShot,
+}
+
+/// Configuration of a VEML7700.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Veml7700Config {
+    pub gain: Gain,
+    pub integration_time: IntegrationTime,
+    pub mode: Veml7700Mode,
+    /// Corrects the non-linear response above 1000 lux.
+    pub correct_nonlinearity: bool,
+}
+
+impl Default for Veml7700Config {
+    fn default() -> Self {
+        Veml7700Config {
+            gain: Gain::One,
+            integration_time: IntegrationTime::Ms100,
+            mode: Veml7700Mode::Continuous,
+            correct_nonlinearity: true,
+        }
+    }
+}
+
+impl Veml7700Config {
+    /// Returns the resolution in lux per count.
+    pub fn resolution(&self) -> f32 {
+        MAX_RESOLUTION * (2.0 / self.gain.factor()) * (800.0 / self.integration_time.ms() as f32)
+    }
+
+    /// Converts a raw measurement to lux.
+    pub fn lux(&self, raw: u16) -> f32 {
+        let lux = raw as f32 * self.resolution();
+        if self.correct_nonlinearity && lux > NONLINEAR_LUX {
+            correct_nonlinearity(lux)
+        } else {
+            lux
+        }
+    }
+
+    fn register(&self, shutdown: bool) -> u16 {
+        (self.gain.bits() << ALS_GAIN_SHIFT)
+            | (self.integration_time.bits() << ALS_IT_SHIFT)
+            | if shutdown { ALS_SD } else { 0 }
+    }
+}
+
+/// Corrects the non-linear response of the sensor at high illuminance, with
+/// the polynomial from the Vishay application note.
+pub fn correct_nonlinearity(lux: f32) -> f32 {
+    let lux = lux as f64;
+    let corrected = (((6.0135e-13 * lux - 9.3924e-9) * lux + 8.1488e-5) * lux + 1.0023) * lux;
+    corrected as f32
+}
+
+/// Represents a VEML7700 ambient light sensor.
+pub struct Veml7700<BUS = I2C<'static, esp_hal::peripherals::I2C0>> {
+    /// The I2C bus the sensor is connected to.
+    pub bus: BUS,
+    /// Delay provider for waiting on measurements.
+    pub delay: Delay,
+    config: Veml7700Config,
+}
+
+impl<BUS, E> Veml7700<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Checks and configures the VEML7700.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus the sensor is connected to.
+    /// * `delay` - A delay provider for waiting on measurements.
+    /// * `config` - Gain, integration time, mode and correction.
+    ///
+    /// # Returns
+    /// A new `Veml7700` instance, or `PeripheralError::InitializationFailed`
+    /// if the sensor does not respond or is not a VEML7700.
+    pub fn create_on_i2c_with_config(
+        bus: BUS,
+        delay: Delay,
+        config: Veml7700Config,
+    ) -> Result<Self, PeripheralError> {
+        let mut sensor = Veml7700 { bus, delay, config };
+        sensor
+            .init()
+            .map_err(|_| PeripheralError::InitializationFailed)?;
+        Ok(sensor)
+    }
+
+    fn init(&mut self) -> Result<(), PeripheralError> {
+        if self.read_register(REG_ID)? & 0xFF != DEVICE_ID as u16 {
+            return Err(PeripheralError::InitializationFailed);
+        }
+        self.apply()
+    }
+
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), PeripheralError> {
+        let [low, high] = value.to_le_bytes();
+        self.bus
+            .write(ADDRESS, &[register, low, high])
+            .map_err(|_| PeripheralError::WriteError)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, PeripheralError> {
+        let mut data = [0u8; 2];
+        self.bus
+            .write_read(ADDRESS, &[register], &mut data)
+            .map_err(|_| PeripheralError::ReadError)?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Writes the configuration; the sensor runs in continuous mode and is
+    /// shut down in one-shot mode.
+    fn apply(&mut self) -> Result<(), PeripheralError> {
+        let shutdown = self.config.mode == Veml7700Mode::OneShot;
+        self.write_register(REG_ALS_CONF, self.config.register(shutdown))
+    }
+
+    /// Returns the current configuration.
+    pub fn config(&self) -> &Veml7700Config {
+        &self.config
+    }
+
+    /// Sets the gain.
+    pub fn set_gain(&mut self, gain: Gain) -> Result<(), PeripheralError> {
+        self.config.gain = gain;
+        self.apply()
+    }
+
+    /// Sets the integration time.
+    pub fn set_integration_time(
+        &mut self,
+        integration_time: IntegrationTime,
+    ) -> Result<(), PeripheralError> {
+        self.config.integration_time = integration_time;
+        self.apply()
+    }
+
+    /// Sets when the sensor measures.
+    pub fn set_mode(&mut self, mode: Veml7700Mode) -> Result<(), PeripheralError> {
+        self.config.mode = mode;
+        self.apply()
+    }
+
+    /// Enables or disables the correction of readings above 1000 lux.
+    pub fn set_nonlinearity_correction(&mut self, enabled: bool) {
+        self.config.correct_nonlinearity = enabled;
+    }
+
+    /// Reads a raw channel, measuring first in one-shot mode.
+    fn read_channel(&mut self, register: u8) -> Result<u16, PeripheralError> {
+        if self.config.mode == Veml7700Mode::OneShot {
+            self.write_register(REG_ALS_CONF, self.config.register(false))?;
+            self.delay
+                .delay_ms(WAKE_UP_MS + self.config.integration_time.ms());
+            let raw = self.read_register(register);
+            self.write_register(REG_ALS_CONF, self.config.register(true))?;
+            raw
+        } else {
+            self.read_register(register)
+        }
+    }
+
+    /// Reads the raw ambient light channel.
+    pub fn read_raw(&mut self) -> Result<u16, PeripheralError> {
+        self.read_channel(REG_ALS)
+    }
+
+    /// Reads the raw white channel, which has a wider spectral response than
+    /// the ambient light channel.
+    pub fn read_white_raw(&mut self) -> Result<u16, PeripheralError> {
+        self.read_channel(REG_WHITE)
+    }
+
+    /// Shuts the sensor down until the next one-shot measurement or
+    /// `set_mode`.
+    pub fn power_down(&mut self) -> Result<(), PeripheralError> {
+        self.write_register(REG_ALS_CONF, self.config.register(true))
+    }
+}
+
+impl I2cPeriph for Veml7700 {
+    type Returnable = Self;
+
+    /// Creates a new `Veml7700` with the default configuration.
+    ///
+    /// # Arguments
+    /// * `bus` - The I2C bus instance to communicate with the sensor.
+    /// * `delay` - A delay provider for waiting on measurements.
+    ///
+    /// # Returns
+    /// A result containing the `Veml7700` or
+    /// `PeripheralError::InitializationFailed`.
+    fn create_on_i2c(
+        bus: I2C<'static, esp_hal::peripherals::I2C0>,
+        delay: Delay,
+    ) -> Result<Self::Returnable, PeripheralError> {
+        Veml7700::create_on_i2c_with_config(bus, delay, Veml7700Config::default())
+    }
+}
+
+impl<BUS, E> LumiSensor for Veml7700<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    fn get_lux(&mut self) -> Result<f32, PeripheralError> {
+        let raw = self.read_raw()?;
+        Ok(self.config.lux(raw))
+    }
+}
+
+impl<BUS, E> UnifiedData for Veml7700<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Output = f32;
+    /// Measures the ambient light in lux.
+    fn read(&mut self, _delay: Delay) -> Result<Self::Output, PeripheralError> {
+        self.get_lux()
+    }
+}
+
+impl<BUS, E> Shutdown for Veml7700<BUS>
+where
+    BUS: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Shuts the sensor down, see `Veml7700::power_down`.
+    fn shutdown(&mut self) -> Result<(), PeripheralError> {
+        self.power_down()
+    }
+}
@@ -0,0 +1,579 @@
+//! # WS2812 / SK6812 Addressable LED Module
+//!
+//! This module drives chains of WS2812 (RGB) and SK6812 (RGB or RGBW)
+//! addressable LEDs using a transmit channel of the RMT peripheral. Colors are
+//! kept in a pixel buffer and sent with `Ws2812::show`, applying the color
+//! order of the chip, a global brightness and gamma correction.
+//!
+//! A small effects library (`Effect`) renders into any pixel buffer from a
+//! timestamp, so effects advance with every tick of your main loop and can be
+//! tested without hardware.
+//!
+//! ## Example
+//! ```no_run
+//! use esp_hal::{prelude::*, systimer::SystemTimer};
+//! use esp_ward::peripherals::ws2812::{Color, ColorOrder, Effect};
+//!
+//! let peripherals = take_periph!();
+//! let system = take_system!(peripherals);
+//! let (clocks, pins, mut delay) = init_chip!(peripherals, system);
+//!
+//! // Onboard LED of an ESP32-C3-DevKitM-1
+//! let mut led =
+//!     esp_ward::create_ws2812!(peripherals, clocks, pins.gpio8, 1, ColorOrder::Grb).unwrap();
+//! led.config.brightness = 64;
+//!
+//! let effect = Effect::Breathe {
+//!     color: Color::rgb(0, 0, 255),
+//!     period_ms: 2000,
+//! };
+//! loop {
+//!     let now = SystemTimer::now() / (SystemTimer::TICKS_PER_SECOND / 1000);
+//!     led.render(&effect, now).unwrap();
+//!     esp_ward::wait!(delay, 20);
+//! }
+//! ```
+
+#[cfg(feature = "esp-hal")]
+use esp_hal::rmt::{PulseCode, TxChannel};
+
+pub use super::Color;
+#[cfg(feature = "esp-hal")]
+use super::{PeripheralError, Writable};
+
+/// Bit timings in nanoseconds, within the tolerances of WS2812 and SK6812.
+const T0H_NS: u32 = 320;
+const T0L_NS: u32 = 930;
+const T1H_NS: u32 = 640;
+const T1L_NS: u32 = 610;
+
+/// Frequency of the RMT clock set up by `create_ws2812!`.
+pub const RMT_CLOCK: fugit::HertzU32 = fugit::HertzU32::MHz(80);
+
+/// Returns the size of the RMT buffer for the given amount of LEDs, enough for
+/// RGBW LEDs and the end marker.
+pub const fn buffer_size(leds: usize) -> usize {
+    leds * 32 + 1
+}
+
+/// Order in which the LED expects the color channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    /// WS2812, WS2812B and SK6812 RGB.
+    Grb,
+    Rgb,
+    Brg,
+    /// SK6812 RGBW.
+    Grbw,
+    Rgbw,
+}
+
+impl ColorOrder {
+    /// Returns the amount of bytes sent per LED.
+    pub fn bytes_per_led(&self) -> usize {
+        match self {
+            ColorOrder::Grbw | ColorOrder::Rgbw => 4,
+            _ => 3,
+        }
+    }
+}
+
+/// Builds a gamma correction table.
+pub fn gamma_table(gamma: f32) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = libm::roundf(libm::powf(value as f32 / 255.0, gamma) * 255.0) as u8;
+    }
+    table
+}
+
+/// Converts a color to the bytes sent to the LED.
+///
+/// # Arguments
+/// * `color` - The color of the LED.
+/// * `order` - Color order of the LED.
+/// * `brightness` - Global brightness, 255 is full brightness.
+/// * `gamma` - Gamma correction table, `None` disables it.
+///
+/// # Returns
+/// The bytes in sending order and their amount.
+pub fn encode_pixel(
+    color: Color,
+    order: ColorOrder,
+    brightness: u8,
+    gamma: Option<&[u8; 256]>,
+) -> ([u8; 4], usize) {
+    let color = color.scale(brightness);
+    let correct = |channel: u8| match gamma {
+        Some(table) => table[channel as usize],
+        None => channel,
+    };
+    let (r, g, b, w) = (
+        correct(color.r),
+        correct(color.g),
+        correct(color.b),
+        correct(color.w),
+    );
+    let bytes = match order {
+        ColorOrder::Grb => [g, r, b, 0],
+        ColorOrder::Rgb => [r, g, b, 0],
+        ColorOrder::Brg => [b, r, g, 0],
+        ColorOrder::Grbw => [g, r, b, w],
+        ColorOrder::Rgbw => [r, g, b, w],
+    };
+    (bytes, order.bytes_per_led())
+}
+
+/// An animation rendered into a pixel buffer from a timestamp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// All LEDs in one color.
+    Solid(Color),
+    /// All LEDs on for the first half of the period, off for the second.
+    Blink { color: Color, period_ms: u32 },
+    /// All LEDs fading in and out.
+    Breathe { color: Color, period_ms: u32 },
+    /// A rainbow moving along the chain, completing a cycle every period.
+    Rainbow { period_ms: u32 },
+    /// A segment of `length` LEDs moving one LED every `step_ms`.
+    Chase {
+        color: Color,
+        background: Color,
+        length: usize,
+        step_ms: u32,
+    },
+    /// A bar filling the chain up to `value` (0.0 to 1.0), the last LED is
+    /// partially lit.
+    Progress {
+        color: Color,
+        background: Color,
+        value: f32,
+    },
+}
+
+impl Effect {
+    /// Renders the effect at the given time.
+    ///
+    /// # Arguments
+    /// * `pixels` - The pixel buffer to render into.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn render(&self, pixels: &mut [Color], now_ms: u64) {
+        let count = pixels.len();
+        match *self {
+            Effect::Solid(color) => pixels.fill(color),
+            Effect::Blink { color, period_ms } => {
+                let period = period_ms.max(1) as u64;
+                let on = now_ms % period < period / 2;
+                pixels.fill(if on { color } else { Color::BLACK });
+            }
+            Effect::Breathe { color, period_ms } => {
+                let period = period_ms.max(1) as u64;
+                let phase = (now_ms % period) as f32 / period as f32;
+                let level = (1.0 - libm::cosf(phase * 2.0 * core::f32::consts::PI)) / 2.0;
+                pixels.fill(color.scale(libm::roundf(level * 255.0) as u8));
+            }
+            Effect::Rainbow { period_ms } => {
+                let period = period_ms.max(1) as u64;
+                let offset = (now_ms % period * 360 / period) as usize;
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let hue = (offset + index * 360 / count) % 360;
+                    *pixel = Color::hsv(hue as u16, 255, 255);
+                }
+            }
+            Effect::Chase {
+                color,
+                background,
+                length,
+                step_ms,
+            } => {
+                if count == 0 {
+                    return;
+                }
+                let head = (now_ms / step_ms.max(1) as u64 % count as u64) as usize;
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let distance = (head + count - index) % count;
+                    *pixel = if distance < length { color } else { background };
+                }
+            }
+            Effect::Progress {
+                color,
+                background,
+                value,
+            } => {
+                let lit = value.clamp(0.0, 1.0) * count as f32;
+                let full = lit as usize;
+                let partial = libm::roundf((lit - full as f32) * 255.0) as u8;
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    *pixel = if index < full {
+                        color
+                    } else if index == full && partial > 0 {
+                        color.scale(partial)
+                    } else {
+                        background
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Configuration of the LED chain.
+#[derive(Debug, Clone, Copy)]
+pub struct Ws2812Config {
+    /// Color order of the LEDs.
+    pub order: ColorOrder,
+    /// Global brightness, 255 is full brightness.
+    pub brightness: u8,
+    /// Whether gamma correction is applied.
+    pub gamma_correction: bool,
+}
+
+impl Default for Ws2812Config {
+    fn default() -> Self {
+        Ws2812Config {
+            order: ColorOrder::Grb,
+            brightness: 255,
+            gamma_correction: true,
+        }
+    }
+}
+
+/// A chain of `N` addressable LEDs on an RMT channel. `B` is the size of the
+/// RMT buffer, see `buffer_size`.
+#[cfg(feature = "esp-hal")]
+pub struct Ws2812<TX: TxChannel, const N: usize, const B: usize> {
+    channel: Option<TX>,
+    /// Colors of the LEDs, sent by `show`.
+    pub pixels: [Color; N],
+    /// Color order, brightness and gamma correction.
+    pub config: Ws2812Config,
+    gamma: [u8; 256],
+    pulses: [u32; B],
+    zero: u32,
+    one: u32,
+}
+
+#[cfg(feature = "esp-hal")]
+impl<TX: TxChannel, const N: usize, const B: usize> Ws2812<TX, N, B> {
+    /// Creates a new `Ws2812` on a configured RMT transmit channel. All LEDs
+    /// are black until the first `show`.
+    ///
+    /// # Arguments
+    /// * `channel` - RMT transmit channel with a clock divider of 1.
+    /// * `config` - Color order, brightness and gamma correction.
+    /// * `clock_mhz` - Frequency of the RMT clock in MHz.
+    ///
+    /// # Returns
+    /// A new `Ws2812` instance, or `Err(PeripheralError::InitializationFailed)`
+    /// if the buffer is too small for `N` LEDs.
+    pub fn create_on_channel(
+        channel: TX,
+        config: Ws2812Config,
+        clock_mhz: u32,
+    ) -> Result<Self, PeripheralError> {
+        if B < buffer_size(N) {
+            return Err(PeripheralError::InitializationFailed);
+        }
+        let ticks = |ns: u32| (ns * clock_mhz / 1000) as u16;
+        let code = |high_ns: u32, low_ns: u32| -> u32 {
+            PulseCode {
+                level1: true,
+                length1: ticks(high_ns),
+                level2: false,
+                length2: ticks(low_ns),
+            }
+            .into()
+        };
+
+        Ok(Ws2812 {
+            channel: Some(channel),
+            pixels: [Color::BLACK; N],
+            config,
+            gamma: gamma_table(2.8),
+            pulses: [0; B],
+            zero: code(T0H_NS, T0L_NS),
+            one: code(T1H_NS, T1L_NS),
+        })
+    }
+
+    /// Sets the color of all LEDs, call `show` to send it.
+    pub fn fill(&mut self, color: Color) {
+        self.pixels.fill(color);
+    }
+
+    /// Sets the color of one LED, call `show` to send it. Indexes out of range
+    /// are ignored.
+    pub fn set_pixel(&mut self, index: usize, color: Color) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            *pixel = color;
+        }
+    }
+
+    /// Renders an effect into the pixel buffer and sends it.
+    ///
+    /// # Arguments
+    /// * `effect` - The effect to render.
+    /// * `now_ms` - Current time in milliseconds.
+    pub fn render(&mut self, effect: &Effect, now_ms: u64) -> Result<(), PeripheralError> {
+        effect.render(&mut self.pixels, now_ms);
+        self.show()
+    }
+
+    /// Sends the pixel buffer to the LEDs, blocking until the transmission
+    /// ends.
+    ///
+    /// # Returns
+    /// `Err(PeripheralError::WriteError)` if the transmission failed.
+    pub fn show(&mut self) -> Result<(), PeripheralError> {
+        let gamma = self.config.gamma_correction.then_some(&self.gamma);
+        let mut position = 0;
+        for pixel in self.pixels.iter() {
+            let (bytes, len) =
+                encode_pixel(*pixel, self.config.order, self.config.brightness, gamma);
+            for byte in &bytes[..len] {
+                for bit in (0..8).rev() {
+                    self.pulses[position] = if byte & (1 << bit) != 0 {
+                        self.one
+                    } else {
+                        self.zero
+                    };
+                    position += 1;
+                }
+            }
+        }
+        // End marker
+        self.pulses[position] = 0;
+
+        let channel = self.channel.take().ok_or(PeripheralError::WriteError)?;
+        match channel.transmit(&self.pulses[..=position]).wait() {
+            Ok(channel) => {
+                self.channel = Some(channel);
+                Ok(())
+            }
+            Err((_, channel)) => {
+                self.channel = Some(channel);
+                Err(PeripheralError::WriteError)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "esp-hal")]
+impl<TX: TxChannel, const N: usize, const B: usize> Writable for Ws2812<TX, N, B> {
+    type Input = Color;
+    /// Sets all LEDs to one color and sends it.
+    fn write(&mut self, data: Self::Input) -> Result<(), PeripheralError> {
+        self.fill(data);
+        self.show()
+    }
+}
+
+/// Macro for creating a `Ws2812` chain on channel 0 of the RMT peripheral,
+/// clocked at `RMT_CLOCK`.
+///
+/// # Arguments
+/// * `$peripherals` - The `esp-hal` `Peripherals` instance.
+/// * `$clocks` - The system clocks initialized beforehand.
+/// * `$pin` - The GPIO pin connected to the data input of the first LED.
+/// * `$leds` - Amount of LEDs in the chain, a constant.
+/// * `$order` - The `ColorOrder` of the LEDs.
+///
+/// # Returns
+/// A new `Ws2812` instance, or `Err(PeripheralError::InitializationFailed)` if
+/// the RMT channel cannot be configured.
+#[macro_export]
+macro_rules! create_ws2812 {
+    ($peripherals: ident, $clocks: ident, $pin: expr, $leds: expr, $order: expr) => {{
+        esp_hal::rmt::Rmt::new(
+            $peripherals.RMT,
+            $crate::peripherals::ws2812::RMT_CLOCK,
+            &$clocks,
+            None,
+        )
+        .map_err(|_| $crate::peripherals::PeripheralError::InitializationFailed)
+        .and_then(|rmt| {
+            esp_hal::rmt::TxChannelCreator::configure(
+                rmt.channel0,
+                $pin,
+                esp_hal::rmt::TxChannelConfig {
+                    clk_divider: 1,
+                    idle_output_level: false,
+                    idle_output: true,
+                    carrier_modulation: false,
+                    ..Default::default()
+                },
+            )
+            .map_err(|_| $crate::peripherals::PeripheralError::InitializationFailed)
+        })
+        .and_then(|channel| {
+            $crate::peripherals::ws2812::Ws2812::<
+                _,
+                { $leds },
+                { $crate::peripherals::ws2812::buffer_size($leds) },
+            >::create_on_channel(
+                channel,
+                $crate::peripherals::ws2812::Ws2812Config {
+                    order: $order,
+                    ..Default::default()
+                },
+                $crate::peripherals::ws2812::RMT_CLOCK.to_MHz(),
+            )
+        })
+    }};
+}
+
+pub use create_ws2812;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: Color = Color::rgb(200, 100, 50);
+    const BACKGROUND: Color = Color::rgb(0, 0, 10);
+
+    #[test]
+    fn gamma_table_endpoints() {
+        let table = gamma_table(2.8);
+        assert_eq!(table[0], 0);
+        assert_eq!(table[255], 255);
+        assert_eq!(table[128], 37);
+        assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let linear = gamma_table(1.0);
+        assert!(linear
+            .iter()
+            .enumerate()
+            .all(|(value, entry)| *entry as usize == value));
+    }
+
+    #[test]
+    fn encodes_color_orders() {
+        let color = Color::rgbw(1, 2, 3, 4);
+        let encode = |order| encode_pixel(color, order, 255, None);
+        assert_eq!(encode(ColorOrder::Grb), ([2, 1, 3, 0], 3));
+        assert_eq!(encode(ColorOrder::Rgb), ([1, 2, 3, 0], 3));
+        assert_eq!(encode(ColorOrder::Brg), ([3, 1, 2, 0], 3));
+        assert_eq!(encode(ColorOrder::Grbw), ([2, 1, 3, 4], 4));
+        assert_eq!(encode(ColorOrder::Rgbw), ([1, 2, 3, 4], 4));
+    }
+
+    #[test]
+    fn encodes_brightness_and_gamma() {
+        let table = gamma_table(2.8);
+        let red = Color::RED;
+        assert_eq!(
+            encode_pixel(red, ColorOrder::Rgb, 128, None).0,
+            [128, 0, 0, 0]
+        );
+        assert_eq!(
+            encode_pixel(red, ColorOrder::Rgb, 128, Some(&table)).0,
+            [table[128], 0, 0, 0]
+        );
+        assert_eq!(
+            encode_pixel(Color::WHITE, ColorOrder::Rgb, 255, Some(&table)).0,
+            [255, 255, 255, 0]
+        );
+        assert_eq!(
+            encode_pixel(Color::WHITE, ColorOrder::Grbw, 0, None).0,
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn solid_and_blink() {
+        let mut pixels = [Color::BLACK; 3];
+        Effect::Solid(COLOR).render(&mut pixels, 1234);
+        assert_eq!(pixels, [COLOR; 3]);
+
+        let blink = Effect::Blink {
+            color: COLOR,
+            period_ms: 1000,
+        };
+        for (now_ms, expected) in [
+            (0, COLOR),
+            (499, COLOR),
+            (500, Color::BLACK),
+            (999, Color::BLACK),
+            (1000, COLOR),
+        ] {
+            blink.render(&mut pixels, now_ms);
+            assert_eq!(pixels, [expected; 3], "{now_ms}");
+        }
+    }
+
+    #[test]
+    fn breathe() {
+        let mut pixels = [Color::BLACK; 2];
+        let breathe = Effect::Breathe {
+            color: Color::WHITE,
+            period_ms: 1000,
+        };
+        for (now_ms, level) in [(0, 0), (250, 128), (500, 255), (750, 128), (1000, 0)] {
+            breathe.render(&mut pixels, now_ms);
+            assert_eq!(pixels, [Color::WHITE.scale(level); 2], "{now_ms}");
+        }
+    }
+
+    #[test]
+    fn rainbow_moves_along_the_chain() {
+        let rainbow = Effect::Rainbow { period_ms: 360 };
+        let mut start = [Color::BLACK; 4];
+        rainbow.render(&mut start, 0);
+        assert_eq!(start[0], Color::RED);
+        assert_eq!(start[2], Color::hsv(180, 255, 255));
+
+        // A quarter of the period moves the colors by one of the four LEDs.
+        let mut moved = [Color::BLACK; 4];
+        rainbow.render(&mut moved, 90);
+        assert_eq!(moved, [start[1], start[2], start[3], start[0]]);
+
+        rainbow.render(&mut moved, 360);
+        assert_eq!(moved, start);
+    }
+
+    #[test]
+    fn chase_moves_every_step() {
+        let chase = Effect::Chase {
+            color: COLOR,
+            background: BACKGROUND,
+            length: 2,
+            step_ms: 100,
+        };
+        let (c, b) = (COLOR, BACKGROUND);
+        let mut pixels = [Color::BLACK; 5];
+        for (now_ms, expected) in [
+            (0, [c, b, b, b, c]),
+            (99, [c, b, b, b, c]),
+            (100, [c, c, b, b, b]),
+            (450, [b, b, b, c, c]),
+            (500, [c, b, b, b, c]),
+        ] {
+            chase.render(&mut pixels, now_ms);
+            assert_eq!(pixels, expected, "{now_ms}");
+        }
+
+        // An empty chain is left alone.
+        chase.render(&mut [], 0);
+    }
+
+    #[test]
+    fn progress_bar() {
+        let (c, b) = (COLOR, BACKGROUND);
+        let mut pixels = [Color::BLACK; 4];
+        let mut render = |value: f32| {
+            Effect::Progress {
+                color: COLOR,
+                background: BACKGROUND,
+                value,
+            }
+            .render(&mut pixels, 0);
+            pixels
+        };
+        assert_eq!(render(0.0), [b; 4]);
+        assert_eq!(render(-1.0), [b; 4]);
+        assert_eq!(render(0.5), [c, c, b, b]);
+        assert_eq!(render(0.625), [c, c, c.scale(128), b]);
+        assert_eq!(render(1.0), [c; 4]);
+        assert_eq!(render(2.0), [c; 4]);
+    }
+}